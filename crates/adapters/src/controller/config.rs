@@ -5,6 +5,7 @@
 //! endpoint configs.  We represent these configs as opaque yaml values, so
 //! that the entire configuration tree can be deserialized from a yaml file.
 
+use dbsp::circuit::{CircuitConfig, Layout, StorageConfig};
 use serde::{Deserialize, Serialize};
 use serde_yaml::Value as YamlValue;
 use std::{borrow::Cow, collections::BTreeMap};
@@ -67,9 +68,44 @@ pub struct GlobalPipelineConfig {
     /// get buffered by the controller, defaults to 0.
     #[serde(default)]
     pub max_buffering_delay_usecs: u64,
+
+    /// Persistent storage for the state of stateful operators.
+    ///
+    /// When specified, operators selected by
+    /// `storage.persistent_operators` (by default all stateful operators)
+    /// spill their state to files in `storage.path`, which allows the state
+    /// to grow larger than RAM.  With `storage.backend` set to `rocks_db`,
+    /// they keep it in a RocksDB database instead, which requires the
+    /// pipeline to be built with the `persistence` feature of DBSP.  Requires
+    /// the pipeline to be started with
+    /// [`server_main_with_config`](`crate::server::server_main_with_config`).
+    /// By default, all state is kept in memory.
+    #[serde(default)]
+    #[schema(value_type = Object)]
+    pub storage: Option<StorageConfig>,
+
+    /// Output streams whose current contents can be queried at runtime.
+    ///
+    /// The controller maintains an integrated snapshot of each listed stream
+    /// (see [`Controller::snapshot`](`crate::Controller::snapshot`)), which
+    /// the pipeline server exposes via the `/view/{stream}` endpoint.
+    /// Snapshots are kept in memory, so they should only be enabled for
+    /// views of moderate size.
+    #[serde(default)]
+    pub snapshot_streams: Vec<String>,
 }
 
-#[derive(Clone, Serialize, Deserialize, ToSchema)]
+impl GlobalPipelineConfig {
+    /// Configuration of the circuit that runs the pipeline.
+    pub fn circuit_config(&self) -> CircuitConfig {
+        CircuitConfig {
+            layout: Layout::new_solo(self.workers as usize),
+            storage: self.storage.clone(),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 pub struct InputEndpointConfig {
     /// Transport endpoint configuration.
     pub transport: TransportConfig,
@@ -92,9 +128,58 @@ pub struct InputEndpointConfig {
     /// The default is 1 million.
     #[serde(default = "default_max_buffered_records")]
     pub max_buffered_records: u64,
+
+    /// What to do with input records that fail to parse.
+    #[serde(default)]
+    pub on_parse_error: ParseErrorPolicy,
+
+    /// Maximal number of parse errors tolerated by the endpoint when
+    /// `on_parse_error` is `skip` or `dead_letter`.  The pipeline fails, as
+    /// with the `fail` policy, once the number of errors exceeds this
+    /// threshold.
+    ///
+    /// By default, the number of errors is not limited.
+    #[serde(default)]
+    pub max_parse_errors: Option<u64>,
+
+    /// Transport endpoint that invalid records are sent to when
+    /// `on_parse_error` is `dead_letter`.
+    ///
+    /// Each invalid record is written as a JSON object on a separate line,
+    /// e.g., `{"endpoint": "input1", "offset": 1024, "error": "...",
+    /// "data": "..."}`, where `offset` is the byte offset of the record in
+    /// the input stream, if known, and `data` contains the raw bytes of the
+    /// record.  Raw bytes that are not valid UTF-8 are written to the
+    /// `data_base64` field instead.
+    #[serde(default)]
+    pub dead_letter: Option<TransportConfig>,
 }
 
-#[derive(Clone, Serialize, Deserialize, ToSchema)]
+/// Policy for handling input records that fail to parse.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ParseErrorPolicy {
+    /// Fail the pipeline on the first invalid record: discard the input
+    /// buffer that contains the record, report a fatal error to the error
+    /// callback and pause the pipeline.
+    Fail,
+
+    /// Skip invalid records, count them in endpoint stats and report them
+    /// to the error callback.
+    Skip,
+
+    /// Skip invalid records, count them in endpoint stats and send them to
+    /// the `dead_letter` transport endpoint.
+    DeadLetter,
+}
+
+impl Default for ParseErrorPolicy {
+    fn default() -> Self {
+        Self::Skip
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 pub struct OutputEndpointConfig {
     /// The name of the output stream of the circuit that this endpoint is
     /// connected to.
@@ -114,7 +199,7 @@ pub struct OutputEndpointConfig {
 }
 
 /// Transport endpoint configuration.
-#[derive(Clone, Serialize, Deserialize, ToSchema)]
+#[derive(Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 pub struct TransportConfig {
     /// Data transport name, e.g., "file", "kafka", "kinesis", etc.
     pub name: Cow<'static, str>,
@@ -130,7 +215,7 @@ pub struct TransportConfig {
 
 /// Data format specification used to parse raw data received from the
 /// endpoint or to encode data sent to the endpoint.
-#[derive(Clone, Serialize, Deserialize, ToSchema)]
+#[derive(Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 pub struct FormatConfig {
     /// Format name, e.g., "csv", "json", "bincode", etc.
     pub name: Cow<'static, str>,
@@ -0,0 +1,84 @@
+//! Dead-letter endpoints.
+//!
+//! An input endpoint configured with the
+//! [`dead_letter`](`super::ParseErrorPolicy::DeadLetter`) error policy sends
+//! records that fail to parse to a dead-letter output transport endpoint
+//! instead of the error callback.  Each record is written as a JSON object on
+//! a separate line.
+
+use crate::{OutputEndpoint, ParseError};
+use anyhow::Result as AnyResult;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde::Serialize;
+use std::{str::from_utf8, sync::Mutex};
+
+/// Invalid input record sent to the dead-letter endpoint.
+#[derive(Serialize)]
+struct DeadLetterRecord<'a> {
+    /// Name of the input endpoint that received the record.
+    endpoint: &'a str,
+
+    /// Byte offset of the record in the input stream.
+    offset: Option<u64>,
+
+    /// Parser error message.
+    error: &'a str,
+
+    /// Raw contents of the record, if valid UTF-8.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<&'a str>,
+
+    /// Base64-encoded raw contents of the record, if not valid UTF-8.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data_base64: Option<String>,
+}
+
+/// Output transport endpoint that receives invalid input records.
+///
+/// The endpoint is shared by all parser instances of an input endpoint.
+pub(crate) struct DeadLetterEndpoint {
+    endpoint: Mutex<Box<dyn OutputEndpoint>>,
+}
+
+impl DeadLetterEndpoint {
+    pub(crate) fn new(endpoint: Box<dyn OutputEndpoint>) -> Self {
+        Self {
+            endpoint: Mutex::new(endpoint),
+        }
+    }
+
+    /// Send `errors` reported by input endpoint `endpoint_name` to the
+    /// dead-letter endpoint as a single buffer.
+    pub(crate) fn send(&self, endpoint_name: &str, errors: &[ParseError]) -> AnyResult<()> {
+        let mut buffer = Vec::new();
+
+        for error in errors.iter() {
+            let data = error.data.as_deref();
+            let text = data.and_then(|data| from_utf8(data).ok());
+
+            let record = DeadLetterRecord {
+                endpoint: endpoint_name,
+                offset: error.offset,
+                error: &error.description,
+                data: text,
+                data_base64: match (data, text) {
+                    (Some(data), None) => Some(BASE64.encode(data)),
+                    _ => None,
+                },
+            };
+
+            serde_json::to_writer(&mut buffer, &record)?;
+            buffer.push(b'\n');
+        }
+
+        self.endpoint.lock().unwrap().push_buffer(&buffer)
+    }
+}
+
+impl Drop for DeadLetterEndpoint {
+    fn drop(&mut self) {
+        if let Ok(endpoint) = self.endpoint.get_mut() {
+            endpoint.disconnect();
+        }
+    }
+}
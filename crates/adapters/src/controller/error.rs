@@ -14,6 +14,12 @@ pub enum ConfigError {
     /// Output endpoint with this name already exists.
     DuplicateOutputEndpoint { endpoint_name: String },
 
+    /// Input endpoint with this name does not exist.
+    UnknownInputEndpoint { endpoint_name: String },
+
+    /// Output endpoint with this name does not exist.
+    UnknownOutputEndpoint { endpoint_name: String },
+
     /// Endpoint configuration specifies unknown input format name.
     UnknownInputFormat { format_name: String },
 
@@ -29,6 +35,14 @@ pub enum ConfigError {
     /// Controller configuration specifies output stream name
     /// that is not found in the circuit catalog.
     UnknownOutputStream { stream_name: String },
+
+    /// Input endpoint with the `dead_letter` error policy doesn't specify a
+    /// dead-letter transport endpoint.
+    MissingDeadLetterEndpoint { endpoint_name: String },
+
+    /// Input endpoint requests exactly-once processing, which a restarted
+    /// pipeline cannot provide for a circuit with state.
+    ExactlyOnceStatefulCircuit { endpoint_name: String },
 }
 
 impl Display for ConfigError {
@@ -46,6 +60,12 @@ impl Display for ConfigError {
             Self::DuplicateOutputEndpoint { endpoint_name } => {
                 write!(f, "output endpoint '{endpoint_name}' already exists")
             }
+            Self::UnknownInputEndpoint { endpoint_name } => {
+                write!(f, "unknown input endpoint '{endpoint_name}'")
+            }
+            Self::UnknownOutputEndpoint { endpoint_name } => {
+                write!(f, "unknown output endpoint '{endpoint_name}'")
+            }
             Self::UnknownOutputFormat { format_name } => {
                 write!(f, "unknown output format '{format_name}'")
             }
@@ -55,6 +75,18 @@ impl Display for ConfigError {
             Self::UnknownOutputStream { stream_name } => {
                 write!(f, "unknown output stream '{stream_name}'")
             }
+            Self::MissingDeadLetterEndpoint { endpoint_name } => {
+                write!(
+                    f,
+                    "input endpoint '{endpoint_name}' uses the 'dead_letter' error policy, but does not specify a 'dead_letter' endpoint"
+                )
+            }
+            Self::ExactlyOnceStatefulCircuit { endpoint_name } => {
+                write!(
+                    f,
+                    "input endpoint '{endpoint_name}' requests exactly-once processing, which is not supported for circuits with stateful operators"
+                )
+            }
         }
     }
 }
@@ -84,6 +116,18 @@ impl ConfigError {
         }
     }
 
+    pub fn unknown_input_endpoint(endpoint_name: &str) -> Self {
+        Self::UnknownInputEndpoint {
+            endpoint_name: endpoint_name.to_owned(),
+        }
+    }
+
+    pub fn unknown_output_endpoint(endpoint_name: &str) -> Self {
+        Self::UnknownOutputEndpoint {
+            endpoint_name: endpoint_name.to_owned(),
+        }
+    }
+
     pub fn unknown_output_format(format_name: &str) -> Self {
         Self::UnknownOutputFormat {
             format_name: format_name.to_owned(),
@@ -101,6 +145,18 @@ impl ConfigError {
             stream_name: stream_name.to_owned(),
         }
     }
+
+    pub fn missing_dead_letter_endpoint(endpoint_name: &str) -> Self {
+        Self::MissingDeadLetterEndpoint {
+            endpoint_name: endpoint_name.to_owned(),
+        }
+    }
+
+    pub fn exactly_once_stateful_circuit(endpoint_name: &str) -> Self {
+        Self::ExactlyOnceStatefulCircuit {
+            endpoint_name: endpoint_name.to_owned(),
+        }
+    }
 }
 
 /// Controller error.
@@ -111,11 +167,13 @@ pub enum ControllerError {
 
     /// Parser error.
     ///
-    /// Error parsing the last input batch.  Parser errors are expected to be
+    /// Error parsing an input record.  Parser errors are expected to be
     /// recoverable, i.e., the parser should be able to successfully parse
-    /// new valid inputs after an error.
+    /// new valid inputs after an error.  The error is fatal if it caused the
+    /// pipeline to fail according to the error policy of the endpoint.
     ParseError {
         endpoint_name: String,
+        fatal: bool,
         error: AnyError,
     },
 
@@ -179,11 +237,13 @@ impl Display for ControllerError {
             }
             Self::ParseError {
                 endpoint_name,
+                fatal,
                 error,
             } => {
                 write!(
                     f,
-                    "parse error on input endpoint '{endpoint_name}': '{error}'"
+                    "{}parse error on input endpoint '{endpoint_name}': '{error}'",
+                    if *fatal { "FATAL " } else { "" }
                 )
             }
             Self::EncodeError {
@@ -227,6 +287,18 @@ impl ControllerError {
         }
     }
 
+    pub fn unknown_input_endpoint(endpoint_name: &str) -> Self {
+        Self::Config {
+            config_error: ConfigError::unknown_input_endpoint(endpoint_name),
+        }
+    }
+
+    pub fn unknown_output_endpoint(endpoint_name: &str) -> Self {
+        Self::Config {
+            config_error: ConfigError::unknown_output_endpoint(endpoint_name),
+        }
+    }
+
     pub fn unknown_output_format(format_name: &str) -> Self {
         Self::Config {
             config_error: ConfigError::unknown_output_format(format_name),
@@ -245,6 +317,18 @@ impl ControllerError {
         }
     }
 
+    pub fn missing_dead_letter_endpoint(endpoint_name: &str) -> Self {
+        Self::Config {
+            config_error: ConfigError::missing_dead_letter_endpoint(endpoint_name),
+        }
+    }
+
+    pub fn exactly_once_stateful_circuit(endpoint_name: &str) -> Self {
+        Self::Config {
+            config_error: ConfigError::exactly_once_stateful_circuit(endpoint_name),
+        }
+    }
+
     pub fn input_transport_error(endpoint_name: &str, fatal: bool, error: AnyError) -> Self {
         Self::InputTransportError {
             endpoint_name: endpoint_name.to_owned(),
@@ -261,9 +345,10 @@ impl ControllerError {
         }
     }
 
-    pub fn parse_error(endpoint_name: &str, error: AnyError) -> Self {
+    pub fn parse_error(endpoint_name: &str, fatal: bool, error: AnyError) -> Self {
         Self::ParseError {
             endpoint_name: endpoint_name.to_owned(),
+            fatal,
             error,
         }
     }
@@ -32,11 +32,28 @@
 //! The probe passes the data through to the parser, while counting the number
 //! of transmitted bytes and records and updating respective performance
 //! counters in the controller.
+//!
+//! # Exactly-once processing
+//!
+//! Transport endpoints that support exactly-once processing report their
+//! position in the input stream along with each input buffer
+//! ([`InputConsumer::input_with_position`]).  The probe records the position
+//! while holding a shared lock that the circuit thread acquires exclusively
+//! for the duration of each step, so that every step is labeled with the
+//! exact input positions it has consumed.  Output endpoints receive these
+//! positions along with the outputs of the step
+//! ([`OutputEndpoint::batch_end`]) and can commit them atomically with the
+//! outputs.  Input positions do not cover the state of the circuit: a
+//! restarted pipeline resumes from the committed positions with an empty
+//! circuit, so exactly-once semantics only hold for circuits whose outputs
+//! depend on the inputs of the current step alone.  The controller rejects
+//! exactly-once input endpoints ([`InputEndpoint::is_exactly_once`]) for
+//! circuits with stateful operators.
 
 use crate::{
-    Catalog, Encoder, InputConsumer, InputEndpoint, InputFormat, InputTransport, OutputConsumer,
-    OutputEndpoint, OutputFormat, OutputTransport, Parser, PipelineState, SerBatch,
-    SerOutputBatchHandle,
+    Catalog, Encoder, InputConsumer, InputEndpoint, InputFormat, InputPosition, InputPositions,
+    InputTransport, OutputConsumer, OutputEndpoint, OutputFormat, OutputTransport, ParseError,
+    Parser, PipelineState, SerBatch, SerOutputBatchHandle, SerTrace, Step,
 };
 use anyhow::{Error as AnyError, Result as AnyResult};
 use crossbeam::{
@@ -50,7 +67,7 @@ use std::{
     borrow::Cow,
     collections::{BTreeMap, BTreeSet, HashSet},
     sync::{
-        atomic::{AtomicBool, AtomicU32, Ordering},
+        atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
         Arc, Mutex,
     },
     thread::{spawn, JoinHandle},
@@ -58,13 +75,15 @@ use std::{
 };
 
 mod config;
+mod dead_letter;
 mod error;
 mod stats;
 
 pub use config::{
-    FormatConfig, GlobalPipelineConfig, InputEndpointConfig, OutputEndpointConfig, PipelineConfig,
-    TransportConfig,
+    FormatConfig, GlobalPipelineConfig, InputEndpointConfig, OutputEndpointConfig,
+    ParseErrorPolicy, PipelineConfig, TransportConfig,
 };
+use dead_letter::DeadLetterEndpoint;
 pub use error::ControllerError;
 pub use stats::{ControllerStatus, InputEndpointStatus, OutputEndpointStatus};
 
@@ -130,11 +149,12 @@ impl Controller {
 
         let inner = Arc::new(ControllerInner::new(
             catalog,
+            circuit.is_stateful(),
             &config.global,
             circuit_thread_unparker,
             backpressure_thread_unparker,
             error_cb,
-        ));
+        )?);
 
         if config.global.cpu_profiler {
             circuit
@@ -190,6 +210,72 @@ impl Controller {
         self.inner.connect_input(endpoint_name, config)
     }
 
+    /// Disconnect an input endpoint.
+    ///
+    /// Stops the endpoint and removes it from the pipeline.  Records
+    /// received from the endpoint before this call are still processed by
+    /// the circuit.
+    pub fn disconnect_input(&self, endpoint_name: &str) -> AnyResult<()> {
+        self.inner.disconnect_input(endpoint_name)
+    }
+
+    /// Change the configuration of an input endpoint.
+    ///
+    /// A change to `max_buffered_records` only is applied to the running
+    /// endpoint.  Any other change replaces the endpoint with a new endpoint
+    /// created with the new configuration.  If the new endpoint fails to
+    /// initialize, the old endpoint keeps running.
+    pub fn reconfigure_input(
+        &self,
+        endpoint_name: &str,
+        config: &InputEndpointConfig,
+    ) -> AnyResult<()> {
+        self.inner.reconfigure_input(endpoint_name, config)
+    }
+
+    /// Connect a new output endpoint with specified name and configuration.
+    ///
+    /// The endpoint receives outputs of the circuit produced by steps
+    /// performed after this call.
+    ///
+    /// # Errors
+    ///
+    /// The method may fail for the following reasons:
+    ///
+    /// * The endpoint configuration is invalid, e.g., specifies an unknown
+    ///   transport or data format.
+    ///
+    /// * The endpoint fails to initialize.
+    pub fn connect_output(
+        &self,
+        endpoint_name: &str,
+        config: &OutputEndpointConfig,
+    ) -> AnyResult<()> {
+        self.inner.connect_output(endpoint_name, config)
+    }
+
+    /// Disconnect an output endpoint.
+    ///
+    /// Outputs queued for the endpoint that haven't been sent to the
+    /// transport yet are discarded.
+    pub fn disconnect_output(&self, endpoint_name: &str) -> AnyResult<()> {
+        self.inner.disconnect_output(endpoint_name)
+    }
+
+    /// Change the configuration of an output endpoint.
+    ///
+    /// A change to `max_buffered_records` only is applied to the running
+    /// endpoint.  Any other change replaces the endpoint with a new endpoint
+    /// created with the new configuration.  If the new endpoint fails to
+    /// initialize, the old endpoint keeps running.
+    pub fn reconfigure_output(
+        &self,
+        endpoint_name: &str,
+        config: &OutputEndpointConfig,
+    ) -> AnyResult<()> {
+        self.inner.reconfigure_output(endpoint_name, config)
+    }
+
     /// Change the state of all input endpoints to running.
     ///
     /// Start streaming data through all connected input endpoints.
@@ -218,6 +304,22 @@ impl Controller {
         self.inner.dump_profile();
     }
 
+    /// Returns the current contents of output stream `stream`.
+    ///
+    /// Returns `None` if the stream is not listed in
+    /// [`snapshot_streams`](`GlobalPipelineConfig::snapshot_streams`).  The
+    /// snapshot reflects all outputs produced by the circuit up to the most
+    /// recent step.  It is returned as a copy of the trace of the stream,
+    /// which the caller consolidates with [`SerTrace::consolidate`].
+    /// Consolidation can be expensive for large streams, so callers should
+    /// not hold locks or block an async runtime while doing so.
+    pub fn snapshot(&self, stream: &str) -> Option<Box<dyn SerTrace>> {
+        self.inner
+            .snapshots
+            .get(stream)
+            .map(|snapshot| snapshot.trace.lock().unwrap().fork())
+    }
+
     /// Terminate the controller, stop all input endpoints and destroy the
     /// circuit.
     pub fn stop(self) -> AnyResult<()> {
@@ -257,6 +359,7 @@ impl Controller {
         parker: Parker,
     ) -> AnyResult<()> {
         let mut start: Option<Instant> = None;
+        let mut step: Step = 0;
 
         let max_buffering_delay =
             Duration::from_micros(controller.status.global_config.max_buffering_delay_usecs);
@@ -313,11 +416,20 @@ impl Controller {
                         // Wake up the backpressure thread to unpause endpoints blocked due to
                         // backpressure.
                         controller.unpark_backpressure();
+
+                        // Block input endpoints that report input positions for the duration
+                        // of the step, so that the positions recorded below match the inputs
+                        // consumed by the step exactly.
+                        let step_lock = controller.step_lock.write().unwrap();
+                        let input_positions =
+                            Arc::new(controller.input_positions.lock().unwrap().clone());
+
                         debug!("circuit thread: calling 'circuit.step'");
                         circuit
                             .step()
                             .unwrap_or_else(|e| controller.error(ControllerError::dbsp_error(e)));
                         debug!("circuit thread: 'circuit.step' returned");
+                        drop(step_lock);
 
                         controller
                             .status
@@ -325,11 +437,15 @@ impl Controller {
 
                         // Push output batches to output pipelines.
                         let outputs = controller.outputs.read().unwrap();
-                        for (_stream, (output_handle, endpoints)) in outputs.iter_by_stream() {
+                        for (stream, (output_handle, endpoints)) in outputs.iter_by_stream() {
                             // TODO: add an endpoint config option to consolidate output batches.
                             let batch = output_handle.take_from_all();
                             let num_records = batch.iter().map(|b| b.len()).sum();
 
+                            if let Some(snapshot) = controller.snapshots.get(stream.as_ref()) {
+                                snapshot.trace.lock().unwrap().insert(&batch);
+                            }
+
                             for endpoint_id in endpoints.iter() {
                                 let endpoint = outputs.lookup_by_id(endpoint_id).unwrap();
 
@@ -339,7 +455,12 @@ impl Controller {
                                 // Associate the input frontier with the batch.  Once the batch has
                                 // been sent to the output endpoint, the endpoint will get labeled
                                 // with this frontier.
-                                endpoint.queue.push((batch.clone(), processed_records));
+                                endpoint.queue.push(StepOutput {
+                                    step,
+                                    batches: batch.clone(),
+                                    processed_records,
+                                    input_positions: input_positions.clone(),
+                                });
 
                                 // Wake up the output thread.  We're not trying to be smart here and
                                 // wake up the thread conditionally if it was previously idle, as I
@@ -347,6 +468,15 @@ impl Controller {
                                 endpoint.unparker.unpark();
                             }
                         }
+
+                        // Update snapshots of streams that don't have any output endpoints.
+                        for (stream, snapshot) in controller.snapshots.iter() {
+                            if !outputs.by_stream.contains_key(stream.as_str()) {
+                                let batch = snapshot.output_handle.take_from_all();
+                                snapshot.trace.lock().unwrap().insert(&batch);
+                            }
+                        }
+                        step += 1;
                     } else if buffered_records > 0 {
                         // We have some buffered data, but less than `min_batch_size_records` --
                         // wait up to `max_buffering_delay` for more data to
@@ -385,6 +515,9 @@ impl Controller {
         loop {
             let inputs = controller.inputs.lock().unwrap();
 
+            // Forget endpoints that have been disconnected.
+            paused_endpoints.retain(|epid| inputs.contains_key(epid));
+
             match controller.state() {
                 PipelineState::Paused => {
                     // Pause circuit if not yet paused.
@@ -464,12 +597,26 @@ impl InputEndpointDescr {
     }
 }
 
+/// Outputs of a single step of the circuit sent to an output endpoint.
+struct StepOutput {
+    /// Sequence number of the step.
+    step: Step,
+
+    /// Output batches.
+    batches: Vec<Arc<dyn SerBatch>>,
+
+    /// Progress label equal to the number of input records fully processed
+    /// by DBSP before emitting this batch of outputs.  The label increases
+    /// monotonically over time.
+    processed_records: u64,
+
+    /// Positions of input endpoints consumed by the step.
+    input_positions: Arc<InputPositions>,
+}
+
 /// A lock-free queue used to send output batches from the circuit thread
-/// to output endpoint threads.  Each entry is annotated with a progress label
-/// that is equal to the number of input records fully processed by
-/// DBSP before emitting this batch of outputs.  The label increases
-/// monotonically over time.
-type BatchQueue = SegQueue<(Vec<Arc<dyn SerBatch>>, u64)>;
+/// to output endpoint threads.
+type BatchQueue = SegQueue<StepOutput>;
 
 /// State tracked by the controller for each output endpoint.
 struct OutputEndpointDescr {
@@ -481,6 +628,9 @@ struct OutputEndpointDescr {
 
     /// Unparker for the endpoint thread.
     unparker: Unparker,
+
+    /// Set when the endpoint is disconnected to stop the endpoint thread.
+    disconnected: Arc<AtomicBool>,
 }
 
 impl OutputEndpointDescr {
@@ -489,6 +639,7 @@ impl OutputEndpointDescr {
             endpoint_name: endpoint_name.to_string(),
             queue: Arc::new(SegQueue::new()),
             unparker,
+            disconnected: Arc::new(AtomicBool::new(false)),
         }
     }
 }
@@ -499,6 +650,10 @@ type StreamEndpointMap =
 struct OutputEndpoints {
     by_id: BTreeMap<EndpointId, OutputEndpointDescr>,
     by_stream: StreamEndpointMap,
+
+    /// Id to assign to the next endpoint.  Ids are not reused after an
+    /// endpoint is disconnected.
+    next_endpoint_id: EndpointId,
 }
 
 impl OutputEndpoints {
@@ -506,6 +661,7 @@ impl OutputEndpoints {
         Self {
             by_id: BTreeMap::new(),
             by_stream: BTreeMap::new(),
+            next_endpoint_id: 0,
         }
     }
 
@@ -530,8 +686,17 @@ impl OutputEndpoints {
             .find(|ep| ep.endpoint_name == endpoint_name)
     }
 
-    fn alloc_endpoint_id(&self) -> EndpointId {
-        self.by_id.keys().rev().next().map(|k| k + 1).unwrap_or(0)
+    fn lookup_id_by_name(&self, endpoint_name: &str) -> Option<EndpointId> {
+        self.by_id
+            .iter()
+            .find(|(_, ep)| ep.endpoint_name == endpoint_name)
+            .map(|(endpoint_id, _)| *endpoint_id)
+    }
+
+    fn alloc_endpoint_id(&mut self) -> EndpointId {
+        let endpoint_id = self.next_endpoint_id;
+        self.next_endpoint_id += 1;
+        endpoint_id
     }
 
     fn insert(
@@ -548,6 +713,24 @@ impl OutputEndpoints {
             .1
             .insert(endpoint_id);
     }
+
+    fn remove(&mut self, endpoint_id: &EndpointId) -> Option<OutputEndpointDescr> {
+        let endpoint_descr = self.by_id.remove(endpoint_id)?;
+
+        // Drop the stream from the map once its last endpoint is removed.
+        self.by_stream.retain(|_, (_, endpoints)| {
+            endpoints.remove(endpoint_id);
+            !endpoints.is_empty()
+        });
+
+        Some(endpoint_descr)
+    }
+}
+
+/// Integrated snapshot of an output stream.
+struct Snapshot {
+    output_handle: Box<dyn SerOutputBatchHandle>,
+    trace: Mutex<Box<dyn SerTrace>>,
 }
 
 /// Controller state sharable across threads.
@@ -560,7 +743,23 @@ struct ControllerInner {
     dump_profile_request: AtomicBool,
     catalog: Arc<Mutex<Catalog>>,
     inputs: Mutex<BTreeMap<EndpointId, InputEndpointDescr>>,
+
+    /// Id to assign to the next input endpoint.
+    next_input_endpoint_id: AtomicU64,
     outputs: ShardedLock<OutputEndpoints>,
+
+    /// Held exclusively by the circuit thread during each step; held in shared
+    /// mode by input probes while pushing data along with input positions.
+    step_lock: ShardedLock<()>,
+
+    /// The latest input positions reported by input endpoints.
+    input_positions: Mutex<InputPositions>,
+
+    /// Snapshots of streams listed in `GlobalPipelineConfig::snapshot_streams`.
+    snapshots: BTreeMap<String, Snapshot>,
+
+    /// `true` if the circuit contains stateful operators.
+    circuit_stateful: bool,
     circuit_thread_unparker: Unparker,
     backpressure_thread_unparker: Unparker,
     error_cb: Box<dyn Fn(ControllerError) + Send + Sync>,
@@ -569,26 +768,48 @@ struct ControllerInner {
 impl ControllerInner {
     fn new(
         catalog: Catalog,
+        circuit_stateful: bool,
         global_config: &GlobalPipelineConfig,
         circuit_thread_unparker: Unparker,
         backpressure_thread_unparker: Unparker,
         error_cb: Box<dyn Fn(ControllerError) + Send + Sync>,
-    ) -> Self {
+    ) -> AnyResult<Self> {
         let status = ControllerStatus::new(global_config);
         let state = AtomicU32::new(PipelineState::Paused as u32);
         let dump_profile_request = AtomicBool::new(false);
 
-        Self {
+        let mut snapshots = BTreeMap::new();
+        for stream in global_config.snapshot_streams.iter() {
+            let output_handle = catalog
+                .output_batch_handle(stream)
+                .ok_or_else(|| ControllerError::unknown_output_stream(stream))?
+                .fork();
+            let trace = Mutex::new(output_handle.new_trace());
+            snapshots.insert(
+                stream.clone(),
+                Snapshot {
+                    output_handle,
+                    trace,
+                },
+            );
+        }
+
+        Ok(Self {
             status,
             state,
             dump_profile_request,
             catalog: Arc::new(Mutex::new(catalog)),
             inputs: Mutex::new(BTreeMap::new()),
+            next_input_endpoint_id: AtomicU64::new(0),
             outputs: ShardedLock::new(OutputEndpoints::new()),
+            step_lock: ShardedLock::new(()),
+            input_positions: Mutex::new(BTreeMap::new()),
+            snapshots,
+            circuit_stateful,
             circuit_thread_unparker,
             backpressure_thread_unparker,
             error_cb,
-        }
+        })
     }
 
     fn connect_input(
@@ -602,6 +823,27 @@ impl ControllerInner {
             Err(ControllerError::duplicate_input_endpoint(endpoint_name))?;
         }
 
+        let (endpoint_id, endpoint) = self.create_input(endpoint_name, endpoint_config)?;
+        self.add_input(
+            &mut inputs,
+            endpoint_id,
+            endpoint_name,
+            endpoint_config,
+            endpoint,
+        );
+
+        drop(inputs);
+
+        self.unpark_backpressure();
+        Ok(())
+    }
+
+    /// Create an input endpoint without connecting it to the pipeline.
+    fn create_input(
+        self: &Arc<Self>,
+        endpoint_name: &str,
+        endpoint_config: &InputEndpointConfig,
+    ) -> AnyResult<(EndpointId, Box<dyn InputEndpoint>)> {
         // Create input pipeline, consisting of a transport endpoint, controller
         // probe, and parser.
         //
@@ -620,12 +862,49 @@ impl ControllerInner {
 
         let parser = format.new_parser(input_stream, &endpoint_config.format.config)?;
 
+        // Create dead-letter endpoint.
+        let dead_letter = match (endpoint_config.on_parse_error, &endpoint_config.dead_letter) {
+            (ParseErrorPolicy::DeadLetter, Some(transport_config)) => {
+                let transport = <dyn OutputTransport>::get_transport(&transport_config.name)
+                    .ok_or_else(|| {
+                        ControllerError::unknown_output_transport(&transport_config.name)
+                    })?;
+
+                let dead_letter_name = format!("{endpoint_name}.dead_letter");
+                let dead_letter_name_str = dead_letter_name.clone();
+                let self_weak = Arc::downgrade(self);
+                let endpoint = transport.new_endpoint(
+                    &dead_letter_name,
+                    &transport_config.config,
+                    Box::new(move |fatal: bool, e: AnyError| {
+                        if let Some(controller) = self_weak.upgrade() {
+                            controller.error(ControllerError::output_transport_error(
+                                &dead_letter_name_str,
+                                fatal,
+                                e,
+                            ))
+                        }
+                    }),
+                )?;
+                Some(DeadLetterEndpoint::new(endpoint))
+            }
+            (ParseErrorPolicy::DeadLetter, None) => {
+                Err(ControllerError::missing_dead_letter_endpoint(endpoint_name))?
+            }
+            _ => None,
+        };
+
         // Create probe.
-        let endpoint_id = inputs.keys().rev().next().map(|k| k + 1).unwrap_or(0);
+        let endpoint_id = self.next_input_endpoint_id.fetch_add(1, Ordering::AcqRel);
         let probe = Box::new(InputProbe::new(
             endpoint_id,
             endpoint_name,
             parser,
+            Arc::new(ParseErrorHandler {
+                policy: endpoint_config.on_parse_error,
+                max_parse_errors: endpoint_config.max_parse_errors,
+                dead_letter,
+            }),
             self.clone(),
             self.circuit_thread_unparker.clone(),
             self.backpressure_thread_unparker.clone(),
@@ -640,21 +919,121 @@ impl ControllerInner {
         let endpoint =
             transport.new_endpoint(endpoint_name, &endpoint_config.transport.config, probe)?;
 
+        // Restarting the pipeline resets the state of the circuit, so its
+        // outputs would not match the committed input positions.
+        if endpoint.is_exactly_once() && self.circuit_stateful {
+            Err(ControllerError::exactly_once_stateful_circuit(
+                endpoint_name,
+            ))?;
+        }
+
+        Ok((endpoint_id, endpoint))
+    }
+
+    /// Connect an endpoint created by [`Self::create_input`] to the pipeline.
+    fn add_input(
+        &self,
+        inputs: &mut BTreeMap<EndpointId, InputEndpointDescr>,
+        endpoint_id: EndpointId,
+        endpoint_name: &str,
+        endpoint_config: &InputEndpointConfig,
+        endpoint: Box<dyn InputEndpoint>,
+    ) {
+        // Initialize endpoint stats.
+        self.status
+            .add_input(&endpoint_id, endpoint_name, endpoint_config);
+
+        // The backpressure thread only starts endpoints when the pipeline
+        // switches to the running state; start endpoints added to a running
+        // pipeline here.
+        if self.state() == PipelineState::Running {
+            endpoint.start().unwrap_or_else(|e| {
+                self.input_transport_error(endpoint_id, endpoint_name, true, e)
+            });
+        }
+
         inputs.insert(
             endpoint_id,
             InputEndpointDescr::new(endpoint_name, endpoint),
         );
+    }
 
+    fn disconnect_input(self: &Arc<Self>, endpoint_name: &str) -> AnyResult<()> {
+        let mut inputs = self.inputs.lock().unwrap();
+
+        let endpoint_id = inputs
+            .iter()
+            .find(|(_, ep)| ep.endpoint_name == endpoint_name)
+            .map(|(endpoint_id, _)| *endpoint_id)
+            .ok_or_else(|| ControllerError::unknown_input_endpoint(endpoint_name))?;
+        let endpoint_descr = inputs.remove(&endpoint_id).unwrap();
         drop(inputs);
 
-        // Initialize endpoint stats.
-        self.status
-            .add_input(&endpoint_id, endpoint_name, endpoint_config);
+        endpoint_descr.endpoint.disconnect();
+        drop(endpoint_descr);
+
+        self.status.remove_input(&endpoint_id);
+        self.input_positions.lock().unwrap().remove(endpoint_name);
 
         self.unpark_backpressure();
         Ok(())
     }
 
+    fn reconfigure_input(
+        self: &Arc<Self>,
+        endpoint_name: &str,
+        endpoint_config: &InputEndpointConfig,
+    ) -> AnyResult<()> {
+        let endpoint_id = self
+            .inputs
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(_, ep)| ep.endpoint_name == endpoint_name)
+            .map(|(endpoint_id, _)| *endpoint_id)
+            .ok_or_else(|| ControllerError::unknown_input_endpoint(endpoint_name))?;
+
+        let mut old_config = match self.status.input_status().get(&endpoint_id) {
+            Some(endpoint_stats) => endpoint_stats.config.clone(),
+            None => Err(ControllerError::unknown_input_endpoint(endpoint_name))?,
+        };
+        old_config.max_buffered_records = endpoint_config.max_buffered_records;
+
+        if &old_config == endpoint_config {
+            self.status
+                .set_input_max_buffered_records(&endpoint_id, endpoint_config.max_buffered_records);
+            self.unpark_backpressure();
+            Ok(())
+        } else {
+            // Create the new endpoint before disconnecting the old one, so that
+            // the old endpoint keeps running if the new one fails to
+            // initialize.
+            let mut inputs = self.inputs.lock().unwrap();
+            if !inputs.contains_key(&endpoint_id) {
+                Err(ControllerError::unknown_input_endpoint(endpoint_name))?;
+            }
+            let (new_endpoint_id, endpoint) = self.create_input(endpoint_name, endpoint_config)?;
+
+            let old_endpoint = inputs.remove(&endpoint_id).unwrap();
+            old_endpoint.endpoint.disconnect();
+            self.status.remove_input(&endpoint_id);
+            self.input_positions.lock().unwrap().remove(endpoint_name);
+
+            self.add_input(
+                &mut inputs,
+                new_endpoint_id,
+                endpoint_name,
+                endpoint_config,
+                endpoint,
+            );
+            drop(inputs);
+            drop(old_endpoint);
+
+            self.unpark_backpressure();
+            Ok(())
+        }
+    }
+
     /// Unpark the circuit thread.
     fn unpark_circuit(&self) {
         self.circuit_thread_unparker.unpark();
@@ -676,6 +1055,25 @@ impl ControllerInner {
             Err(ControllerError::duplicate_output_endpoint(endpoint_name))?;
         }
 
+        let endpoint_id = self.add_output(&mut outputs, endpoint_name, endpoint_config)?;
+        drop(outputs);
+
+        // Initialize endpoint stats.
+        self.status
+            .add_output(&endpoint_id, endpoint_name, endpoint_config);
+
+        Ok(())
+    }
+
+    /// Create an output endpoint and connect it to the pipeline.
+    ///
+    /// Leaves `outputs` unmodified on error.
+    fn add_output(
+        self: &Arc<Self>,
+        outputs: &mut OutputEndpoints,
+        endpoint_name: &str,
+        endpoint_config: &OutputEndpointConfig,
+    ) -> AnyResult<EndpointId> {
         // Create output pipeline, consisting of an encoder, output probe and
         // transport endpoint; run the pipeline in a separate thread.
         //
@@ -692,6 +1090,9 @@ impl ControllerInner {
             .ok_or_else(|| ControllerError::unknown_output_stream(&endpoint_config.stream))?
             .fork();
 
+        let format = <dyn OutputFormat>::get_format(&endpoint_config.format.name)
+            .ok_or_else(|| ControllerError::unknown_output_format(&endpoint_config.format.name))?;
+
         // Create transport endpoint.
         let transport = <dyn OutputTransport>::get_transport(&endpoint_config.transport.name)
             .ok_or_else(|| {
@@ -702,7 +1103,7 @@ impl ControllerInner {
         let endpoint_name_str = endpoint_name.to_string();
 
         let self_weak = Arc::downgrade(self);
-        let endpoint = transport.new_endpoint(
+        let mut endpoint = transport.new_endpoint(
             endpoint_name,
             &endpoint_config.transport.config,
             Box::new(move |fatal: bool, e: AnyError| {
@@ -712,22 +1113,50 @@ impl ControllerInner {
             }),
         )?;
 
+        let parker = Parker::new();
+
+        // Integrated contents of the stream used to serve snapshot requests.
+        // If the controller maintains a snapshot of the stream, start from its
+        // current contents, so that endpoints connected to a running pipeline
+        // serve complete snapshots.  The snapshot cannot change while we hold
+        // the `outputs` lock.
+        let trace = if endpoint.supports_snapshots() {
+            // Wake up the output thread when the endpoint requests a snapshot.
+            let unparker = parker.unparker().clone();
+            endpoint.set_snapshot_notifier(Box::new(move || unparker.unpark()));
+
+            Some(match self.snapshots.get(endpoint_config.stream.as_ref()) {
+                Some(snapshot) => snapshot.trace.lock().unwrap().fork(),
+                None => collection_handle.new_trace(),
+            })
+        } else {
+            None
+        };
+
+        // The endpoint is shared by the probe, which pushes encoded buffers to it,
+        // and the output thread, which notifies it about step boundaries.
+        let endpoint = Arc::new(Mutex::new(endpoint));
+
         // Create probe.
         let probe = Box::new(OutputProbe::new(
             endpoint_id,
             endpoint_name,
-            endpoint,
+            endpoint.clone(),
             self.clone(),
         ));
 
         // Create encoder.
-        let format = <dyn OutputFormat>::get_format(&endpoint_config.format.name)
-            .ok_or_else(|| ControllerError::unknown_output_format(&endpoint_config.format.name))?;
-        let encoder = format.new_encoder(&endpoint_config.format.config, probe)?;
+        let encoder = match format.new_encoder(&endpoint_config.format.config, probe) {
+            Ok(encoder) => encoder,
+            Err(e) => {
+                endpoint.lock().unwrap().disconnect();
+                return Err(e);
+            }
+        };
 
-        let parker = Parker::new();
         let endpoint_state = OutputEndpointDescr::new(endpoint_name, parker.unparker().clone());
         let queue = endpoint_state.queue.clone();
+        let disconnected = endpoint_state.disconnected.clone();
         let controller = self.clone();
 
         outputs.insert(
@@ -744,42 +1173,154 @@ impl ControllerInner {
                 endpoint_id,
                 endpoint_name_string,
                 encoder,
+                endpoint,
+                trace,
                 parker,
                 queue,
+                disconnected,
                 controller,
             )
         });
 
-        drop(outputs);
+        Ok(endpoint_id)
+    }
 
-        // Initialize endpoint stats.
-        self.status
-            .add_output(&endpoint_id, endpoint_name, endpoint_config);
+    fn disconnect_output(self: &Arc<Self>, endpoint_name: &str) -> AnyResult<()> {
+        let mut outputs = self.outputs.write().unwrap();
 
+        let endpoint_id = outputs
+            .lookup_id_by_name(endpoint_name)
+            .ok_or_else(|| ControllerError::unknown_output_endpoint(endpoint_name))?;
+        let endpoint_descr = outputs.remove(&endpoint_id).unwrap();
+        drop(outputs);
+
+        self.stop_output(endpoint_id, endpoint_descr);
         Ok(())
     }
 
+    /// Stop the output thread of an endpoint removed from `outputs`.
+    fn stop_output(&self, endpoint_id: EndpointId, endpoint_descr: OutputEndpointDescr) {
+        endpoint_descr.disconnected.store(true, Ordering::Release);
+        endpoint_descr.unparker.unpark();
+
+        self.status.remove_output(&endpoint_id);
+
+        // The circuit thread may be waiting for space in the endpoint's buffer.
+        self.unpark_circuit();
+    }
+
+    fn reconfigure_output(
+        self: &Arc<Self>,
+        endpoint_name: &str,
+        endpoint_config: &OutputEndpointConfig,
+    ) -> AnyResult<()> {
+        let endpoint_id = self
+            .outputs
+            .read()
+            .unwrap()
+            .lookup_id_by_name(endpoint_name)
+            .ok_or_else(|| ControllerError::unknown_output_endpoint(endpoint_name))?;
+
+        let mut old_config = match self.status.output_status().get(&endpoint_id) {
+            Some(endpoint_stats) => endpoint_stats.config.clone(),
+            None => Err(ControllerError::unknown_output_endpoint(endpoint_name))?,
+        };
+        old_config.max_buffered_records = endpoint_config.max_buffered_records;
+
+        if &old_config == endpoint_config {
+            self.status.set_output_max_buffered_records(
+                &endpoint_id,
+                endpoint_config.max_buffered_records,
+            );
+            self.unpark_circuit();
+            Ok(())
+        } else {
+            // Create the new endpoint before disconnecting the old one, so that
+            // the old endpoint keeps running if the new one fails to
+            // initialize.
+            let mut outputs = self.outputs.write().unwrap();
+            if outputs.lookup_by_id(&endpoint_id).is_none() {
+                Err(ControllerError::unknown_output_endpoint(endpoint_name))?;
+            }
+            let new_endpoint_id = self.add_output(&mut outputs, endpoint_name, endpoint_config)?;
+            let old_endpoint = outputs.remove(&endpoint_id).unwrap();
+            drop(outputs);
+
+            self.stop_output(endpoint_id, old_endpoint);
+            self.status
+                .add_output(&new_endpoint_id, endpoint_name, endpoint_config);
+            Ok(())
+        }
+    }
+
     fn output_thread_func(
         endpoint_id: EndpointId,
         endpoint_name: String,
         mut encoder: Box<dyn Encoder>,
+        endpoint: Arc<Mutex<Box<dyn OutputEndpoint>>>,
+        mut trace: Option<Box<dyn SerTrace>>,
         parker: Parker,
         queue: Arc<BatchQueue>,
+        disconnected: Arc<AtomicBool>,
         controller: Arc<ControllerInner>,
     ) {
         loop {
-            if controller.state() == PipelineState::Terminated {
+            if controller.state() == PipelineState::Terminated
+                || disconnected.load(Ordering::Acquire)
+            {
+                endpoint.lock().unwrap().disconnect();
                 return;
             }
 
+            // Serve snapshot requests between steps, so that the snapshot
+            // includes exactly the outputs sent to the endpoint so far.
+            if let Some(trace) = &mut trace {
+                if endpoint.lock().unwrap().snapshot_requested() {
+                    Self::send_snapshot(
+                        endpoint_id,
+                        &endpoint_name,
+                        &mut *encoder,
+                        &endpoint,
+                        trace.consolidate(),
+                        &controller,
+                    );
+                }
+            }
+
             // Dequeue the next output batch and push it to the encoder.
-            if let Some((data, processed_records)) = queue.pop() {
-                let num_records = data.iter().map(|b| b.len()).sum();
+            if let Some(StepOutput {
+                step,
+                batches,
+                processed_records,
+                input_positions,
+            }) = queue.pop()
+            {
+                let num_records = batches.iter().map(|b| b.len()).sum();
+
+                endpoint
+                    .lock()
+                    .unwrap()
+                    .batch_start(step)
+                    .unwrap_or_else(|e| {
+                        controller.output_transport_error(endpoint_id, &endpoint_name, true, e)
+                    });
 
                 encoder
-                    .encode(data.as_slice())
+                    .encode(batches.as_slice())
                     .unwrap_or_else(|e| controller.encode_error(endpoint_id, &endpoint_name, e));
 
+                if let Some(trace) = &mut trace {
+                    trace.insert(batches.as_slice());
+                }
+
+                endpoint
+                    .lock()
+                    .unwrap()
+                    .batch_end(step, &input_positions)
+                    .unwrap_or_else(|e| {
+                        controller.output_transport_error(endpoint_id, &endpoint_name, true, e)
+                    });
+
                 // `num_records` output records have been transmitted --
                 // update output stats, wake up the circuit thread if the
                 // number of queued records drops below high water mark.
@@ -797,6 +1338,29 @@ impl ControllerInner {
         }
     }
 
+    /// Push `snapshot` to an output endpoint that requested it.
+    fn send_snapshot(
+        endpoint_id: EndpointId,
+        endpoint_name: &str,
+        encoder: &mut dyn Encoder,
+        endpoint: &Mutex<Box<dyn OutputEndpoint>>,
+        snapshot: Arc<dyn SerBatch>,
+        controller: &ControllerInner,
+    ) {
+        if let Err(e) = endpoint.lock().unwrap().snapshot_start() {
+            controller.output_transport_error(endpoint_id, endpoint_name, false, e);
+            return;
+        }
+
+        encoder
+            .encode(&[snapshot])
+            .unwrap_or_else(|e| controller.encode_error(endpoint_id, endpoint_name, e));
+
+        endpoint.lock().unwrap().snapshot_end().unwrap_or_else(|e| {
+            controller.output_transport_error(endpoint_id, endpoint_name, false, e)
+        });
+    }
+
     fn state(self: &Arc<Self>) -> PipelineState {
         PipelineState::from_u32(self.state.load(Ordering::Acquire)).unwrap()
     }
@@ -856,9 +1420,71 @@ impl ControllerInner {
         ));
     }
 
-    fn parse_error(&self, endpoint_id: EndpointId, endpoint_name: &str, error: AnyError) {
-        self.status.parse_error(endpoint_id);
-        self.error(ControllerError::parse_error(endpoint_name, error));
+    /// Process errors returned by the parser of an input endpoint.
+    ///
+    /// Updates endpoint stats and forwards errors to the dead-letter endpoint
+    /// or the error callback.  Fails the pipeline, i.e., reports a fatal error
+    /// and pauses the pipeline, if required by the error policy of the
+    /// endpoint.
+    ///
+    /// Returns `true` if the pipeline failed, in which case the caller must
+    /// discard the input buffer that contains the errors.
+    fn parse_errors(
+        self: &Arc<Self>,
+        endpoint_id: EndpointId,
+        endpoint_name: &str,
+        handler: &ParseErrorHandler,
+        errors: Vec<ParseError>,
+    ) -> bool {
+        if errors.is_empty() {
+            return false;
+        }
+
+        let num_errors = self.status.parse_errors(endpoint_id, errors.len() as u64);
+
+        let fatal_error = match (handler.policy, handler.max_parse_errors) {
+            (ParseErrorPolicy::Fail, _) => Some(AnyError::new(errors[0].clone())),
+            (_, Some(max_parse_errors)) if num_errors > max_parse_errors => {
+                Some(AnyError::msg(format!(
+                    "the number of parse errors ({num_errors}) exceeds 'max_parse_errors' ({max_parse_errors})"
+                )))
+            }
+            _ => None,
+        };
+
+        match (&handler.dead_letter, handler.policy) {
+            (Some(dead_letter), _) => {
+                dead_letter
+                    .send(endpoint_name, &errors)
+                    .unwrap_or_else(|e| {
+                        self.error(ControllerError::output_transport_error(
+                            &format!("{endpoint_name}.dead_letter"),
+                            false,
+                            e,
+                        ))
+                    });
+            }
+            // Reported as a fatal error below.
+            (None, ParseErrorPolicy::Fail) => {}
+            (None, _) => {
+                for error in errors.into_iter() {
+                    self.error(ControllerError::parse_error(
+                        endpoint_name,
+                        false,
+                        AnyError::new(error),
+                    ));
+                }
+            }
+        }
+
+        if let Some(error) = fatal_error {
+            self.status.fatal_parse_error(endpoint_id, &error);
+            self.error(ControllerError::parse_error(endpoint_name, true, error));
+            self.pause();
+            true
+        } else {
+            false
+        }
     }
 
     fn encode_error(&self, endpoint_id: EndpointId, endpoint_name: &str, error: AnyError) {
@@ -890,12 +1516,21 @@ impl ControllerInner {
     }
 }
 
+/// Parse error handling policy of an input endpoint, shared by all probes
+/// of the endpoint.
+struct ParseErrorHandler {
+    policy: ParseErrorPolicy,
+    max_parse_errors: Option<u64>,
+    dead_letter: Option<DeadLetterEndpoint>,
+}
+
 /// An input probe inserted between the transport endpoint and the parser to
 /// track stats and errors.
 struct InputProbe {
     endpoint_id: EndpointId,
     endpoint_name: String,
     parser: Box<dyn Parser>,
+    error_handler: Arc<ParseErrorHandler>,
     controller: Arc<ControllerInner>,
     circuit_thread_unparker: Unparker,
     backpressure_thread_unparker: Unparker,
@@ -906,6 +1541,7 @@ impl InputProbe {
         endpoint_id: EndpointId,
         endpoint_name: &str,
         parser: Box<dyn Parser>,
+        error_handler: Arc<ParseErrorHandler>,
         controller: Arc<ControllerInner>,
         circuit_thread_unparker: Unparker,
         backpressure_thread_unparker: Unparker,
@@ -914,36 +1550,65 @@ impl InputProbe {
             endpoint_id,
             endpoint_name: endpoint_name.to_owned(),
             parser,
+            error_handler,
             controller,
             circuit_thread_unparker,
             backpressure_thread_unparker,
         }
     }
+
+    /// Parse `data` and push valid records to the input handle.
+    ///
+    /// Returns `false` if the pipeline failed due to parse errors, in which
+    /// case the entire buffer is discarded.
+    fn parse_input(&mut self, data: &[u8]) -> bool {
+        // println!("input consumer {} bytes", data.len());
+        // Pass input buffer to the parser.
+        let (num_records, errors) = self.parser.input(data);
+
+        if self.controller.parse_errors(
+            self.endpoint_id,
+            &self.endpoint_name,
+            &self.error_handler,
+            errors,
+        ) {
+            // The pipeline failed: discard the entire buffer.
+            self.parser.clear();
+            false
+        } else {
+            // Push valid records to the input handle, update stats.
+            self.parser.flush();
+            self.controller.status.input_batch(
+                self.endpoint_id,
+                data.len(),
+                num_records,
+                &self.controller.status.global_config,
+                &self.circuit_thread_unparker,
+                &self.backpressure_thread_unparker,
+            );
+            true
+        }
+    }
 }
 
 /// `InputConsumer` interface exposed to the transport endpoint.
 impl InputConsumer for InputProbe {
     fn input(&mut self, data: &[u8]) {
-        // println!("input consumer {} bytes", data.len());
-        // Pass input buffer to the parser.
-        match self.parser.input(data) {
-            Ok(num_records) => {
-                // Success: push data to the input handle, update stats.
-                self.parser.flush();
-                self.controller.status.input_batch(
-                    self.endpoint_id,
-                    data.len(),
-                    num_records,
-                    &self.controller.status.global_config,
-                    &self.circuit_thread_unparker,
-                    &self.backpressure_thread_unparker,
-                );
-            }
-            Err(error) => {
-                self.parser.clear();
-                self.controller
-                    .parse_error(self.endpoint_id, &self.endpoint_name, error);
-            }
+        self.parse_input(data);
+    }
+
+    fn input_with_position(&mut self, data: &[u8], position: InputPosition) {
+        let controller = self.controller.clone();
+        let _step_lock = controller.step_lock.read().unwrap();
+
+        // Don't advance the position past a buffer that failed the pipeline, so
+        // that it is processed again after restart.
+        if self.parse_input(data) {
+            controller
+                .input_positions
+                .lock()
+                .unwrap()
+                .insert(self.endpoint_name.clone(), position);
         }
     }
 
@@ -952,21 +1617,49 @@ impl InputConsumer for InputProbe {
         // no new data has been received, the parser may contain some partially
         // parsed data and may be waiting for, e.g., and end-of-line or
         // end-of-file to finish parsing it).
-        match self.parser.eoi() {
-            Ok(num_records) => {
-                self.parser.flush();
-                self.controller.status.eoi(
-                    self.endpoint_id,
-                    num_records,
-                    &self.circuit_thread_unparker,
-                );
-            }
-            Err(error) => {
-                self.parser.clear();
-                self.controller
-                    .error(ControllerError::parse_error(&self.endpoint_name, error));
-            }
+        let (mut num_records, errors) = self.parser.eoi();
+
+        if self.controller.parse_errors(
+            self.endpoint_id,
+            &self.endpoint_name,
+            &self.error_handler,
+            errors,
+        ) {
+            self.parser.clear();
+            num_records = 0;
+        } else {
+            self.parser.flush();
         }
+
+        self.controller
+            .status
+            .eoi(self.endpoint_id, num_records, &self.circuit_thread_unparker);
+    }
+
+    fn end_of_fragment(&mut self) {
+        let (num_records, errors) = self.parser.eoi();
+
+        if self.controller.parse_errors(
+            self.endpoint_id,
+            &self.endpoint_name,
+            &self.error_handler,
+            errors,
+        ) {
+            self.parser.clear();
+        } else {
+            self.parser.flush();
+            self.controller.status.input_batch(
+                self.endpoint_id,
+                0,
+                num_records,
+                &self.controller.status.global_config,
+                &self.circuit_thread_unparker,
+                &self.backpressure_thread_unparker,
+            );
+        }
+
+        // Start the next fragment with a parser in its initial state.
+        self.parser = self.parser.fork();
     }
 
     fn error(&mut self, fatal: bool, error: AnyError) {
@@ -979,6 +1672,7 @@ impl InputConsumer for InputProbe {
             self.endpoint_id,
             &self.endpoint_name,
             self.parser.fork(),
+            self.error_handler.clone(),
             self.controller.clone(),
             self.circuit_thread_unparker.clone(),
             self.backpressure_thread_unparker.clone(),
@@ -991,7 +1685,7 @@ impl InputConsumer for InputProbe {
 struct OutputProbe {
     endpoint_id: EndpointId,
     endpoint_name: String,
-    endpoint: Box<dyn OutputEndpoint>,
+    endpoint: Arc<Mutex<Box<dyn OutputEndpoint>>>,
     controller: Arc<ControllerInner>,
 }
 
@@ -999,7 +1693,7 @@ impl OutputProbe {
     pub fn new(
         endpoint_id: EndpointId,
         endpoint_name: &str,
-        endpoint: Box<dyn OutputEndpoint>,
+        endpoint: Arc<Mutex<Box<dyn OutputEndpoint>>>,
         controller: Arc<ControllerInner>,
     ) -> Self {
         Self {
@@ -1015,7 +1709,8 @@ impl OutputConsumer for OutputProbe {
     fn push_buffer(&mut self, buffer: &[u8]) {
         let num_bytes = buffer.len();
 
-        match self.endpoint.push_buffer(buffer) {
+        let result = self.endpoint.lock().unwrap().push_buffer(buffer);
+        match result {
             Ok(()) => {
                 self.controller
                     .status
@@ -1037,10 +1732,16 @@ impl OutputConsumer for OutputProbe {
 mod test {
     use crate::{
         test::{generate_test_batch, test_circuit, wait, TestStruct},
-        Controller, PipelineConfig,
+        Controller, ControllerError, PipelineConfig,
     };
     use csv::{ReaderBuilder as CsvReaderBuilder, WriterBuilder as CsvWriterBuilder};
-    use std::fs::remove_file;
+    use dbsp::circuit::CircuitConfig;
+    use serde_json::Value as JsonValue;
+    use std::{
+        fs::{read_to_string, remove_file},
+        io::Write,
+        sync::{atomic::Ordering, Arc, Mutex},
+    };
     use tempfile::NamedTempFile;
 
     use proptest::prelude::*;
@@ -1057,7 +1758,7 @@ mod test {
             input_buffer_size_bytes in 1..1000usize,
             output_buffer_size_records in 1..100usize)
         {
-            let (circuit, catalog) = test_circuit(4);
+            let (circuit, catalog) = test_circuit(CircuitConfig::from(4));
 
             let temp_input_file = NamedTempFile::new().unwrap();
             let temp_output_path = NamedTempFile::new().unwrap().into_temp_path();
@@ -1147,4 +1848,125 @@ outputs:
             assert_eq!(actual, expected);
         }
     }
+
+    fn parse_error_config(input_path: &str, on_parse_error: &str) -> PipelineConfig {
+        let config_str = format!(
+            r#"
+name: test
+inputs:
+    test_input1:
+        stream: test_input1
+        transport:
+            name: file
+            config:
+                path: {input_path:?}
+                follow: false
+        format:
+            name: csv
+{on_parse_error}
+        "#
+        );
+
+        serde_yaml::from_str(&config_str).unwrap()
+    }
+
+    #[test]
+    fn test_dead_letter() {
+        let (circuit, catalog) = test_circuit(CircuitConfig::from(1));
+
+        let mut temp_input_file = NamedTempFile::new().unwrap();
+        temp_input_file
+            .write_all(b"1,true,,foo\nxxx\n2,false,5,bar\n")
+            .unwrap();
+        let dead_letter_file = NamedTempFile::new().unwrap();
+
+        let config = parse_error_config(
+            temp_input_file.path().to_str().unwrap(),
+            &format!(
+                r#"
+        on_parse_error: dead_letter
+        dead_letter:
+            name: file
+            config:
+                path: {:?}"#,
+                dead_letter_file.path().to_str().unwrap()
+            ),
+        );
+
+        let controller = Controller::with_config(
+            circuit,
+            catalog,
+            &config,
+            Box::new(|e| panic!("error: {e}")),
+        )
+        .unwrap();
+        controller.start();
+
+        // The invalid record doesn't prevent the pipeline from ingesting
+        // the rest of the input.
+        wait(|| controller.pipeline_complete(), None);
+        let input_status = controller.status().input_status();
+        let metrics = &input_status.get(&0).unwrap().metrics;
+        assert_eq!(metrics.total_records.load(Ordering::Acquire), 2);
+        assert_eq!(metrics.num_parse_errors.load(Ordering::Acquire), 1);
+        drop(input_status);
+
+        controller.stop().unwrap();
+
+        let dead_letter = read_to_string(dead_letter_file.path()).unwrap();
+        let records = dead_letter
+            .lines()
+            .map(|line| serde_json::from_str::<JsonValue>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0]["endpoint"], "test_input1");
+        assert_eq!(records[0]["offset"], 12);
+        assert_eq!(records[0]["data"], "xxx");
+        assert!(records[0]["error"].is_string());
+    }
+
+    #[test]
+    fn test_max_parse_errors() {
+        let (circuit, catalog) = test_circuit(CircuitConfig::from(1));
+
+        let mut temp_input_file = NamedTempFile::new().unwrap();
+        temp_input_file
+            .write_all(b"1,true,,foo\nxxx\nyyy\n2,false,5,bar\n")
+            .unwrap();
+
+        let config = parse_error_config(
+            temp_input_file.path().to_str().unwrap(),
+            "        max_parse_errors: 1",
+        );
+
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let errors_clone = errors.clone();
+        let controller = Controller::with_config(
+            circuit,
+            catalog,
+            &config,
+            Box::new(move |e| {
+                if let ControllerError::ParseError { fatal, .. } = e {
+                    errors_clone.lock().unwrap().push(fatal);
+                }
+            }),
+        )
+        .unwrap();
+        controller.start();
+
+        // Two non-fatal errors followed by a fatal error.
+        wait(|| errors.lock().unwrap().len() == 3, None);
+        assert_eq!(*errors.lock().unwrap(), vec![false, false, true]);
+        assert!(controller
+            .status()
+            .input_status()
+            .get(&0)
+            .unwrap()
+            .fatal_error
+            .lock()
+            .unwrap()
+            .is_some());
+
+        controller.stop().unwrap();
+    }
 }
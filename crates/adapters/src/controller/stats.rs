@@ -182,6 +182,38 @@ impl ControllerStatus {
         );
     }
 
+    /// Remove stats of a disconnected input endpoint.
+    pub fn remove_input(&self, endpoint_id: &EndpointId) {
+        self.inputs.write().unwrap().remove(endpoint_id);
+    }
+
+    /// Remove stats of a disconnected output endpoint.
+    pub fn remove_output(&self, endpoint_id: &EndpointId) {
+        self.outputs.write().unwrap().remove(endpoint_id);
+    }
+
+    /// Change the backpressure threshold of an input endpoint.
+    pub fn set_input_max_buffered_records(
+        &self,
+        endpoint_id: &EndpointId,
+        max_buffered_records: u64,
+    ) {
+        if let Some(endpoint_stats) = self.inputs.write().unwrap().get_mut(endpoint_id) {
+            endpoint_stats.config.max_buffered_records = max_buffered_records;
+        }
+    }
+
+    /// Change the backpressure threshold of an output endpoint.
+    pub fn set_output_max_buffered_records(
+        &self,
+        endpoint_id: &EndpointId,
+        max_buffered_records: u64,
+    ) {
+        if let Some(endpoint_stats) = self.outputs.write().unwrap().get_mut(endpoint_id) {
+            endpoint_stats.config.max_buffered_records = max_buffered_records;
+        }
+    }
+
     /// Total number of records currently buffered by all input endpoints.
     pub fn num_buffered_input_records(&self) -> u64 {
         self.global_metrics.num_buffered_input_records()
@@ -384,9 +416,21 @@ impl ControllerStatus {
         })
     }
 
-    pub fn parse_error(&self, endpoint_id: EndpointId) {
+    /// Increment the parse error counter of an input endpoint by
+    /// `num_errors`.
+    ///
+    /// Returns the total number of parse errors at the endpoint.
+    pub fn parse_errors(&self, endpoint_id: EndpointId, num_errors: u64) -> u64 {
+        self.input_status()
+            .get(&endpoint_id)
+            .map(|endpoint_stats| endpoint_stats.parse_errors(num_errors))
+            .unwrap_or(0)
+    }
+
+    /// Record a parse error that caused the pipeline to fail.
+    pub fn fatal_parse_error(&self, endpoint_id: EndpointId, error: &AnyError) {
         if let Some(endpoint_stats) = self.input_status().get(&endpoint_id) {
-            endpoint_stats.parse_error();
+            endpoint_stats.fatal_error(error);
         }
     }
 
@@ -471,7 +515,7 @@ pub struct InputEndpointMetrics {
 pub struct InputEndpointStatus {
     pub endpoint_name: String,
 
-    /// Endpoint configuration (doesn't change).
+    /// Endpoint configuration (only `max_buffered_records` can change).
     pub config: InputEndpointConfig,
 
     /// Performance metrics.
@@ -527,9 +571,13 @@ impl InputEndpointStatus {
         self.metrics.end_of_input.load(Ordering::Acquire)
     }
 
-    /// Increment parser error counter.
-    fn parse_error(&self) {
-        self.metrics.num_parse_errors.fetch_add(1, Ordering::AcqRel);
+    /// Increment parser error counter by `num_errors`; return the new
+    /// value of the counter.
+    fn parse_errors(&self, num_errors: u64) -> u64 {
+        self.metrics
+            .num_parse_errors
+            .fetch_add(num_errors, Ordering::AcqRel)
+            + num_errors
     }
 
     /// Increment transport error counter.  If this is the first fatal error,
@@ -539,10 +587,16 @@ impl InputEndpointStatus {
             .num_transport_errors
             .fetch_add(1, Ordering::AcqRel);
         if fatal {
-            let mut fatal_error = self.fatal_error.lock().unwrap();
-            if fatal_error.is_none() {
-                *fatal_error = Some(error.to_string());
-            }
+            self.fatal_error(error);
+        }
+    }
+
+    /// Save `error` in `self.fatal_error` unless a fatal error has already
+    /// occurred.
+    fn fatal_error(&self, error: &AnyError) {
+        let mut fatal_error = self.fatal_error.lock().unwrap();
+        if fatal_error.is_none() {
+            *fatal_error = Some(error.to_string());
         }
     }
 }
@@ -571,7 +625,7 @@ pub struct OutputEndpointMetrics {
 pub struct OutputEndpointStatus {
     pub endpoint_name: String,
 
-    /// Endpoint configuration (doesn't change).
+    /// Endpoint configuration (only `max_buffered_records` can change).
     pub config: OutputEndpointConfig,
 
     /// Performance metrics.
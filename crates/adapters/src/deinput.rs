@@ -1,6 +1,7 @@
 use dbsp::{algebra::ZRingValue, CollectionHandle, DBData, DBWeight, InputHandle, UpsertHandle};
 use erased_serde::{deserialize, Deserializer as ErasedDeserializer, Error as EError};
-use serde::Deserialize;
+use serde::{de::Error as _, Deserialize};
+use std::cmp::Ordering;
 
 /// Maximal buffer size reused across clock cycles.
 ///
@@ -163,6 +164,35 @@ pub trait DeCollectionHandle: Send {
     /// documentation for details.
     fn delete(&mut self, deserializer: &mut dyn ErasedDeserializer) -> Result<(), EError>;
 
+    /// Buffer an update with an explicit weight.
+    ///
+    /// Equivalent to calling [`insert`](`Self::insert`) `weight` times if
+    /// `weight` is positive or [`delete`](`Self::delete`) `-weight` times if
+    /// `weight` is negative, but buffers a single update regardless of the
+    /// magnitude of `weight`.  Streams with set or map semantics only
+    /// take the sign of the weight into account.  Updates with zero weight
+    /// are ignored.
+    ///
+    /// Returns an error if deserialization fails or if `weight` does not fit
+    /// the weight type of the underlying input stream.
+    fn update(
+        &mut self,
+        deserializer: &mut dyn ErasedDeserializer,
+        weight: i64,
+    ) -> Result<(), EError>;
+
+    /// Buffer a delete update followed by an insert update.
+    ///
+    /// Equivalent to calling [`delete`](`Self::delete`) with `delete` and
+    /// [`insert`](`Self::insert`) with `insert`, except that the updates are
+    /// only buffered if both records deserialize successfully.  On error,
+    /// neither update is buffered.
+    fn replace(
+        &mut self,
+        delete: &mut dyn ErasedDeserializer,
+        insert: &mut dyn ErasedDeserializer,
+    ) -> Result<(), EError>;
+
     /// Reserve space for at least `reservation` more updates in the
     /// internal input buffer.
     ///
@@ -230,7 +260,7 @@ impl<K, R> DeZSetHandle<K, R> {
 impl<K, R> DeCollectionHandle for DeZSetHandle<K, R>
 where
     K: DBData + for<'de> Deserialize<'de>,
-    R: DBWeight + ZRingValue,
+    R: DBWeight + ZRingValue + TryFrom<i64>,
 {
     fn insert(&mut self, deserializer: &mut dyn ErasedDeserializer) -> Result<(), EError> {
         let key = deserialize::<K>(deserializer)?;
@@ -246,6 +276,35 @@ where
         Ok(())
     }
 
+    fn update(
+        &mut self,
+        deserializer: &mut dyn ErasedDeserializer,
+        weight: i64,
+    ) -> Result<(), EError> {
+        if weight == 0 {
+            return Ok(());
+        }
+        let key = deserialize::<K>(deserializer)?;
+        let weight = R::try_from(weight)
+            .map_err(|_| EError::custom(format!("weight {weight} is out of range")))?;
+
+        self.updates.push((key, weight));
+        Ok(())
+    }
+
+    fn replace(
+        &mut self,
+        delete: &mut dyn ErasedDeserializer,
+        insert: &mut dyn ErasedDeserializer,
+    ) -> Result<(), EError> {
+        let old = deserialize::<K>(delete)?;
+        let new = deserialize::<K>(insert)?;
+
+        self.updates.push((old, R::one().neg()));
+        self.updates.push((new, R::one()));
+        Ok(())
+    }
+
     fn reserve(&mut self, reservation: usize) {
         self.updates.reserve(reservation);
     }
@@ -308,6 +367,31 @@ where
         Ok(())
     }
 
+    fn update(
+        &mut self,
+        deserializer: &mut dyn ErasedDeserializer,
+        weight: i64,
+    ) -> Result<(), EError> {
+        match weight.cmp(&0) {
+            Ordering::Greater => self.insert(deserializer),
+            Ordering::Less => self.delete(deserializer),
+            Ordering::Equal => Ok(()),
+        }
+    }
+
+    fn replace(
+        &mut self,
+        delete: &mut dyn ErasedDeserializer,
+        insert: &mut dyn ErasedDeserializer,
+    ) -> Result<(), EError> {
+        let old = deserialize::<K>(delete)?;
+        let new = deserialize::<K>(insert)?;
+
+        self.updates.push((old, false));
+        self.updates.push((new, true));
+        Ok(())
+    }
+
     fn reserve(&mut self, reservation: usize) {
         self.updates.reserve(reservation);
     }
@@ -376,6 +460,32 @@ where
         Ok(())
     }
 
+    fn update(
+        &mut self,
+        deserializer: &mut dyn ErasedDeserializer,
+        weight: i64,
+    ) -> Result<(), EError> {
+        match weight.cmp(&0) {
+            Ordering::Greater => self.insert(deserializer),
+            Ordering::Less => self.delete(deserializer),
+            Ordering::Equal => Ok(()),
+        }
+    }
+
+    fn replace(
+        &mut self,
+        delete: &mut dyn ErasedDeserializer,
+        insert: &mut dyn ErasedDeserializer,
+    ) -> Result<(), EError> {
+        let old_key = deserialize::<K>(delete)?;
+        let val = deserialize::<V>(insert)?;
+        let key = (self.key_func)(&val);
+
+        self.updates.push((old_key, None));
+        self.updates.push((key, Some(val)));
+        Ok(())
+    }
+
     fn reserve(&mut self, reservation: usize) {
         self.updates.reserve(reservation);
     }
@@ -723,4 +833,127 @@ mod test {
 
         dbsp.kill().unwrap();
     }
+
+    #[test]
+    fn test_weighted_update() {
+        let (mut dbsp, mut input_handles, output_handles) = decollection_test_circuit(NUM_WORKERS);
+
+        let input = TestStruct {
+            id: 1,
+            s: "foo".to_string(),
+            b: true,
+            o: None,
+        };
+        let input_json = to_json_string(&input).unwrap();
+
+        // Push `input` with `weight` to the z-set and set streams.
+        let mut update = |weight: i64| {
+            for handle in [&mut input_handles.0, &mut input_handles.1] {
+                let mut deserializer = JsonDeserializer::new(StrRead::new(&input_json));
+                let mut deserializer = <dyn ErasedDeserializer>::erase(&mut deserializer);
+                handle.update(&mut deserializer, weight).unwrap();
+                handle.flush();
+            }
+        };
+
+        // A large weight is applied as a single update.
+        update(1_000_000_000_000);
+        dbsp.step().unwrap();
+        assert_eq!(
+            output_handles.0.consolidate(),
+            OrdZSet::from_tuples((), vec![(input.clone(), 1_000_000_000_000)])
+        );
+        assert_eq!(
+            output_handles.1.consolidate(),
+            OrdZSet::from_tuples((), vec![(input.clone(), 1)])
+        );
+
+        // Zero weights are ignored.
+        update(0);
+        dbsp.step().unwrap();
+        assert_eq!(output_handles.0.consolidate(), OrdZSet::empty(()));
+        assert_eq!(output_handles.1.consolidate(), OrdZSet::empty(()));
+
+        update(-1_000_000_000_000);
+        dbsp.step().unwrap();
+        assert_eq!(
+            output_handles.0.consolidate(),
+            OrdZSet::from_tuples((), vec![(input.clone(), -1_000_000_000_000)])
+        );
+        assert_eq!(
+            output_handles.1.consolidate(),
+            OrdZSet::from_tuples((), vec![(input, -1)])
+        );
+
+        dbsp.kill().unwrap();
+    }
+
+    #[test]
+    fn test_replace() {
+        let (mut dbsp, mut input_handles, output_handles) = decollection_test_circuit(NUM_WORKERS);
+
+        let old = TestStruct {
+            id: 1,
+            s: "foo".to_string(),
+            b: true,
+            o: None,
+        };
+        let new = TestStruct {
+            s: "bar".to_string(),
+            ..old.clone()
+        };
+        insert_csv(
+            &mut dbsp,
+            &mut input_handles,
+            &output_handles,
+            &[old.clone()],
+        );
+
+        // Replace `old` with the record serialized in `new_json`.
+        let mut replace = |new_json: &str| -> Vec<bool> {
+            let old_json = to_json_string(&old).unwrap();
+            let old_id = to_json_string(&old.id).unwrap();
+            [
+                (&mut input_handles.0, &old_json),
+                (&mut input_handles.1, &old_json),
+                (&mut input_handles.2, &old_id),
+            ]
+            .into_iter()
+            .map(|(handle, delete)| {
+                let mut delete = JsonDeserializer::new(StrRead::new(delete));
+                let mut delete = <dyn ErasedDeserializer>::erase(&mut delete);
+                let mut insert = JsonDeserializer::new(StrRead::new(new_json));
+                let mut insert = <dyn ErasedDeserializer>::erase(&mut insert);
+                let result = handle.replace(&mut delete, &mut insert).is_ok();
+                handle.flush();
+                result
+            })
+            .collect()
+        };
+
+        // An invalid new record leaves the old record in place.
+        assert_eq!(replace(r#"{"id": "foo"}"#), vec![false, false, false]);
+        dbsp.step().unwrap();
+        assert_eq!(output_handles.0.consolidate(), OrdZSet::empty(()));
+        assert_eq!(output_handles.1.consolidate(), OrdZSet::empty(()));
+        assert_eq!(output_handles.2.consolidate(), OrdIndexedZSet::empty(()));
+
+        assert_eq!(
+            replace(&to_json_string(&new).unwrap()),
+            vec![true, true, true]
+        );
+        dbsp.step().unwrap();
+        let zset = OrdZSet::from_tuples((), vec![(old.clone(), -1), (new.clone(), 1)]);
+        assert_eq!(output_handles.0.consolidate(), zset);
+        assert_eq!(output_handles.1.consolidate(), zset);
+        assert_eq!(
+            output_handles.2.consolidate(),
+            <OrdIndexedZSet<i64, TestStruct, isize, usize>>::from_tuples(
+                (),
+                vec![((1, old), -1), ((1, new), 1)]
+            )
+        );
+
+        dbsp.kill().unwrap();
+    }
 }
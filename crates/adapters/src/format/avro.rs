@@ -0,0 +1,1134 @@
+use crate::{
+    format::{Encoder, InputFormat, OutputFormat, ParseError, Parser},
+    DeCollectionHandle, OutputConsumer, SerBatch,
+};
+use anyhow::{Error as AnyError, Result as AnyResult};
+use apache_avro::{
+    from_avro_datum, rabin::Rabin, to_avro_datum, to_value, types::Value as AvroValue,
+    Reader as AvroReader, Schema as AvroSchema,
+};
+use erased_serde::Deserializer as ErasedDeserializer;
+use reqwest::blocking::Client as HttpClient;
+use serde::{
+    de::{
+        value::{Error as DeError, MapDeserializer, SeqDeserializer},
+        Deserializer, Error as _, IntoDeserializer, Visitor,
+    },
+    forward_to_deserialize_any, Deserialize, Serialize,
+};
+use serde_json::{json, Value as JsonValue};
+use serde_yaml::Value as YamlValue;
+use std::{borrow::Cow, collections::HashMap, mem::take, sync::Arc, thread};
+use utoipa::ToSchema;
+
+/// Magic bytes at the start of an Avro object container file.
+const CONTAINER_MAGIC: [u8; 4] = [b'O', b'b', b'j', 1];
+
+/// Marker at the start of a message in the Avro single-object encoding.
+const SINGLE_OBJECT_MAGIC: [u8; 2] = [0xc3, 0x01];
+
+/// Length of the single-object encoding header: marker followed by the
+/// 8-byte Rabin fingerprint of the schema.
+const SINGLE_OBJECT_HEADER_LEN: usize = 10;
+
+/// Length of the Confluent wire format header: a zero byte followed by the
+/// 4-byte big-endian schema id.
+const CONFLUENT_HEADER_LEN: usize = 5;
+
+/// Supported Avro update envelopes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AvroUpdateFormat {
+    /// Each Avro record is a row of the collection, which is always
+    /// interpreted as an insertion.
+    Raw,
+
+    /// Each Avro record contains a `data` field with the row and a `weight`
+    /// field of type `long`.  Positive weights insert the row `weight`
+    /// times, negative weights delete it `-weight` times.
+    Weighted,
+}
+
+impl Default for AvroUpdateFormat {
+    fn default() -> Self {
+        Self::Raw
+    }
+}
+
+/// Wrap the schema of a row in the schema of a weighted update.
+fn weighted_update_schema(row_schema: &str) -> AnyResult<String> {
+    let row_schema: JsonValue = serde_json::from_str(row_schema)
+        .map_err(|e| AnyError::msg(format!("invalid Avro schema: {e}")))?;
+
+    Ok(json!({
+        "type": "record",
+        "name": "Update",
+        "namespace": "dbsp",
+        "fields": [
+            {"name": "data", "type": row_schema},
+            {"name": "weight", "type": "long"}
+        ]
+    })
+    .to_string())
+}
+
+/// Minimal client of a Confluent-compatible schema registry.
+///
+/// `reqwest`'s blocking client panics when used or dropped inside a tokio
+/// runtime, which is where parsers and encoders run when data arrives over
+/// HTTP, so each request runs on a dedicated thread with its own client.
+struct SchemaRegistry {
+    url: String,
+
+    /// Schemas retrieved from the registry so far, indexed by id.
+    schemas: HashMap<u32, Arc<AvroSchema>>,
+}
+
+#[derive(Deserialize)]
+struct RegistrySchema {
+    schema: String,
+}
+
+#[derive(Deserialize)]
+struct RegistryId {
+    id: u32,
+}
+
+impl SchemaRegistry {
+    fn new(url: &str) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+            schemas: HashMap::new(),
+        }
+    }
+
+    /// Run `request` on a dedicated thread.
+    fn request<T, F>(request: F) -> AnyResult<T>
+    where
+        F: FnOnce(&HttpClient) -> AnyResult<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        thread::spawn(move || request(&HttpClient::new()))
+            .join()
+            .map_err(|_| AnyError::msg("schema registry request panicked"))?
+    }
+
+    /// Lookup schema by id, fetching it from the registry if necessary.
+    fn schema(&mut self, id: u32) -> AnyResult<Arc<AvroSchema>> {
+        if let Some(schema) = self.schemas.get(&id) {
+            return Ok(schema.clone());
+        }
+
+        let url = format!("{}/schemas/ids/{id}", self.url);
+        let schema = Self::request({
+            let url = url.clone();
+            move |client| {
+                let response = client.get(&url).send()?.error_for_status()?;
+                let RegistrySchema { schema } = serde_json::from_reader(response)?;
+                Ok(schema)
+            }
+        })?;
+        let schema =
+            Arc::new(AvroSchema::parse_str(&schema).map_err(|e| {
+                AnyError::msg(format!("invalid Avro schema returned by '{url}': {e}"))
+            })?);
+
+        self.schemas.insert(id, schema.clone());
+        Ok(schema)
+    }
+
+    /// Register `schema` under `subject`; returns the id assigned to the
+    /// schema by the registry.
+    fn register(&self, subject: &str, schema: &str) -> AnyResult<u32> {
+        let url = format!("{}/subjects/{subject}/versions", self.url);
+        let body = json!({ "schema": schema }).to_string();
+
+        Self::request(move |client| {
+            let response = client
+                .post(&url)
+                .header("Content-Type", "application/vnd.schemaregistry.v1+json")
+                .body(body)
+                .send()?
+                .error_for_status()?;
+            let RegistryId { id } = serde_json::from_reader(response)?;
+
+            Ok(id)
+        })
+    }
+}
+
+/// Deserializer that reads a Rust value from an Avro value.
+struct AvroDeserializer<'a> {
+    value: &'a AvroValue,
+}
+
+impl<'a> AvroDeserializer<'a> {
+    fn new(value: &'a AvroValue) -> Self {
+        // Unions are transparent to serde, except when deserializing
+        // an `Option`.
+        let mut value = value;
+        while let AvroValue::Union(_, inner) = value {
+            value = &**inner;
+        }
+
+        Self { value }
+    }
+}
+
+impl<'de, 'a> IntoDeserializer<'de, DeError> for AvroDeserializer<'a> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de, 'a> Deserializer<'de> for AvroDeserializer<'a> {
+    type Error = DeError;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, DeError>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            AvroValue::Null => visitor.visit_unit(),
+            AvroValue::Boolean(b) => visitor.visit_bool(*b),
+            AvroValue::Int(i) | AvroValue::Date(i) | AvroValue::TimeMillis(i) => {
+                visitor.visit_i32(*i)
+            }
+            AvroValue::Long(i)
+            | AvroValue::TimeMicros(i)
+            | AvroValue::TimestampMillis(i)
+            | AvroValue::TimestampMicros(i) => visitor.visit_i64(*i),
+            AvroValue::Float(f) => visitor.visit_f32(*f),
+            AvroValue::Double(f) => visitor.visit_f64(*f),
+            AvroValue::Bytes(bytes) | AvroValue::Fixed(_, bytes) => visitor.visit_bytes(bytes),
+            AvroValue::String(s) | AvroValue::Enum(_, s) => visitor.visit_str(s),
+            AvroValue::Uuid(uuid) => visitor.visit_string(uuid.to_string()),
+            AvroValue::Array(values) => visitor.visit_seq(SeqDeserializer::new(
+                values.iter().map(AvroDeserializer::new),
+            )),
+            AvroValue::Map(entries) => visitor.visit_map(MapDeserializer::new(
+                entries
+                    .iter()
+                    .map(|(k, v)| (k.as_str(), AvroDeserializer::new(v))),
+            )),
+            AvroValue::Record(fields) => visitor.visit_map(MapDeserializer::new(
+                fields
+                    .iter()
+                    .map(|(k, v)| (k.as_str(), AvroDeserializer::new(v))),
+            )),
+            value => Err(DeError::custom(format!(
+                "unsupported Avro value '{value:?}'"
+            ))),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, DeError>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            AvroValue::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeError>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            AvroValue::Enum(_, symbol) | AvroValue::String(symbol) => {
+                IntoDeserializer::<'_, DeError>::into_deserializer(symbol.as_str())
+                    .deserialize_enum(name, variants, visitor)
+            }
+            value => Err(DeError::custom(format!(
+                "expected an Avro enum, found '{value:?}'"
+            ))),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+/// Avro format parser.
+pub struct AvroInputFormat;
+
+/// Avro parser configuration.
+///
+/// The parser accepts the following encodings:
+///
+/// * Object container files, which carry their own schema.  The parser
+///   recognizes a container file by the magic bytes at the start of the
+///   input stream and decodes it once the end of the input stream is
+///   reached.
+///
+/// * Messages in the Confluent wire format (a zero byte and a 4-byte schema
+///   id followed by the Avro datum), if `registry_url` is specified.
+///
+/// * Messages in the Avro single-object encoding.
+///
+/// * Raw Avro datums encoded with `schema`.
+///
+/// Except for container files, each buffer received from the transport
+/// endpoint must contain one or more complete messages, which is the case for
+/// message-oriented transports such as Kafka.
+#[derive(Clone, Deserialize, ToSchema)]
+pub struct AvroParserConfig {
+    /// Avro schema of input messages, in JSON.  Required for the
+    /// single-object encoding and for raw datums.
+    #[serde(default)]
+    schema: Option<String>,
+
+    /// URL of a Confluent-compatible schema registry used to retrieve
+    /// schemas referenced by messages in the Confluent wire format.
+    #[serde(default)]
+    registry_url: Option<String>,
+
+    /// Update envelope used by input messages.
+    #[serde(default)]
+    update_format: AvroUpdateFormat,
+}
+
+impl InputFormat for AvroInputFormat {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("avro")
+    }
+
+    fn new_parser(
+        &self,
+        input_stream: &dyn DeCollectionHandle,
+        config: &YamlValue,
+    ) -> AnyResult<Box<dyn Parser>> {
+        let config = AvroParserConfig::deserialize(config)?;
+
+        Ok(Box::new(AvroParser::new(input_stream, config)?) as Box<dyn Parser>)
+    }
+}
+
+struct AvroParser {
+    /// Input handle to push parsed data to.
+    input_stream: Box<dyn DeCollectionHandle>,
+
+    config: AvroParserConfig,
+
+    /// Parsed `config.schema`.
+    schema: Option<Arc<AvroSchema>>,
+
+    /// Rabin fingerprint of `schema`, used to validate single-object encoded
+    /// messages.
+    fingerprint: Vec<u8>,
+
+    registry: Option<SchemaRegistry>,
+
+    /// Object container file received so far.
+    container: Option<Vec<u8>>,
+
+    /// Number of bytes received from the input stream so far.
+    offset: u64,
+}
+
+impl AvroParser {
+    fn new(input_stream: &dyn DeCollectionHandle, config: AvroParserConfig) -> AnyResult<Self> {
+        let schema = config
+            .schema
+            .as_ref()
+            .map(|schema| {
+                AvroSchema::parse_str(schema)
+                    .map_err(|e| AnyError::msg(format!("invalid Avro schema: {e}")))
+            })
+            .transpose()?;
+        let fingerprint = schema
+            .as_ref()
+            .map(|schema| schema.fingerprint::<Rabin>().bytes)
+            .unwrap_or_default();
+        let registry = config.registry_url.as_deref().map(SchemaRegistry::new);
+
+        Ok(Self {
+            input_stream: input_stream.fork(),
+            config,
+            schema: schema.map(Arc::new),
+            fingerprint,
+            registry,
+            container: None,
+            offset: 0,
+        })
+    }
+
+    /// Consume the header of the next message in `data` and return the
+    /// writer schema of the message.
+    fn message_schema(&mut self, data: &mut &[u8]) -> AnyResult<Arc<AvroSchema>> {
+        if let Some(registry) = &mut self.registry {
+            if data.len() < CONFLUENT_HEADER_LEN || data[0] != 0 {
+                return Err(AnyError::msg(
+                    "expected an Avro message in the Confluent wire format",
+                ));
+            }
+            let id = u32::from_be_bytes(data[1..CONFLUENT_HEADER_LEN].try_into().unwrap());
+            *data = &data[CONFLUENT_HEADER_LEN..];
+
+            return registry.schema(id);
+        }
+
+        let schema = self.schema.clone().ok_or_else(|| {
+            AnyError::msg("Avro schema must be specified in the parser configuration")
+        })?;
+
+        if data.starts_with(&SINGLE_OBJECT_MAGIC) {
+            if data.len() < SINGLE_OBJECT_HEADER_LEN
+                || data[SINGLE_OBJECT_MAGIC.len()..SINGLE_OBJECT_HEADER_LEN] != self.fingerprint
+            {
+                return Err(AnyError::msg(
+                    "schema fingerprint of a single-object encoded Avro message does not match the configured schema",
+                ));
+            }
+            *data = &data[SINGLE_OBJECT_HEADER_LEN..];
+        }
+
+        Ok(schema)
+    }
+
+    fn parse_messages(&mut self, data: &[u8]) -> (usize, Vec<ParseError>) {
+        let mut num_records = 0;
+        let mut errors = Vec::new();
+        let mut remainder = data;
+
+        while !remainder.is_empty() {
+            let start = data.len() - remainder.len();
+            let value = self.message_schema(&mut remainder).and_then(|schema| {
+                from_avro_datum(&schema, &mut remainder, None)
+                    .map_err(|e| AnyError::msg(format!("failed to decode Avro message: {e}")))
+            });
+
+            match value {
+                Ok(value) => match self.apply_value(&value) {
+                    Ok(n) => num_records += n,
+                    Err(e) => {
+                        let end = data.len() - remainder.len();
+                        errors.push(ParseError::new(
+                            e.to_string(),
+                            Some(self.offset + start as u64),
+                            Some(&data[start..end]),
+                        ));
+                    }
+                },
+                Err(e) => {
+                    // We don't know where the invalid message ends; skip the
+                    // rest of the buffer.
+                    errors.push(ParseError::new(
+                        e.to_string(),
+                        Some(self.offset + start as u64),
+                        Some(&data[start..]),
+                    ));
+                    break;
+                }
+            }
+        }
+
+        (num_records, errors)
+    }
+
+    fn parse_container(&mut self, data: &[u8]) -> (usize, Vec<ParseError>) {
+        let reader = match AvroReader::new(data) {
+            Ok(reader) => reader,
+            Err(e) => {
+                return (
+                    0,
+                    vec![ParseError::new(
+                        format!("invalid Avro container file: {e}"),
+                        None,
+                        None,
+                    )],
+                )
+            }
+        };
+        let mut num_records = 0;
+        let mut errors = Vec::new();
+
+        for value in reader {
+            match value {
+                Ok(value) => match self.apply_value(&value) {
+                    Ok(n) => num_records += n,
+                    Err(e) => errors.push(ParseError::new(e.to_string(), None, None)),
+                },
+                Err(e) => {
+                    // The reader cannot recover from a corrupted block.
+                    errors.push(ParseError::new(
+                        format!("failed to decode Avro record: {e}"),
+                        None,
+                        None,
+                    ));
+                    break;
+                }
+            }
+        }
+
+        (num_records, errors)
+    }
+
+    /// Push the update encoded in an Avro record to the circuit.
+    fn apply_value(&mut self, value: &AvroValue) -> AnyResult<usize> {
+        match self.config.update_format {
+            AvroUpdateFormat::Raw => {
+                self.insert(value)?;
+                Ok(1)
+            }
+            AvroUpdateFormat::Weighted => {
+                let field = |name: &str| match value {
+                    AvroValue::Record(fields) => fields
+                        .iter()
+                        .find(|(field, _)| field == name)
+                        .map(|(_, value)| value),
+                    _ => None,
+                };
+
+                let (data, weight) = match (field("data"), field("weight")) {
+                    (Some(data), Some(AvroValue::Long(weight))) => (data, *weight),
+                    (Some(data), Some(AvroValue::Int(weight))) => (data, *weight as i64),
+                    _ => {
+                        return Err(AnyError::msg(format!(
+                            "invalid weighted update '{value:?}': expected a record with 'data' and 'weight' fields"
+                        )))
+                    }
+                };
+
+                self.update(data, weight)?;
+                Ok((weight != 0) as usize)
+            }
+        }
+    }
+
+    fn insert(&mut self, record: &AvroValue) -> AnyResult<()> {
+        let mut deserializer = <dyn ErasedDeserializer>::erase(AvroDeserializer::new(record));
+        self.input_stream.insert(&mut deserializer).map_err(|e| {
+            AnyError::msg(format!(
+                "failed to deserialize Avro record '{record:?}': {e}"
+            ))
+        })
+    }
+
+    fn delete(&mut self, record: &AvroValue) -> AnyResult<()> {
+        let mut deserializer = <dyn ErasedDeserializer>::erase(AvroDeserializer::new(record));
+        self.input_stream.delete(&mut deserializer).map_err(|e| {
+            AnyError::msg(format!(
+                "failed to deserialize Avro record '{record:?}': {e}"
+            ))
+        })
+    }
+
+    fn update(&mut self, record: &AvroValue, weight: i64) -> AnyResult<()> {
+        let mut deserializer = <dyn ErasedDeserializer>::erase(AvroDeserializer::new(record));
+        self.input_stream
+            .update(&mut deserializer, weight)
+            .map_err(|e| {
+                AnyError::msg(format!(
+                    "failed to deserialize Avro record '{record:?}': {e}"
+                ))
+            })
+    }
+}
+
+impl Parser for AvroParser {
+    fn input(&mut self, data: &[u8]) -> (usize, Vec<ParseError>) {
+        let result = if let Some(container) = &mut self.container {
+            container.extend_from_slice(data);
+            (0, Vec::new())
+        } else if data.starts_with(&CONTAINER_MAGIC) {
+            self.container = Some(data.to_vec());
+            (0, Vec::new())
+        } else {
+            self.parse_messages(data)
+        };
+        self.offset += data.len() as u64;
+
+        result
+    }
+
+    fn eoi(&mut self) -> (usize, Vec<ParseError>) {
+        match take(&mut self.container) {
+            Some(container) => self.parse_container(&container),
+            None => (0, Vec::new()),
+        }
+    }
+
+    fn flush(&mut self) {
+        self.input_stream.flush();
+    }
+
+    fn clear(&mut self) {
+        self.input_stream.clear_buffer();
+    }
+
+    fn fork(&self) -> Box<dyn Parser> {
+        // The schema has already been validated by `Self::new`.
+        Box::new(Self::new(&*self.input_stream, self.config.clone()).unwrap())
+    }
+}
+
+/// Avro format encoder.
+pub struct AvroOutputFormat;
+
+/// Avro encoder configuration.
+///
+/// The encoder sends each update as a separate message.  Messages use the
+/// Confluent wire format if `registry_url` is specified and the Avro
+/// single-object encoding otherwise.
+#[derive(Deserialize, ToSchema)]
+pub struct AvroEncoderConfig {
+    /// Avro schema of the rows of the output stream, in JSON.
+    ///
+    /// When not specified, the schema is derived from the rows of the
+    /// stream: a record named `Row` whose fields follow the columns of the
+    /// stream.  The type of a nullable column is only known once the encoder
+    /// has seen a non-null value in it; until then the column has type
+    /// `null`.  The schema of output messages changes when that happens,
+    /// which is reflected in the schema fingerprint or, with `registry_url`,
+    /// by registering a new version of the schema.
+    #[serde(default)]
+    schema: Option<String>,
+
+    /// URL of a Confluent-compatible schema registry.  The schema of output
+    /// messages is registered under `subject` when the encoder is created.
+    #[serde(default)]
+    registry_url: Option<String>,
+
+    /// Schema registry subject, e.g., `<topic>-value`.  Required if
+    /// `registry_url` is specified.
+    #[serde(default)]
+    subject: Option<String>,
+
+    /// Update envelope used to encode output rows.  In the `weighted`
+    /// format, the schema of output messages is a record with a `data`
+    /// field of type `schema` and a `weight` field of type `long`.
+    #[serde(default)]
+    update_format: AvroUpdateFormat,
+}
+
+impl OutputFormat for AvroOutputFormat {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("avro")
+    }
+
+    fn new_encoder(
+        &self,
+        config: &YamlValue,
+        consumer: Box<dyn OutputConsumer>,
+    ) -> AnyResult<Box<dyn Encoder>> {
+        let config = AvroEncoderConfig::deserialize(config)?;
+
+        Ok(Box::new(AvroEncoder::new(consumer, config)?))
+    }
+}
+
+/// Avro schema derived from the values of output rows.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum InferredSchema {
+    /// Only null values seen so far.
+    Null,
+    Boolean,
+    Int,
+    Long,
+    Float,
+    Double,
+    Bytes,
+    String,
+    /// Union of `null` and the inner type.
+    Nullable(Box<InferredSchema>),
+    Array(Box<InferredSchema>),
+    Map(Box<InferredSchema>),
+    Record(Vec<(String, InferredSchema)>),
+}
+
+impl InferredSchema {
+    fn from_value(value: &AvroValue) -> AnyResult<Self> {
+        Ok(match value {
+            AvroValue::Null => Self::Null,
+            AvroValue::Boolean(_) => Self::Boolean,
+            AvroValue::Int(_) => Self::Int,
+            AvroValue::Long(_) => Self::Long,
+            AvroValue::Float(_) => Self::Float,
+            AvroValue::Double(_) => Self::Double,
+            AvroValue::Bytes(_) | AvroValue::Fixed(..) => Self::Bytes,
+            AvroValue::String(_) | AvroValue::Enum(..) => Self::String,
+            AvroValue::Union(_, value) => Self::from_value(value)?.nullable(),
+            AvroValue::Array(items) => Self::Array(Box::new(Self::merge_values(items.iter())?)),
+            AvroValue::Map(entries) => Self::Map(Box::new(Self::merge_values(entries.values())?)),
+            AvroValue::Record(fields) => Self::Record(
+                fields
+                    .iter()
+                    .map(|(name, value)| Ok((name.clone(), Self::from_value(value)?)))
+                    .collect::<AnyResult<_>>()?,
+            ),
+            _ => {
+                return Err(AnyError::msg(format!(
+                    "cannot derive Avro schema of value '{value:?}'"
+                )))
+            }
+        })
+    }
+
+    /// Schema of the elements of an array or map.
+    fn merge_values<'a>(values: impl Iterator<Item = &'a AvroValue>) -> AnyResult<Self> {
+        values.fold(Ok(Self::Null), |schema, value| {
+            schema?.merge(Self::from_value(value)?)
+        })
+    }
+
+    fn nullable(self) -> Self {
+        match self {
+            Self::Null | Self::Nullable(_) => self,
+            _ => Self::Nullable(Box::new(self)),
+        }
+    }
+
+    /// Smallest schema that describes values of both `self` and `other`.
+    fn merge(self, other: Self) -> AnyResult<Self> {
+        Ok(match (self, other) {
+            (this, other) if this == other => this,
+            (Self::Null, other) | (other, Self::Null) => other.nullable(),
+            (Self::Nullable(this), other) | (other, Self::Nullable(this)) => {
+                (*this).merge(other)?.nullable()
+            }
+            (Self::Int, Self::Long) | (Self::Long, Self::Int) => Self::Long,
+            (Self::Float, Self::Double) | (Self::Double, Self::Float) => Self::Double,
+            (Self::Array(this), Self::Array(other)) => {
+                Self::Array(Box::new((*this).merge(*other)?))
+            }
+            (Self::Map(this), Self::Map(other)) => Self::Map(Box::new((*this).merge(*other)?)),
+            (Self::Record(this), Self::Record(other))
+                if this.len() == other.len()
+                    && this.iter().zip(other.iter()).all(|(f1, f2)| f1.0 == f2.0) =>
+            {
+                Self::Record(
+                    this.into_iter()
+                        .zip(other)
+                        .map(|((name, this), (_, other))| Ok((name, this.merge(other)?)))
+                        .collect::<AnyResult<_>>()?,
+                )
+            }
+            (this, other) => {
+                return Err(AnyError::msg(format!(
+                    "output rows have incompatible Avro types {this:?} and {other:?}"
+                )))
+            }
+        })
+    }
+
+    /// JSON representation of the schema.  Record types are named after
+    /// `name` and the path to the record.
+    fn to_json(&self, name: &str) -> JsonValue {
+        match self {
+            Self::Null => json!("null"),
+            Self::Boolean => json!("boolean"),
+            Self::Int => json!("int"),
+            Self::Long => json!("long"),
+            Self::Float => json!("float"),
+            Self::Double => json!("double"),
+            Self::Bytes => json!("bytes"),
+            Self::String => json!("string"),
+            Self::Nullable(inner) => json!(["null", inner.to_json(name)]),
+            Self::Array(items) => json!({
+                "type": "array",
+                "items": items.to_json(&format!("{name}_item"))
+            }),
+            Self::Map(values) => json!({
+                "type": "map",
+                "values": values.to_json(&format!("{name}_value"))
+            }),
+            Self::Record(fields) => json!({
+                "type": "record",
+                "name": name,
+                "fields": fields
+                    .iter()
+                    .map(|(field, schema)| json!({
+                        "name": field,
+                        "type": schema.to_json(&format!("{name}_{field}"))
+                    }))
+                    .collect::<Vec<_>>()
+            }),
+        }
+    }
+}
+
+struct AvroEncoder {
+    /// Input handle to push serialized data to.
+    output_consumer: Box<dyn OutputConsumer>,
+
+    config: AvroEncoderConfig,
+
+    /// Schema derived from the rows encoded so far, if `config.schema` is
+    /// not specified.
+    inferred_schema: Option<InferredSchema>,
+
+    /// Schema of output messages and the header prepended to each message.
+    /// `None` until the first row is encoded if the schema is derived from
+    /// the rows.
+    schema: Option<(AvroSchema, Vec<u8>)>,
+
+    buffer: Vec<u8>,
+}
+
+impl AvroEncoder {
+    fn new(output_consumer: Box<dyn OutputConsumer>, config: AvroEncoderConfig) -> AnyResult<Self> {
+        if config.registry_url.is_some() && config.subject.is_none() {
+            return Err(AnyError::msg(
+                "'subject' must be specified along with 'registry_url'",
+            ));
+        }
+
+        let mut encoder = Self {
+            output_consumer,
+            config,
+            inferred_schema: None,
+            schema: None,
+            buffer: Vec::new(),
+        };
+
+        if let Some(row_schema) = encoder.config.schema.clone() {
+            encoder.set_schema(&row_schema)?;
+        }
+
+        Ok(encoder)
+    }
+
+    /// Compute the schema of output messages and the message header given
+    /// the schema of output rows.
+    fn set_schema(&mut self, row_schema: &str) -> AnyResult<()> {
+        let schema_str = match self.config.update_format {
+            AvroUpdateFormat::Raw => row_schema.to_string(),
+            AvroUpdateFormat::Weighted => weighted_update_schema(row_schema)?,
+        };
+        let schema = AvroSchema::parse_str(&schema_str)
+            .map_err(|e| AnyError::msg(format!("invalid Avro schema: {e}")))?;
+
+        let header = match (&self.config.registry_url, &self.config.subject) {
+            (Some(registry_url), Some(subject)) => {
+                let id = SchemaRegistry::new(registry_url).register(subject, &schema_str)?;
+
+                let mut header = vec![0];
+                header.extend_from_slice(&id.to_be_bytes());
+                header
+            }
+            _ => {
+                let mut header = SINGLE_OBJECT_MAGIC.to_vec();
+                header.extend_from_slice(&schema.fingerprint::<Rabin>().bytes);
+                header
+            }
+        };
+
+        self.schema = Some((schema, header));
+        Ok(())
+    }
+
+    /// Refine the derived schema to cover `value`, updating the schema of
+    /// output messages if it changes.
+    fn infer_schema(&mut self, value: &AvroValue) -> AnyResult<()> {
+        let schema = InferredSchema::from_value(value)?;
+        let schema = match &self.inferred_schema {
+            Some(inferred_schema) => inferred_schema.clone().merge(schema)?,
+            None => schema,
+        };
+
+        if self.inferred_schema.as_ref() != Some(&schema) {
+            self.set_schema(&schema.to_json("Row").to_string())?;
+            self.inferred_schema = Some(schema);
+        }
+
+        Ok(())
+    }
+
+    fn push_message(&mut self, value: AvroValue) -> AnyResult<()> {
+        let (schema, header) = self.schema.as_ref().unwrap();
+        let value = value
+            .resolve(schema)
+            .map_err(|e| AnyError::msg(format!("failed to encode record as Avro: {e}")))?;
+
+        self.buffer.clear();
+        self.buffer.extend_from_slice(header);
+        self.buffer.extend(to_avro_datum(schema, value)?);
+        self.output_consumer.push_buffer(&self.buffer);
+
+        Ok(())
+    }
+}
+
+impl Encoder for AvroEncoder {
+    fn encode(&mut self, batches: &[Arc<dyn SerBatch>]) -> AnyResult<()> {
+        for batch in batches.iter() {
+            let mut cursor = batch.cursor();
+
+            while cursor.key_valid() {
+                let w = cursor.weight();
+                let value = to_value(cursor.key())?;
+                if self.config.schema.is_none() {
+                    self.infer_schema(&value)?;
+                }
+
+                match self.config.update_format {
+                    AvroUpdateFormat::Raw => {
+                        if w < 0 {
+                            return Err(AnyError::msg(
+                                "the 'raw' Avro update format cannot represent deletions",
+                            ));
+                        }
+                        for _ in 0..w {
+                            self.push_message(value.clone())?;
+                        }
+                    }
+                    AvroUpdateFormat::Weighted => {
+                        if w != 0 {
+                            self.push_message(AvroValue::Record(vec![
+                                ("data".to_string(), value),
+                                ("weight".to_string(), AvroValue::Long(w)),
+                            ]))?;
+                        }
+                    }
+                }
+
+                cursor.step_key();
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SINGLE_OBJECT_MAGIC;
+    use crate::{
+        seroutput::SerBatchImpl,
+        test::{MockDeZSet, MockOutputConsumer, TestStruct},
+        InputFormat, OutputFormat, Parser, SerBatch,
+    };
+    use apache_avro::{
+        from_avro_datum, rabin::Rabin, to_avro_datum, to_value, types::Value as AvroValue,
+        Schema as AvroSchema, Writer as AvroWriter,
+    };
+    use dbsp::{trace::Batch, OrdZSet};
+    use serde_json::{json, Value as JsonValue};
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        sync::Arc,
+        thread,
+    };
+
+    const TEST_SCHEMA: &str = r#"{
+        "type": "record",
+        "name": "TestStruct",
+        "fields": [
+            {"name": "id", "type": "long"},
+            {"name": "b", "type": "boolean"},
+            {"name": "i", "type": ["null", "long"]},
+            {"name": "s", "type": "string"}
+        ]
+    }"#;
+
+    fn test_data() -> Vec<TestStruct> {
+        vec![
+            TestStruct {
+                id: 1,
+                b: true,
+                i: None,
+                s: "foo".to_string(),
+            },
+            TestStruct {
+                id: 2,
+                b: false,
+                i: Some(-10),
+                s: "bar".to_string(),
+            },
+        ]
+    }
+
+    fn avro_value(record: &TestStruct, schema: &AvroSchema) -> AvroValue {
+        to_value(record).unwrap().resolve(schema).unwrap()
+    }
+
+    fn parser(config: &str, input_handle: &MockDeZSet<TestStruct>) -> Box<dyn Parser> {
+        <dyn InputFormat>::get_format("avro")
+            .unwrap()
+            .new_parser(input_handle, &serde_yaml::from_str(config).unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn container_file() {
+        let schema = AvroSchema::parse_str(TEST_SCHEMA).unwrap();
+        let mut writer = AvroWriter::new(&schema, Vec::new());
+        for record in test_data().iter() {
+            writer.append(avro_value(record, &schema)).unwrap();
+        }
+        let container = writer.into_inner().unwrap();
+
+        let input_handle = MockDeZSet::<TestStruct>::new();
+        let mut parser = parser("{}", &input_handle);
+
+        // The container file is decoded at the end of input.
+        let (first, second) = container.split_at(container.len() / 2);
+        assert_eq!(parser.input(first), (0, Vec::new()));
+        assert_eq!(parser.input(second), (0, Vec::new()));
+        assert_eq!(parser.eoi(), (2, Vec::new()));
+        parser.flush();
+
+        assert_eq!(
+            input_handle.state().flushed,
+            test_data()
+                .into_iter()
+                .map(|record| (record, true))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn single_object_roundtrip() {
+        let config = serde_yaml::to_string(&serde_yaml::Mapping::from_iter([
+            ("schema".into(), TEST_SCHEMA.into()),
+            ("update_format".into(), "weighted".into()),
+        ]))
+        .unwrap();
+
+        let consumer = MockOutputConsumer::new();
+        let mut encoder = <dyn OutputFormat>::get_format("avro")
+            .unwrap()
+            .new_encoder(
+                &serde_yaml::from_str(&config).unwrap(),
+                Box::new(consumer.clone()),
+            )
+            .unwrap();
+
+        let data = test_data();
+        let batch = OrdZSet::from_keys((), vec![(data[0].clone(), 1), (data[1].clone(), -2)]);
+        encoder
+            .encode(&[Arc::new(SerBatchImpl::new(batch)) as Arc<dyn SerBatch>])
+            .unwrap();
+
+        let messages = consumer.buffers();
+        assert_eq!(messages.len(), 2);
+
+        let input_handle = MockDeZSet::<TestStruct>::new();
+        let mut parser = parser(&config, &input_handle);
+        for message in messages.iter() {
+            assert_eq!(parser.input(message).1, Vec::new());
+        }
+        parser.flush();
+
+        assert_eq!(
+            input_handle.state().flushed,
+            vec![
+                (data[0].clone(), true),
+                (data[1].clone(), false),
+                (data[1].clone(), false)
+            ]
+        );
+    }
+
+    #[test]
+    fn inferred_schema() {
+        let consumer = MockOutputConsumer::new();
+        let mut encoder = <dyn OutputFormat>::get_format("avro")
+            .unwrap()
+            .new_encoder(
+                &serde_yaml::from_str("{}").unwrap(),
+                Box::new(consumer.clone()),
+            )
+            .unwrap();
+
+        // `i` is null in the first row, so its type is only known after
+        // encoding the second row.
+        let data = test_data();
+        let batch = OrdZSet::from_keys((), vec![(data[0].clone(), 1), (data[1].clone(), 1)]);
+        encoder
+            .encode(&[Arc::new(SerBatchImpl::new(batch)) as Arc<dyn SerBatch>])
+            .unwrap();
+
+        let messages = consumer.buffers();
+        assert_eq!(messages.len(), 2);
+
+        let row_schema = |i_type: JsonValue| {
+            AvroSchema::parse_str(
+                &json!({
+                    "type": "record",
+                    "name": "Row",
+                    "fields": [
+                        {"name": "id", "type": "long"},
+                        {"name": "b", "type": "boolean"},
+                        {"name": "i", "type": i_type},
+                        {"name": "s", "type": "string"}
+                    ]
+                })
+                .to_string(),
+            )
+            .unwrap()
+        };
+        let schemas = [
+            row_schema(json!("null")),
+            row_schema(json!(["null", "long"])),
+        ];
+
+        for ((message, schema), record) in messages.iter().zip(schemas.iter()).zip(data.iter()) {
+            assert_eq!(message[0..2], SINGLE_OBJECT_MAGIC);
+            assert_eq!(message[2..10], schema.fingerprint::<Rabin>().bytes);
+            assert_eq!(
+                from_avro_datum(schema, &mut &message[10..], None).unwrap(),
+                avro_value(record, schema)
+            );
+        }
+    }
+
+    #[test]
+    fn schema_registry() {
+        // Local stand-in for the schema registry that serves a single
+        // schema.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let registry_url = format!("http://{}", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 1024];
+            while !request.ends_with(b"\r\n\r\n") {
+                let len = stream.read(&mut buf).unwrap();
+                request.extend_from_slice(&buf[0..len]);
+            }
+            let body = json!({ "schema": TEST_SCHEMA }).to_string();
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .unwrap();
+            String::from_utf8(request).unwrap()
+        });
+
+        let schema = AvroSchema::parse_str(TEST_SCHEMA).unwrap();
+        let mut message = vec![0, 0, 0, 0, 42];
+        message.extend(to_avro_datum(&schema, avro_value(&test_data()[1], &schema)).unwrap());
+
+        let input_handle = MockDeZSet::<TestStruct>::new();
+        let mut parser = parser(&format!("registry_url: {registry_url}"), &input_handle);
+        assert_eq!(parser.input(&message), (1, Vec::new()));
+        // The schema is cached after the first lookup.
+        assert_eq!(parser.input(&message), (1, Vec::new()));
+        parser.flush();
+
+        assert!(server.join().unwrap().starts_with("GET /schemas/ids/42 "));
+        assert_eq!(
+            input_handle.state().flushed,
+            vec![
+                (test_data()[1].clone(), true),
+                (test_data()[1].clone(), true)
+            ]
+        );
+    }
+}
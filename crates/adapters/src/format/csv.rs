@@ -1,23 +1,98 @@
 use crate::{
-    format::{Encoder, InputFormat, OutputFormat, Parser},
+    format::{Encoder, InputFormat, OutputFormat, ParseError, Parser},
     DeCollectionHandle, OutputConsumer, SerBatch,
 };
 use anyhow::{Error as AnyError, Result as AnyResult};
 use csv::{
-    byte_record_deserializer, Reader as CsvReader, ReaderBuilder as CsvReaderBuilder,
+    byte_record_deserializer, ByteRecord, ReaderBuilder as CsvReaderBuilder, Writer as CsvWriter,
     WriterBuilder as CsvWriterBuilder,
 };
-use erased_serde::Deserializer as ErasedDeserializer;
-use serde::Deserialize;
+use erased_serde::{Deserializer as ErasedDeserializer, Serialize as ErasedSerialize};
+use serde::{
+    ser::{SerializeStruct, SerializeTuple},
+    Deserialize, Serialize, Serializer,
+};
 use serde_yaml::Value as YamlValue;
 use std::{borrow::Cow, io::Read, mem::take, sync::Arc};
 use utoipa::ToSchema;
 
+/// Interpretation of the last column of a CSV record.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CsvUpdateFormat {
+    /// All columns belong to the record; every record is an insertion.
+    Raw,
+
+    /// The last column contains the integer weight of the record.  Positive
+    /// weights insert the record `weight` times, negative weights delete it
+    /// `-weight` times.
+    Weighted,
+
+    /// The last column contains the operation to apply to the record:
+    /// `insert` or `delete`.
+    InsertDelete,
+}
+
+const fn default_delimiter() -> char {
+    ','
+}
+
+const fn default_quote() -> char {
+    '"'
+}
+
+const fn default_parser_update_format() -> CsvUpdateFormat {
+    CsvUpdateFormat::Raw
+}
+
+const fn default_encoder_update_format() -> CsvUpdateFormat {
+    CsvUpdateFormat::Weighted
+}
+
+/// Convert a character from the configuration to a byte.
+fn ascii_char(name: &str, c: char) -> AnyResult<u8> {
+    if c.is_ascii() {
+        Ok(c as u8)
+    } else {
+        Err(AnyError::msg(format!(
+            "CSV {name} must be an ASCII character, found '{c}'"
+        )))
+    }
+}
+
 /// CSV format parser.
 pub struct CsvInputFormat;
 
-#[derive(Deserialize, ToSchema)]
-pub struct CsvParserConfig;
+#[derive(Clone, Deserialize, ToSchema)]
+pub struct CsvParserConfig {
+    /// Field delimiter.
+    #[serde(default = "default_delimiter")]
+    delimiter: char,
+
+    /// Quote character.
+    #[serde(default = "default_quote")]
+    quote: char,
+
+    /// Escape character used inside quoted fields.  When not specified,
+    /// quotes are escaped by doubling them.
+    #[serde(default)]
+    escape: Option<char>,
+
+    /// The first record of the input stream is a header.  Columns are
+    /// mapped to fields of the input collection by name rather than by
+    /// position.
+    #[serde(default)]
+    headers: bool,
+
+    /// Field value that represents NULL.  Such fields are parsed as empty
+    /// fields, which deserialize to `None`.
+    #[serde(default)]
+    null_token: Option<String>,
+
+    /// Interpretation of the last column of each record.
+    #[serde(default = "default_parser_update_format")]
+    update_format: CsvUpdateFormat,
+}
 
 impl InputFormat for CsvInputFormat {
     fn name(&self) -> Cow<'static, str> {
@@ -27,9 +102,11 @@ impl InputFormat for CsvInputFormat {
     fn new_parser(
         &self,
         input_stream: &dyn DeCollectionHandle,
-        _config: &YamlValue,
+        config: &YamlValue,
     ) -> AnyResult<Box<dyn Parser>> {
-        Ok(Box::new(CsvParser::new(input_stream)) as Box<dyn Parser>)
+        let config = CsvParserConfig::deserialize(config)?;
+
+        Ok(Box::new(CsvParser::new(input_stream, config)?) as Box<dyn Parser>)
     }
 }
 
@@ -37,6 +114,8 @@ struct CsvParser {
     /// Input handle to push parsed data to.
     input_stream: Box<dyn DeCollectionHandle>,
 
+    config: CsvParserConfig,
+
     /// Since we cannot assume that the input buffer ends on line end,
     /// we save the "leftover" part of the buffer after the last new-line
     /// character and prepend it to the next input buffer.
@@ -45,43 +124,175 @@ struct CsvParser {
     /// Builder used to create a new CSV reader for each received data
     /// buffer.
     builder: CsvReaderBuilder,
+
+    /// Column names read from the header of the input stream, excluding
+    /// the update column.
+    headers: Option<ByteRecord>,
+
+    /// Offset of the first byte of `leftover` in the input stream.
+    offset: u64,
 }
 
 impl CsvParser {
-    fn new(input_stream: &dyn DeCollectionHandle) -> Self {
+    fn new(input_stream: &dyn DeCollectionHandle, config: CsvParserConfig) -> AnyResult<Self> {
         let mut builder = CsvReaderBuilder::new();
-        builder.has_headers(false);
-
-        Self {
+        builder
+            .has_headers(false)
+            .delimiter(ascii_char("delimiter", config.delimiter)?)
+            .quote(ascii_char("quote", config.quote)?)
+            .escape(
+                config
+                    .escape
+                    .map(|escape| ascii_char("escape", escape))
+                    .transpose()?,
+            )
+            .double_quote(config.escape.is_none());
+
+        Ok(Self {
             input_stream: input_stream.fork(),
+            config,
             leftover: Vec::new(),
             builder,
-        }
+            headers: None,
+            offset: 0,
+        })
     }
 
-    fn parse_from_reader<R>(
-        input_stream: &mut dyn DeCollectionHandle,
-        mut reader: CsvReader<R>,
-    ) -> AnyResult<usize>
-    where
-        R: Read,
-    {
+    /// Parse complete CSV records in the concatenation of `prefix` and
+    /// `data`.
+    fn parse(&mut self, prefix: &[u8], data: &[u8]) -> (usize, Vec<ParseError>) {
+        let mut reader = self.builder.from_reader(Read::chain(prefix, data));
         let mut num_records = 0;
+        let mut errors = Vec::new();
+        let mut record = ByteRecord::new();
+
+        loop {
+            let start = reader.position().byte();
+            let result = reader.read_byte_record(&mut record);
+            let end = reader.position().byte();
+
+            let error = match result {
+                Ok(false) => break,
+                Ok(true) if self.config.headers && self.headers.is_none() => {
+                    let mut headers = record.clone();
+                    if self.config.update_format != CsvUpdateFormat::Raw {
+                        headers.truncate(headers.len().saturating_sub(1));
+                    }
+                    self.headers = Some(headers);
+                    continue;
+                }
+                Ok(true) => match self.apply_record(&record) {
+                    Ok(n) => {
+                        num_records += n;
+                        continue;
+                    }
+                    Err(e) => e.to_string(),
+                },
+                Err(e) => format!("invalid CSV record: {e}"),
+            };
+
+            errors.push(ParseError {
+                description: error,
+                offset: Some(self.offset + start),
+                data: Some(Self::record_bytes(
+                    prefix,
+                    data,
+                    start as usize,
+                    end as usize,
+                )),
+            });
+
+            // Don't get stuck if the reader fails without making progress.
+            if end == start {
+                break;
+            }
+        }
+
+        self.offset += (prefix.len() + data.len()) as u64;
+
+        (num_records, errors)
+    }
+
+    /// Returns bytes `start..end` of the concatenation of `prefix` and
+    /// `data`, without the trailing line terminator.
+    fn record_bytes(prefix: &[u8], data: &[u8], start: usize, end: usize) -> Vec<u8> {
+        let mut bytes = prefix
+            .iter()
+            .chain(data.iter())
+            .skip(start)
+            .take(end.saturating_sub(start))
+            .copied()
+            .collect::<Vec<u8>>();
+        while matches!(bytes.last(), Some(b'\n' | b'\r')) {
+            bytes.pop();
+        }
+        bytes
+    }
 
-        for record in reader.byte_records() {
-            let record = record?;
+    /// Push the update encoded in a CSV record to the circuit.
+    fn apply_record(&mut self, record: &ByteRecord) -> AnyResult<usize> {
+        let (weight, mut fields) = match self.config.update_format {
+            CsvUpdateFormat::Raw => (1, Cow::Borrowed(record)),
+            CsvUpdateFormat::Weighted | CsvUpdateFormat::InsertDelete => {
+                let update = record
+                    .len()
+                    .checked_sub(1)
+                    .and_then(|last| record.get(last))
+                    .map(String::from_utf8_lossy)
+                    .unwrap_or_default();
+
+                let weight = if self.config.update_format == CsvUpdateFormat::Weighted {
+                    update.trim().parse::<i64>().map_err(|e| {
+                        AnyError::msg(format!(
+                            "invalid weight '{update}' in csv record '{record:?}': {e}"
+                        ))
+                    })?
+                } else {
+                    match update.trim() {
+                        "insert" => 1,
+                        "delete" => -1,
+                        _ => {
+                            return Err(AnyError::msg(format!(
+                                "invalid operation '{update}' in csv record '{record:?}': expected 'insert' or 'delete'"
+                            )))
+                        }
+                    }
+                };
+
+                let mut fields = record.clone();
+                fields.truncate(record.len() - 1);
+                (weight, Cow::Owned(fields))
+            }
+        };
+
+        if let Some(null_token) = &self.config.null_token {
+            if fields.iter().any(|field| field == null_token.as_bytes()) {
+                fields = Cow::Owned(
+                    fields
+                        .iter()
+                        .map(|field| {
+                            if field == null_token.as_bytes() {
+                                &[][..]
+                            } else {
+                                field
+                            }
+                        })
+                        .collect(),
+                );
+            }
+        }
 
-            let mut deserializer = byte_record_deserializer(&record, None);
-            let mut deserializer = <dyn ErasedDeserializer>::erase(&mut deserializer);
-            input_stream.insert(&mut deserializer).map_err(|e| {
+        let mut deserializer = byte_record_deserializer(&fields, self.headers.as_ref());
+        let mut deserializer = <dyn ErasedDeserializer>::erase(&mut deserializer);
+        self.input_stream
+            .update(&mut deserializer, weight)
+            .map_err(|e| {
                 AnyError::msg(format!(
                     "failed to deserialize csv record '{record:?}': {e}"
                 ))
             })?;
-            num_records += 1;
-        }
 
-        Ok(num_records)
+        Ok((weight != 0) as usize)
     }
 
     /// Returns the index of the first character following the last newline
@@ -99,46 +310,35 @@ impl CsvParser {
 }
 
 impl Parser for CsvParser {
-    fn input(&mut self, data: &[u8]) -> AnyResult<usize> {
-        // println!("input {} bytes:\n{}\nself.leftover:\n{}", data.len(),
-        //    std::str::from_utf8(data).map(|s| s.to_string()).unwrap_or_else(|e|
-        // format!("invalid csv: {e}")),    std::str::from_utf8(&self.leftover).
-        // map(|s| s.to_string()).unwrap_or_else(|e| format!("invalid csv: {e}")));
-
+    fn input(&mut self, data: &[u8]) -> (usize, Vec<ParseError>) {
         let leftover = Self::split_on_newline(data);
 
-        // println!("leftover: {leftover}");
-
         if leftover == 0 {
             // `data` doesn't contain a new-line character; append it to
             // the `leftover` buffer so it gets processed with the next input
             // buffer.
             self.leftover.extend_from_slice(data);
-            Ok(0)
+            (0, Vec::new())
         } else {
-            let reader = self
-                .builder
-                .from_reader(Read::chain(&*self.leftover, &data[0..leftover]));
+            let mut prefix = take(&mut self.leftover);
+            let res = self.parse(&prefix, &data[0..leftover]);
 
-            let res = Self::parse_from_reader(&mut *self.input_stream, reader);
-            // println!("parse returned: {res:?}");
-
-            self.leftover.clear();
-            self.leftover.extend_from_slice(&data[leftover..]);
+            prefix.clear();
+            prefix.extend_from_slice(&data[leftover..]);
+            self.leftover = prefix;
 
             res
         }
     }
 
-    fn eoi(&mut self) -> AnyResult<usize> {
+    fn eoi(&mut self) -> (usize, Vec<ParseError>) {
         if self.leftover.is_empty() {
-            return Ok(0);
+            return (0, Vec::new());
         }
 
         // Try to interpret the leftover chunk as a complete CSV line.
-        let reader = self.builder.from_reader(&*self.leftover);
-
-        Self::parse_from_reader(&mut *self.input_stream, reader)
+        let leftover = take(&mut self.leftover);
+        self.parse(&leftover, &[])
     }
 
     fn flush(&mut self) {
@@ -150,7 +350,278 @@ impl Parser for CsvParser {
     }
 
     fn fork(&self) -> Box<dyn Parser> {
-        Box::new(Self::new(&*self.input_stream))
+        // The configuration has already been validated by `Self::new`.
+        Box::new(Self::new(&*self.input_stream, self.config.clone()).unwrap())
+    }
+}
+
+/// Serializes `value`, writing `None` as `token`.
+struct WithNullToken<'a, T: ?Sized> {
+    value: &'a T,
+    token: &'a str,
+}
+
+impl<'a, T> Serialize for WithNullToken<'a, T>
+where
+    T: Serialize + ?Sized,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.value.serialize(NullTokenSerializer {
+            inner: serializer,
+            token: self.token,
+        })
+    }
+}
+
+/// Serializer wrapper that replaces `None` with a string token in the
+/// fields of structs and tuples.
+struct NullTokenSerializer<'a, S> {
+    inner: S,
+    token: &'a str,
+}
+
+/// Struct or tuple serializer that wraps fields in [`WithNullToken`].
+struct NullTokenCompound<'a, C> {
+    inner: C,
+    token: &'a str,
+}
+
+impl<'a, C> SerializeTuple for NullTokenCompound<'a, C>
+where
+    C: SerializeTuple,
+{
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), C::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.inner.serialize_element(&WithNullToken {
+            value,
+            token: self.token,
+        })
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+impl<'a, C> SerializeStruct for NullTokenCompound<'a, C>
+where
+    C: SerializeStruct,
+{
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), C::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.inner.serialize_field(
+            key,
+            &WithNullToken {
+                value,
+                token: self.token,
+            },
+        )
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+impl<'a, S> Serializer for NullTokenSerializer<'a, S>
+where
+    S: Serializer,
+{
+    type Ok = S::Ok;
+    type Error = S::Error;
+    type SerializeSeq = S::SerializeSeq;
+    type SerializeTuple = NullTokenCompound<'a, S::SerializeTuple>;
+    type SerializeTupleStruct = S::SerializeTupleStruct;
+    type SerializeTupleVariant = S::SerializeTupleVariant;
+    type SerializeMap = S::SerializeMap;
+    type SerializeStruct = NullTokenCompound<'a, S::SerializeStruct>;
+    type SerializeStructVariant = S::SerializeStructVariant;
+
+    fn serialize_bool(self, v: bool) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_bool(v)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_i8(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_i16(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_i32(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_i64(v)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_i128(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_u8(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_u16(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_u32(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_u64(v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_u128(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_f32(v)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_f64(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_char(v)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_str(v)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_bytes(v)
+    }
+
+    fn serialize_none(self) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_str(self.token)
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.inner.serialize_some(value)
+    }
+
+    fn serialize_unit(self) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_unit()
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_unit_struct(name)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+    ) -> Result<S::Ok, S::Error> {
+        self.inner
+            .serialize_unit_variant(name, variant_index, variant)
+    }
+
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.inner.serialize_newtype_struct(name, value)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.inner
+            .serialize_newtype_variant(name, variant_index, variant, value)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<S::SerializeSeq, S::Error> {
+        self.inner.serialize_seq(len)
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, S::Error> {
+        Ok(NullTokenCompound {
+            inner: self.inner.serialize_tuple(len)?,
+            token: self.token,
+        })
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<S::SerializeTupleStruct, S::Error> {
+        self.inner.serialize_tuple_struct(name, len)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<S::SerializeTupleVariant, S::Error> {
+        self.inner
+            .serialize_tuple_variant(name, variant_index, variant, len)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<S::SerializeMap, S::Error> {
+        self.inner.serialize_map(len)
+    }
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, S::Error> {
+        Ok(NullTokenCompound {
+            inner: self.inner.serialize_struct(name, len)?,
+            token: self.token,
+        })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<S::SerializeStructVariant, S::Error> {
+        self.inner
+            .serialize_struct_variant(name, variant_index, variant, len)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.inner.is_human_readable()
     }
 }
 
@@ -165,6 +636,36 @@ const fn default_buffer_size_records() -> usize {
 pub struct CsvEncoderConfig {
     #[serde(default = "default_buffer_size_records")]
     buffer_size_records: usize,
+
+    /// Field delimiter.
+    #[serde(default = "default_delimiter")]
+    delimiter: char,
+
+    /// Quote character.
+    #[serde(default = "default_quote")]
+    quote: char,
+
+    /// Escape character used inside quoted fields.  When not specified,
+    /// quotes are escaped by doubling them.
+    #[serde(default)]
+    escape: Option<char>,
+
+    /// Write a header row with column names before the first record.  The
+    /// update column is named `weight` or `operation`, depending on
+    /// `update_format`.
+    #[serde(default)]
+    headers: bool,
+
+    /// String written in place of NULL (`None`) values.  By default, NULLs
+    /// are written as empty fields.
+    #[serde(default)]
+    null_token: Option<String>,
+
+    /// Content of the last column of each record.  The `raw` format cannot
+    /// represent deletions; the `insert_delete` format writes a record
+    /// `|weight|` times.
+    #[serde(default = "default_encoder_update_format")]
+    update_format: CsvUpdateFormat,
 }
 
 impl OutputFormat for CsvOutputFormat {
@@ -179,7 +680,7 @@ impl OutputFormat for CsvOutputFormat {
     ) -> AnyResult<Box<dyn Encoder>> {
         let config = CsvEncoderConfig::deserialize(config)?;
 
-        Ok(Box::new(CsvEncoder::new(consumer, config)))
+        Ok(Box::new(CsvEncoder::new(consumer, config)?))
     }
 }
 
@@ -194,25 +695,94 @@ struct CsvEncoder {
     config: CsvEncoderConfig,
 
     buffer: Vec<u8>,
+
+    /// The header row has been written.
+    headers_written: bool,
 }
 
 impl CsvEncoder {
-    fn new(output_consumer: Box<dyn OutputConsumer>, config: CsvEncoderConfig) -> Self {
+    fn new(output_consumer: Box<dyn OutputConsumer>, config: CsvEncoderConfig) -> AnyResult<Self> {
         let mut builder = CsvWriterBuilder::new();
-        builder.has_headers(false);
+        builder
+            .has_headers(false)
+            .delimiter(ascii_char("delimiter", config.delimiter)?)
+            .quote(ascii_char("quote", config.quote)?);
+        if let Some(escape) = config.escape {
+            builder
+                .escape(ascii_char("escape", escape)?)
+                .double_quote(false);
+        }
 
-        Self {
+        Ok(Self {
             output_consumer,
             builder,
             config,
             buffer: Vec::new(),
+            headers_written: false,
+        })
+    }
+
+    /// Append the header row derived from the field names of `record` to
+    /// `buffer`.
+    fn write_headers(&self, record: &dyn ErasedSerialize, buffer: &mut Vec<u8>) -> AnyResult<()> {
+        // Let the CSV writer derive column names from the record and keep
+        // the first line of its output.  Column names don't contain
+        // newlines.
+        let mut builder = self.builder.clone();
+        let mut writer = builder.has_headers(true).from_writer(Vec::new());
+        writer.serialize(record)?;
+        let output = writer.into_inner()?;
+        let headers = &output[0..output.iter().position(|&c| c == b'\n').unwrap_or(0)];
+        let headers = headers.strip_suffix(b"\r").unwrap_or(headers);
+
+        buffer.extend_from_slice(headers);
+        match self.config.update_format {
+            CsvUpdateFormat::Raw => {}
+            CsvUpdateFormat::Weighted => {
+                buffer.push(self.config.delimiter as u8);
+                buffer.extend_from_slice(b"weight");
+            }
+            CsvUpdateFormat::InsertDelete => {
+                buffer.push(self.config.delimiter as u8);
+                buffer.extend_from_slice(b"operation");
+            }
         }
+        buffer.push(b'\n');
+
+        Ok(())
+    }
+
+    fn write_record<T>(&self, writer: &mut CsvWriter<Vec<u8>>, record: &T) -> AnyResult<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        match &self.config.null_token {
+            Some(token) => writer.serialize(WithNullToken {
+                value: record,
+                token,
+            })?,
+            None => writer.serialize(record)?,
+        }
+
+        Ok(())
     }
 }
 
 impl Encoder for CsvEncoder {
     fn encode(&mut self, batches: &[Arc<dyn SerBatch>]) -> AnyResult<()> {
-        let buffer = take(&mut self.buffer);
+        let mut buffer = take(&mut self.buffer);
+
+        if self.config.headers && !self.headers_written {
+            for batch in batches.iter() {
+                let cursor = batch.cursor();
+                if cursor.key_valid() {
+                    self.write_headers(cursor.key(), &mut buffer)?;
+                    self.headers_written = true;
+                    break;
+                }
+            }
+        }
+
         let mut writer = self.builder.from_writer(buffer);
         let mut num_records = 0;
 
@@ -221,7 +791,27 @@ impl Encoder for CsvEncoder {
 
             while cursor.key_valid() {
                 let w = cursor.weight();
-                writer.serialize((cursor.key(), w))?;
+                match self.config.update_format {
+                    CsvUpdateFormat::Raw => {
+                        if w < 0 {
+                            return Err(AnyError::msg(
+                                "the 'raw' CSV update format cannot represent deletions",
+                            ));
+                        }
+                        for _ in 0..w {
+                            self.write_record(&mut writer, cursor.key())?;
+                        }
+                    }
+                    CsvUpdateFormat::Weighted => {
+                        self.write_record(&mut writer, &(cursor.key(), w))?;
+                    }
+                    CsvUpdateFormat::InsertDelete => {
+                        let operation = if w > 0 { "insert" } else { "delete" };
+                        for _ in 0..w.unsigned_abs() {
+                            self.write_record(&mut writer, &(cursor.key(), operation))?;
+                        }
+                    }
+                }
                 num_records += 1;
 
                 if num_records >= self.config.buffer_size_records {
@@ -239,7 +829,7 @@ impl Encoder for CsvEncoder {
 
         let mut buffer = writer.into_inner()?;
 
-        if num_records > 0 {
+        if !buffer.is_empty() {
             self.output_consumer.push_buffer(&buffer);
             buffer.clear();
         }
@@ -249,3 +839,155 @@ impl Encoder for CsvEncoder {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        seroutput::SerBatchImpl,
+        test::{MockDeZSet, MockOutputConsumer, TestStruct},
+        InputFormat, OutputFormat, SerBatch,
+    };
+    use dbsp::{trace::Batch, OrdZSet};
+    use std::sync::Arc;
+
+    fn test_data() -> Vec<TestStruct> {
+        vec![
+            TestStruct {
+                id: 1,
+                b: true,
+                i: None,
+                s: "foo".to_string(),
+            },
+            TestStruct {
+                id: 2,
+                b: false,
+                i: Some(5),
+                s: "bar;baz".to_string(),
+            },
+        ]
+    }
+
+    fn parse(config: &str, data: &[u8]) -> Vec<(TestStruct, bool)> {
+        let input_handle = MockDeZSet::<TestStruct>::new();
+        let mut parser = <dyn InputFormat>::get_format("csv")
+            .unwrap()
+            .new_parser(&input_handle, &serde_yaml::from_str(config).unwrap())
+            .unwrap();
+        let (_, errors) = parser.input(data);
+        assert_eq!(errors, Vec::new());
+        let (_, errors) = parser.eoi();
+        assert_eq!(errors, Vec::new());
+        parser.flush();
+
+        let result = input_handle.state().flushed.clone();
+        result
+    }
+
+    fn encode(config: &str, batch: Vec<(TestStruct, i32)>) -> String {
+        let consumer = MockOutputConsumer::new();
+        let mut encoder = <dyn OutputFormat>::get_format("csv")
+            .unwrap()
+            .new_encoder(
+                &serde_yaml::from_str(config).unwrap(),
+                Box::new(consumer.clone()),
+            )
+            .unwrap();
+
+        let batch = OrdZSet::from_keys((), batch);
+        encoder
+            .encode(&[Arc::new(SerBatchImpl::new(batch)) as Arc<dyn SerBatch>])
+            .unwrap();
+
+        String::from_utf8(consumer.data()).unwrap()
+    }
+
+    #[test]
+    fn parse_dialect() {
+        let data = test_data();
+
+        // Header with columns in a different order than struct fields.
+        assert_eq!(
+            parse(
+                "{delimiter: ';', quote: \"'\", headers: true, null_token: 'NULL'}",
+                b"s;i;b;id\nfoo;NULL;true;1\n'bar;baz';5;false;2\n"
+            ),
+            vec![(data[0].clone(), true), (data[1].clone(), true)]
+        );
+
+        assert_eq!(
+            parse(
+                "update_format: weighted",
+                b"1,true,,foo,1\n2,false,5,\"bar;baz\",-2\n"
+            ),
+            vec![
+                (data[0].clone(), true),
+                (data[1].clone(), false),
+                (data[1].clone(), false)
+            ]
+        );
+
+        assert_eq!(
+            parse(
+                "{update_format: insert_delete, headers: true}",
+                b"id,b,i,s,operation\n2,false,5,\"bar;baz\",delete\n1,true,,foo,insert\n"
+            ),
+            vec![(data[1].clone(), false), (data[0].clone(), true)]
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        let data = test_data();
+        let input_handle = MockDeZSet::<TestStruct>::new();
+        let mut parser = <dyn InputFormat>::get_format("csv")
+            .unwrap()
+            .new_parser(&input_handle, &serde_yaml::Value::Null)
+            .unwrap();
+
+        let (num_records, errors) = parser.input(b"1,true,,foo\nbad\n2,false,5,\"bar;baz\"\nx");
+        assert_eq!(num_records, 2);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].offset, Some(12));
+        assert_eq!(errors[0].data.as_deref(), Some(&b"bad"[..]));
+
+        // Offsets are relative to the start of the stream.
+        let (num_records, errors) = parser.input(b",true,,foo\n");
+        assert_eq!(num_records, 0);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].offset, Some(36));
+        assert_eq!(errors[0].data.as_deref(), Some(&b"x,true,,foo"[..]));
+
+        parser.flush();
+        assert_eq!(
+            input_handle.state().flushed,
+            vec![(data[0].clone(), true), (data[1].clone(), true)]
+        );
+    }
+
+    #[test]
+    fn encode_dialect() {
+        let data = test_data();
+        let batch = vec![(data[0].clone(), 1), (data[1].clone(), -1)];
+
+        // Default configuration.
+        assert_eq!(
+            encode("{}", batch.clone()),
+            "1,true,,foo,1\n2,false,5,bar;baz,-1\n"
+        );
+
+        assert_eq!(
+            encode(
+                "{delimiter: ';', headers: true, null_token: 'NULL', update_format: insert_delete}",
+                batch.clone()
+            ),
+            "id;b;i;s;operation\n1;true;NULL;foo;insert\n2;false;5;\"bar;baz\";delete\n"
+        );
+
+        // Round trip.
+        let config = "{delimiter: '|', headers: true, null_token: 'NULL', update_format: weighted}";
+        assert_eq!(
+            parse(config, encode(config, batch).as_bytes()),
+            vec![(data[0].clone(), true), (data[1].clone(), false)]
+        );
+    }
+}
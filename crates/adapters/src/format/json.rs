@@ -0,0 +1,843 @@
+use crate::{
+    format::{Encoder, InputFormat, OutputFormat, ParseError, Parser},
+    DeCollectionHandle, OutputConsumer, SerBatch,
+};
+use anyhow::{Error as AnyError, Result as AnyResult};
+use erased_serde::{Deserializer as ErasedDeserializer, Serialize as ErasedSerialize};
+use serde::{Deserialize, Serialize};
+use serde_json::{Deserializer as JsonDeserializer, Value as JsonValue};
+use serde_yaml::Value as YamlValue;
+use std::{borrow::Cow, mem::take, sync::Arc};
+use utoipa::ToSchema;
+
+/// Supported JSON update envelopes.
+///
+/// An update envelope determines how a JSON value received from or sent to
+/// a transport endpoint maps to an insertion or deletion of a record.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum JsonUpdateFormat {
+    /// Each update is an object with an `insert` and/or a `delete` field
+    /// that contains the record to insert or delete, e.g.,
+    /// `{"insert": {"id": 1, "name": "foo"}}`.  When both fields are
+    /// present, the deletion is applied first.
+    InsertDelete,
+
+    /// Each update is a raw record, which is always interpreted as an
+    /// insertion.
+    Raw,
+
+    /// Each update is an object with a `data` field that contains the
+    /// record and an integer `weight` field, e.g.,
+    /// `{"data": {"id": 1, "name": "foo"}, "weight": -1}`.  Positive weights
+    /// insert the record `weight` times, negative weights delete it
+    /// `-weight` times.
+    Weighted,
+
+    /// Debezium change event, e.g.,
+    /// `{"payload": {"before": {...}, "after": {...}, "op": "u"}}`.
+    ///
+    /// Both plain events and events wrapped in a `schema`/`payload` envelope
+    /// are accepted.  Create (`c`) and read (`r`) events insert the `after`
+    /// record, delete (`d`) events delete the `before` record, and update
+    /// (`u`) events do both.  Deletions and updates therefore require the
+    /// source database to include the complete old row in change events
+    /// (e.g., `REPLICA IDENTITY FULL` in Postgres).  Tombstones (`null`
+    /// values) are ignored.
+    ///
+    /// This format is only supported by the parser.
+    Debezium,
+}
+
+impl Default for JsonUpdateFormat {
+    fn default() -> Self {
+        Self::InsertDelete
+    }
+}
+
+/// Insert/delete update envelope, as received from the input stream.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct InsDelUpdate {
+    #[serde(default)]
+    insert: Option<JsonValue>,
+    #[serde(default)]
+    delete: Option<JsonValue>,
+}
+
+/// Insert/delete update envelope sent to the output stream.
+#[derive(Serialize)]
+struct InsDelUpdateRef<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    insert: Option<&'a dyn ErasedSerialize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    delete: Option<&'a dyn ErasedSerialize>,
+}
+
+/// Weighted update envelope, as received from the input stream.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WeightedUpdate {
+    data: JsonValue,
+    weight: i64,
+}
+
+/// Payload of a Debezium change event.
+#[derive(Deserialize)]
+struct DebeziumPayload {
+    #[serde(default)]
+    before: Option<JsonValue>,
+    #[serde(default)]
+    after: Option<JsonValue>,
+    op: String,
+}
+
+/// Weighted update envelope sent to the output stream.
+#[derive(Serialize)]
+struct WeightedUpdateRef<'a> {
+    data: &'a dyn ErasedSerialize,
+    weight: i64,
+}
+
+/// JSON format parser.
+pub struct JsonInputFormat;
+
+#[derive(Clone, Deserialize, ToSchema)]
+pub struct JsonParserConfig {
+    /// Update envelope used by the input stream.
+    #[serde(default)]
+    update_format: JsonUpdateFormat,
+
+    /// Set to `true` if updates are packaged into JSON arrays, e.g.,
+    /// `[{"insert": {...}}, {"delete": {...}}]`, rather than sent as
+    /// individual JSON values.
+    #[serde(default)]
+    array: bool,
+}
+
+impl InputFormat for JsonInputFormat {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("json")
+    }
+
+    fn new_parser(
+        &self,
+        input_stream: &dyn DeCollectionHandle,
+        config: &YamlValue,
+    ) -> AnyResult<Box<dyn Parser>> {
+        let config = JsonParserConfig::deserialize(config)?;
+
+        Ok(Box::new(JsonParser::new(input_stream, config)) as Box<dyn Parser>)
+    }
+}
+
+struct JsonParser {
+    /// Input handle to push parsed data to.
+    input_stream: Box<dyn DeCollectionHandle>,
+
+    config: JsonParserConfig,
+
+    /// Since we cannot assume that the input buffer ends on a JSON value
+    /// boundary, we save the incomplete value at the end of the buffer and
+    /// prepend it to the next input buffer.
+    leftover: Vec<u8>,
+
+    /// Offset of the first byte of `leftover` in the input stream.
+    offset: u64,
+}
+
+impl JsonParser {
+    fn new(input_stream: &dyn DeCollectionHandle, config: JsonParserConfig) -> Self {
+        Self {
+            input_stream: input_stream.fork(),
+            config,
+            leftover: Vec::new(),
+            offset: 0,
+        }
+    }
+
+    /// Parse a sequence of whitespace-separated JSON values from `data`.
+    ///
+    /// Returns the number of records pushed to the circuit and the errors
+    /// encountered, along with the number of bytes consumed from `data`.  A
+    /// trailing incomplete value is not consumed, unless `eoi` is `true`, in
+    /// which case it is reported as an error.  After a syntax error, the
+    /// parser skips to the end of the line that contains the error.
+    fn parse_values(&mut self, data: &[u8], eoi: bool) -> (usize, Vec<ParseError>, usize) {
+        let mut num_records = 0;
+        let mut errors = Vec::new();
+        let mut start = 0;
+
+        'outer: while start < data.len() {
+            let mut stream = JsonDeserializer::from_slice(&data[start..]).into_iter::<JsonValue>();
+
+            loop {
+                let offset = start + stream.byte_offset();
+
+                match stream.next() {
+                    None => break 'outer,
+                    Some(Err(e)) if e.is_eof() && !eoi => {
+                        return (num_records, errors, offset);
+                    }
+                    Some(Err(e)) => {
+                        let error_pos =
+                            start + line_col_offset(&data[start..], e.line(), e.column());
+                        let next = data[error_pos..]
+                            .iter()
+                            .position(|&c| c == b'\n')
+                            .map(|pos| error_pos + pos + 1)
+                            .unwrap_or(data.len());
+                        errors.push(self.error(
+                            format!("failed to parse JSON: {e}"),
+                            data,
+                            offset,
+                            next,
+                        ));
+                        start = next;
+                        continue 'outer;
+                    }
+                    Some(Ok(value)) => {
+                        let end = start + stream.byte_offset();
+                        num_records += self.apply_value(value, data, offset, end, &mut errors);
+                    }
+                }
+            }
+        }
+
+        (num_records, errors, data.len())
+    }
+
+    /// Create a parse error for the input in `data[start..end]`.
+    fn error(&self, description: String, data: &[u8], start: usize, end: usize) -> ParseError {
+        let data = &data[start..end];
+        let skipped = data
+            .iter()
+            .position(|c| !c.is_ascii_whitespace())
+            .unwrap_or(data.len());
+        let len = data
+            .iter()
+            .rposition(|c| !c.is_ascii_whitespace())
+            .map(|pos| pos + 1)
+            .unwrap_or(skipped);
+        let data = &data[skipped..len];
+
+        ParseError {
+            description,
+            offset: Some(self.offset + (start + skipped) as u64),
+            data: Some(data.to_vec()),
+        }
+    }
+
+    /// Push all updates in a JSON value received from the input stream to
+    /// the circuit.
+    ///
+    /// `data[start..end]` is the serialized representation of `value`.
+    /// Invalid updates are added to `errors`.
+    fn apply_value(
+        &mut self,
+        value: JsonValue,
+        data: &[u8],
+        start: usize,
+        end: usize,
+        errors: &mut Vec<ParseError>,
+    ) -> usize {
+        if self.config.array {
+            match value {
+                JsonValue::Array(updates) => {
+                    let mut num_records = 0;
+                    for update in updates.iter() {
+                        match self.apply_update(update) {
+                            Ok(n) => num_records += n,
+                            Err(e) => {
+                                let mut error = self.error(e.to_string(), data, start, end);
+                                error.data = Some(update.to_string().into_bytes());
+                                errors.push(error);
+                            }
+                        }
+                    }
+                    num_records
+                }
+                _ => {
+                    errors.push(self.error(
+                        format!("expected a JSON array of updates, found '{value}'"),
+                        data,
+                        start,
+                        end,
+                    ));
+                    0
+                }
+            }
+        } else {
+            match self.apply_update(&value) {
+                Ok(n) => n,
+                Err(e) => {
+                    errors.push(self.error(e.to_string(), data, start, end));
+                    0
+                }
+            }
+        }
+    }
+
+    /// Push a single update to the circuit.
+    fn apply_update(&mut self, update: &JsonValue) -> AnyResult<usize> {
+        match self.config.update_format {
+            JsonUpdateFormat::InsertDelete => {
+                let InsDelUpdate { insert, delete } =
+                    InsDelUpdate::deserialize(update).map_err(|e| {
+                        AnyError::msg(format!("invalid insert/delete update '{update}': {e}"))
+                    })?;
+
+                match (&delete, &insert) {
+                    (Some(delete), Some(insert)) => {
+                        self.replace(delete, insert)?;
+                        Ok(2)
+                    }
+                    (Some(delete), None) => {
+                        self.delete(delete)?;
+                        Ok(1)
+                    }
+                    (None, Some(insert)) => {
+                        self.insert(insert)?;
+                        Ok(1)
+                    }
+                    (None, None) => Err(AnyError::msg(format!(
+                        "update '{update}' contains neither an 'insert' nor a 'delete' field"
+                    ))),
+                }
+            }
+            JsonUpdateFormat::Raw => {
+                self.insert(update)?;
+                Ok(1)
+            }
+            JsonUpdateFormat::Weighted => {
+                let WeightedUpdate { data, weight } =
+                    WeightedUpdate::deserialize(update).map_err(|e| {
+                        AnyError::msg(format!("invalid weighted update '{update}': {e}"))
+                    })?;
+
+                self.update(&data, weight)?;
+                Ok((weight != 0) as usize)
+            }
+            JsonUpdateFormat::Debezium => {
+                // Kafka tombstone that follows a delete event.
+                if update.is_null() {
+                    return Ok(0);
+                }
+
+                // Events serialized with schemas enabled wrap the payload in
+                // a `{"schema": ..., "payload": ...}` envelope.
+                let payload = match update.get("payload") {
+                    Some(payload) if update.get("op").is_none() => payload,
+                    _ => update,
+                };
+
+                let DebeziumPayload { before, after, op } = DebeziumPayload::deserialize(payload)
+                    .map_err(|e| {
+                    AnyError::msg(format!("invalid Debezium change event '{update}': {e}"))
+                })?;
+
+                let (delete, insert) = match op.as_str() {
+                    "c" | "r" => (None, after.as_ref()),
+                    "u" => (before.as_ref(), after.as_ref()),
+                    "d" => (before.as_ref(), None),
+                    _ => {
+                        return Err(AnyError::msg(format!(
+                            "unsupported Debezium operation '{op}' in change event '{update}'"
+                        )))
+                    }
+                };
+
+                if delete.is_none() && (op == "u" || op == "d") {
+                    return Err(AnyError::msg(format!(
+                        "Debezium change event '{update}' does not contain a 'before' record"
+                    )));
+                }
+                if insert.is_none() && op != "d" {
+                    return Err(AnyError::msg(format!(
+                        "Debezium change event '{update}' does not contain an 'after' record"
+                    )));
+                }
+
+                match (delete, insert) {
+                    (Some(delete), Some(insert)) => {
+                        self.replace(delete, insert)?;
+                        Ok(2)
+                    }
+                    (Some(delete), None) => {
+                        self.delete(delete)?;
+                        Ok(1)
+                    }
+                    (None, Some(insert)) => {
+                        self.insert(insert)?;
+                        Ok(1)
+                    }
+                    (None, None) => Ok(0),
+                }
+            }
+        }
+    }
+
+    fn insert(&mut self, record: &JsonValue) -> AnyResult<()> {
+        let mut deserializer = <dyn ErasedDeserializer>::erase(record);
+        self.input_stream.insert(&mut deserializer).map_err(|e| {
+            AnyError::msg(format!("failed to deserialize JSON record '{record}': {e}"))
+        })
+    }
+
+    fn delete(&mut self, record: &JsonValue) -> AnyResult<()> {
+        let mut deserializer = <dyn ErasedDeserializer>::erase(record);
+        self.input_stream.delete(&mut deserializer).map_err(|e| {
+            AnyError::msg(format!("failed to deserialize JSON record '{record}': {e}"))
+        })
+    }
+
+    /// Delete `delete` and insert `insert`, or neither if either record is
+    /// invalid.
+    fn replace(&mut self, delete: &JsonValue, insert: &JsonValue) -> AnyResult<()> {
+        let mut delete_deserializer = <dyn ErasedDeserializer>::erase(delete);
+        let mut insert_deserializer = <dyn ErasedDeserializer>::erase(insert);
+        self.input_stream
+            .replace(&mut delete_deserializer, &mut insert_deserializer)
+            .map_err(|e| {
+                AnyError::msg(format!(
+                    "failed to deserialize JSON records '{delete}' and '{insert}': {e}"
+                ))
+            })
+    }
+
+    fn update(&mut self, record: &JsonValue, weight: i64) -> AnyResult<()> {
+        let mut deserializer = <dyn ErasedDeserializer>::erase(record);
+        self.input_stream
+            .update(&mut deserializer, weight)
+            .map_err(|e| {
+                AnyError::msg(format!("failed to deserialize JSON record '{record}': {e}"))
+            })
+    }
+}
+
+/// Returns the offset of the 1-based `line` and `column` in `data`.
+fn line_col_offset(data: &[u8], line: usize, column: usize) -> usize {
+    let line_start = if line <= 1 {
+        0
+    } else {
+        data.iter()
+            .enumerate()
+            .filter(|(_, &c)| c == b'\n')
+            .nth(line - 2)
+            .map(|(pos, _)| pos + 1)
+            .unwrap_or(data.len())
+    };
+
+    (line_start + column.saturating_sub(1)).min(data.len())
+}
+
+impl Parser for JsonParser {
+    fn input(&mut self, data: &[u8]) -> (usize, Vec<ParseError>) {
+        let mut buffer = take(&mut self.leftover);
+        buffer.extend_from_slice(data);
+
+        let (num_records, errors, consumed) = self.parse_values(&buffer, false);
+
+        buffer.drain(0..consumed);
+        self.leftover = buffer;
+        self.offset += consumed as u64;
+
+        (num_records, errors)
+    }
+
+    fn eoi(&mut self) -> (usize, Vec<ParseError>) {
+        if self.leftover.is_empty() {
+            return (0, Vec::new());
+        }
+
+        let buffer = take(&mut self.leftover);
+        let (num_records, errors, consumed) = self.parse_values(&buffer, true);
+        self.offset += consumed as u64;
+
+        (num_records, errors)
+    }
+
+    fn flush(&mut self) {
+        self.input_stream.flush();
+    }
+
+    fn clear(&mut self) {
+        self.input_stream.clear_buffer();
+    }
+
+    fn fork(&self) -> Box<dyn Parser> {
+        Box::new(Self::new(&*self.input_stream, self.config.clone()))
+    }
+}
+
+/// JSON format encoder.
+pub struct JsonOutputFormat;
+
+const fn default_buffer_size_records() -> usize {
+    10_000
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct JsonEncoderConfig {
+    /// Update envelope used to encode output records.
+    #[serde(default)]
+    update_format: JsonUpdateFormat,
+
+    /// Package updates in each output buffer into a JSON array instead of
+    /// writing them as newline-delimited JSON values.
+    #[serde(default)]
+    array: bool,
+
+    #[serde(default = "default_buffer_size_records")]
+    buffer_size_records: usize,
+}
+
+impl OutputFormat for JsonOutputFormat {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("json")
+    }
+
+    fn new_encoder(
+        &self,
+        config: &YamlValue,
+        consumer: Box<dyn OutputConsumer>,
+    ) -> AnyResult<Box<dyn Encoder>> {
+        let config = JsonEncoderConfig::deserialize(config)?;
+
+        if config.update_format == JsonUpdateFormat::Debezium {
+            return Err(AnyError::msg(
+                "the 'debezium' update format is not supported by the JSON encoder",
+            ));
+        }
+
+        Ok(Box::new(JsonEncoder::new(consumer, config)))
+    }
+}
+
+struct JsonEncoder {
+    /// Input handle to push serialized data to.
+    output_consumer: Box<dyn OutputConsumer>,
+
+    config: JsonEncoderConfig,
+
+    buffer: Vec<u8>,
+
+    /// Number of records in `buffer`.
+    num_records: usize,
+}
+
+impl JsonEncoder {
+    fn new(output_consumer: Box<dyn OutputConsumer>, config: JsonEncoderConfig) -> Self {
+        Self {
+            output_consumer,
+            config,
+            buffer: Vec::new(),
+            num_records: 0,
+        }
+    }
+
+    /// Append a JSON value to the buffer; send the buffer to the consumer
+    /// once it contains `buffer_size_records` values.
+    fn push_record<T>(&mut self, record: &T) -> AnyResult<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        if self.config.array {
+            self.buffer
+                .push(if self.num_records == 0 { b'[' } else { b',' });
+        }
+        serde_json::to_writer(&mut self.buffer, record)?;
+        if !self.config.array {
+            self.buffer.push(b'\n');
+        }
+        self.num_records += 1;
+
+        if self.num_records >= self.config.buffer_size_records {
+            self.push_buffer();
+        }
+
+        Ok(())
+    }
+
+    fn push_buffer(&mut self) {
+        if self.num_records > 0 {
+            if self.config.array {
+                self.buffer.extend_from_slice(b"]\n");
+            }
+            self.output_consumer.push_buffer(&self.buffer);
+            self.buffer.clear();
+            self.num_records = 0;
+        }
+    }
+}
+
+impl Encoder for JsonEncoder {
+    fn encode(&mut self, batches: &[Arc<dyn SerBatch>]) -> AnyResult<()> {
+        for batch in batches.iter() {
+            let mut cursor = batch.cursor();
+
+            while cursor.key_valid() {
+                let w = cursor.weight();
+
+                match self.config.update_format {
+                    JsonUpdateFormat::InsertDelete => {
+                        let update = if w > 0 {
+                            InsDelUpdateRef {
+                                insert: Some(cursor.key()),
+                                delete: None,
+                            }
+                        } else {
+                            InsDelUpdateRef {
+                                insert: None,
+                                delete: Some(cursor.key()),
+                            }
+                        };
+                        for _ in 0..w.unsigned_abs() {
+                            self.push_record(&update)?;
+                        }
+                    }
+                    JsonUpdateFormat::Raw => {
+                        if w < 0 {
+                            return Err(AnyError::msg(
+                                "the 'raw' JSON update format cannot represent deletions",
+                            ));
+                        }
+                        for _ in 0..w {
+                            self.push_record(cursor.key())?;
+                        }
+                    }
+                    JsonUpdateFormat::Weighted => {
+                        if w != 0 {
+                            self.push_record(&WeightedUpdateRef {
+                                data: cursor.key(),
+                                weight: w,
+                            })?;
+                        }
+                    }
+                    // Rejected by `JsonOutputFormat::new_encoder`.
+                    JsonUpdateFormat::Debezium => unreachable!(),
+                }
+
+                cursor.step_key();
+            }
+        }
+
+        self.push_buffer();
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        controller::FormatConfig,
+        seroutput::SerBatchImpl,
+        test::{MockDeZSet, MockInputConsumer, MockOutputConsumer, TestStruct},
+        InputConsumer, OutputFormat, SerBatch,
+    };
+    use dbsp::{trace::Batch, OrdZSet};
+    use serde_yaml::Value as YamlValue;
+    use std::{
+        borrow::Cow,
+        sync::{Arc, Mutex},
+    };
+
+    fn test_struct(id: u32, s: &str) -> TestStruct {
+        TestStruct {
+            id,
+            b: id % 2 == 0,
+            i: None,
+            s: s.to_string(),
+        }
+    }
+
+    fn mock_parser(config: &str) -> (MockInputConsumer, MockDeZSet<TestStruct>) {
+        let input_handle = MockDeZSet::<TestStruct>::new();
+        let consumer = MockInputConsumer::from_handle(
+            &input_handle,
+            &FormatConfig {
+                name: Cow::from("json"),
+                config: serde_yaml::from_str(config).unwrap(),
+            },
+        );
+
+        (consumer, input_handle)
+    }
+
+    #[test]
+    fn parse_insert_delete() {
+        let (mut consumer, input_handle) = mock_parser("update_format: insert_delete");
+
+        // Updates split across buffer boundaries.
+        consumer.input(br#"{"insert": {"id": 1, "b": false, "i": null, "s": "foo"}}"#);
+        consumer.input(b"\n{\"delete\": {\"id\": 2, \"b\"");
+        consumer.input(br#": true, "i": null, "s": "bar"}}"#);
+
+        assert_eq!(
+            input_handle.state().flushed,
+            vec![
+                (test_struct(1, "foo"), true),
+                (test_struct(2, "bar"), false)
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_array() {
+        let (mut consumer, input_handle) = mock_parser("update_format: raw\narray: true");
+
+        consumer.input(
+            br#"[{"id": 1, "b": false, "i": null, "s": "foo"}, {"id": 2, "b": true, "i": null, "s": "bar"}]"#,
+        );
+        assert_eq!(
+            input_handle.state().flushed,
+            vec![(test_struct(1, "foo"), true), (test_struct(2, "bar"), true)]
+        );
+    }
+
+    #[test]
+    fn parse_weighted() {
+        let (mut consumer, input_handle) = mock_parser("update_format: weighted");
+
+        consumer.input(
+            br#"{"data": {"id": 1, "b": false, "i": null, "s": "foo"}, "weight": 2}
+{"data": {"id": 2, "b": true, "i": null, "s": "bar"}, "weight": -1}
+"#,
+        );
+        assert_eq!(
+            input_handle.state().flushed,
+            vec![
+                (test_struct(1, "foo"), true),
+                (test_struct(1, "foo"), true),
+                (test_struct(2, "bar"), false)
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_debezium() {
+        let (mut consumer, input_handle) = mock_parser("update_format: debezium");
+
+        consumer.input(
+            br#"{"before": null, "after": {"id": 1, "b": false, "i": null, "s": "foo"}, "op": "c", "ts_ms": 1}
+{"schema": {"type": "struct"}, "payload": {"before": {"id": 1, "b": false, "i": null, "s": "foo"}, "after": {"id": 1, "b": false, "i": null, "s": "bar"}, "op": "u"}}
+{"before": {"id": 1, "b": false, "i": null, "s": "bar"}, "after": null, "op": "d"}
+null
+"#,
+        );
+        assert_eq!(
+            input_handle.state().flushed,
+            vec![
+                (test_struct(1, "foo"), true),
+                (test_struct(1, "foo"), false),
+                (test_struct(1, "bar"), true),
+                (test_struct(1, "bar"), false)
+            ]
+        );
+
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let errors_clone = errors.clone();
+        consumer.on_error(Some(Box::new(move |e| {
+            errors_clone.lock().unwrap().push(e.to_string())
+        })));
+
+        // Update without the old row.
+        consumer.input(
+            br#"{"before": null, "after": {"id": 1, "b": false, "i": null, "s": "foo"}, "op": "u"}"#,
+        );
+        // Unsupported operation.
+        consumer.input(br#"{"op": "t"}"#);
+        // Update with an invalid new row: the old row is not deleted.
+        consumer.input(
+            br#"{"before": {"id": 1, "b": false, "i": null, "s": "foo"}, "after": {"id": "foo"}, "op": "u"}"#,
+        );
+        assert_eq!(errors.lock().unwrap().len(), 3);
+        assert_eq!(input_handle.state().flushed.len(), 4);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let (mut consumer, input_handle) = mock_parser("");
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let errors_clone = errors.clone();
+        consumer.on_error(Some(Box::new(move |e| {
+            errors_clone.lock().unwrap().push(e.to_string())
+        })));
+
+        // Missing envelope.
+        consumer.input(br#"{"id": 1, "b": false, "i": null, "s": "foo"}"#);
+        // Invalid record.
+        consumer.input(br#"{"insert": {"id": "foo"}}"#);
+        // Update with an invalid new value: the old value is not deleted.
+        consumer.input(
+            br#"{"delete": {"id": 1, "b": false, "i": null, "s": "foo"}, "insert": {"id": "foo"}}"#,
+        );
+        // Invalid JSON.
+        consumer.input(b"{]\n");
+        assert_eq!(
+            consumer.state().parser_result.as_ref().unwrap().1[0]
+                .data
+                .as_deref(),
+            Some(&b"{]"[..])
+        );
+        // Parser recovers after errors.
+        consumer.input(br#"{"insert": {"id": 1, "b": false, "i": null, "s": "foo"}}"#);
+
+        assert_eq!(errors.lock().unwrap().len(), 4);
+        assert_eq!(
+            input_handle.state().flushed,
+            vec![(test_struct(1, "foo"), true)]
+        );
+    }
+
+    fn encode(config: &str, batch: Vec<(TestStruct, i32)>) -> Vec<String> {
+        let consumer = MockOutputConsumer::new();
+        let config: YamlValue = serde_yaml::from_str(config).unwrap();
+        let mut encoder = <dyn OutputFormat>::get_format("json")
+            .unwrap()
+            .new_encoder(&config, Box::new(consumer.clone()))
+            .unwrap();
+
+        let batch = OrdZSet::from_keys((), batch);
+        encoder
+            .encode(&[Arc::new(SerBatchImpl::new(batch)) as Arc<dyn SerBatch>])
+            .unwrap();
+
+        consumer
+            .buffers()
+            .into_iter()
+            .map(|buffer| String::from_utf8(buffer).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn encode_insert_delete() {
+        assert_eq!(
+            encode(
+                "update_format: insert_delete",
+                vec![(test_struct(1, "foo"), 2), (test_struct(2, "bar"), -1)]
+            ),
+            vec![
+                r#"{"insert":{"id":1,"b":false,"i":null,"s":"foo"}}
+{"insert":{"id":1,"b":false,"i":null,"s":"foo"}}
+{"delete":{"id":2,"b":true,"i":null,"s":"bar"}}
+"#
+            ]
+        );
+    }
+
+    #[test]
+    fn encode_weighted_array() {
+        assert_eq!(
+            encode(
+                "update_format: weighted\narray: true\nbuffer_size_records: 1",
+                vec![(test_struct(1, "foo"), 2), (test_struct(2, "bar"), -1)]
+            ),
+            vec![
+                "[{\"data\":{\"id\":1,\"b\":false,\"i\":null,\"s\":\"foo\"},\"weight\":2}]\n",
+                "[{\"data\":{\"id\":2,\"b\":true,\"i\":null,\"s\":\"bar\"},\"weight\":-1}]\n",
+            ]
+        );
+    }
+}
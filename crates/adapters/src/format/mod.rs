@@ -1,23 +1,78 @@
-use crate::{DeCollectionHandle, SerBatch};
+use crate::{registry::register, DeCollectionHandle, SerBatch};
 use anyhow::Result as AnyResult;
 use once_cell::sync::Lazy;
 use serde_yaml::Value as YamlValue;
-use std::{borrow::Cow, collections::BTreeMap, sync::Arc};
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    error::Error as StdError,
+    fmt::{Display, Error as FmtError, Formatter},
+    sync::{Arc, RwLock},
+};
 
+#[cfg(feature = "with-avro")]
+mod avro;
 mod csv;
+mod json;
+#[cfg(feature = "with-parquet")]
+mod parquet;
 
-pub use self::csv::{CsvEncoderConfig, CsvParserConfig};
+#[cfg(feature = "with-avro")]
+pub use self::avro::{AvroEncoderConfig, AvroParserConfig, AvroUpdateFormat};
+#[cfg(feature = "with-avro")]
+use self::avro::{AvroInputFormat, AvroOutputFormat};
+
+pub use self::csv::{CsvEncoderConfig, CsvParserConfig, CsvUpdateFormat};
 use self::csv::{CsvInputFormat, CsvOutputFormat};
+pub use self::json::{JsonEncoderConfig, JsonParserConfig, JsonUpdateFormat};
+use self::json::{JsonInputFormat, JsonOutputFormat};
+#[cfg(feature = "with-parquet")]
+pub use self::parquet::{ParquetEncoderConfig, ParquetParserConfig};
+#[cfg(feature = "with-parquet")]
+use self::parquet::{ParquetInputFormat, ParquetOutputFormat};
 
-/// Static map of supported input formats.
-// TODO: support for registering new formats at runtime in order to allow
-// external crates to implement new formats.
-static INPUT_FORMATS: Lazy<BTreeMap<&'static str, Box<dyn InputFormat>>> =
-    Lazy::new(|| BTreeMap::from([("csv", Box::new(CsvInputFormat) as Box<dyn InputFormat>)]));
+/// Registry of supported input formats.
+///
+/// Initialized with the formats built into this crate; additional formats can
+/// be added with [`InputFormat::register_format`].
+static INPUT_FORMATS: Lazy<RwLock<BTreeMap<String, &'static dyn InputFormat>>> = Lazy::new(|| {
+    let formats: Vec<&'static dyn InputFormat> = vec![
+        #[cfg(feature = "with-avro")]
+        &AvroInputFormat,
+        &CsvInputFormat,
+        &JsonInputFormat,
+        #[cfg(feature = "with-parquet")]
+        &ParquetInputFormat,
+    ];
+    RwLock::new(
+        formats
+            .into_iter()
+            .map(|format| (format.name().into_owned(), format))
+            .collect(),
+    )
+});
 
-/// Static map of supported output formats.
-static OUTPUT_FORMATS: Lazy<BTreeMap<&'static str, Box<dyn OutputFormat>>> =
-    Lazy::new(|| BTreeMap::from([("csv", Box::new(CsvOutputFormat) as Box<dyn OutputFormat>)]));
+/// Registry of supported output formats.
+///
+/// Initialized with the formats built into this crate; additional formats can
+/// be added with [`OutputFormat::register_format`].
+static OUTPUT_FORMATS: Lazy<RwLock<BTreeMap<String, &'static dyn OutputFormat>>> =
+    Lazy::new(|| {
+        let formats: Vec<&'static dyn OutputFormat> = vec![
+            #[cfg(feature = "with-avro")]
+            &AvroOutputFormat,
+            &CsvOutputFormat,
+            &JsonOutputFormat,
+            #[cfg(feature = "with-parquet")]
+            &ParquetOutputFormat,
+        ];
+        RwLock::new(
+            formats
+                .into_iter()
+                .map(|format| (format.name().into_owned(), format))
+                .collect(),
+        )
+    });
 
 /// Trait that represents a specific data format.
 ///
@@ -43,7 +98,55 @@ pub trait InputFormat: Send + Sync {
 impl dyn InputFormat {
     /// Lookup input format by name.
     pub fn get_format(name: &str) -> Option<&'static dyn InputFormat> {
-        INPUT_FORMATS.get(name).map(|f| &**f)
+        INPUT_FORMATS.read().unwrap().get(name).copied()
+    }
+
+    /// Make `format` available to [`get_format`](`Self::get_format`) under
+    /// the name returned by its [`name`](`InputFormat::name`) method.
+    ///
+    /// Allows crates that depend on this one to implement new formats.
+    /// Formats must be registered before creating the controller that uses
+    /// them.
+    ///
+    /// # Errors
+    ///
+    /// Fails if a format with the same name is already registered.
+    pub fn register_format(format: Box<dyn InputFormat>) -> AnyResult<()> {
+        register(&INPUT_FORMATS, "format", format.name(), format)
+    }
+}
+
+/// Error parsing an individual record or a fragment of the input stream.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// Error message.
+    pub description: String,
+
+    /// Byte offset of the invalid input in the input stream, if known.
+    pub offset: Option<u64>,
+
+    /// Raw bytes of the invalid input, if available.
+    pub data: Option<Vec<u8>>,
+}
+
+impl ParseError {
+    pub fn new(description: String, offset: Option<u64>, data: Option<&[u8]>) -> Self {
+        Self {
+            description,
+            offset,
+            data: data.map(|data| data.to_vec()),
+        }
+    }
+}
+
+impl StdError for ParseError {}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        match self.offset {
+            Some(offset) => write!(f, "{} (at byte offset {offset})", self.description),
+            None => write!(f, "{}", self.description),
+        }
     }
 }
 
@@ -59,18 +162,22 @@ pub trait Parser: Send {
     /// that cannot be fully parsed until more data or an end-of-file
     /// notification is received.
     ///
-    /// Returns the number of records in the parsed representation or an error
-    /// if parsing fails.
-    fn input(&mut self, data: &[u8]) -> AnyResult<usize>;
+    /// Invalid records don't stop the parser: it skips them and continues
+    /// with the next record, if it is able to find the boundary of the
+    /// invalid record in the input stream.
+    ///
+    /// Returns the number of records pushed to the circuit along with the
+    /// errors encountered while parsing `data`.
+    fn input(&mut self, data: &[u8]) -> (usize, Vec<ParseError>);
 
     /// End-of-input-stream notification.
     ///
     /// No more data will be received from the stream.  The parser uses this
     /// notification to complete or discard any incompletely parsed records.
     ///
-    /// Returns the number of additional records pushed to the circuit or an
-    /// error if parsing fails.
-    fn eoi(&mut self) -> AnyResult<usize>;
+    /// Returns the number of additional records pushed to the circuit along
+    /// with the errors encountered while parsing buffered data.
+    fn eoi(&mut self) -> (usize, Vec<ParseError>);
 
     /// Flush input handles.
     ///
@@ -114,7 +221,15 @@ pub trait OutputFormat: Send + Sync {
 impl dyn OutputFormat {
     /// Lookup output format by name.
     pub fn get_format(name: &str) -> Option<&'static dyn OutputFormat> {
-        OUTPUT_FORMATS.get(name).map(|f| &**f)
+        OUTPUT_FORMATS.read().unwrap().get(name).copied()
+    }
+
+    /// Make `format` available to [`get_format`](`Self::get_format`) under
+    /// the name returned by its [`name`](`OutputFormat::name`) method.
+    ///
+    /// See [`InputFormat::register_format`].
+    pub fn register_format(format: Box<dyn OutputFormat>) -> AnyResult<()> {
+        register(&OUTPUT_FORMATS, "format", format.name(), format)
     }
 }
 
@@ -125,3 +240,82 @@ pub trait Encoder: Send {
 pub trait OutputConsumer: Send {
     fn push_buffer(&mut self, buffer: &[u8]);
 }
+
+#[cfg(test)]
+mod test {
+    use super::{JsonInputFormat, JsonOutputFormat};
+    use crate::{DeCollectionHandle, Encoder, InputFormat, OutputConsumer, OutputFormat, Parser};
+    use anyhow::Result as AnyResult;
+    use serde_yaml::Value as YamlValue;
+    use std::borrow::Cow;
+
+    /// Input format implemented outside of the built-in set, which delegates
+    /// to the `json` format.
+    struct TestInputFormat;
+
+    impl InputFormat for TestInputFormat {
+        fn name(&self) -> Cow<'static, str> {
+            Cow::Borrowed("test_registered_input")
+        }
+
+        fn new_parser(
+            &self,
+            input_stream: &dyn DeCollectionHandle,
+            config: &YamlValue,
+        ) -> AnyResult<Box<dyn Parser>> {
+            JsonInputFormat.new_parser(input_stream, config)
+        }
+    }
+
+    /// Output format implemented outside of the built-in set, which delegates
+    /// to the `json` format.
+    struct TestOutputFormat;
+
+    impl OutputFormat for TestOutputFormat {
+        fn name(&self) -> Cow<'static, str> {
+            Cow::Borrowed("test_registered_output")
+        }
+
+        fn new_encoder(
+            &self,
+            config: &YamlValue,
+            consumer: Box<dyn OutputConsumer>,
+        ) -> AnyResult<Box<dyn Encoder>> {
+            JsonOutputFormat.new_encoder(config, consumer)
+        }
+    }
+
+    #[test]
+    fn test_register_input_format() {
+        assert!(<dyn InputFormat>::get_format("test_registered_input").is_none());
+
+        <dyn InputFormat>::register_format(Box::new(TestInputFormat)).unwrap();
+        let format = <dyn InputFormat>::get_format("test_registered_input").unwrap();
+        assert_eq!(format.name(), "test_registered_input");
+
+        // Names must be unique, including the names of built-in formats.
+        assert!(<dyn InputFormat>::register_format(Box::new(TestInputFormat)).is_err());
+        assert!(<dyn InputFormat>::register_format(Box::new(JsonInputFormat)).is_err());
+        assert!(<dyn InputFormat>::get_format("json").is_some());
+
+        // Input and output formats are registered separately.
+        assert!(<dyn OutputFormat>::get_format("test_registered_input").is_none());
+    }
+
+    #[test]
+    fn test_register_output_format() {
+        assert!(<dyn OutputFormat>::get_format("test_registered_output").is_none());
+
+        <dyn OutputFormat>::register_format(Box::new(TestOutputFormat)).unwrap();
+        let format = <dyn OutputFormat>::get_format("test_registered_output").unwrap();
+        assert_eq!(format.name(), "test_registered_output");
+
+        // Names must be unique, including the names of built-in formats.
+        assert!(<dyn OutputFormat>::register_format(Box::new(TestOutputFormat)).is_err());
+        assert!(<dyn OutputFormat>::register_format(Box::new(JsonOutputFormat)).is_err());
+        assert!(<dyn OutputFormat>::get_format("json").is_some());
+
+        // Input and output formats are registered separately.
+        assert!(<dyn InputFormat>::get_format("test_registered_output").is_none());
+    }
+}
@@ -0,0 +1,677 @@
+use crate::{
+    format::{Encoder, InputFormat, OutputFormat, ParseError, Parser},
+    DeCollectionHandle, OutputConsumer, SerBatch,
+};
+use anyhow::{Error as AnyError, Result as AnyResult};
+use bytes::Bytes;
+use erased_serde::Deserializer as ErasedDeserializer;
+use parquet::{
+    basic::{Repetition, Type as PhysicalType},
+    column::writer::ColumnWriter,
+    data_type::ByteArray,
+    file::{
+        properties::WriterProperties,
+        reader::{FileReader, SerializedFileReader},
+        writer::SerializedFileWriter,
+    },
+    record::{Field, Row},
+    schema::{parser::parse_message_type, types::Type as SchemaType},
+};
+use serde::{
+    de::{
+        value::{Error as DeError, MapDeserializer, SeqDeserializer},
+        Deserializer, Error as _, IntoDeserializer, Visitor,
+    },
+    forward_to_deserialize_any, Deserialize,
+};
+use serde_json::{Map as JsonMap, Value as JsonValue};
+use serde_yaml::Value as YamlValue;
+use std::{borrow::Cow, mem::take, sync::Arc};
+use utoipa::ToSchema;
+
+/// Deserializer that reads a Rust value from a Parquet field.
+struct FieldDeserializer<'a> {
+    field: &'a Field,
+}
+
+impl<'a> FieldDeserializer<'a> {
+    fn new(field: &'a Field) -> Self {
+        Self { field }
+    }
+}
+
+/// Deserializer that reads a struct from the columns of a Parquet row,
+/// skipping column `skip`.
+fn row_deserializer<'a>(
+    row: &'a Row,
+    skip: Option<&'a str>,
+) -> impl Deserializer<'static, Error = DeError> + 'a {
+    MapDeserializer::new(
+        row.get_column_iter()
+            .filter(move |(name, _)| Some(name.as_str()) != skip)
+            .map(|(name, field)| (name.as_str(), FieldDeserializer::new(field))),
+    )
+}
+
+impl<'de, 'a> IntoDeserializer<'de, DeError> for FieldDeserializer<'a> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de, 'a> Deserializer<'de> for FieldDeserializer<'a> {
+    type Error = DeError;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, DeError>
+    where
+        V: Visitor<'de>,
+    {
+        match self.field {
+            Field::Null => visitor.visit_unit(),
+            Field::Bool(b) => visitor.visit_bool(*b),
+            Field::Byte(i) => visitor.visit_i8(*i),
+            Field::Short(i) => visitor.visit_i16(*i),
+            Field::Int(i) | Field::Date(i) => visitor.visit_i32(*i),
+            Field::Long(i) | Field::TimestampMillis(i) | Field::TimestampMicros(i) => {
+                visitor.visit_i64(*i)
+            }
+            Field::UByte(i) => visitor.visit_u8(*i),
+            Field::UShort(i) => visitor.visit_u16(*i),
+            Field::UInt(i) => visitor.visit_u32(*i),
+            Field::ULong(i) => visitor.visit_u64(*i),
+            Field::Float(f) => visitor.visit_f32(*f),
+            Field::Double(f) => visitor.visit_f64(*f),
+            Field::Str(s) => visitor.visit_str(s),
+            Field::Bytes(bytes) => visitor.visit_bytes(bytes.data()),
+            Field::Group(row) => visitor
+                .visit_map(MapDeserializer::new(row.get_column_iter().map(
+                    |(name, field)| (name.as_str(), FieldDeserializer::new(field)),
+                ))),
+            Field::ListInternal(list) => visitor.visit_seq(SeqDeserializer::new(
+                list.elements().iter().map(FieldDeserializer::new),
+            )),
+            Field::MapInternal(map) => visitor.visit_map(MapDeserializer::new(
+                map.entries()
+                    .iter()
+                    .map(|(k, v)| (FieldDeserializer::new(k), FieldDeserializer::new(v))),
+            )),
+            field => Err(DeError::custom(format!(
+                "unsupported Parquet field '{field}'"
+            ))),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, DeError>
+    where
+        V: Visitor<'de>,
+    {
+        match self.field {
+            Field::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+/// Parquet format parser.
+pub struct ParquetInputFormat;
+
+/// Parquet parser configuration.
+///
+/// Parquet files are not streamable: the parser accumulates the contents of
+/// the file and decodes it, one row group at a time, once the end of the
+/// input stream is reached.  Columns of the file are mapped to fields of the
+/// input collection by name.
+#[derive(Clone, Deserialize, ToSchema)]
+pub struct ParquetParserConfig {
+    /// Integer column that contains the weight of each row.  Positive
+    /// weights insert the row `weight` times, negative weights delete it
+    /// `-weight` times.  When not specified, every row is an insertion.
+    ///
+    /// Set to the `weight_column` of the encoder to load files produced by
+    /// the Parquet encoder.
+    #[serde(default)]
+    weight_column: Option<String>,
+}
+
+impl InputFormat for ParquetInputFormat {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("parquet")
+    }
+
+    fn new_parser(
+        &self,
+        input_stream: &dyn DeCollectionHandle,
+        config: &YamlValue,
+    ) -> AnyResult<Box<dyn Parser>> {
+        let config = ParquetParserConfig::deserialize(config)?;
+
+        Ok(Box::new(ParquetParser::new(input_stream, config)) as Box<dyn Parser>)
+    }
+}
+
+struct ParquetParser {
+    /// Input handle to push parsed data to.
+    input_stream: Box<dyn DeCollectionHandle>,
+
+    config: ParquetParserConfig,
+
+    /// File contents received so far.
+    data: Vec<u8>,
+}
+
+impl ParquetParser {
+    fn new(input_stream: &dyn DeCollectionHandle, config: ParquetParserConfig) -> Self {
+        Self {
+            input_stream: input_stream.fork(),
+            config,
+            data: Vec::new(),
+        }
+    }
+
+    fn parse_file(&mut self, data: Vec<u8>) -> (usize, Vec<ParseError>) {
+        let reader = match SerializedFileReader::new(Bytes::from(data)) {
+            Ok(reader) => reader,
+            Err(e) => {
+                return (
+                    0,
+                    vec![ParseError::new(
+                        format!("invalid Parquet file: {e}"),
+                        None,
+                        None,
+                    )],
+                )
+            }
+        };
+        let mut num_records = 0;
+        let mut errors = Vec::new();
+
+        for i in 0..reader.metadata().num_row_groups() {
+            let row_group = reader.get_row_group(i);
+            let rows = row_group
+                .as_ref()
+                .map_err(|e| e.to_string())
+                .and_then(|row_group| row_group.get_row_iter(None).map_err(|e| e.to_string()));
+            let rows = match rows {
+                Ok(rows) => rows,
+                Err(e) => {
+                    errors.push(ParseError::new(
+                        format!("failed to read Parquet row group {i}: {e}"),
+                        None,
+                        None,
+                    ));
+                    continue;
+                }
+            };
+
+            for row in rows {
+                match row {
+                    Ok(row) => match self.apply_row(&row) {
+                        Ok(n) => num_records += n,
+                        Err(e) => errors.push(ParseError::new(e.to_string(), None, None)),
+                    },
+                    Err(e) => {
+                        // Skip the rest of a corrupted row group.
+                        errors.push(ParseError::new(
+                            format!("failed to read Parquet row group {i}: {e}"),
+                            None,
+                            None,
+                        ));
+                        break;
+                    }
+                }
+            }
+        }
+
+        (num_records, errors)
+    }
+
+    fn apply_row(&mut self, row: &Row) -> AnyResult<usize> {
+        let weight_column = self.config.weight_column.as_deref();
+
+        let weight = match weight_column {
+            None => 1,
+            Some(weight_column) => {
+                let weight = row
+                    .get_column_iter()
+                    .find(|(name, _)| name.as_str() == weight_column)
+                    .map(|(_, field)| field);
+                match weight {
+                    Some(Field::Long(weight)) => *weight,
+                    Some(Field::Int(weight)) => *weight as i64,
+                    _ => {
+                        return Err(AnyError::msg(format!(
+                            "Parquet row '{row}' does not contain integer weight column '{weight_column}'"
+                        )))
+                    }
+                }
+            }
+        };
+
+        let mut deserializer =
+            <dyn ErasedDeserializer>::erase(row_deserializer(row, weight_column));
+        self.input_stream
+            .update(&mut deserializer, weight)
+            .map_err(|e| {
+                AnyError::msg(format!("failed to deserialize Parquet row '{row}': {e}"))
+            })?;
+
+        Ok((weight != 0) as usize)
+    }
+}
+
+impl Parser for ParquetParser {
+    fn input(&mut self, data: &[u8]) -> (usize, Vec<ParseError>) {
+        self.data.extend_from_slice(data);
+        (0, Vec::new())
+    }
+
+    fn eoi(&mut self) -> (usize, Vec<ParseError>) {
+        if self.data.is_empty() {
+            return (0, Vec::new());
+        }
+
+        let data = take(&mut self.data);
+        self.parse_file(data)
+    }
+
+    fn flush(&mut self) {
+        self.input_stream.flush();
+    }
+
+    fn clear(&mut self) {
+        self.input_stream.clear_buffer();
+    }
+
+    fn fork(&self) -> Box<dyn Parser> {
+        Box::new(Self::new(&*self.input_stream, self.config.clone()))
+    }
+}
+
+/// Parquet format encoder.
+pub struct ParquetOutputFormat;
+
+const fn default_buffer_size_records() -> usize {
+    100_000
+}
+
+fn default_weight_column() -> String {
+    "__weight".to_string()
+}
+
+/// Parquet encoder configuration.
+///
+/// The encoder sends each output batch to the transport endpoint as one
+/// or more complete Parquet files of at most `buffer_size_records` rows.
+/// Combine it with the `rolling` option of the `file` output transport to
+/// write each file separately.
+#[derive(Deserialize, ToSchema)]
+pub struct ParquetEncoderConfig {
+    /// Parquet schema of the rows of the output stream, in the Parquet
+    /// message type syntax, e.g.,
+    /// `message row { required int64 id; optional binary name (UTF8); }`.
+    ///
+    /// Only flat schemas whose columns have type `boolean`, `int32`,
+    /// `int64`, `float`, `double`, or `binary` are supported.
+    schema: String,
+
+    /// Name of the `int64` column added to the schema to store the weight
+    /// of each row.
+    #[serde(default = "default_weight_column")]
+    weight_column: String,
+
+    /// Maximal number of rows in a Parquet file.
+    #[serde(default = "default_buffer_size_records")]
+    buffer_size_records: usize,
+}
+
+impl OutputFormat for ParquetOutputFormat {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("parquet")
+    }
+
+    fn new_encoder(
+        &self,
+        config: &YamlValue,
+        consumer: Box<dyn OutputConsumer>,
+    ) -> AnyResult<Box<dyn Encoder>> {
+        let config = ParquetEncoderConfig::deserialize(config)?;
+
+        Ok(Box::new(ParquetEncoder::new(consumer, config)?))
+    }
+}
+
+/// Column of the output schema.
+struct Column {
+    name: String,
+    optional: bool,
+    physical_type: PhysicalType,
+}
+
+struct ParquetEncoder {
+    /// Input handle to push serialized data to.
+    output_consumer: Box<dyn OutputConsumer>,
+
+    config: ParquetEncoderConfig,
+
+    /// Output schema, including the weight column.
+    schema: Arc<SchemaType>,
+
+    /// Columns of the output schema, excluding the weight column.
+    columns: Vec<Column>,
+
+    /// Rows accumulated for the next file.
+    rows: Vec<JsonMap<String, JsonValue>>,
+
+    /// Weights of `rows`.
+    weights: Vec<i64>,
+
+    buffer: Vec<u8>,
+}
+
+impl ParquetEncoder {
+    fn new(
+        output_consumer: Box<dyn OutputConsumer>,
+        config: ParquetEncoderConfig,
+    ) -> AnyResult<Self> {
+        // Add the weight column to the schema.
+        let schema = config.schema.trim_end();
+        let schema = schema
+            .strip_suffix('}')
+            .ok_or_else(|| AnyError::msg(format!("invalid Parquet schema '{}'", config.schema)))?;
+        let schema = format!("{schema} required int64 {}; }}", config.weight_column);
+        let schema = parse_message_type(&schema)
+            .map_err(|e| AnyError::msg(format!("invalid Parquet schema: {e}")))?;
+
+        let mut columns = Vec::new();
+        for field in schema.get_fields().iter() {
+            if !field.is_primitive() {
+                return Err(AnyError::msg(format!(
+                    "column '{}' has a nested type; only flat Parquet schemas are supported",
+                    field.name()
+                )));
+            }
+            if field.name() == config.weight_column {
+                continue;
+            }
+
+            let optional = match field.get_basic_info().repetition() {
+                Repetition::REQUIRED => false,
+                Repetition::OPTIONAL => true,
+                Repetition::REPEATED => {
+                    return Err(AnyError::msg(format!(
+                        "repeated column '{}' is not supported",
+                        field.name()
+                    )))
+                }
+            };
+
+            let physical_type = field.get_physical_type();
+            match physical_type {
+                PhysicalType::BOOLEAN
+                | PhysicalType::INT32
+                | PhysicalType::INT64
+                | PhysicalType::FLOAT
+                | PhysicalType::DOUBLE
+                | PhysicalType::BYTE_ARRAY => {}
+                _ => {
+                    return Err(AnyError::msg(format!(
+                        "column '{}' has unsupported type {physical_type}",
+                        field.name()
+                    )))
+                }
+            }
+
+            columns.push(Column {
+                name: field.name().to_string(),
+                optional,
+                physical_type,
+            });
+        }
+
+        Ok(Self {
+            output_consumer,
+            config,
+            schema: Arc::new(schema),
+            columns,
+            rows: Vec::new(),
+            weights: Vec::new(),
+            buffer: Vec::new(),
+        })
+    }
+
+    /// Extract the values of a column from `rows`.
+    ///
+    /// Returns the non-null values along with the definition levels of the
+    /// column, if the column is optional.
+    fn column_values<T, F>(
+        rows: &[JsonMap<String, JsonValue>],
+        column: &Column,
+        convert: F,
+    ) -> AnyResult<(Vec<T>, Option<Vec<i16>>)>
+    where
+        F: Fn(&JsonValue) -> Option<T>,
+    {
+        let mut values = Vec::with_capacity(rows.len());
+        let mut def_levels = Vec::with_capacity(rows.len());
+
+        for row in rows.iter() {
+            match row.get(&column.name) {
+                None | Some(JsonValue::Null) => {
+                    if !column.optional {
+                        return Err(AnyError::msg(format!(
+                            "required column '{}' is null in row '{}'",
+                            column.name,
+                            JsonValue::Object(row.clone())
+                        )));
+                    }
+                    def_levels.push(0);
+                }
+                Some(value) => {
+                    let value = convert(value).ok_or_else(|| {
+                        AnyError::msg(format!(
+                            "invalid value '{value}' of column '{}' of type {}",
+                            column.name, column.physical_type
+                        ))
+                    })?;
+                    values.push(value);
+                    def_levels.push(1);
+                }
+            }
+        }
+
+        Ok((values, column.optional.then_some(def_levels)))
+    }
+
+    /// Write accumulated rows to a Parquet file and send it to the consumer.
+    fn push_file(&mut self) -> AnyResult<()> {
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+
+        self.buffer.clear();
+        let mut writer = SerializedFileWriter::new(
+            &mut self.buffer,
+            self.schema.clone(),
+            Arc::new(WriterProperties::builder().build()),
+        )?;
+        let mut row_group = writer.next_row_group()?;
+        let mut columns = self.columns.iter();
+
+        while let Some(mut column_writer) = row_group.next_column()? {
+            match columns.next() {
+                None => {
+                    // Weight column.
+                    match column_writer.untyped() {
+                        ColumnWriter::Int64ColumnWriter(w) => {
+                            w.write_batch(&self.weights, None, None)?;
+                        }
+                        _ => unreachable!(),
+                    }
+                }
+                Some(column) => match column_writer.untyped() {
+                    ColumnWriter::BoolColumnWriter(w) => {
+                        let (values, def_levels) =
+                            Self::column_values(&self.rows, column, JsonValue::as_bool)?;
+                        w.write_batch(&values, def_levels.as_deref(), None)?;
+                    }
+                    ColumnWriter::Int32ColumnWriter(w) => {
+                        let (values, def_levels) = Self::column_values(&self.rows, column, |v| {
+                            v.as_i64().and_then(|v| i32::try_from(v).ok())
+                        })?;
+                        w.write_batch(&values, def_levels.as_deref(), None)?;
+                    }
+                    ColumnWriter::Int64ColumnWriter(w) => {
+                        let (values, def_levels) =
+                            Self::column_values(&self.rows, column, JsonValue::as_i64)?;
+                        w.write_batch(&values, def_levels.as_deref(), None)?;
+                    }
+                    ColumnWriter::FloatColumnWriter(w) => {
+                        let (values, def_levels) = Self::column_values(&self.rows, column, |v| {
+                            v.as_f64().map(|v| v as f32)
+                        })?;
+                        w.write_batch(&values, def_levels.as_deref(), None)?;
+                    }
+                    ColumnWriter::DoubleColumnWriter(w) => {
+                        let (values, def_levels) =
+                            Self::column_values(&self.rows, column, JsonValue::as_f64)?;
+                        w.write_batch(&values, def_levels.as_deref(), None)?;
+                    }
+                    ColumnWriter::ByteArrayColumnWriter(w) => {
+                        let (values, def_levels) = Self::column_values(&self.rows, column, |v| {
+                            v.as_str().map(ByteArray::from)
+                        })?;
+                        w.write_batch(&values, def_levels.as_deref(), None)?;
+                    }
+                    // Rejected by `ParquetEncoder::new`.
+                    _ => unreachable!(),
+                },
+            }
+            column_writer.close()?;
+        }
+
+        row_group.close()?;
+        writer.close()?;
+
+        self.output_consumer.push_buffer(&self.buffer);
+        self.rows.clear();
+        self.weights.clear();
+
+        Ok(())
+    }
+}
+
+impl Encoder for ParquetEncoder {
+    fn encode(&mut self, batches: &[Arc<dyn SerBatch>]) -> AnyResult<()> {
+        for batch in batches.iter() {
+            let mut cursor = batch.cursor();
+
+            while cursor.key_valid() {
+                let w = cursor.weight();
+
+                match serde_json::to_value(cursor.key())? {
+                    JsonValue::Object(row) => self.rows.push(row),
+                    value => {
+                        return Err(AnyError::msg(format!(
+                            "cannot encode '{value}' as a Parquet row: expected a struct"
+                        )))
+                    }
+                }
+                self.weights.push(w);
+
+                if self.rows.len() >= self.config.buffer_size_records {
+                    self.push_file()?;
+                }
+
+                cursor.step_key();
+            }
+        }
+
+        self.push_file()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        seroutput::SerBatchImpl,
+        test::{MockDeZSet, MockOutputConsumer, TestStruct},
+        InputFormat, OutputFormat, SerBatch,
+    };
+    use dbsp::{trace::Batch, OrdZSet};
+    use std::sync::Arc;
+
+    #[test]
+    fn roundtrip() {
+        let consumer = MockOutputConsumer::new();
+        let config = serde_yaml::from_str(
+            r#"
+schema: "message TestStruct { required int64 id; required boolean b; optional int64 i; required binary s (UTF8); }"
+buffer_size_records: 2
+"#,
+        )
+        .unwrap();
+        let mut encoder = <dyn OutputFormat>::get_format("parquet")
+            .unwrap()
+            .new_encoder(&config, Box::new(consumer.clone()))
+            .unwrap();
+
+        let data = (0..3)
+            .map(|id| TestStruct {
+                id,
+                b: id % 2 == 0,
+                i: if id % 2 == 0 { None } else { Some(id as i64) },
+                s: format!("foo{id}"),
+            })
+            .collect::<Vec<_>>();
+        let batch = OrdZSet::from_keys(
+            (),
+            vec![
+                (data[0].clone(), 1),
+                (data[1].clone(), 2),
+                (data[2].clone(), -1),
+            ],
+        );
+        encoder
+            .encode(&[Arc::new(SerBatchImpl::new(batch)) as Arc<dyn SerBatch>])
+            .unwrap();
+
+        // Three rows split into two files.
+        let files = consumer.buffers();
+        assert_eq!(files.len(), 2);
+
+        let input_handle = MockDeZSet::<TestStruct>::new();
+        let format = <dyn InputFormat>::get_format("parquet").unwrap();
+        for file in files.iter() {
+            let mut parser = format
+                .new_parser(
+                    &input_handle,
+                    &serde_yaml::from_str("weight_column: __weight").unwrap(),
+                )
+                .unwrap();
+            // Deliver the file in two chunks.
+            let (first, second) = file.split_at(file.len() / 2);
+            assert_eq!(parser.input(first), (0, Vec::new()));
+            assert_eq!(parser.input(second), (0, Vec::new()));
+            assert_eq!(parser.eoi().1, Vec::new());
+            parser.flush();
+        }
+
+        assert_eq!(
+            input_handle.state().flushed,
+            vec![
+                (data[0].clone(), true),
+                (data[1].clone(), true),
+                (data[1].clone(), true),
+                (data[2].clone(), false)
+            ]
+        );
+    }
+}
@@ -117,6 +117,7 @@ mod catalog;
 mod controller;
 mod deinput;
 pub mod format;
+mod registry;
 mod seroutput;
 #[cfg(feature = "server")]
 pub mod server;
@@ -141,16 +142,16 @@ pub use catalog::Catalog;
 pub use deinput::{
     DeCollectionHandle, DeMapHandle, DeScalarHandle, DeScalarHandleImpl, DeSetHandle, DeZSetHandle,
 };
-pub use format::{Encoder, InputFormat, OutputConsumer, OutputFormat, Parser};
-pub use seroutput::{SerBatch, SerCursor, SerOutputBatchHandle};
+pub use format::{Encoder, InputFormat, OutputConsumer, OutputFormat, ParseError, Parser};
+pub use seroutput::{SerBatch, SerCursor, SerOutputBatchHandle, SerTrace};
 
 pub use controller::{
     Controller, ControllerError, ControllerStatus, FormatConfig, GlobalPipelineConfig,
-    InputEndpointConfig, OutputEndpointConfig, PipelineConfig, TransportConfig,
+    InputEndpointConfig, OutputEndpointConfig, ParseErrorPolicy, PipelineConfig, TransportConfig,
 };
 pub use transport::{
-    FileInputTransport, InputConsumer, InputEndpoint, InputTransport, OutputEndpoint,
-    OutputTransport,
+    FileInputTransport, InputConsumer, InputEndpoint, InputPosition, InputPositions,
+    InputTransport, OutputEndpoint, OutputTransport, Step,
 };
 
 #[cfg(feature = "server")]
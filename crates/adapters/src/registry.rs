@@ -0,0 +1,29 @@
+//! Registries of the input/output formats and transports available to the
+//! controller.
+
+use anyhow::{Error as AnyError, Result as AnyResult};
+use std::{borrow::Cow, collections::BTreeMap, sync::RwLock};
+
+/// Add `adapter` to `registry` under `name`.
+///
+/// `kind` describes the registry, e.g., `"format"` or `"transport"`, in error
+/// messages.  Registered adapters live for the remainder of the program.
+///
+/// # Errors
+///
+/// Fails if `registry` already contains an adapter named `name`.
+pub(crate) fn register<T: ?Sized + 'static>(
+    registry: &RwLock<BTreeMap<String, &'static T>>,
+    kind: &str,
+    name: Cow<'static, str>,
+    adapter: Box<T>,
+) -> AnyResult<()> {
+    let mut registry = registry.write().unwrap();
+    if registry.contains_key(name.as_ref()) {
+        return Err(AnyError::msg(format!(
+            "{kind} '{name}' is already registered"
+        )));
+    }
+    registry.insert(name.into_owned(), Box::leak(adapter));
+    Ok(())
+}
@@ -2,9 +2,11 @@ use dbsp::{
     trace::{Batch, BatchReader, Cursor},
     OutputHandle,
 };
-use erased_serde::Serialize as ErasedSerialize;
-use serde::Serialize;
-use std::sync::Arc;
+use erased_serde::{
+    deserialize, Deserializer as ErasedDeserializer, Error as EError, Serialize as ErasedSerialize,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{any::Any, sync::Arc};
 
 /// A type-erased batch whose contents can be serialized.
 ///
@@ -25,6 +27,20 @@ pub trait SerBatch: Send + Sync {
     /// Cursor over the batch.
     fn cursor<'a>(&'a self) -> Box<dyn SerCursor + 'a>;
 
+    /// Cursor over the keys of the batch in the range `[start, end)`.
+    ///
+    /// The bounds are deserialized into the key type of the batch.  A
+    /// missing bound leaves the range unbounded on that side.
+    fn range_cursor<'a>(
+        &'a self,
+        start: Option<&mut dyn ErasedDeserializer>,
+        end: Option<&mut dyn ErasedDeserializer>,
+    ) -> Result<Box<dyn SerCursor + 'a>, EError>;
+
+    /// Returns `self` as `Any`, so that it can be downcast to the concrete
+    /// batch type.
+    fn as_any(&self) -> &dyn Any;
+
     // fn fork(&self) -> Box<dyn SerBatch>;
 }
 
@@ -106,8 +122,8 @@ where
 
 impl<B> SerBatch for SerBatchImpl<B>
 where
-    B: BatchReader<Time = ()> + Clone + Send + Sync,
-    B::Key: Serialize,
+    B: BatchReader<Time = ()> + Clone + Send + Sync + 'static,
+    B::Key: Serialize + DeserializeOwned,
     B::Val: Serialize,
     B::R: Into<i64>,
 {
@@ -123,6 +139,25 @@ where
         Box::new(SerBatchCursor::new(&*self.batch))
     }
 
+    fn range_cursor<'a>(
+        &'a self,
+        start: Option<&mut dyn ErasedDeserializer>,
+        end: Option<&mut dyn ErasedDeserializer>,
+    ) -> Result<Box<dyn SerCursor + 'a>, EError> {
+        let start = start.map(deserialize::<B::Key>).transpose()?;
+        let end = end.map(deserialize::<B::Key>).transpose()?;
+
+        Ok(Box::new(SerBatchCursor::with_range(
+            &*self.batch,
+            start,
+            end,
+        )))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
     /*fn fork(&self) -> Box<dyn SerBatch> {
         Box::new(Self {
             batch: self.batch.clone(),
@@ -130,12 +165,19 @@ where
     }*/
 }
 
-/// [`SerCursor`] implementation that wraps a [`Cursor`].
+/// [`SerCursor`] implementation that wraps a [`Cursor`], optionally
+/// restricted to a range of keys.
 pub struct SerBatchCursor<'a, B>
 where
     B: BatchReader,
 {
     cursor: B::Cursor<'a>,
+
+    /// Lower bound (inclusive) of the keys returned by the cursor.
+    start: Option<B::Key>,
+
+    /// Upper bound (exclusive) of the keys returned by the cursor.
+    end: Option<B::Key>,
 }
 
 impl<'a, B> SerBatchCursor<'a, B>
@@ -143,9 +185,17 @@ where
     B: BatchReader,
 {
     pub fn new(batch: &'a B) -> Self {
-        let cursor = batch.cursor();
+        Self::with_range(batch, None, None)
+    }
+
+    /// Cursor over keys in the range `[start, end)`.
+    pub fn with_range(batch: &'a B, start: Option<B::Key>, end: Option<B::Key>) -> Self {
+        let mut cursor = batch.cursor();
+        if let Some(start) = &start {
+            cursor.seek_key(start);
+        }
 
-        Self { cursor }
+        Self { cursor, start, end }
     }
 }
 
@@ -158,6 +208,10 @@ where
 {
     fn key_valid(&self) -> bool {
         self.cursor.key_valid()
+            && match &self.end {
+                Some(end) => self.cursor.key() < end,
+                None => true,
+            }
     }
 
     fn val_valid(&self) -> bool {
@@ -188,6 +242,9 @@ where
     /// Rewinds the cursor to the first key.
     fn rewind_keys(&mut self) {
         self.cursor.rewind_keys();
+        if let Some(start) = &self.start {
+            self.cursor.seek_key(start);
+        }
     }
 
     /// Rewinds the cursor to the first value for current key.
@@ -216,12 +273,16 @@ pub trait SerOutputBatchHandle: Send + Sync {
 
     /// Returns an alias to `self`.
     fn fork(&self) -> Box<dyn SerOutputBatchHandle>;
+
+    /// Creates an empty [`SerTrace`] that accumulates batches produced by
+    /// this handle.
+    fn new_trace(&self) -> Box<dyn SerTrace>;
 }
 
 impl<B> SerOutputBatchHandle for OutputHandle<B>
 where
-    B: Batch<Time = ()> + Send + Sync,
-    B::Key: Serialize,
+    B: Batch<Time = ()> + Send + Sync + 'static,
+    B::Key: Serialize + DeserializeOwned,
     B::Val: Serialize,
     B::R: Into<i64>,
 {
@@ -245,4 +306,111 @@ where
     fn fork(&self) -> Box<dyn SerOutputBatchHandle> {
         Box::new(self.clone())
     }
+
+    fn new_trace(&self) -> Box<dyn SerTrace> {
+        Box::new(SerTraceImpl::<B>::new())
+    }
+}
+
+/// A type-erased trace that integrates the output batches of a stream.
+///
+/// The trace accumulates all changes produced by an output stream, so that
+/// the current contents of the stream can be retrieved at any point without
+/// replaying the stream from the beginning.
+pub trait SerTrace: Send {
+    /// Add `batches` to the trace.
+    ///
+    /// Panics if the batches were not produced by the output handle that
+    /// created this trace.
+    fn insert(&mut self, batches: &[Arc<dyn SerBatch>]);
+
+    /// Returns the consolidated contents of the trace.
+    fn consolidate(&mut self) -> Arc<dyn SerBatch>;
+
+    /// Returns a copy of the trace.
+    ///
+    /// The copy shares batches with `self`, so forking is cheap, unlike
+    /// [`consolidate`](`Self::consolidate`), which can be called on the copy
+    /// without holding a lock on the original trace.
+    fn fork(&self) -> Box<dyn SerTrace>;
+}
+
+/// [`SerTrace`] implementation that stores a stack of batches of
+/// geometrically decreasing sizes.
+///
+/// Each new batch is merged with its predecessors until every batch in the
+/// stack is at least twice as large as the next one, which keeps the number
+/// of batches logarithmic in the size of the trace and the amortized cost of
+/// an insertion low.
+pub struct SerTraceImpl<B> {
+    batches: Vec<Arc<B>>,
+}
+
+impl<B> SerTraceImpl<B> {
+    pub fn new() -> Self {
+        Self {
+            batches: Vec::new(),
+        }
+    }
+}
+
+impl<B> Default for SerTraceImpl<B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<B> SerTrace for SerTraceImpl<B>
+where
+    B: Batch<Time = ()> + Send + Sync + 'static,
+    B::Key: Serialize + DeserializeOwned,
+    B::Val: Serialize,
+    B::R: Into<i64>,
+{
+    fn insert(&mut self, batches: &[Arc<dyn SerBatch>]) {
+        for batch in batches.iter() {
+            let batch = &batch
+                .as_any()
+                .downcast_ref::<SerBatchImpl<B>>()
+                .expect("batch type does not match trace type")
+                .batch;
+            if batch.is_empty() {
+                continue;
+            }
+            self.batches.push(batch.clone());
+
+            while self.batches.len() >= 2 {
+                let n = self.batches.len();
+                if self.batches[n - 1].len() * 2 < self.batches[n - 2].len() {
+                    break;
+                }
+                let last = self.batches.pop().unwrap();
+                let prev = self.batches.pop().unwrap();
+                self.batches.push(Arc::new(prev.merge(&last)));
+            }
+        }
+    }
+
+    fn consolidate(&mut self) -> Arc<dyn SerBatch> {
+        let batch = match self.batches.split_first() {
+            None => Arc::new(B::empty(())),
+            Some((first, rest)) => {
+                let batch = rest
+                    .iter()
+                    .fold(first.clone(), |acc, batch| Arc::new(acc.merge(batch)));
+                // Keep the merged batch, so that consecutive calls don't
+                // repeat the work.
+                self.batches = vec![batch.clone()];
+                batch
+            }
+        };
+
+        Arc::new(SerBatchImpl { batch })
+    }
+
+    fn fork(&self) -> Box<dyn SerTrace> {
+        Box::new(Self {
+            batches: self.batches.clone(),
+        })
+    }
 }
@@ -1,11 +1,14 @@
 use crate::{
-    Catalog, Controller, ControllerError, HttpInputTransport, HttpOutputTransport, PipelineConfig,
+    transport::HttpInputError, Catalog, Controller, ControllerError, HttpInputTransport,
+    HttpOutputTransport, InputEndpointConfig, OutputEndpointConfig, PipelineConfig,
 };
 use actix_web::{
+    delete,
     dev::{Server, ServiceFactory, ServiceRequest},
     get,
+    http::header,
     middleware::Logger,
-    rt, web,
+    post, put, rt, web,
     web::Data as WebData,
     App, Error as ActixError, HttpRequest, HttpResponse, HttpServer, Responder,
 };
@@ -13,23 +16,34 @@ use actix_web_static_files::ResourceFiles;
 use anyhow::{Error as AnyError, Result as AnyResult};
 use clap::Parser;
 use colored::Colorize;
-use dbsp::DBSPHandle;
+use dbsp::{circuit::CircuitConfig, DBSPHandle};
 use env_logger::Env;
 use log::{error, info};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::io::Write;
-use std::{net::TcpListener, sync::Mutex};
+use std::{
+    net::TcpListener,
+    sync::RwLock,
+    time::{Duration, Instant},
+};
 use tokio::{
     spawn,
     sync::mpsc::{channel, Receiver, Sender},
 };
 mod prometheus;
+mod view;
 
 use self::prometheus::PrometheusMetrics;
+use self::view::{render_snapshot, ViewQuery};
 
 struct ServerState {
     metadata: String,
-    controller: Mutex<Option<Controller>>,
+    /// The controller; `None` after shutdown.
+    ///
+    /// Requests share the lock, so that a slow request, e.g., connecting an
+    /// endpoint, doesn't block other requests.  Shutdown takes the lock
+    /// exclusively.
+    controller: RwLock<Option<Controller>>,
     prometheus: PrometheusMetrics,
     /// Channel used to send a `kill` command to
     /// the self-destruct task when shutting down
@@ -46,7 +60,7 @@ impl ServerState {
     ) -> Self {
         Self {
             metadata: meta,
-            controller: Mutex::new(Some(controller)),
+            controller: RwLock::new(Some(controller)),
             prometheus,
             terminate_sender,
         }
@@ -99,13 +113,62 @@ pub const SERVER_PORT_FILE: &str = "port";
 ///
 /// # Arguments
 ///
-/// * `circuit_factory` - a function that creates a circuit and builds an
-///   input/output stream
-/// catalog.
+/// * `circuit_factory` - a function that creates a circuit with the
+///   specified number of workers and builds an input/output stream catalog.
+///
+/// Circuits created this way keep all state in memory; use
+/// [`server_main_with_config`] to run pipelines that configure persistent
+/// storage.
 pub fn server_main<F>(circuit_factory: &F) -> AnyResult<()>
 where
     F: Fn(usize) -> (DBSPHandle, Catalog),
 {
+    let (config, meta, default_port) = init()?;
+
+    if config.global.storage.is_some() {
+        let err = "the pipeline configures persistent storage, which is not supported by \
+            circuits that only take the number of workers"
+            .to_string();
+        error!("Failed to create pipeline: {err}");
+        return Err(AnyError::msg(err));
+    }
+
+    run_server(
+        &|circuit_config: CircuitConfig| {
+            circuit_factory(circuit_config.layout.local_workers().len())
+        },
+        &config,
+        meta,
+        default_port,
+    )
+    .map_err(|e| {
+        error!("{e}");
+        e
+    })
+}
+
+/// Server main function for circuits that take a [`CircuitConfig`].
+///
+/// Like [`server_main`], but `circuit_factory` creates a circuit from the
+/// [`CircuitConfig`] derived from the pipeline configuration, including its
+/// persistent storage settings.
+pub fn server_main_with_config<F>(circuit_factory: &F) -> AnyResult<()>
+where
+    F: Fn(CircuitConfig) -> (DBSPHandle, Catalog),
+{
+    let (config, meta, default_port) = init()?;
+
+    run_server(circuit_factory, &config, meta, default_port).map_err(|e| {
+        error!("{e}");
+        e
+    })
+}
+
+/// Parse command line arguments and the pipeline configuration and setup
+/// logging.
+///
+/// Returns pipeline configuration, pipeline metadata and the default port.
+fn init() -> AnyResult<(PipelineConfig, String, Option<u16>)> {
     let args = Args::try_parse()?;
     let yaml_config = std::fs::read(&args.config_file)?;
     let yaml_config = String::from_utf8(yaml_config)?;
@@ -137,10 +200,8 @@ where
             String::from_utf8(meta)?
         }
     };
-    run_server(circuit_factory, &config, meta, args.default_port).map_err(|e| {
-        error!("{e}");
-        e
-    })
+
+    Ok((config, meta, args.default_port))
 }
 
 pub fn run_server<F>(
@@ -150,7 +211,7 @@ pub fn run_server<F>(
     default_port: Option<u16>,
 ) -> AnyResult<()>
 where
-    F: Fn(usize) -> (DBSPHandle, Catalog),
+    F: Fn(CircuitConfig) -> (DBSPHandle, Catalog),
 {
     // NOTE: The pipeline manager monitors pipeline log for one of the following
     // messages ("Failed to create pipeline..." or "Started HTTP server...").
@@ -183,9 +244,9 @@ pub fn create_server<F>(
     default_port: Option<u16>,
 ) -> AnyResult<(u16, Server, Receiver<()>)>
 where
-    F: Fn(usize) -> (DBSPHandle, Catalog),
+    F: Fn(CircuitConfig) -> (DBSPHandle, Catalog),
 {
-    let (circuit, catalog) = circuit_factory(config.global.workers as usize);
+    let (circuit, catalog) = circuit_factory(config.global.circuit_config());
 
     let controller = Controller::with_config(
         circuit,
@@ -253,12 +314,20 @@ where
         .service(metadata)
         .service(dump_profile)
         .service(input_endpoint)
+        .service(input_endpoint_post)
         .service(output_endpoint)
+        .service(view)
+        .service(connect_input_endpoint)
+        .service(reconfigure_input_endpoint)
+        .service(disconnect_input_endpoint)
+        .service(connect_output_endpoint)
+        .service(reconfigure_output_endpoint)
+        .service(disconnect_output_endpoint)
 }
 
 #[get("/start")]
 async fn start(state: WebData<ServerState>) -> impl Responder {
-    match &*state.controller.lock().unwrap() {
+    match &*state.controller.read().unwrap() {
         Some(controller) => {
             controller.start();
             HttpResponse::Ok().json("The pipeline is running")
@@ -271,7 +340,7 @@ async fn start(state: WebData<ServerState>) -> impl Responder {
 
 #[get("/pause")]
 async fn pause(state: WebData<ServerState>) -> impl Responder {
-    match &*state.controller.lock().unwrap() {
+    match &*state.controller.read().unwrap() {
         Some(controller) => {
             controller.pause();
             HttpResponse::Ok().json("Pipeline paused")
@@ -284,7 +353,7 @@ async fn pause(state: WebData<ServerState>) -> impl Responder {
 
 #[get("/status")]
 async fn status(state: WebData<ServerState>) -> impl Responder {
-    match &*state.controller.lock().unwrap() {
+    match &*state.controller.read().unwrap() {
         Some(controller) => {
             let json_string = serde_json::to_string(controller.status()).unwrap();
             HttpResponse::Ok()
@@ -300,7 +369,7 @@ async fn status(state: WebData<ServerState>) -> impl Responder {
 /// This endpoint is invoked by the Prometheus server.
 #[get("/metrics")]
 async fn metrics(state: WebData<ServerState>) -> impl Responder {
-    match &*state.controller.lock().unwrap() {
+    match &*state.controller.read().unwrap() {
         Some(controller) => match state.prometheus.metrics(controller) {
             Ok(metrics) => HttpResponse::Ok()
                 .content_type(mime::TEXT_PLAIN)
@@ -322,7 +391,7 @@ async fn metadata(state: WebData<ServerState>) -> impl Responder {
 
 #[get("/dump_profile")]
 async fn dump_profile(state: WebData<ServerState>) -> impl Responder {
-    match &*state.controller.lock().unwrap() {
+    match &*state.controller.read().unwrap() {
         Some(controller) => {
             controller.dump_profile();
             HttpResponse::Ok().json("Profile dump initiated")
@@ -335,7 +404,7 @@ async fn dump_profile(state: WebData<ServerState>) -> impl Responder {
 
 #[get("/shutdown")]
 async fn shutdown(state: WebData<ServerState>) -> impl Responder {
-    let controller = state.controller.lock().unwrap().take();
+    let controller = state.controller.write().unwrap().take();
     if let Some(controller) = controller {
         match controller.stop() {
             Ok(()) => {
@@ -369,22 +438,860 @@ async fn input_endpoint(req: HttpRequest, stream: web::Payload) -> impl Responde
     }
 }
 
+/// Interval between checks for completion of a POST request to an input
+/// endpoint submitted with `?wait=processed`.
+const INPUT_POST_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+fn default_input_post_timeout_ms() -> u64 {
+    60_000
+}
+
+/// When to respond to a POST request to an input endpoint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum InputPostWait {
+    /// After the data has been queued for processing.
+    Queued,
+
+    /// After the data has been processed by a step of the circuit.
+    Processed,
+}
+
+impl Default for InputPostWait {
+    fn default() -> Self {
+        Self::Queued
+    }
+}
+
+/// Query parameters of the `POST /input_endpoint/{endpoint_name}` request.
+#[derive(Deserialize)]
+struct InputPostQuery {
+    #[serde(default)]
+    wait: InputPostWait,
+
+    /// Maximal time to wait for the data to be processed with
+    /// `?wait=processed`, in milliseconds.
+    #[serde(default = "default_input_post_timeout_ms")]
+    timeout_ms: u64,
+}
+
+/// Push the body of the request to an HTTP input endpoint.
+///
+/// By default, responds after the data has been queued for processing;
+/// with `?wait=processed`, responds after the circuit has processed it.  If
+/// the data has not been processed within `timeout_ms` milliseconds (60
+/// seconds by default), responds with `202 Accepted`: the data remains
+/// queued.  Returns `503 Service Unavailable` with a `Retry-After` header if
+/// the endpoint is paused, e.g., due to backpressure.
+#[post("/input_endpoint/{endpoint_name}")]
+async fn input_endpoint_post(
+    state: WebData<ServerState>,
+    req: HttpRequest,
+    query: web::Query<InputPostQuery>,
+    payload: web::Payload,
+) -> impl Responder {
+    let endpoint_name = match req.match_info().get("endpoint_name") {
+        None => return HttpResponse::BadRequest().body("Missing endpoint name argument"),
+        Some(endpoint_name) => endpoint_name,
+    };
+
+    if let Err(e) = HttpInputTransport::post_data(endpoint_name, payload).await {
+        let mut response = HttpResponse::build(e.status_code());
+        if matches!(e, HttpInputError::Paused) {
+            response.insert_header((header::RETRY_AFTER, "1"));
+        }
+        return response.json(&ErrorResponse::new(&format!(
+            "Failed to push data to input HTTP endpoint: {e}"
+        )));
+    }
+
+    if query.wait == InputPostWait::Queued {
+        return HttpResponse::Ok().json("Data queued");
+    }
+
+    // All records queued so far are processed by the time the number of
+    // processed records reaches the number of input records.
+    let terminated =
+        || HttpResponse::Conflict().json(&ErrorResponse::new("The pipeline has been terminated"));
+    let total_input_records = match &*state.controller.read().unwrap() {
+        Some(controller) => controller.status().num_total_input_records(),
+        None => return terminated(),
+    };
+    let deadline = Instant::now() + Duration::from_millis(query.timeout_ms);
+    loop {
+        match &*state.controller.read().unwrap() {
+            Some(controller)
+                if controller.status().num_total_processed_records() >= total_input_records =>
+            {
+                return HttpResponse::Ok().json("Data processed")
+            }
+            Some(_) => {}
+            None => return terminated(),
+        }
+        if Instant::now() >= deadline {
+            return HttpResponse::Accepted()
+                .json("Data queued; timed out waiting for it to be processed");
+        }
+        rt::time::sleep(INPUT_POST_POLL_INTERVAL).await;
+    }
+}
+
+/// Connect to an HTTP output endpoint.
+///
+/// Opens a websocket if the request is a websocket upgrade request;
+/// otherwise streams output in the body of the response.
 #[get("/output_endpoint/{endpoint_name}")]
 async fn output_endpoint(req: HttpRequest, stream: web::Payload) -> impl Responder {
     match req.match_info().get("endpoint_name") {
         None => HttpResponse::BadRequest().body("Missing endpoint name argument"),
         Some(endpoint_name) => {
-            HttpOutputTransport::get_endpoint_websocket(endpoint_name, &req, stream).unwrap_or_else(
-                |e| {
-                    HttpResponse::InternalServerError().json(&ErrorResponse::new(&format!(
-                        "Failed to establish connection to output HTTP endpoint: {e}"
-                    )))
-                },
-            )
+            let response = if req.headers().contains_key(header::UPGRADE) {
+                HttpOutputTransport::get_endpoint_websocket(endpoint_name, &req, stream)
+            } else {
+                HttpOutputTransport::get_endpoint_stream(endpoint_name, &req)
+            };
+            response.unwrap_or_else(|e| {
+                HttpResponse::build(e.status_code()).json(&ErrorResponse::new(&format!(
+                    "Failed to establish connection to output HTTP endpoint: {e}"
+                )))
+            })
         }
     }
 }
 
+/// Returns the current contents of an output stream listed in
+/// `snapshot_streams`.
+///
+/// Supports the following query parameters:
+///
+/// * `format` - `json` (default) or `csv`.
+/// * `start`, `end` - JSON-encoded records that bound the range of records to
+///   return.
+/// * `limit` - maximal number of distinct records to return.
+#[get("/view/{stream_name}")]
+async fn view(
+    state: WebData<ServerState>,
+    req: HttpRequest,
+    query: web::Query<ViewQuery>,
+) -> impl Responder {
+    let stream_name = match req.match_info().get("stream_name") {
+        None => return HttpResponse::BadRequest().body("Missing stream name argument"),
+        Some(stream_name) => stream_name.to_string(),
+    };
+
+    // Consolidating and encoding the snapshot can take a while; do it on the
+    // blocking thread pool after releasing the controller lock.
+    let result = web::block({
+        let stream_name = stream_name.clone();
+        let query = query.into_inner();
+        move || {
+            let trace = state
+                .controller
+                .read()
+                .unwrap()
+                .as_ref()
+                .map(|controller| controller.snapshot(&stream_name));
+            trace.map(|trace| trace.map(|mut trace| render_snapshot(trace.consolidate(), &query)))
+        }
+    })
+    .await;
+
+    match result {
+        Ok(Some(Some(Ok((content_type, body))))) => {
+            HttpResponse::Ok().content_type(content_type).body(body)
+        }
+        Ok(Some(Some(Err(e)))) => HttpResponse::BadRequest().json(&ErrorResponse::new(&format!(
+            "Failed to query stream '{stream_name}': {e}"
+        ))),
+        Ok(Some(None)) => HttpResponse::NotFound().json(&ErrorResponse::new(&format!(
+            "Stream '{stream_name}' does not exist or is not listed in 'snapshot_streams'"
+        ))),
+        Ok(None) => {
+            HttpResponse::Conflict().json(&ErrorResponse::new("The pipeline has been terminated"))
+        }
+        Err(e) => HttpResponse::InternalServerError().json(&ErrorResponse::new(&format!(
+            "Failed to query stream '{stream_name}': {e}"
+        ))),
+    }
+}
+
+/// Apply `f` to the controller and the endpoint name in the request path.
+///
+/// `action` describes the request in error messages; `done` describes its
+/// outcome in the response to a successful request.  `f` runs on the
+/// blocking thread pool, since connecting an endpoint can block, e.g., while
+/// connecting to a Kafka broker.
+async fn endpoint_request<F>(
+    state: WebData<ServerState>,
+    req: &HttpRequest,
+    action: &str,
+    done: &str,
+    f: F,
+) -> HttpResponse
+where
+    F: FnOnce(&Controller, &str) -> AnyResult<()> + Send + 'static,
+{
+    let endpoint_name = match req.match_info().get("endpoint_name") {
+        None => return HttpResponse::BadRequest().body("Missing endpoint name argument"),
+        Some(endpoint_name) => endpoint_name.to_string(),
+    };
+
+    let result = web::block({
+        let endpoint_name = endpoint_name.clone();
+        move || {
+            state
+                .controller
+                .read()
+                .unwrap()
+                .as_ref()
+                .map(|controller| f(controller, &endpoint_name))
+        }
+    })
+    .await;
+
+    match result {
+        Ok(Some(Ok(()))) => HttpResponse::Ok().json(format!("Endpoint '{endpoint_name}' {done}")),
+        Ok(Some(Err(e))) => HttpResponse::BadRequest().json(&ErrorResponse::new(&format!(
+            "Failed to {action} '{endpoint_name}': {e}"
+        ))),
+        Ok(None) => {
+            HttpResponse::Conflict().json(&ErrorResponse::new("The pipeline has been terminated"))
+        }
+        Err(e) => HttpResponse::InternalServerError().json(&ErrorResponse::new(&format!(
+            "Failed to {action} '{endpoint_name}': {e}"
+        ))),
+    }
+}
+
+/// Connect a new input endpoint; the request body contains endpoint
+/// configuration in JSON format.
+#[post("/input_endpoints/{endpoint_name}")]
+async fn connect_input_endpoint(
+    state: WebData<ServerState>,
+    req: HttpRequest,
+    config: web::Json<InputEndpointConfig>,
+) -> impl Responder {
+    let config = config.into_inner();
+    endpoint_request(
+        state,
+        &req,
+        "connect input endpoint",
+        "connected",
+        move |controller, endpoint_name| controller.connect_input(endpoint_name, &config),
+    )
+    .await
+}
+
+/// Change the configuration of an input endpoint; the request body contains
+/// the new endpoint configuration in JSON format.
+#[put("/input_endpoints/{endpoint_name}")]
+async fn reconfigure_input_endpoint(
+    state: WebData<ServerState>,
+    req: HttpRequest,
+    config: web::Json<InputEndpointConfig>,
+) -> impl Responder {
+    let config = config.into_inner();
+    endpoint_request(
+        state,
+        &req,
+        "reconfigure input endpoint",
+        "reconfigured",
+        move |controller, endpoint_name| controller.reconfigure_input(endpoint_name, &config),
+    )
+    .await
+}
+
+#[delete("/input_endpoints/{endpoint_name}")]
+async fn disconnect_input_endpoint(
+    state: WebData<ServerState>,
+    req: HttpRequest,
+) -> impl Responder {
+    endpoint_request(
+        state,
+        &req,
+        "disconnect input endpoint",
+        "disconnected",
+        |controller, endpoint_name| controller.disconnect_input(endpoint_name),
+    )
+    .await
+}
+
+/// Connect a new output endpoint; the request body contains endpoint
+/// configuration in JSON format.
+#[post("/output_endpoints/{endpoint_name}")]
+async fn connect_output_endpoint(
+    state: WebData<ServerState>,
+    req: HttpRequest,
+    config: web::Json<OutputEndpointConfig>,
+) -> impl Responder {
+    let config = config.into_inner();
+    endpoint_request(
+        state,
+        &req,
+        "connect output endpoint",
+        "connected",
+        move |controller, endpoint_name| controller.connect_output(endpoint_name, &config),
+    )
+    .await
+}
+
+/// Change the configuration of an output endpoint; the request body contains
+/// the new endpoint configuration in JSON format.
+#[put("/output_endpoints/{endpoint_name}")]
+async fn reconfigure_output_endpoint(
+    state: WebData<ServerState>,
+    req: HttpRequest,
+    config: web::Json<OutputEndpointConfig>,
+) -> impl Responder {
+    let config = config.into_inner();
+    endpoint_request(
+        state,
+        &req,
+        "reconfigure output endpoint",
+        "reconfigured",
+        move |controller, endpoint_name| controller.reconfigure_output(endpoint_name, &config),
+    )
+    .await
+}
+
+#[delete("/output_endpoints/{endpoint_name}")]
+async fn disconnect_output_endpoint(
+    state: WebData<ServerState>,
+    req: HttpRequest,
+) -> impl Responder {
+    endpoint_request(
+        state,
+        &req,
+        "disconnect output endpoint",
+        "disconnected",
+        |controller, endpoint_name| controller.disconnect_output(endpoint_name),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod test {
+    use super::{build_app, PrometheusMetrics, ServerState};
+    use crate::{
+        test::{
+            test_circuit,
+            websocket::{TestWsReceiver, TestWsSender},
+            TestStruct,
+        },
+        Controller, ControllerError, PipelineConfig,
+    };
+    use actix_http::ws::Frame as WsFrame;
+    use actix_web::{http::StatusCode, rt, web::Data as WebData, App};
+    use bytes::Bytes;
+    use csv::{ReaderBuilder as CsvReaderBuilder, WriterBuilder as CsvWriterBuilder};
+    use dbsp::circuit::CircuitConfig;
+    use futures::StreamExt;
+    use serde_json::{json, Value as JsonValue};
+    use std::{pin::Pin, thread::sleep, time::Duration};
+    use tempfile::NamedTempFile;
+
+    fn test_data() -> Vec<TestStruct> {
+        (0..5)
+            .map(|id| TestStruct {
+                id,
+                b: id % 2 == 0,
+                i: if id % 2 == 0 { Some(id as i64) } else { None },
+                s: format!("foo{id}"),
+            })
+            .collect()
+    }
+
+    fn test_server(config_str: &str) -> actix_test::TestServer {
+        let config: PipelineConfig = serde_yaml::from_str(config_str).unwrap();
+
+        let (circuit, catalog) = test_circuit(CircuitConfig::from(2));
+        let controller = Controller::with_config(
+            circuit,
+            catalog,
+            &config,
+            Box::new(|e| panic!("error: {e}")) as Box<dyn Fn(ControllerError) + Send + Sync>,
+        )
+        .unwrap();
+        controller.start();
+
+        let prometheus = PrometheusMetrics::new(&controller).unwrap();
+        let state = WebData::new(ServerState::new(
+            controller,
+            prometheus,
+            "metadata".to_string(),
+            None,
+        ));
+        actix_test::start(move || build_app(App::new(), state.clone()))
+    }
+
+    #[actix_web::test]
+    async fn test_view() {
+        let data = test_data();
+
+        let temp_input_file = NamedTempFile::new().unwrap();
+        let mut writer = CsvWriterBuilder::new()
+            .has_headers(false)
+            .from_writer(temp_input_file.as_file());
+        for val in data.iter() {
+            writer.serialize(val).unwrap();
+        }
+        writer.flush().unwrap();
+
+        let config_str = format!(
+            r#"
+name: test
+snapshot_streams: [test_output1]
+inputs:
+    test_input1:
+        stream: test_input1
+        transport:
+            name: file
+            config:
+                path: {:?}
+        format:
+            name: csv
+"#,
+            temp_input_file.path().to_str().unwrap(),
+        );
+        let server = test_server(&config_str);
+
+        // JSON snapshot; wait for the pipeline to process the input file.
+        let mut records: Vec<TestStruct> = Vec::new();
+        while records.len() < data.len() {
+            sleep(Duration::from_millis(100));
+            let mut resp = server.get("/view/test_output1").send().await.unwrap();
+            assert!(resp.status().is_success());
+            records = resp.json().await.unwrap();
+        }
+        records.sort();
+        assert_eq!(records, data);
+
+        // CSV snapshot.
+        let mut resp = server
+            .get("/view/test_output1?format=csv")
+            .send()
+            .await
+            .unwrap();
+        assert!(resp.status().is_success());
+        let body = resp.body().await.unwrap();
+        let mut records: Vec<TestStruct> = CsvReaderBuilder::new()
+            .has_headers(false)
+            .from_reader(&body[..])
+            .deserialize()
+            .map(|record| record.unwrap())
+            .collect();
+        records.sort();
+        assert_eq!(records, data);
+
+        // Key range.
+        let start = serde_json::to_string(&data[1]).unwrap();
+        let end = serde_json::to_string(&data[3]).unwrap();
+        let mut resp = server
+            .get("/view/test_output1")
+            .query(&[("start", start.as_str()), ("end", end.as_str())])
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        assert!(resp.status().is_success());
+        let mut records: Vec<TestStruct> = resp.json().await.unwrap();
+        records.sort();
+        assert_eq!(records, data[1..3]);
+
+        // Limit.
+        let mut resp = server
+            .get("/view/test_output1?limit=2")
+            .send()
+            .await
+            .unwrap();
+        assert!(resp.status().is_success());
+        let records: Vec<TestStruct> = resp.json().await.unwrap();
+        assert_eq!(records.len(), 2);
+
+        // Invalid requests.
+        let resp = server.get("/view/unknown").send().await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let resp = server
+            .get("/view/test_output1?format=avro")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        // Bounds must be complete records.
+        let resp = server
+            .get("/view/test_output1")
+            .query(&[("start", r#"{"id": 1}"#)])
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let resp = server.get("/shutdown").send().await.unwrap();
+        assert!(resp.status().is_success());
+    }
+
+    #[actix_web::test]
+    async fn test_snapshot_subscription() {
+        let data = test_data();
+        let batch1 = vec![data[0..3].to_vec()];
+        let batch2 = vec![data[3..].to_vec()];
+
+        let server = test_server(
+            r#"
+name: test
+inputs:
+    test_snapshot_input_http:
+        stream: test_input1
+        transport:
+            name: http
+        format:
+            name: csv
+outputs:
+    test_snapshot_output_http:
+        stream: test_output1
+        transport:
+            name: http
+            config:
+                snapshots: true
+        format:
+            name: csv
+    test_changes_output_http:
+        stream: test_output1
+        transport:
+            name: http
+        format:
+            name: csv
+"#,
+        );
+
+        let mut input_ws = server
+            .ws_at("/input_endpoint/test_snapshot_input_http")
+            .await
+            .unwrap();
+        let mut changes_ws = server
+            .ws_at("/output_endpoint/test_snapshot_output_http")
+            .await
+            .unwrap();
+
+        TestWsSender::send_to_websocket(Pin::new(&mut input_ws), &batch1).await;
+        TestWsReceiver::wait_for_output_unordered(Pin::new(&mut changes_ws), &batch1).await;
+
+        // A late subscriber receives the current contents of the stream,
+        // followed by a marker.
+        let mut snapshot_ws = server
+            .ws_at("/output_endpoint/test_snapshot_output_http?mode=snapshot")
+            .await
+            .unwrap();
+        TestWsReceiver::wait_for_output_unordered(Pin::new(&mut snapshot_ws), &batch1).await;
+        assert_eq!(
+            snapshot_ws.next().await.unwrap().unwrap(),
+            WsFrame::Text(Bytes::from("snapshot_complete"))
+        );
+
+        // Endpoints don't serve snapshots unless configured to.
+        assert!(server
+            .ws_at("/output_endpoint/test_changes_output_http?mode=snapshot")
+            .await
+            .is_err());
+
+        // Both subscribers receive subsequent changes.
+        TestWsSender::send_to_websocket(Pin::new(&mut input_ws), &batch2).await;
+        TestWsReceiver::wait_for_output_unordered(Pin::new(&mut changes_ws), &batch2).await;
+        TestWsReceiver::wait_for_output_unordered(Pin::new(&mut snapshot_ws), &batch2).await;
+
+        let resp = server.get("/shutdown").send().await.unwrap();
+        assert!(resp.status().is_success());
+    }
+
+    #[actix_web::test]
+    async fn test_runtime_endpoints() {
+        let data = vec![test_data()];
+
+        let server = test_server("name: test\ninputs: {}");
+
+        let input_config = json!({
+            "stream": "test_input1",
+            "transport": {"name": "http"},
+            "format": {"name": "csv"}
+        });
+        let output_config = json!({
+            "stream": "test_output1",
+            "transport": {"name": "http"},
+            "format": {"name": "csv"}
+        });
+
+        // Connect endpoints.
+        let resp = server
+            .post("/input_endpoints/test_runtime_input_http")
+            .send_json(&input_config)
+            .await
+            .unwrap();
+        assert!(resp.status().is_success());
+        let resp = server
+            .post("/output_endpoints/test_runtime_output_http")
+            .send_json(&output_config)
+            .await
+            .unwrap();
+        assert!(resp.status().is_success());
+
+        // Endpoint names must be unique.
+        let resp = server
+            .post("/output_endpoints/test_runtime_output_http")
+            .send_json(&output_config)
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let mut input_ws = server
+            .ws_at("/input_endpoint/test_runtime_input_http")
+            .await
+            .unwrap();
+        let mut output_ws = server
+            .ws_at("/output_endpoint/test_runtime_output_http")
+            .await
+            .unwrap();
+        TestWsSender::send_to_websocket(Pin::new(&mut input_ws), &data).await;
+        TestWsReceiver::wait_for_output_unordered(Pin::new(&mut output_ws), &data).await;
+
+        // Change backpressure threshold.
+        let mut input_config2 = input_config.clone();
+        input_config2["max_buffered_records"] = json!(100);
+        let resp = server
+            .put("/input_endpoints/test_runtime_input_http")
+            .send_json(&input_config2)
+            .await
+            .unwrap();
+        assert!(resp.status().is_success());
+        let status: JsonValue = server
+            .get("/status")
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(
+            status["inputs"][0]["config"]["max_buffered_records"],
+            json!(100)
+        );
+
+        // Disconnect the output endpoint: its websocket gets closed.
+        let resp = server
+            .delete("/output_endpoints/test_runtime_output_http")
+            .send()
+            .await
+            .unwrap();
+        assert!(resp.status().is_success());
+        assert!(matches!(
+            output_ws.next().await.unwrap().unwrap(),
+            WsFrame::Close(_)
+        ));
+        let resp = server
+            .delete("/output_endpoints/test_runtime_output_http")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        // The name can be reused.
+        let resp = server
+            .post("/output_endpoints/test_runtime_output_http")
+            .send_json(&output_config)
+            .await
+            .unwrap();
+        assert!(resp.status().is_success());
+        let mut output_ws = server
+            .ws_at("/output_endpoint/test_runtime_output_http")
+            .await
+            .unwrap();
+        TestWsSender::send_to_websocket(Pin::new(&mut input_ws), &data).await;
+        TestWsReceiver::wait_for_output_unordered(Pin::new(&mut output_ws), &data).await;
+
+        // A failed reconfiguration leaves the old endpoint running.
+        let mut output_config2 = output_config.clone();
+        output_config2["format"]["name"] = json!("no_such_format");
+        let resp = server
+            .put("/output_endpoints/test_runtime_output_http")
+            .send_json(&output_config2)
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        TestWsSender::send_to_websocket(Pin::new(&mut input_ws), &data).await;
+        TestWsReceiver::wait_for_output_unordered(Pin::new(&mut output_ws), &data).await;
+
+        // A successful reconfiguration replaces the endpoint.
+        let mut output_config3 = output_config.clone();
+        output_config3["format"]["config"] = json!({"buffer_size_records": 1});
+        let resp = server
+            .put("/output_endpoints/test_runtime_output_http")
+            .send_json(&output_config3)
+            .await
+            .unwrap();
+        assert!(resp.status().is_success());
+        assert!(matches!(
+            output_ws.next().await.unwrap().unwrap(),
+            WsFrame::Close(_)
+        ));
+        let mut output_ws = server
+            .ws_at("/output_endpoint/test_runtime_output_http")
+            .await
+            .unwrap();
+        TestWsSender::send_to_websocket(Pin::new(&mut input_ws), &data).await;
+        TestWsReceiver::wait_for_output_unordered(Pin::new(&mut output_ws), &data).await;
+
+        // Disconnect the input endpoint.
+        let resp = server
+            .delete("/input_endpoints/test_runtime_input_http")
+            .send()
+            .await
+            .unwrap();
+        assert!(resp.status().is_success());
+        let status: JsonValue = server
+            .get("/status")
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(status["inputs"], json!([]));
+
+        let resp = server.get("/shutdown").send().await.unwrap();
+        assert!(resp.status().is_success());
+    }
+
+    /// Parse CSV records in the `(TestStruct, weight)` format.
+    fn parse_csv_output(bytes: &[u8]) -> Vec<TestStruct> {
+        let mut records: Vec<TestStruct> = CsvReaderBuilder::new()
+            .has_headers(false)
+            .from_reader(bytes)
+            .deserialize::<(TestStruct, i32)>()
+            .map(|record| record.unwrap().0)
+            .collect();
+        records.sort();
+        records
+    }
+
+    #[actix_web::test]
+    async fn test_post_and_stream() {
+        let data = test_data();
+
+        let server = test_server(
+            r#"
+name: test
+inputs:
+    test_post_input_http:
+        stream: test_input1
+        transport:
+            name: http
+        format:
+            name: csv
+outputs:
+    test_post_output_http:
+        stream: test_output1
+        transport:
+            name: http
+            config:
+                snapshots: true
+        format:
+            name: csv
+"#,
+        );
+
+        // Stream changes using chunked transfer encoding.
+        let mut changes = server
+            .get("/output_endpoint/test_post_output_http")
+            .send()
+            .await
+            .unwrap();
+        assert!(changes.status().is_success());
+        rt::time::sleep(Duration::from_millis(100)).await;
+
+        let mut writer = CsvWriterBuilder::new()
+            .has_headers(false)
+            .from_writer(Vec::new());
+        for val in data.iter() {
+            writer.serialize(val).unwrap();
+        }
+        let body = writer.into_inner().unwrap();
+
+        let resp = server
+            .post("/input_endpoint/test_post_input_http?wait=processed")
+            .send_body(body)
+            .await
+            .unwrap();
+        assert!(resp.status().is_success());
+
+        let mut output = Vec::new();
+        while output.iter().filter(|byte| **byte == b'\n').count() < data.len() {
+            output.extend_from_slice(&changes.next().await.unwrap().unwrap());
+        }
+        assert_eq!(parse_csv_output(&output), data);
+
+        // Snapshot followed by a marker using server-sent events.
+        let mut events = server
+            .get("/output_endpoint/test_post_output_http?mode=snapshot")
+            .insert_header(("Accept", "text/event-stream"))
+            .send()
+            .await
+            .unwrap();
+        assert!(events.status().is_success());
+
+        let mut text = String::new();
+        while !text.contains("event: snapshot_complete") {
+            text.push_str(std::str::from_utf8(&events.next().await.unwrap().unwrap()).unwrap());
+        }
+        let snapshot: String = text
+            .split("event: snapshot_complete")
+            .next()
+            .unwrap()
+            .lines()
+            .filter_map(|line| line.strip_prefix("data: "))
+            .filter(|line| !line.is_empty())
+            .map(|line| format!("{line}\n"))
+            .collect();
+        assert_eq!(parse_csv_output(snapshot.as_bytes()), data);
+
+        // Chunked responses don't support snapshots.
+        let resp = server
+            .get("/output_endpoint/test_post_output_http?mode=snapshot")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let resp = server
+            .post("/input_endpoint/no_such_endpoint")
+            .send_body("1,true,,foo\n")
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        // Requests are rejected while the endpoint is paused.
+        let resp = server.get("/pause").send().await.unwrap();
+        assert!(resp.status().is_success());
+        let resp = loop {
+            let resp = server
+                .post("/input_endpoint/test_post_input_http")
+                .send()
+                .await
+                .unwrap();
+            if resp.status() != StatusCode::OK {
+                break resp;
+            }
+            rt::time::sleep(Duration::from_millis(10)).await;
+        };
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert!(resp.headers().contains_key("retry-after"));
+
+        let resp = server.get("/shutdown").send().await.unwrap();
+        assert!(resp.status().is_success());
+    }
+}
+
 #[cfg(test)]
 #[cfg(feature = "with-kafka")]
 #[cfg(feature = "server")]
@@ -394,7 +1301,7 @@ mod test_with_kafka {
         test::{
             generate_test_batches,
             kafka::{BufferConsumer, KafkaResources, TestProducer},
-            test_circuit, wait,
+            test_circuit,
             websocket::{TestWsReceiver, TestWsSender},
             TEST_LOGGER,
         },
@@ -405,6 +1312,7 @@ mod test_with_kafka {
     use bytes::Bytes;
     use bytestring::ByteString;
     use crossbeam::queue::SegQueue;
+    use dbsp::circuit::CircuitConfig;
     use futures::{SinkExt, StreamExt};
     use log::{error, LevelFilter};
     use proptest::{
@@ -477,7 +1385,7 @@ outputs:
 
         // Create circuit
         println!("Creating circuit");
-        let (circuit, catalog) = test_circuit(4);
+        let (circuit, catalog) = test_circuit(CircuitConfig::from(4));
 
         let errors = Arc::new(SegQueue::new());
         let errors_clone = errors.clone();
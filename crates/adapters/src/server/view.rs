@@ -0,0 +1,320 @@
+//! Ad hoc queries over snapshots of output streams.
+//!
+//! Implements the `/view/{stream}` endpoint, which returns the current
+//! contents of an output stream listed in
+//! [`snapshot_streams`](`crate::GlobalPipelineConfig::snapshot_streams`),
+//! encoded using the existing output formats.
+
+use crate::{OutputConsumer, OutputFormat, SerBatch, SerCursor};
+use anyhow::{Error as AnyError, Result as AnyResult};
+use erased_serde::{
+    Deserializer as ErasedDeserializer, Error as EError, Serialize as ErasedSerialize,
+};
+use mime::Mime;
+use serde::{de::Error as _, Deserialize};
+use serde_json::Deserializer as JsonDeserializer;
+use std::{
+    any::Any,
+    mem::take,
+    sync::{Arc, Mutex},
+};
+
+fn default_view_format() -> String {
+    "json".to_string()
+}
+
+/// Query parameters of the `/view/{stream}` endpoint.
+#[derive(Deserialize)]
+pub(super) struct ViewQuery {
+    /// Output format: `json` (default) or `csv`.
+    #[serde(default = "default_view_format")]
+    format: String,
+
+    /// Lower bound (inclusive) of the key range to return: a JSON-encoded
+    /// record of the stream.
+    start: Option<String>,
+
+    /// Upper bound (exclusive) of the key range to return: a JSON-encoded
+    /// record of the stream.
+    end: Option<String>,
+
+    /// Maximal number of distinct records to return.
+    limit: Option<usize>,
+}
+
+/// Cursor over the records of `batch` in the key range `[start, end)`, where
+/// `start` and `end` are JSON-encoded keys.
+fn range_cursor<'a>(
+    batch: &'a dyn SerBatch,
+    start: Option<&str>,
+    end: Option<&str>,
+) -> Result<Box<dyn SerCursor + 'a>, EError> {
+    let mut start = start.map(JsonDeserializer::from_str);
+    let mut end = end.map(JsonDeserializer::from_str);
+    let mut start = start.as_mut().map(<dyn ErasedDeserializer>::erase);
+    let mut end = end.as_mut().map(<dyn ErasedDeserializer>::erase);
+
+    batch.range_cursor(
+        start
+            .as_mut()
+            .map(|start| start as &mut dyn ErasedDeserializer),
+        end.as_mut().map(|end| end as &mut dyn ErasedDeserializer),
+    )
+}
+
+/// A view of a batch restricted to a range of keys, limited to at most
+/// `limit` keys.
+///
+/// The bounds of the range are JSON-encoded keys, which must deserialize
+/// into the record type of the stream.  Records are compared with the bounds
+/// using the ordering of the record type, so the view can be positioned
+/// using [`Cursor::seek_key`](`dbsp::trace::Cursor::seek_key`) instead of
+/// scanning the batch.
+struct FilteredBatch {
+    batch: Arc<dyn SerBatch>,
+    start: Option<String>,
+    end: Option<String>,
+    limit: Option<usize>,
+}
+
+impl FilteredBatch {
+    fn new(
+        batch: Arc<dyn SerBatch>,
+        start: Option<String>,
+        end: Option<String>,
+        limit: Option<usize>,
+    ) -> AnyResult<Self> {
+        // Validate the bounds, so that `cursor` doesn't fail.
+        range_cursor(&*batch, start.as_deref(), None)
+            .map_err(|e| AnyError::msg(format!("invalid 'start' bound: {e}")))?;
+        range_cursor(&*batch, None, end.as_deref())
+            .map_err(|e| AnyError::msg(format!("invalid 'end' bound: {e}")))?;
+
+        Ok(Self {
+            batch,
+            start,
+            end,
+            limit,
+        })
+    }
+}
+
+impl SerBatch for FilteredBatch {
+    fn key_count(&self) -> usize {
+        let mut cursor = self.cursor();
+        let mut count = 0;
+        while cursor.key_valid() {
+            count += 1;
+            cursor.step_key();
+        }
+        count
+    }
+
+    fn len(&self) -> usize {
+        let mut cursor = self.cursor();
+        let mut count = 0;
+        while cursor.key_valid() {
+            while cursor.val_valid() {
+                count += 1;
+                cursor.step_val();
+            }
+            cursor.step_key();
+        }
+        count
+    }
+
+    fn cursor<'a>(&'a self) -> Box<dyn SerCursor + 'a> {
+        let cursor = range_cursor(&*self.batch, self.start.as_deref(), self.end.as_deref())
+            .expect("key range validated by FilteredBatch::new");
+
+        Box::new(FilteredCursor::new(cursor, self.limit))
+    }
+
+    fn range_cursor<'a>(
+        &'a self,
+        _start: Option<&mut dyn ErasedDeserializer>,
+        _end: Option<&mut dyn ErasedDeserializer>,
+    ) -> Result<Box<dyn SerCursor + 'a>, EError> {
+        Err(EError::custom(
+            "key range queries over a filtered batch are not supported",
+        ))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Cursor that returns at most `limit` keys.
+struct FilteredCursor<'a> {
+    cursor: Box<dyn SerCursor + 'a>,
+    limit: Option<usize>,
+
+    /// Number of keys that can still be returned before reaching `limit`.
+    remaining: Option<usize>,
+}
+
+impl<'a> FilteredCursor<'a> {
+    fn new(cursor: Box<dyn SerCursor + 'a>, limit: Option<usize>) -> Self {
+        Self {
+            cursor,
+            limit,
+            remaining: limit,
+        }
+    }
+}
+
+impl<'a> SerCursor for FilteredCursor<'a> {
+    fn key_valid(&self) -> bool {
+        self.remaining != Some(0) && self.cursor.key_valid()
+    }
+
+    fn val_valid(&self) -> bool {
+        self.cursor.val_valid()
+    }
+
+    fn key(&self) -> &dyn ErasedSerialize {
+        self.cursor.key()
+    }
+
+    fn val(&self) -> &dyn ErasedSerialize {
+        self.cursor.val()
+    }
+
+    fn weight(&mut self) -> i64 {
+        self.cursor.weight()
+    }
+
+    fn step_key(&mut self) {
+        self.cursor.step_key();
+        if let Some(remaining) = &mut self.remaining {
+            *remaining = remaining.saturating_sub(1);
+        }
+    }
+
+    fn step_val(&mut self) {
+        self.cursor.step_val();
+    }
+
+    fn rewind_keys(&mut self) {
+        self.cursor.rewind_keys();
+        self.remaining = self.limit;
+    }
+
+    fn rewind_vals(&mut self) {
+        self.cursor.rewind_vals();
+    }
+}
+
+/// [`OutputConsumer`] that accumulates encoded buffers in memory.
+#[derive(Clone, Default)]
+struct SnapshotConsumer(Arc<Mutex<Vec<u8>>>);
+
+impl OutputConsumer for SnapshotConsumer {
+    fn push_buffer(&mut self, buffer: &[u8]) {
+        self.0.lock().unwrap().extend_from_slice(buffer);
+    }
+}
+
+/// Encode the records of `snapshot` selected by `query`.
+///
+/// Returns the content type and the body of the response.  Records are
+/// encoded in the `raw` update format, i.e., a record with weight `n` is
+/// written `n` times.  JSON records are returned as an array.
+pub(super) fn render_snapshot(
+    snapshot: Arc<dyn SerBatch>,
+    query: &ViewQuery,
+) -> AnyResult<(Mime, Vec<u8>)> {
+    let (content_type, config) = match query.format.as_str() {
+        "json" => (
+            mime::APPLICATION_JSON,
+            format!(
+                "{{update_format: raw, array: true, buffer_size_records: {}}}",
+                usize::MAX
+            ),
+        ),
+        "csv" => (
+            mime::TEXT_CSV,
+            format!(
+                "{{update_format: raw, buffer_size_records: {}}}",
+                usize::MAX
+            ),
+        ),
+        format => {
+            return Err(AnyError::msg(format!(
+                "unsupported view format '{format}'; supported formats are 'json' and 'csv'"
+            )))
+        }
+    };
+
+    let batch = Arc::new(FilteredBatch::new(
+        snapshot,
+        query.start.clone(),
+        query.end.clone(),
+        query.limit,
+    )?) as Arc<dyn SerBatch>;
+
+    let consumer = SnapshotConsumer::default();
+    let mut encoder = <dyn OutputFormat>::get_format(&query.format)
+        .unwrap()
+        .new_encoder(&serde_yaml::from_str(&config)?, Box::new(consumer.clone()))?;
+    encoder.encode(&[batch])?;
+    drop(encoder);
+
+    let mut body = take(&mut *consumer.0.lock().unwrap());
+    if body.is_empty() && query.format == "json" {
+        body.extend_from_slice(b"[]\n");
+    }
+
+    Ok((content_type, body))
+}
+
+#[cfg(test)]
+mod test {
+    use super::FilteredBatch;
+    use crate::{seroutput::SerBatchImpl, test::TestStruct, SerBatch};
+    use dbsp::{trace::Batch, OrdZSet};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_filtered_batch() {
+        let data: Vec<TestStruct> = (0..5)
+            .map(|id| TestStruct {
+                id,
+                b: false,
+                i: None,
+                s: format!("foo{id}"),
+            })
+            .collect();
+        let batch = Arc::new(SerBatchImpl::new(OrdZSet::from_keys(
+            (),
+            data.iter().map(|record| (record.clone(), 1)).collect(),
+        ))) as Arc<dyn SerBatch>;
+        let bound = |record: &TestStruct| Some(serde_json::to_string(record).unwrap());
+
+        let keys = |batch: &FilteredBatch| {
+            let mut cursor = batch.cursor();
+            let mut keys = Vec::new();
+            while cursor.key_valid() {
+                keys.push(
+                    serde_json::from_value::<TestStruct>(
+                        serde_json::to_value(cursor.key()).unwrap(),
+                    )
+                    .unwrap(),
+                );
+                cursor.step_key();
+            }
+            keys
+        };
+
+        let filtered =
+            FilteredBatch::new(batch.clone(), bound(&data[1]), bound(&data[4]), None).unwrap();
+        assert_eq!(keys(&filtered), data[1..4]);
+        assert_eq!(filtered.key_count(), 3);
+
+        let filtered = FilteredBatch::new(batch.clone(), bound(&data[1]), None, Some(2)).unwrap();
+        assert_eq!(keys(&filtered), data[1..3]);
+
+        assert!(FilteredBatch::new(batch, Some(r#"{"id": 1}"#.to_string()), None, None).is_err());
+    }
+}
@@ -175,6 +175,8 @@ impl BufferConsumer {
                     .set("enable.auto.commit", "true")
                     .set("enable.auto.offset.store", "true")
                     .set("auto.offset.reset", "earliest")
+                    // Only observe committed outputs of transactional producers.
+                    .set("isolation.level", "read_committed")
                     .set("group.id", &group_id)
                     .create::<BaseConsumer>()
                     .unwrap();
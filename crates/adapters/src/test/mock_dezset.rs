@@ -67,7 +67,7 @@ impl<T> MockDeZSet<T> {
 
 impl<T> DeCollectionHandle for MockDeZSet<T>
 where
-    T: for<'de> Deserialize<'de> + Send + 'static,
+    T: for<'de> Deserialize<'de> + Clone + Send + 'static,
 {
     fn insert(&mut self, deserializer: &mut dyn ErasedDeserializer) -> Result<(), EError> {
         let val = deserialize::<T>(deserializer)?;
@@ -81,6 +81,36 @@ where
         Ok(())
     }
 
+    /// Buffers `|weight|` copies of the record.
+    fn update(
+        &mut self,
+        deserializer: &mut dyn ErasedDeserializer,
+        weight: i64,
+    ) -> Result<(), EError> {
+        if weight == 0 {
+            return Ok(());
+        }
+        let val = deserialize::<T>(deserializer)?;
+        let mut state = self.0.lock().unwrap();
+        for _ in 0..weight.unsigned_abs() {
+            state.buffered.push((val.clone(), weight > 0));
+        }
+        Ok(())
+    }
+
+    fn replace(
+        &mut self,
+        delete: &mut dyn ErasedDeserializer,
+        insert: &mut dyn ErasedDeserializer,
+    ) -> Result<(), EError> {
+        let old = deserialize::<T>(delete)?;
+        let new = deserialize::<T>(insert)?;
+        let mut state = self.0.lock().unwrap();
+        state.buffered.push((old, false));
+        state.buffered.push((new, true));
+        Ok(())
+    }
+
     fn reserve(&mut self, _reservation: usize) {}
 
     fn flush(&mut self) {
@@ -1,5 +1,7 @@
-use crate::{controller::FormatConfig, DeCollectionHandle, InputConsumer, InputFormat, Parser};
-use anyhow::{Error as AnyError, Result as AnyResult};
+use crate::{
+    controller::FormatConfig, DeCollectionHandle, InputConsumer, InputFormat, ParseError, Parser,
+};
+use anyhow::Error as AnyError;
 use std::sync::{Arc, Mutex, MutexGuard};
 
 pub type ErrorCallback = Box<dyn FnMut(&AnyError) + Send>;
@@ -12,11 +14,16 @@ pub struct MockInputConsumerState {
     /// `eoi` has been received since the last `reset`.
     pub eoi: bool,
 
+    /// Number of `end_of_fragment` notifications received since the last
+    /// `reset`.
+    pub num_fragments: usize,
+
     /// The last error received from the endpoint since the last `reset`.
     pub endpoint_error: Option<AnyError>,
 
-    /// The last result returned by the parser.
-    pub parser_result: Option<AnyResult<usize>>,
+    /// The last result returned by the parser: the number of parsed records
+    /// and parse errors.
+    pub parser_result: Option<(usize, Vec<ParseError>)>,
 
     /// Parser to push data to.
     parser: Box<dyn Parser>,
@@ -32,6 +39,7 @@ impl MockInputConsumerState {
         Self {
             data: Vec::new(),
             eoi: false,
+            num_fragments: 0,
             endpoint_error: None,
             parser_result: None,
             parser,
@@ -54,6 +62,7 @@ impl MockInputConsumerState {
     pub fn reset(&mut self) {
         self.data.clear();
         self.eoi = false;
+        self.num_fragments = 0;
         self.endpoint_error = None;
         self.parser_result = None;
     }
@@ -95,9 +104,9 @@ impl InputConsumer for MockInputConsumer {
         state.data.extend_from_slice(data);
         let parser_result = state.parser.input(data);
         // println!("parser returned '{:?}'", state.parser_result);
-        if let Err(e) = &parser_result {
+        for e in parser_result.1.iter() {
             if let Some(error_cb) = &mut state.error_cb {
-                error_cb(e);
+                error_cb(&AnyError::new(e.clone()));
             } else {
                 panic!("mock_input_consumer: parse error '{e}'");
             }
@@ -121,6 +130,23 @@ impl InputConsumer for MockInputConsumer {
         self.state().eoi = true;
     }
 
+    fn end_of_fragment(&mut self) {
+        let mut state = self.state();
+
+        let parser_result = state.parser.eoi();
+        for e in parser_result.1.iter() {
+            if let Some(error_cb) = &mut state.error_cb {
+                error_cb(&AnyError::new(e.clone()));
+            } else {
+                panic!("mock_input_consumer: parse error '{e}'");
+            }
+        }
+        state.parser_result = Some(parser_result);
+        state.parser.flush();
+        state.parser = state.parser.fork();
+        state.num_fragments += 1;
+    }
+
     fn fork(&self) -> Box<dyn InputConsumer> {
         Box::new(self.clone())
     }
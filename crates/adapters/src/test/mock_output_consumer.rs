@@ -0,0 +1,31 @@
+use crate::OutputConsumer;
+use std::sync::{Arc, Mutex};
+
+/// [`OutputConsumer`] that records all buffers pushed to it.
+///
+/// Clones of the consumer share the same buffers, so a test can pass a clone
+/// to the encoder and inspect its output via the original.
+#[derive(Clone, Default)]
+pub struct MockOutputConsumer(Arc<Mutex<Vec<Vec<u8>>>>);
+
+impl MockOutputConsumer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Buffers received so far.
+    pub fn buffers(&self) -> Vec<Vec<u8>> {
+        self.0.lock().unwrap().clone()
+    }
+
+    /// Concatenation of all buffers received so far.
+    pub fn data(&self) -> Vec<u8> {
+        self.0.lock().unwrap().concat()
+    }
+}
+
+impl OutputConsumer for MockOutputConsumer {
+    fn push_buffer(&mut self, buffer: &[u8]) {
+        self.0.lock().unwrap().push(buffer.to_vec());
+    }
+}
@@ -1,7 +1,7 @@
 //! Test framework for the `adapters` crate.
 
 use crate::{controller::InputEndpointConfig, Catalog, InputEndpoint, InputTransport};
-use dbsp::{DBSPHandle, Runtime};
+use dbsp::{circuit::CircuitConfig, DBSPHandle, Runtime};
 use log::{Log, Metadata, Record};
 use serde::Deserialize;
 use std::{
@@ -19,10 +19,12 @@ pub mod websocket;
 
 mod mock_dezset;
 mod mock_input_consumer;
+mod mock_output_consumer;
 
 pub use data::{generate_test_batch, generate_test_batches, TestStruct};
 pub use mock_dezset::MockDeZSet;
 pub use mock_input_consumer::MockInputConsumer;
+pub use mock_output_consumer::MockOutputConsumer;
 
 pub struct TestLogger;
 pub static TEST_LOGGER: TestLogger = TestLogger;
@@ -76,7 +78,7 @@ pub fn mock_input_pipeline<T>(
     config: InputEndpointConfig,
 ) -> (Box<dyn InputEndpoint>, MockInputConsumer, MockDeZSet<T>)
 where
-    T: for<'de> Deserialize<'de> + Send + 'static,
+    T: for<'de> Deserialize<'de> + Clone + Send + 'static,
 {
     let input_handle = <MockDeZSet<T>>::new();
 
@@ -97,8 +99,8 @@ where
 /// Create a simple test circuit that passes the input stream right through to
 /// the output.
 // TODO: parameterize with the number (and types?) of input and output streams.
-pub fn test_circuit(workers: usize) -> (DBSPHandle, Catalog) {
-    let (circuit, (input, output)) = Runtime::init_circuit(workers, |circuit| {
+pub fn test_circuit(config: CircuitConfig) -> (DBSPHandle, Catalog) {
+    let (circuit, (input, output)) = Runtime::init_circuit_with_config(config, |circuit| {
         let (input, hinput) = circuit.add_input_zset::<TestStruct, i32>();
 
         let houtput = input.output();
@@ -2,13 +2,16 @@ use super::{InputConsumer, InputEndpoint, InputTransport, OutputEndpoint, Output
 use crate::PipelineState;
 use anyhow::{Error as AnyError, Result as AnyResult};
 use crossbeam::sync::{Parker, Unparker};
+use glob::{glob, Pattern};
 use num_traits::FromPrimitive;
 use serde::Deserialize;
 use serde_yaml::Value as YamlValue;
 use std::{
     borrow::Cow,
-    fs::File,
+    collections::{BTreeSet, VecDeque},
+    fs::{read_dir, remove_file, rename, File},
     io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
     sync::{
         atomic::{AtomicU32, Ordering},
         Arc,
@@ -50,7 +53,14 @@ impl InputTransport for FileInputTransport {
 /// Configuration for reading data from a file with [`FileInputTransport`].
 #[derive(Deserialize, ToSchema)]
 pub struct FileInputConfig {
-    /// File path.
+    /// File path, directory, or glob pattern.
+    ///
+    /// When `path` is a directory or a glob pattern, i.e., contains one of
+    /// the `*`, `?`, or `[` characters, e.g., `/data/events-*.csv`, the
+    /// endpoint reads all matching files one by one, in lexicographic order
+    /// of their paths.  Files whose names start with `.` are ignored in
+    /// directory mode.  Each file is parsed independently, e.g., a CSV
+    /// header or a Parquet footer is expected in every file.
     pub path: String,
 
     /// Read buffer size.
@@ -65,12 +75,99 @@ pub struct FileInputConfig {
     /// message and stops upon reaching the end of file.  When `true`, the
     /// endpoint will keep watching the file and outputting any new content
     /// appended to it.
+    ///
+    /// In directory or glob mode, the endpoint stops after reading all files
+    /// that exist when it reaches the end of the last file.  In follow mode,
+    /// it keeps watching for new files.  The last file is tailed until a new
+    /// file appears, which allows following rotated logs.
     #[serde(default)]
     pub follow: bool,
+
+    /// What to do with a file once it has been read to completion.
+    ///
+    /// In follow mode, a file is complete once a new file appears after it.
+    #[serde(default)]
+    pub after_read: FileAfterRead,
+
+    /// Directory to move files to when `after_read` is `move`.
+    #[serde(default)]
+    pub move_to: Option<String>,
+}
+
+/// Action applied to an input file after reading it to completion.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FileAfterRead {
+    /// Leave the file in place.
+    Keep,
+
+    /// Delete the file.
+    Delete,
+
+    /// Move the file to the `move_to` directory.
+    Move,
+}
+
+impl Default for FileAfterRead {
+    fn default() -> Self {
+        Self::Keep
+    }
+}
+
+/// Set of files read by a [`FileInputEndpoint`].
+enum FileSource {
+    File(PathBuf),
+    Directory(PathBuf),
+    Glob(String),
+}
+
+impl FileSource {
+    fn new(path: &str) -> AnyResult<Self> {
+        if Path::new(path).is_dir() {
+            Ok(Self::Directory(PathBuf::from(path)))
+        } else if path.contains(|c| matches!(c, '*' | '?' | '[')) {
+            Pattern::new(path)
+                .map_err(|e| AnyError::msg(format!("invalid glob pattern '{path}': {e}")))?;
+            Ok(Self::Glob(path.to_string()))
+        } else {
+            Ok(Self::File(PathBuf::from(path)))
+        }
+    }
+
+    /// List matching files in lexicographic order.
+    fn list(&self) -> AnyResult<Vec<PathBuf>> {
+        let mut files = match self {
+            Self::File(path) => vec![path.clone()],
+            Self::Directory(dir) => {
+                let mut files = Vec::new();
+                for entry in read_dir(dir).map_err(|e| {
+                    AnyError::msg(format!(
+                        "Failed to read input directory '{}': {e}",
+                        dir.display()
+                    ))
+                })? {
+                    let entry = entry?;
+                    if entry.file_type()?.is_file()
+                        && !entry.file_name().to_string_lossy().starts_with('.')
+                    {
+                        files.push(entry.path());
+                    }
+                }
+                files
+            }
+            Self::Glob(pattern) => glob(pattern)?
+                .filter_map(|path| path.ok())
+                .filter(|path| path.is_file())
+                .collect(),
+        };
+        files.sort();
+
+        Ok(files)
+    }
 }
 
 struct FileInputEndpoint {
-    config: FileInputConfig,
+    config: Arc<FileInputConfig>,
     status: Arc<AtomicU32>,
     unparker: Option<Unparker>,
 }
@@ -78,30 +175,72 @@ struct FileInputEndpoint {
 impl FileInputEndpoint {
     fn new(config: FileInputConfig) -> Self {
         Self {
-            config,
+            config: Arc::new(config),
             status: Arc::new(AtomicU32::new(PipelineState::Paused as u32)),
             unparker: None,
         }
     }
 
     fn connect(&mut self, consumer: Box<dyn InputConsumer>) -> AnyResult<()> {
-        let file = File::open(&self.config.path).map_err(|e| {
+        if self.config.after_read == FileAfterRead::Move && self.config.move_to.is_none() {
+            return Err(AnyError::msg(
+                "'move_to' directory must be specified when 'after_read' is 'move'",
+            ));
+        }
+
+        let source = FileSource::new(&self.config.path)?;
+
+        // Fail early if a single input file cannot be opened.
+        let reader = match &source {
+            FileSource::File(path) => Some((path.clone(), Self::open(&self.config, path)?)),
+            _ => None,
+        };
+
+        let parker = Parker::new();
+        self.unparker = Some(parker.unparker().clone());
+        let status = self.status.clone();
+        let config = self.config.clone();
+        let _worker =
+            spawn(move || Self::worker_thread(config, source, reader, consumer, parker, status));
+        Ok(())
+    }
+
+    fn open(config: &FileInputConfig, path: &Path) -> AnyResult<BufReader<File>> {
+        let file = File::open(path).map_err(|e| {
             AnyError::msg(format!(
                 "Failed to open input file '{}': {e}",
-                self.config.path
+                path.display()
             ))
         })?;
-        let reader = match self.config.buffer_size_bytes {
+
+        Ok(match config.buffer_size_bytes {
             Some(buffer_size) if buffer_size > 0 => BufReader::with_capacity(buffer_size, file),
             _ => BufReader::new(file),
-        };
+        })
+    }
 
-        let parker = Parker::new();
-        self.unparker = Some(parker.unparker().clone());
-        let status = self.status.clone();
-        let follow = self.config.follow;
-        let _worker = spawn(move || Self::worker_thread(reader, consumer, parker, status, follow));
-        Ok(())
+    /// Apply `config.after_read` to a file that has been read to completion.
+    fn after_read(config: &FileInputConfig, path: &Path) -> AnyResult<()> {
+        match config.after_read {
+            FileAfterRead::Keep => Ok(()),
+            FileAfterRead::Delete => remove_file(path).map_err(|e| {
+                AnyError::msg(format!(
+                    "Failed to delete input file '{}': {e}",
+                    path.display()
+                ))
+            }),
+            FileAfterRead::Move => {
+                let move_to = Path::new(config.move_to.as_ref().unwrap());
+                let destination = move_to.join(path.file_name().unwrap_or_default());
+                rename(path, &destination).map_err(|e| {
+                    AnyError::msg(format!(
+                        "Failed to move input file '{}' to '{}': {e}",
+                        path.display(),
+                        destination.display()
+                    ))
+                })
+            }
+        }
     }
 
     fn unpark(&self) {
@@ -111,36 +250,104 @@ impl FileInputEndpoint {
     }
 
     fn worker_thread(
-        mut reader: BufReader<File>,
+        config: Arc<FileInputConfig>,
+        source: FileSource,
+        mut reader: Option<(PathBuf, BufReader<File>)>,
         mut consumer: Box<dyn InputConsumer>,
         parker: Parker,
         status: Arc<AtomicU32>,
-        follow: bool,
     ) {
+        // Files that are being read or queued for reading, or that have been
+        // read and still exist.
+        let mut seen: BTreeSet<PathBuf> = reader.iter().map(|(path, _)| path.clone()).collect();
+
+        // Files waiting to be read.
+        let mut queue = VecDeque::new();
+
+        // Add new files to `queue`.
+        let poll = |seen: &mut BTreeSet<PathBuf>,
+                    queue: &mut VecDeque<PathBuf>,
+                    consumer: &mut Box<dyn InputConsumer>| match source.list() {
+            Ok(files) => {
+                // Forget files that no longer exist, so that `seen` does not
+                // grow without bound and a file re-created under the same
+                // name is read again.
+                let files: BTreeSet<PathBuf> = files.into_iter().collect();
+                seen.retain(|path| files.contains(path) || queue.contains(path));
+                for path in files.into_iter() {
+                    if seen.insert(path.clone()) {
+                        queue.push_back(path);
+                    }
+                }
+                true
+            }
+            Err(e) => {
+                consumer.error(true, e);
+                false
+            }
+        };
+
         loop {
             match PipelineState::from_u32(status.load(Ordering::Acquire)) {
                 Some(PipelineState::Paused) => parker.park(),
                 Some(PipelineState::Running) => {
-                    let data = reader.fill_buf();
-                    match data {
-                        Err(e) => {
-                            consumer.error(true, AnyError::from(e));
+                    if reader.is_none() {
+                        if queue.is_empty() && !poll(&mut seen, &mut queue, &mut consumer) {
                             return;
                         }
-                        Ok(data) if data.is_empty() => {
-                            if !follow {
+                        match queue.pop_front() {
+                            Some(path) => match Self::open(&config, &path) {
+                                Ok(file_reader) => reader = Some((path, file_reader)),
+                                // The file may have been removed since we listed it.
+                                Err(e) => consumer.error(false, e),
+                            },
+                            None if config.follow => sleep(Duration::from_millis(SLEEP_MS)),
+                            None => {
                                 consumer.eoi();
                                 return;
-                            } else {
-                                sleep(Duration::from_millis(SLEEP_MS));
                             }
                         }
+                        continue;
+                    }
+
+                    let (path, file_reader) = reader.as_mut().unwrap();
+                    let eof = match file_reader.fill_buf() {
+                        Err(e) => {
+                            consumer.error(true, AnyError::from(e));
+                            return;
+                        }
+                        Ok(data) if data.is_empty() => true,
                         Ok(data) => {
                             // println!("read {} bytes from file", data.len());
                             consumer.input(data);
                             let len = data.len();
-                            reader.consume(len);
+                            file_reader.consume(len);
+                            false
+                        }
+                    };
+
+                    if eof {
+                        // In follow mode, keep tailing the file until a new
+                        // file appears.
+                        if config.follow {
+                            if queue.is_empty() && !poll(&mut seen, &mut queue, &mut consumer) {
+                                return;
+                            }
+                            if queue.is_empty() {
+                                sleep(Duration::from_millis(SLEEP_MS));
+                                continue;
+                            }
                         }
+
+                        consumer.end_of_fragment();
+                        match Self::after_read(&config, path) {
+                            Ok(()) if config.after_read != FileAfterRead::Keep => {
+                                seen.remove(path.as_path());
+                            }
+                            Ok(()) => {}
+                            Err(e) => consumer.error(false, e),
+                        }
+                        reader = None;
                     }
                 }
                 Some(PipelineState::Terminated) => return,
@@ -215,38 +422,83 @@ impl OutputTransport for FileOutputTransport {
 pub struct FileOutputConfig {
     /// File path.
     pub path: String,
+
+    /// Write each buffer received from the encoder to a new file.
+    ///
+    /// When `true`, `path` must contain a `{}` placeholder, which is replaced
+    /// with the sequence number of the file, e.g., `/data/part-{}.parquet`.
+    /// This is required by formats like Parquet, whose encoder produces a
+    /// complete file per buffer.
+    #[serde(default)]
+    pub rolling: bool,
 }
 
 struct FileOutputEndpoint {
-    file: File,
+    config: FileOutputConfig,
+
+    /// Output file; `None` in rolling mode.
+    file: Option<File>,
+
+    /// Sequence number of the next file in rolling mode.
+    seq: usize,
 }
 
 impl FileOutputEndpoint {
     fn new(config: FileOutputConfig) -> AnyResult<Self> {
-        let file = File::create(&config.path).map_err(|e| {
-            AnyError::msg(format!(
-                "Failed to create output file '{}': {e}",
-                config.path
-            ))
-        })?;
-        Ok(Self { file })
+        let file = if config.rolling {
+            if !config.path.contains("{}") {
+                return Err(AnyError::msg(format!(
+                    "Output file path '{}' must contain a '{{}}' placeholder in rolling mode",
+                    config.path
+                )));
+            }
+            None
+        } else {
+            Some(Self::create_file(&config.path)?)
+        };
+
+        Ok(Self {
+            config,
+            file,
+            seq: 0,
+        })
+    }
+
+    fn create_file(path: &str) -> AnyResult<File> {
+        File::create(path)
+            .map_err(|e| AnyError::msg(format!("Failed to create output file '{path}': {e}")))
     }
 }
 
 impl OutputEndpoint for FileOutputEndpoint {
     fn push_buffer(&mut self, buffer: &[u8]) -> AnyResult<()> {
-        self.file.write_all(buffer)?;
+        match &mut self.file {
+            Some(file) => file.write_all(buffer)?,
+            None => {
+                let path = self.config.path.replace("{}", &format!("{:06}", self.seq));
+                self.seq += 1;
+                Self::create_file(&path)?.write_all(buffer)?;
+            }
+        }
         Ok(())
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::test::{mock_input_pipeline, wait};
+    use crate::{
+        test::{mock_input_pipeline, wait},
+        OutputTransport,
+    };
     use csv::WriterBuilder as CsvWriterBuilder;
     use serde::{Deserialize, Serialize};
-    use std::{io::Write, thread::sleep, time::Duration};
-    use tempfile::NamedTempFile;
+    use std::{
+        fs::{create_dir, read_to_string, write},
+        io::Write,
+        thread::sleep,
+        time::Duration,
+    };
+    use tempfile::{tempdir, NamedTempFile};
 
     #[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
     struct TestStruct {
@@ -383,7 +635,7 @@ format:
             || {
                 let state = consumer.state();
                 // println!("result: {:?}", state.parser_result);
-                state.parser_result.is_some() && state.parser_result.as_ref().unwrap().is_err()
+                state.parser_result.is_some() && !state.parser_result.as_ref().unwrap().1.is_empty()
             },
             None,
         );
@@ -393,4 +645,148 @@ format:
 
         endpoint.disconnect();
     }
+
+    #[test]
+    fn test_directory_input() {
+        let temp_dir = tempdir().unwrap();
+        write(temp_dir.path().join("b.csv"), "s,b,i\nbar,false,-10").unwrap();
+        write(temp_dir.path().join("a.csv"), "s,b,i\nfoo,true,10\n").unwrap();
+        // Hidden files are ignored.
+        write(temp_dir.path().join(".c.csv"), "s,b,i\nbaz,true,0\n").unwrap();
+
+        let config_str = format!(
+            r#"
+stream: test_input
+transport:
+    name: file
+    config:
+        path: {:?}
+        after_read: delete
+format:
+    name: csv
+    config:
+        headers: true
+"#,
+            temp_dir.path().to_str().unwrap()
+        );
+
+        let (endpoint, consumer, zset) =
+            mock_input_pipeline::<TestStruct>(serde_yaml::from_str(&config_str).unwrap());
+        endpoint.start().unwrap();
+
+        // Files are read in order; each file has its own header.
+        wait(|| consumer.state().eoi, None);
+        assert_eq!(consumer.state().num_fragments, 2);
+        assert_eq!(
+            zset.state().flushed,
+            vec![
+                (TestStruct::new("foo".to_string(), true, 10), true),
+                (TestStruct::new("bar".to_string(), false, -10), true)
+            ]
+        );
+
+        assert!(!temp_dir.path().join("a.csv").exists());
+        assert!(!temp_dir.path().join("b.csv").exists());
+        assert!(temp_dir.path().join(".c.csv").exists());
+    }
+
+    #[test]
+    fn test_glob_input_follow() {
+        let temp_dir = tempdir().unwrap();
+        let input_dir = temp_dir.path().join("input");
+        let done_dir = temp_dir.path().join("done");
+        create_dir(&input_dir).unwrap();
+        create_dir(&done_dir).unwrap();
+        write(input_dir.join("1.csv"), "foo,true,10\n").unwrap();
+        write(input_dir.join("1.txt"), "not csv\n").unwrap();
+
+        let config_str = format!(
+            r#"
+stream: test_input
+transport:
+    name: file
+    config:
+        path: {:?}
+        follow: true
+        after_read: move
+        move_to: {:?}
+format:
+    name: csv
+"#,
+            input_dir.join("*.csv").to_str().unwrap(),
+            done_dir.to_str().unwrap(),
+        );
+
+        let (endpoint, consumer, zset) =
+            mock_input_pipeline::<TestStruct>(serde_yaml::from_str(&config_str).unwrap());
+        endpoint.start().unwrap();
+
+        wait(|| zset.state().flushed.len() == 1, None);
+
+        // The last file is tailed until a new file appears.
+        sleep(Duration::from_millis(500));
+        assert_eq!(consumer.state().num_fragments, 0);
+        assert!(input_dir.join("1.csv").exists());
+
+        write(input_dir.join("2.csv"), "bar,false,-10\n").unwrap();
+        wait(|| zset.state().flushed.len() == 2, None);
+        wait(|| done_dir.join("1.csv").exists(), None);
+
+        assert_eq!(
+            zset.state().flushed,
+            vec![
+                (TestStruct::new("foo".to_string(), true, 10), true),
+                (TestStruct::new("bar".to_string(), false, -10), true)
+            ]
+        );
+        assert!(!input_dir.join("1.csv").exists());
+        assert!(input_dir.join("2.csv").exists());
+        assert!(input_dir.join("1.txt").exists());
+        assert!(!consumer.state().eoi);
+
+        // A file re-created under the name of a file that has been moved is
+        // read again.
+        write(input_dir.join("1.csv"), "baz,true,0\n").unwrap();
+        wait(|| zset.state().flushed.len() == 3, None);
+        assert_eq!(
+            zset.state().flushed[2],
+            (TestStruct::new("baz".to_string(), true, 0), true)
+        );
+
+        endpoint.disconnect();
+    }
+
+    #[test]
+    fn test_rolling_file_output() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("part-{}.csv");
+        let config = serde_yaml::from_str(&format!(
+            "path: {:?}\nrolling: true",
+            path.to_str().unwrap()
+        ))
+        .unwrap();
+
+        let mut endpoint = <dyn OutputTransport>::get_transport("file")
+            .unwrap()
+            .new_endpoint("test_output", &config, Box::new(|_, _| {}))
+            .unwrap();
+        endpoint.push_buffer(b"foo\n").unwrap();
+        endpoint.push_buffer(b"bar\n").unwrap();
+
+        assert_eq!(
+            read_to_string(temp_dir.path().join("part-000000.csv")).unwrap(),
+            "foo\n"
+        );
+        assert_eq!(
+            read_to_string(temp_dir.path().join("part-000001.csv")).unwrap(),
+            "bar\n"
+        );
+
+        // Rolling mode requires a placeholder in the path.
+        let config = serde_yaml::from_str("path: /tmp/output.csv\nrolling: true").unwrap();
+        assert!(<dyn OutputTransport>::get_transport("file")
+            .unwrap()
+            .new_endpoint("test_output", &config, Box::new(|_, _| {}))
+            .is_err());
+    }
 }
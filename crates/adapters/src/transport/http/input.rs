@@ -2,7 +2,11 @@ use super::MAX_SOCKETS_PER_ENDPOINT;
 use crate::{InputConsumer, InputEndpoint, InputTransport, PipelineState};
 use actix::{Actor, ActorContext, Addr, AsyncContext, Handler, Message, StreamHandler};
 use actix_http::ws::Item as WsItem;
-use actix_web::{web::Payload, HttpRequest, HttpResponse};
+use actix_web::{
+    http::StatusCode,
+    web::{self, Payload},
+    HttpRequest, HttpResponse,
+};
 use actix_web_actors::ws::{
     self, CloseCode as WsCloseCode, CloseReason as WsCloseReason, Message as WsMessage,
     ProtocolError as WsProtocolError, WebsocketContext,
@@ -10,6 +14,7 @@ use actix_web_actors::ws::{
 use anyhow::{anyhow, Result as AnyResult};
 use byteorder::{BigEndian, WriteBytesExt};
 use bytes::Bytes;
+use futures::StreamExt;
 use log::{debug, info};
 use num_traits::FromPrimitive;
 use once_cell::sync::Lazy;
@@ -18,6 +23,7 @@ use serde_yaml::Value as YamlValue;
 use std::{
     borrow::Cow,
     collections::{BTreeMap, HashSet},
+    fmt::{Display, Error as FmtError, Formatter},
     sync::{
         atomic::{AtomicU32, Ordering},
         Arc, Mutex, RwLock,
@@ -26,9 +32,24 @@ use std::{
 use utoipa::ToSchema;
 
 /// Global map of input HTTP endpoints.
-static INPUT_HTTP_ENDPOINTS: Lazy<RwLock<BTreeMap<String, HttpInputEndpoint>>> =
+///
+/// The controller creates the new endpoint before disconnecting the old one
+/// when an endpoint is reconfigured, so there can be more than one endpoint
+/// with the same name.  The most recently created endpoint receives new
+/// connections.
+static INPUT_HTTP_ENDPOINTS: Lazy<RwLock<BTreeMap<String, Vec<HttpInputEndpoint>>>> =
     Lazy::new(|| RwLock::new(BTreeMap::new()));
 
+/// Look up the most recently created endpoint named `endpoint_name`.
+fn lookup_endpoint(endpoint_name: &str) -> Option<HttpInputEndpoint> {
+    INPUT_HTTP_ENDPOINTS
+        .read()
+        .unwrap()
+        .get(endpoint_name)
+        .and_then(|endpoints| endpoints.last())
+        .cloned()
+}
+
 /// [`InputTransport`] implementation that reads data from a websocket.
 ///
 /// This input transport is only available if the crate is configured with the
@@ -48,6 +69,12 @@ impl InputTransport for HttpInputTransport {
     /// `/input_endpoint/<name>` endpoint, where `name` is the argument passed
     /// here.
     ///
+    /// Alternatively, the client can push data with a plain POST request to
+    /// the same URL, e.g., `curl --data-binary @data.csv`.  The body of the
+    /// request must be a self-contained fragment of the input stream in the
+    /// endpoint's format, e.g., a CSV file or a sequence of JSON records.
+    /// See [`HttpInputError`] for the status codes returned by the request.
+    ///
     /// See [`InputTransport::new_endpoint()`] for more information.
     fn new_endpoint(
         &self,
@@ -67,11 +94,7 @@ impl HttpInputTransport {
         req: &HttpRequest,
         stream: Payload,
     ) -> AnyResult<HttpResponse> {
-        let endpoint = INPUT_HTTP_ENDPOINTS
-            .read()
-            .unwrap()
-            .get(endpoint_name)
-            .map(Clone::clone)
+        let endpoint = lookup_endpoint(endpoint_name)
             .ok_or_else(|| anyhow!("unknown HTTP input endpoint '{endpoint_name}'"))?;
         if endpoint.num_sockets() >= MAX_SOCKETS_PER_ENDPOINT {
             return Err(anyhow!(
@@ -85,6 +108,110 @@ impl HttpInputTransport {
     }
 }
 
+impl HttpInputTransport {
+    /// Push the body of a POST request to endpoint `endpoint_name`.
+    ///
+    /// The body is parsed by a new instance of the endpoint's parser as a
+    /// self-contained fragment of the input stream, so concurrent requests
+    /// and websockets don't interfere with each other.  Parsing runs on the
+    /// blocking thread pool, so that large requests don't stall the async
+    /// runtime.  Returns after all records in the body have been queued for
+    /// processing by the circuit.
+    ///
+    /// On error, records received before the error may have been queued.
+    pub(crate) async fn post_data(
+        endpoint_name: &str,
+        mut payload: Payload,
+    ) -> Result<(), HttpInputError> {
+        let endpoint = lookup_endpoint(endpoint_name)
+            .ok_or_else(|| HttpInputError::UnknownEndpoint(endpoint_name.to_string()))?;
+
+        match endpoint.state() {
+            PipelineState::Running => {}
+            PipelineState::Paused => return Err(HttpInputError::Paused),
+            PipelineState::Terminated => return Err(HttpInputError::Terminated),
+        }
+
+        let mut consumer = endpoint.inner.consumer.lock().unwrap().fork();
+        let mut num_bytes = 0;
+
+        while let Some(chunk) = payload.next().await {
+            let chunk = chunk.map_err(|e| HttpInputError::Payload(e.to_string()))?;
+            // Keep receiving the request if the endpoint has been paused since
+            // it started, but stop if the endpoint is gone.
+            if endpoint.state() == PipelineState::Terminated {
+                return Err(HttpInputError::Terminated);
+            }
+            num_bytes += chunk.len();
+            consumer = web::block(move || {
+                consumer.input(&chunk);
+                consumer
+            })
+            .await
+            .map_err(|e| HttpInputError::Internal(e.to_string()))?;
+        }
+        web::block(move || consumer.end_of_fragment())
+            .await
+            .map_err(|e| HttpInputError::Internal(e.to_string()))?;
+
+        debug!("HTTP input endpoint '{endpoint_name}': received {num_bytes}-byte POST request");
+        Ok(())
+    }
+}
+
+/// Error pushing data to an HTTP input endpoint with a POST request.
+///
+/// Each variant maps to a distinct HTTP status code, which allows clients to
+/// implement flow control by retrying requests rejected with
+/// `503 Service Unavailable`.
+#[derive(Debug)]
+pub(crate) enum HttpInputError {
+    /// The endpoint does not exist (`404 Not Found`).
+    UnknownEndpoint(String),
+
+    /// The endpoint is paused, because either the pipeline is paused or the
+    /// endpoint has reached its `max_buffered_records` limit
+    /// (`503 Service Unavailable`).  The client should retry later.
+    Paused,
+
+    /// The endpoint has been disconnected or the pipeline is shutting down
+    /// (`410 Gone`).
+    Terminated,
+
+    /// Error receiving the request body (`400 Bad Request`).
+    Payload(String),
+
+    /// Error running the parser on the blocking thread pool, e.g., because
+    /// the server is shutting down (`500 Internal Server Error`).
+    Internal(String),
+}
+
+impl HttpInputError {
+    pub(crate) fn status_code(&self) -> StatusCode {
+        match self {
+            Self::UnknownEndpoint(_) => StatusCode::NOT_FOUND,
+            Self::Paused => StatusCode::SERVICE_UNAVAILABLE,
+            Self::Terminated => StatusCode::GONE,
+            Self::Payload(_) => StatusCode::BAD_REQUEST,
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl Display for HttpInputError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+        match self {
+            Self::UnknownEndpoint(endpoint_name) => {
+                write!(f, "unknown HTTP input endpoint '{endpoint_name}'")
+            }
+            Self::Paused => f.write_str("the endpoint is paused; retry the request later"),
+            Self::Terminated => f.write_str("the endpoint has been disconnected"),
+            Self::Payload(error) => write!(f, "error receiving request body: {error}"),
+            Self::Internal(error) => write!(f, "error parsing request body: {error}"),
+        }
+    }
+}
+
 /// Configuration for reading data from a websocket with `HttpOutputTransport`.
 ///
 /// This is an empty struct because this kind of transport doesn't accept any
@@ -138,15 +265,14 @@ impl HttpInputEndpoint {
     fn new(name: &str, consumer: Box<dyn InputConsumer>) -> AnyResult<Self> {
         let mut endpoint_map = INPUT_HTTP_ENDPOINTS.write().unwrap();
 
-        if endpoint_map.contains_key(name) {
-            return Err(anyhow!(format!("Duplicate HTTP endpoint name '{name}'")));
-        }
-
         let endpoint = Self {
             inner: Arc::new(HttpInputEndpointInner::new(name, consumer)),
         };
 
-        endpoint_map.insert(name.to_string(), endpoint.clone());
+        endpoint_map
+            .entry(name.to_string())
+            .or_default()
+            .push(endpoint.clone());
         Ok(endpoint)
     }
 
@@ -207,6 +333,16 @@ impl InputEndpoint for HttpInputEndpoint {
     }
 
     fn disconnect(&self) {
+        // Unregister the endpoint.
+        let mut endpoint_map = INPUT_HTTP_ENDPOINTS.write().unwrap();
+        if let Some(endpoints) = endpoint_map.get_mut(self.name()) {
+            endpoints.retain(|endpoint| !Arc::ptr_eq(&endpoint.inner, &self.inner));
+            if endpoints.is_empty() {
+                endpoint_map.remove(self.name());
+            }
+        }
+        drop(endpoint_map);
+
         self.inner
             .state
             .store(PipelineState::Terminated as u32, Ordering::Release);
@@ -3,5 +3,6 @@ mod output;
 
 pub(self) static MAX_SOCKETS_PER_ENDPOINT: usize = 5;
 
+pub(crate) use input::HttpInputError;
 pub use input::HttpInputTransport;
 pub use output::HttpOutputTransport;
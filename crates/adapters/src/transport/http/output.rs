@@ -1,27 +1,59 @@
 use super::MAX_SOCKETS_PER_ENDPOINT;
 use crate::{OutputEndpoint, OutputTransport};
-use actix::{Actor, ActorContext, Addr, AsyncContext, Handler, Message, StreamHandler};
-use actix_web::{web::Payload, HttpRequest, HttpResponse};
+use actix::{Actor, ActorContext, Addr, AsyncContext, Context, Handler, Message, StreamHandler};
+use actix_web::{
+    http::{header, StatusCode},
+    web::{Payload, Query},
+    Error as ActixError, HttpRequest, HttpResponse,
+};
 use actix_web_actors::ws::{
     self, Message as WsMessage, ProtocolError as WsProtocolError, WebsocketContext,
 };
-use anyhow::{anyhow, Error as AnyError, Result as AnyResult};
-use futures::executor::block_on;
-use log::{debug, info};
+use anyhow::{Error as AnyError, Result as AnyResult};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use bytes::Bytes;
+use futures::{
+    channel::mpsc::{channel, Sender},
+    executor::block_on,
+};
+use log::{debug, info, warn};
 use once_cell::sync::Lazy;
 use serde::Deserialize;
 use serde_yaml::Value as YamlValue;
 use std::{
     borrow::Cow,
     collections::{BTreeMap, HashSet},
-    sync::{Arc, RwLock},
+    fmt::{Display, Error as FmtError, Formatter},
+    mem::take,
+    sync::{Arc, Mutex, RwLock},
 };
 use utoipa::ToSchema;
 
+/// Maximal number of output buffers queued for a streaming HTTP response.
+///
+/// A client that falls this far behind is disconnected, so that a slow client
+/// can't make the server buffer an unbounded amount of output.
+const MAX_STREAM_BUFFERS: usize = 256;
+
 /// Global map of output HTTP endpoints.
-static OUTPUT_HTTP_ENDPOINTS: Lazy<RwLock<BTreeMap<String, HttpOutputEndpoint>>> =
+///
+/// The controller creates the new endpoint before disconnecting the old one
+/// when an endpoint is reconfigured, so there can be more than one endpoint
+/// with the same name.  The most recently created endpoint receives new
+/// connections.
+static OUTPUT_HTTP_ENDPOINTS: Lazy<RwLock<BTreeMap<String, Vec<HttpOutputEndpoint>>>> =
     Lazy::new(|| RwLock::new(BTreeMap::new()));
 
+/// Look up the most recently created endpoint named `endpoint_name`.
+fn lookup_endpoint(endpoint_name: &str) -> Option<HttpOutputEndpoint> {
+    OUTPUT_HTTP_ENDPOINTS
+        .read()
+        .unwrap()
+        .get(endpoint_name)
+        .and_then(|endpoints| endpoints.last())
+        .cloned()
+}
+
 /// [`OutputTransport`] implementation that writes data to a websocket.
 ///
 /// This output transport is only available if the crate is configured with the
@@ -35,77 +67,271 @@ impl OutputTransport for HttpOutputTransport {
         Cow::Borrowed("http")
     }
 
-    /// Creates a new [`OutputEndpoint`] for sending data to a websocket.  There
-    /// is no configuration (`_config` is ignored).  Rather, the client
-    /// connects a websocket by issuing a GET request to the
-    /// `/output_endpoint/<name>` endpoint, where `name` is the argument passed
-    /// here.
+    /// Creates a new [`OutputEndpoint`] for sending data to a websocket.
+    /// `config` is an optional [`HttpOutputConfig`].  The client connects a
+    /// websocket by issuing a GET request to the `/output_endpoint/<name>`
+    /// endpoint, where `name` is the argument passed here.
+    ///
+    /// By default, the websocket receives changes to the output stream
+    /// produced after the connection has been established.  If the endpoint
+    /// is configured with `snapshots: true`, a client that connects with the
+    /// `?mode=snapshot` query string first receives the current contents of
+    /// the stream as a sequence of insertions, followed by a
+    /// `snapshot_complete` text message, followed by changes produced by
+    /// subsequent steps.
+    ///
+    /// A GET request to the same URL that does not open a websocket receives
+    /// output buffers in the body of a streaming HTTP response.  If the
+    /// request accepts the `text/event-stream` content type, each buffer is
+    /// sent as a server-sent event, and the end of the snapshot requested
+    /// with `?mode=snapshot` is marked by a `snapshot_complete` event.
+    /// Otherwise, buffers are sent as is using chunked transfer encoding,
+    /// which does not support the `snapshot` mode.  Server-sent events can
+    /// only carry text, so buffers that are not valid UTF-8 or contain
+    /// carriage returns are sent as `binary` events with base64-encoded
+    /// payload.  A streaming client that does not keep up with the output
+    /// is disconnected.
     ///
     /// See [`OutputTransport::new_endpoint()`] for more information.
     fn new_endpoint(
         &self,
         name: &str,
-        _config: &YamlValue,
+        config: &YamlValue,
         async_error_callback: Box<dyn Fn(bool, AnyError) + Send + Sync>,
     ) -> AnyResult<Box<dyn OutputEndpoint>> {
-        // let _config = HttpOutputConfig::deserialize(config)?;
-        let ep = HttpOutputEndpoint::new(name, async_error_callback)?;
+        let config = if config.is_null() {
+            HttpOutputConfig::default()
+        } else {
+            HttpOutputConfig::deserialize(config)?
+        };
+        let ep = HttpOutputEndpoint::new(name, &config, async_error_callback)?;
         Ok(Box::new(ep))
     }
 }
 
 impl HttpOutputTransport {
-    pub(crate) fn get_endpoint_websocket(
+    /// Look up endpoint `endpoint_name` and the mode requested by `req`.
+    fn lookup_endpoint(
         endpoint_name: &str,
         req: &HttpRequest,
-        stream: Payload,
-    ) -> AnyResult<HttpResponse> {
-        let endpoint = OUTPUT_HTTP_ENDPOINTS
-            .read()
-            .unwrap()
-            .get(endpoint_name)
-            .map(Clone::clone)
-            .ok_or_else(|| anyhow!("unknown HTTP output endpoint '{endpoint_name}'"))?;
+    ) -> Result<(HttpOutputEndpoint, HttpOutputMode), HttpOutputError> {
+        let endpoint = lookup_endpoint(endpoint_name)
+            .ok_or_else(|| HttpOutputError::UnknownEndpoint(endpoint_name.to_string()))?;
         if endpoint.num_sockets() >= MAX_SOCKETS_PER_ENDPOINT {
-            return Err(anyhow!(
-                "maximum number of connections per HTTP endpoint exceeded"
-            ));
+            return Err(HttpOutputError::TooManyConnections);
         }
-        let resp = ws::start(HttpOutputWs::new(endpoint), req, stream)
-            .map_err(|e| anyhow!(format!("error initializing websocket: {e}")))?;
+        let mode = Query::<HttpOutputWsQuery>::from_query(req.query_string())
+            .map_err(|e| HttpOutputError::InvalidRequest(format!("invalid query string: {e}")))?
+            .into_inner()
+            .mode;
+        endpoint.check_mode(mode)?;
+
+        Ok((endpoint, mode))
+    }
+
+    pub(crate) fn get_endpoint_websocket(
+        endpoint_name: &str,
+        req: &HttpRequest,
+        stream: Payload,
+    ) -> Result<HttpResponse, HttpOutputError> {
+        let (endpoint, mode) = Self::lookup_endpoint(endpoint_name, req)?;
+        let resp = ws::start(HttpOutputWs::new(endpoint, mode), req, stream).map_err(|e| {
+            HttpOutputError::InvalidRequest(format!("error initializing websocket: {e}"))
+        })?;
         info!("HTTP output endpoint '{endpoint_name}': opened websocket");
         Ok(resp)
     }
+
+    /// Respond to a GET request that does not open a websocket with a
+    /// streaming response that carries output buffers.
+    pub(crate) fn get_endpoint_stream(
+        endpoint_name: &str,
+        req: &HttpRequest,
+    ) -> Result<HttpResponse, HttpOutputError> {
+        let (endpoint, mode) = Self::lookup_endpoint(endpoint_name, req)?;
+        let accepts_sse = req
+            .headers()
+            .get(header::ACCEPT)
+            .and_then(|accept| accept.to_str().ok())
+            .map(|accept| accept.contains(mime::TEXT_EVENT_STREAM.essence_str()))
+            .unwrap_or(false);
+        let format = if accepts_sse {
+            HttpOutputStreamFormat::Sse
+        } else {
+            HttpOutputStreamFormat::Chunked
+        };
+        if mode == HttpOutputMode::Snapshot && format == HttpOutputStreamFormat::Chunked {
+            return Err(HttpOutputError::InvalidRequest(
+                "'snapshot' mode requires a websocket or server-sent events".to_string(),
+            ));
+        }
+
+        let (sender, receiver) = channel(MAX_STREAM_BUFFERS);
+        HttpOutputStream::new(endpoint, mode, format, sender).start();
+        info!("HTTP output endpoint '{endpoint_name}': opened {format:?} stream");
+
+        let content_type = match format {
+            HttpOutputStreamFormat::Chunked => mime::APPLICATION_OCTET_STREAM,
+            HttpOutputStreamFormat::Sse => mime::TEXT_EVENT_STREAM,
+        };
+        Ok(HttpResponse::Ok()
+            .content_type(content_type)
+            .streaming(receiver))
+    }
 }
 
-/// Configuration for writing data to a websocket with `HttpOutputTransport`.
+/// Error connecting to an HTTP output endpoint.
 ///
-/// This is an empty struct because this kind of transport doesn't accept any
-/// configuration.
-#[derive(Clone, Deserialize, ToSchema)]
-pub struct HttpOutputConfig;
+/// Each variant maps to a distinct HTTP status code.
+#[derive(Debug)]
+pub(crate) enum HttpOutputError {
+    /// The endpoint does not exist (`404 Not Found`).
+    UnknownEndpoint(String),
+
+    /// The endpoint has reached the maximum number of connections
+    /// (`503 Service Unavailable`).
+    TooManyConnections,
+
+    /// The endpoint cannot serve the request, e.g., because the request asks
+    /// for a snapshot that the endpoint is not configured to serve
+    /// (`400 Bad Request`).
+    InvalidRequest(String),
+}
+
+impl HttpOutputError {
+    pub(crate) fn status_code(&self) -> StatusCode {
+        match self {
+            Self::UnknownEndpoint(_) => StatusCode::NOT_FOUND,
+            Self::TooManyConnections => StatusCode::SERVICE_UNAVAILABLE,
+            Self::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+impl Display for HttpOutputError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+        match self {
+            Self::UnknownEndpoint(endpoint_name) => {
+                write!(f, "unknown HTTP output endpoint '{endpoint_name}'")
+            }
+            Self::TooManyConnections => {
+                f.write_str("maximum number of connections per HTTP endpoint exceeded")
+            }
+            Self::InvalidRequest(error) => f.write_str(error),
+        }
+    }
+}
+
+/// Configuration for writing data to a websocket with `HttpOutputTransport`.
+#[derive(Clone, Default, Deserialize, ToSchema)]
+pub struct HttpOutputConfig {
+    /// Serve `?mode=snapshot` requests.
+    ///
+    /// When enabled, the controller maintains the integrated contents of the
+    /// output stream for this endpoint, which takes memory proportional to
+    /// the size of the stream.  Disabled by default.
+    #[serde(default)]
+    pub snapshots: bool,
+}
+
+/// Data sent to a websocket.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum HttpOutputMode {
+    /// Changes produced after the websocket was opened.
+    Changes,
+
+    /// The current contents of the stream, followed by subsequent changes.
+    Snapshot,
+}
+
+impl Default for HttpOutputMode {
+    fn default() -> Self {
+        Self::Changes
+    }
+}
+
+/// Query parameters of the `/output_endpoint/{endpoint_name}` request.
+#[derive(Deserialize)]
+struct HttpOutputWsQuery {
+    #[serde(default)]
+    mode: HttpOutputMode,
+}
+
+/// Actor that delivers output buffers to a client: either a websocket or a
+/// streaming HTTP response.
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Subscriber {
+    Websocket(Addr<HttpOutputWs>),
+    Stream(Addr<HttpOutputStream>),
+}
+
+impl Subscriber {
+    /// Send `event` to the actor and wait for it to be handled.
+    fn send(&self, event: Event) -> AnyResult<()> {
+        match self {
+            Self::Websocket(addr) => block_on(addr.send(event))?,
+            Self::Stream(addr) => block_on(addr.send(event))?,
+        }
+        Ok(())
+    }
+
+    /// Send `event` to the actor without waiting.
+    fn do_send(&self, event: Event) {
+        match self {
+            Self::Websocket(addr) => addr.do_send(event),
+            Self::Stream(addr) => addr.do_send(event),
+        }
+    }
+}
 
 struct HttpOutputEndpointInner {
     name: String,
 
-    /// Addresses of websocket actors associated with the endpoint.
+    /// Websocket and stream actors associated with the endpoint.
     ///
     /// HTTP endpoint supports up to MAX_SOCKETS_PER_ENDPOINT simultaneously
-    /// open websockets.  The client opens a new websocket by issuing a GET
+    /// open connections.  The client opens a new connection by issuing a GET
     /// request to the `/output_endpoint/{endpoint_name}` endpoint.  The
-    /// websocket is removed from this set when client connection closes.
+    /// actor is removed from this set when client connection closes.
+    ///
+    /// This field is used to notify all actors about new data buffers to send
+    /// out.
+    socket_addrs: RwLock<HashSet<Subscriber>>,
+
+    /// `true` if the endpoint serves `?mode=snapshot` requests.
+    snapshots: bool,
+
+    /// Callback that notifies the controller about new snapshot requests.
+    /// Set by the controller via
+    /// [`OutputEndpoint::set_snapshot_notifier`].
+    snapshot_notifier: Mutex<Option<Box<dyn Fn() + Send + Sync>>>,
+
+    /// Connections waiting for a snapshot of the stream.
     ///
-    /// This field is used to notify all websocket actors about new data
-    /// buffers to send out.
-    socket_addrs: RwLock<HashSet<Addr<HttpOutputWs>>>,
+    /// These connections are not yet in `socket_addrs` and don't receive any
+    /// changes until the snapshot has been delivered.
+    snapshot_requests: Mutex<HashSet<Subscriber>>,
+
+    /// Connections that receive the snapshot that is currently being sent;
+    /// `None` if no snapshot is in progress.
+    snapshot_addrs: Mutex<Option<Vec<Subscriber>>>,
     _async_error_callback: Box<dyn Fn(bool, AnyError) + Send + Sync>,
 }
 
 impl HttpOutputEndpointInner {
-    fn new(name: &str, async_error_callback: Box<dyn Fn(bool, AnyError) + Send + Sync>) -> Self {
+    fn new(
+        name: &str,
+        config: &HttpOutputConfig,
+        async_error_callback: Box<dyn Fn(bool, AnyError) + Send + Sync>,
+    ) -> Self {
         Self {
             name: name.to_string(),
             socket_addrs: RwLock::new(HashSet::new()),
+            snapshots: config.snapshots,
+            snapshot_notifier: Mutex::new(None),
+            snapshot_requests: Mutex::new(HashSet::new()),
+            snapshot_addrs: Mutex::new(None),
             _async_error_callback: async_error_callback,
         }
     }
@@ -124,21 +350,23 @@ struct HttpOutputEndpoint {
 impl HttpOutputEndpoint {
     fn new(
         name: &str,
+        config: &HttpOutputConfig,
         async_error_callback: Box<dyn Fn(bool, AnyError) + Send + Sync>,
     ) -> AnyResult<Self> {
         let mut endpoint_map = OUTPUT_HTTP_ENDPOINTS.write().unwrap();
 
-        if endpoint_map.contains_key(name) {
-            return Err(anyhow!(format!(
-                "duplicate HTTP output endpoint name '{name}'"
-            )));
-        }
-
         let endpoint = Self {
-            inner: Arc::new(HttpOutputEndpointInner::new(name, async_error_callback)),
+            inner: Arc::new(HttpOutputEndpointInner::new(
+                name,
+                config,
+                async_error_callback,
+            )),
         };
 
-        endpoint_map.insert(name.to_string(), endpoint.clone());
+        endpoint_map
+            .entry(name.to_string())
+            .or_default()
+            .push(endpoint.clone());
         Ok(endpoint)
     }
 
@@ -146,45 +374,157 @@ impl HttpOutputEndpoint {
         self.inner.name.as_str()
     }
 
-    /// Number of connected websockets.
+    /// Number of connected websockets and streams.
     fn num_sockets(&self) -> usize {
         self.inner.socket_addrs.read().unwrap().len()
+            + self.inner.snapshot_requests.lock().unwrap().len()
+            + self
+                .inner
+                .snapshot_addrs
+                .lock()
+                .unwrap()
+                .as_ref()
+                .map(Vec::len)
+                .unwrap_or(0)
     }
 
-    /// Register new websocket actor.
-    fn add_socket(&self, addr: Addr<HttpOutputWs>) {
+    /// Register new websocket or stream actor.
+    fn add_socket(&self, addr: Subscriber) {
         self.inner.socket_addrs.write().unwrap().insert(addr);
     }
 
-    /// Remove closed websocket.
-    fn remove_socket(&self, addr: &Addr<HttpOutputWs>) {
+    /// Fails if the endpoint cannot serve connections in the given `mode`.
+    fn check_mode(&self, mode: HttpOutputMode) -> Result<(), HttpOutputError> {
+        if mode == HttpOutputMode::Snapshot && !self.inner.snapshots {
+            return Err(HttpOutputError::InvalidRequest(format!(
+                "HTTP output endpoint '{}' is not configured to serve snapshots (set 'snapshots: true' in the transport configuration)",
+                self.name()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Register new actor that requested a snapshot of the stream and wake
+    /// up the controller to serve the request.
+    fn add_snapshot_socket(&self, addr: Subscriber) {
+        self.inner.snapshot_requests.lock().unwrap().insert(addr);
+        if let Some(notifier) = &*self.inner.snapshot_notifier.lock().unwrap() {
+            notifier();
+        }
+    }
+
+    /// Register an actor in the given `mode`.
+    fn subscribe(&self, addr: Subscriber, mode: HttpOutputMode) {
+        match mode {
+            HttpOutputMode::Changes => self.add_socket(addr),
+            HttpOutputMode::Snapshot => self.add_snapshot_socket(addr),
+        }
+    }
+
+    /// Remove closed websocket or stream.
+    fn remove_socket(&self, addr: &Subscriber) {
         self.inner.socket_addrs.write().unwrap().remove(addr);
+        self.inner.snapshot_requests.lock().unwrap().remove(addr);
+        if let Some(snapshot_addrs) = &mut *self.inner.snapshot_addrs.lock().unwrap() {
+            snapshot_addrs.retain(|snapshot_addr| snapshot_addr != addr);
+        }
     }
 }
 
 impl OutputEndpoint for HttpOutputEndpoint {
     fn push_buffer(&mut self, buffer: &[u8]) -> AnyResult<()> {
-        for addr in self.inner.socket_addrs.read().unwrap().iter() {
-            block_on(addr.send(Event::Buffer(Vec::from(buffer))))?;
+        // While a snapshot is being sent, buffers only go to websockets that
+        // requested it.
+        let snapshot_addrs = self.inner.snapshot_addrs.lock().unwrap().clone();
+        if let Some(snapshot_addrs) = snapshot_addrs {
+            for addr in snapshot_addrs.iter() {
+                addr.send(Event::Buffer(Vec::from(buffer)))?;
+            }
+        } else {
+            for addr in self.inner.socket_addrs.read().unwrap().iter() {
+                addr.send(Event::Buffer(Vec::from(buffer)))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn supports_snapshots(&self) -> bool {
+        self.inner.snapshots
+    }
+
+    fn set_snapshot_notifier(&mut self, notifier: Box<dyn Fn() + Send + Sync>) {
+        *self.inner.snapshot_notifier.lock().unwrap() = Some(notifier);
+    }
+
+    fn snapshot_requested(&mut self) -> bool {
+        !self.inner.snapshot_requests.lock().unwrap().is_empty()
+    }
+
+    fn snapshot_start(&mut self) -> AnyResult<()> {
+        let requests = take(&mut *self.inner.snapshot_requests.lock().unwrap());
+        *self.inner.snapshot_addrs.lock().unwrap() = Some(requests.into_iter().collect());
+        Ok(())
+    }
+
+    fn snapshot_end(&mut self) -> AnyResult<()> {
+        let addrs = take(&mut *self.inner.snapshot_addrs.lock().unwrap());
+
+        for addr in addrs.into_iter().flatten() {
+            // Skip connections that were closed before receiving the snapshot.
+            if addr.send(Event::SnapshotComplete).is_ok() {
+                self.add_socket(addr);
+            }
         }
         Ok(())
     }
+
+    fn disconnect(&mut self) {
+        // Unregister the endpoint and close all connections.
+        let mut endpoint_map = OUTPUT_HTTP_ENDPOINTS.write().unwrap();
+        if let Some(endpoints) = endpoint_map.get_mut(self.name()) {
+            endpoints.retain(|endpoint| !Arc::ptr_eq(&endpoint.inner, &self.inner));
+            if endpoints.is_empty() {
+                endpoint_map.remove(self.name());
+            }
+        }
+        drop(endpoint_map);
+
+        let inner = &self.inner;
+        let addrs = inner
+            .socket_addrs
+            .read()
+            .unwrap()
+            .iter()
+            .chain(inner.snapshot_requests.lock().unwrap().iter())
+            .cloned()
+            .collect::<Vec<_>>();
+        for addr in addrs.into_iter() {
+            addr.do_send(Event::Disconnect);
+        }
+    }
 }
 
 #[derive(Message)]
 #[rtype(result = "()")]
 enum Event {
     Buffer(Vec<u8>),
+
+    /// Marks the end of a snapshot.
+    SnapshotComplete,
+
+    /// The endpoint has been disconnected from the pipeline.
+    Disconnect,
 }
 
 /// Actix actor that handles websocket communication.
 struct HttpOutputWs {
     endpoint: HttpOutputEndpoint,
+    mode: HttpOutputMode,
 }
 
 impl HttpOutputWs {
-    fn new(endpoint: HttpOutputEndpoint) -> Self {
-        Self { endpoint }
+    fn new(endpoint: HttpOutputEndpoint, mode: HttpOutputMode) -> Self {
+        Self { endpoint, mode }
     }
 }
 
@@ -193,14 +533,14 @@ impl Actor for HttpOutputWs {
 
     fn started(&mut self, ctx: &mut Self::Context) {
         // Websocket connection established: register websocket actor with the endpoint.
-        let addr = ctx.address();
-        self.endpoint.add_socket(addr);
+        self.endpoint
+            .subscribe(Subscriber::Websocket(ctx.address()), self.mode);
     }
 
     fn stopped(&mut self, ctx: &mut Self::Context) {
         // Connection closed: unregister the actor.
-        let addr = ctx.address();
-        self.endpoint.remove_socket(&addr);
+        self.endpoint
+            .remove_socket(&Subscriber::Websocket(ctx.address()));
     }
 }
 
@@ -238,6 +578,184 @@ impl Handler<Event> for HttpOutputWs {
 
                 ctx.binary(buf);
             }
+            Event::SnapshotComplete => {
+                debug!(
+                    "HTTP output endpoint '{}': snapshot complete",
+                    self.endpoint.name(),
+                );
+
+                ctx.text("snapshot_complete");
+            }
+            Event::Disconnect => {
+                info!(
+                    "HTTP output endpoint '{}': endpoint disconnected, closing websocket",
+                    self.endpoint.name(),
+                );
+
+                ctx.close(None);
+                ctx.stop();
+            }
         }
     }
 }
+
+/// Encoding of output buffers in a streaming HTTP response.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum HttpOutputStreamFormat {
+    /// Raw buffers sent using chunked transfer encoding.
+    Chunked,
+
+    /// Server-sent events, one event per buffer.
+    Sse,
+}
+
+/// Encode output buffer `data` as a server-sent event.
+///
+/// Buffers that are valid UTF-8 without carriage returns, which the client
+/// would treat as line breaks, are sent as regular messages; other buffers
+/// are sent as `binary` events with base64-encoded payload.
+fn sse_buffer(data: &[u8]) -> Bytes {
+    match std::str::from_utf8(data) {
+        Ok(text) if !text.contains('\r') => sse_event(None, text),
+        _ => sse_event(Some("binary"), &BASE64.encode(data)),
+    }
+}
+
+/// Encode a server-sent event with optional event type `event` and payload
+/// `data`, which is sent as one `data:` field per line, so that the client
+/// reconstructs `data` exactly.
+fn sse_event(event: Option<&str>, data: &str) -> Bytes {
+    let mut result = String::new();
+    if let Some(event) = event {
+        result.push_str(&format!("event: {event}\n"));
+    }
+    for line in data.split('\n') {
+        result.push_str(&format!("data: {line}\n"));
+    }
+    result.push('\n');
+
+    Bytes::from(result)
+}
+
+/// Actix actor that sends output buffers to a streaming HTTP response.
+struct HttpOutputStream {
+    endpoint: HttpOutputEndpoint,
+    mode: HttpOutputMode,
+    format: HttpOutputStreamFormat,
+
+    /// Sender side of the response body; dropping it completes the response.
+    sender: Sender<Result<Bytes, ActixError>>,
+}
+
+impl HttpOutputStream {
+    fn new(
+        endpoint: HttpOutputEndpoint,
+        mode: HttpOutputMode,
+        format: HttpOutputStreamFormat,
+        sender: Sender<Result<Bytes, ActixError>>,
+    ) -> Self {
+        Self {
+            endpoint,
+            mode,
+            format,
+            sender,
+        }
+    }
+
+    /// Send `bytes` to the client; stop the actor if the client has closed
+    /// the connection or has fallen [`MAX_STREAM_BUFFERS`] buffers behind.
+    fn send_bytes(&mut self, bytes: Bytes, ctx: &mut Context<Self>) {
+        if let Err(e) = self.sender.try_send(Ok(bytes)) {
+            if e.is_full() {
+                warn!(
+                    "HTTP output endpoint '{}': client is not keeping up with the stream, closing connection",
+                    self.endpoint.name(),
+                );
+            } else {
+                info!(
+                    "HTTP output endpoint '{}': stream closed by client",
+                    self.endpoint.name(),
+                );
+            }
+            ctx.stop();
+        }
+    }
+}
+
+impl Actor for HttpOutputStream {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.endpoint
+            .subscribe(Subscriber::Stream(ctx.address()), self.mode);
+    }
+
+    fn stopped(&mut self, ctx: &mut Self::Context) {
+        self.endpoint
+            .remove_socket(&Subscriber::Stream(ctx.address()));
+    }
+}
+
+impl Handler<Event> for HttpOutputStream {
+    type Result = ();
+
+    fn handle(&mut self, msg: Event, ctx: &mut Self::Context) {
+        match msg {
+            Event::Buffer(buf) => {
+                debug!(
+                    "HTTP output endpoint '{}': streaming {} bytes",
+                    self.endpoint.name(),
+                    buf.len(),
+                );
+
+                let bytes = match self.format {
+                    HttpOutputStreamFormat::Chunked => Bytes::from(buf),
+                    HttpOutputStreamFormat::Sse => sse_buffer(&buf),
+                };
+                self.send_bytes(bytes, ctx);
+            }
+            Event::SnapshotComplete => {
+                if self.format == HttpOutputStreamFormat::Sse {
+                    self.send_bytes(sse_event(Some("snapshot_complete"), ""), ctx);
+                }
+            }
+            Event::Disconnect => {
+                info!(
+                    "HTTP output endpoint '{}': endpoint disconnected, closing stream",
+                    self.endpoint.name(),
+                );
+
+                // Stopping the actor drops `sender`, which completes the
+                // response.
+                ctx.stop();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{sse_buffer, sse_event};
+
+    #[test]
+    fn test_sse_event() {
+        assert_eq!(
+            &sse_buffer(b"1,true,foo,1\n2,false,bar,1\n")[..],
+            b"data: 1,true,foo,1\ndata: 2,false,bar,1\ndata: \n\n"
+        );
+        assert_eq!(
+            &sse_event(Some("snapshot_complete"), "")[..],
+            b"event: snapshot_complete\ndata: \n\n"
+        );
+
+        // Buffers that can't be sent as text are base64-encoded.
+        assert_eq!(
+            &sse_buffer(b"foo\r\nbar\r\n")[..],
+            b"event: binary\ndata: Zm9vDQpiYXINCg==\n\n"
+        );
+        assert_eq!(
+            &sse_buffer(&[0xc3, 0x01])[..],
+            b"event: binary\ndata: wwE=\n\n"
+        );
+    }
+}
@@ -5,9 +5,12 @@ use log::debug;
 use num_traits::FromPrimitive;
 use rdkafka::{
     config::{FromClientConfigAndContext, RDKafkaLogLevel},
-    consumer::{BaseConsumer, Consumer, ConsumerContext, Rebalance, RebalanceProtocol},
+    consumer::{
+        BaseConsumer, Consumer, ConsumerContext, ConsumerGroupMetadata, Rebalance,
+        RebalanceProtocol,
+    },
     error::{KafkaError, KafkaResult},
-    ClientConfig, ClientContext, Message,
+    ClientConfig, ClientContext, Message, Offset, TopicPartitionList,
 };
 use serde::Deserialize;
 use serde_yaml::Value as YamlValue;
@@ -32,6 +35,11 @@ use utoipa::{
 
 const POLL_TIMEOUT: Duration = Duration::from_millis(100);
 
+/// In exactly-once mode, messages that are already available are combined
+/// into buffers of up to this many bytes, reported to the consumer with a
+/// single position.
+const MAX_BATCH_BYTES: usize = 1 << 20;
+
 /// On startup, the endpoint waits to join the consumer group.
 /// This constant defines the default wait timeout.
 const fn default_group_join_timeout_secs() -> u32 {
@@ -77,8 +85,10 @@ pub struct KafkaInputConfig {
     /// used to configure the Kafka consumer.  Not all options are valid with
     /// this Kafka adapter:
     ///
-    /// * "enable.auto.commit", if present, must be set to "false",
-    /// * "enable.auto.offset.store", if present, must be set to "false"
+    /// * "enable.auto.commit", if present, must be set to "true", or to
+    ///   "false" in exactly-once mode,
+    /// * "enable.auto.offset.store", if present, must be set to "true", or to
+    ///   "false" in exactly-once mode.
     #[serde(flatten)]
     pub kafka_options: BTreeMap<String, String>,
 
@@ -95,6 +105,34 @@ pub struct KafkaInputConfig {
     /// consumer group during initialization.
     #[serde(default = "default_group_join_timeout_secs")]
     pub group_join_timeout_secs: u32,
+
+    /// Enable exactly-once processing.
+    ///
+    /// When `false`, consumed offsets are committed automatically by the
+    /// Kafka client, so a crash can cause records to be lost or processed
+    /// twice.  When `true`, the endpoint reports consumed offsets to the
+    /// controller, which records them for each step of the circuit; offsets
+    /// are only committed by a Kafka output endpoint in exactly-once mode,
+    /// as part of the transaction that contains the outputs of the step.  A
+    /// restarted pipeline resumes from the offsets of the last step whose
+    /// outputs were committed.
+    ///
+    /// A restarted pipeline resumes Kafka offsets, but not the state of the
+    /// circuit, which starts empty.  Exactly-once processing is therefore
+    /// only supported for circuits without stateful operators, whose outputs
+    /// only depend on the inputs of the current step, e.g., circuits
+    /// consisting of filters and projections.  The controller rejects the
+    /// endpoint if the circuit has state (see
+    /// [`DBSPHandle::is_stateful`](`dbsp::DBSPHandle::is_stateful`)).
+    ///
+    /// Offsets of partitions revoked from the consumer by a group rebalance
+    /// before the step that consumed them was committed are not committed;
+    /// the new owner of the partition processes these messages again.
+    ///
+    /// Requires an explicit `group.id`.  The `isolation.level` option
+    /// defaults to `read_committed` in this mode.
+    #[serde(default)]
+    pub exactly_once: bool,
 }
 
 // The auto-derived implementation gets confused by the flattened
@@ -124,6 +162,19 @@ impl<'s> ToSchema<'s> for KafkaInputConfig {
                         .format(Some(SchemaFormat::KnownFormat(KnownFormat::Int32)))
                         .description(Some("Maximum timeout in seconds to wait for the endpoint to join the Kafka consumer group during initialization.")),
                 )
+                .property(
+                    "exactly_once",
+                    ObjectBuilder::new()
+                        .schema_type(SchemaType::Boolean)
+                        .description(Some(r#"Enable exactly-once processing.
+
+When `true`, consumed offsets are recorded by the controller for each step
+of the circuit and committed by a Kafka output endpoint in exactly-once
+mode, as part of the transaction that contains the outputs of the step.
+Only guaranteed for circuits whose outputs depend on the inputs of the
+current step alone, since a restarted pipeline resumes Kafka offsets, but
+not the state of the circuit.  Requires an explicit `group.id`."#)),
+                )
                 .additional_properties(Some(
                         ObjectBuilder::new()
                         .schema_type(SchemaType::String)
@@ -145,7 +196,7 @@ impl KafkaInputConfig {
             .entry(option.to_string())
             .or_insert_with(|| val.to_string());
         if option_val != val {
-            Err(AnyError::msg("cannot override '{option}' option: the Kafka transport adapter sets this option to '{val}'"))?;
+            Err(AnyError::msg(format!("cannot override '{option}' option: the Kafka transport adapter sets this option to '{val}'")))?;
         }
         Ok(())
     }
@@ -165,10 +216,25 @@ impl KafkaInputConfig {
             &env::var("REDPANDA_BROKERS").unwrap_or_else(|_| "localhost".to_string()),
         );
 
-        // Commit automatically.
-        // See https://docs.confluent.io/platform/current/clients/consumer.html#offset-management
-        self.enforce_option("enable.auto.commit", "true")?;
-        self.enforce_option("enable.auto.offset.store", "true")?;
+        if self.exactly_once {
+            // Committed offsets identify the position to resume from after
+            // restart, so the consumer group must be stable.
+            if !self.kafka_options.contains_key("group.id") {
+                return Err(AnyError::msg(
+                    "'group.id' must be specified in exactly-once mode",
+                ));
+            }
+
+            // Offsets are committed by the output endpoint.
+            self.enforce_option("enable.auto.commit", "false")?;
+            self.enforce_option("enable.auto.offset.store", "false")?;
+            self.set_option_if_missing("isolation.level", "read_committed");
+        } else {
+            // Commit automatically.
+            // See https://docs.confluent.io/platform/current/clients/consumer.html#offset-management
+            self.enforce_option("enable.auto.commit", "true")?;
+            self.enforce_option("enable.auto.offset.store", "true")?;
+        }
 
         let group_id = format!(
             "{}",
@@ -219,6 +285,18 @@ impl KafkaInputContext {
 impl ClientContext for KafkaInputContext {}
 
 impl ConsumerContext for KafkaInputContext {
+    fn pre_rebalance(&self, rebalance: &Rebalance<'_>) {
+        // This consumer can no longer commit offsets of revoked partitions.
+        if let Rebalance::Revoke(partitions) = rebalance {
+            if let Some(endpoint) = self.endpoint.lock().unwrap().upgrade() {
+                let mut offsets = endpoint.offsets.lock().unwrap();
+                for partition in partitions.elements().iter() {
+                    offsets.remove(&(partition.topic().to_string(), partition.partition()));
+                }
+            }
+        }
+    }
+
     fn post_rebalance(&self, rebalance: &Rebalance<'_>) {
         // println!("Rebalance: {rebalance:?}");
         if matches!(rebalance, Rebalance::Assign(_)) {
@@ -237,9 +315,14 @@ impl ConsumerContext for KafkaInputContext {
     }
 }
 
-struct KafkaInputEndpointInner {
+pub(super) struct KafkaInputEndpointInner {
     state: AtomicU32,
     kafka_consumer: BaseConsumer<KafkaInputContext>,
+    exactly_once: bool,
+
+    /// In exactly-once mode, the offset of the next message in each
+    /// partition assigned to the consumer, indexed by topic and partition.
+    offsets: Mutex<BTreeMap<(String, i32), i64>>,
 }
 
 impl KafkaInputEndpointInner {
@@ -267,6 +350,8 @@ impl KafkaInputEndpointInner {
         let endpoint = Arc::new(Self {
             state: AtomicU32::new(PipelineState::Paused as u32),
             kafka_consumer,
+            exactly_once: config.exactly_once,
+            offsets: Mutex::new(BTreeMap::new()),
         });
 
         *endpoint.kafka_consumer.context().endpoint.lock().unwrap() = Arc::downgrade(&endpoint);
@@ -346,8 +431,29 @@ impl KafkaInputEndpointInner {
         refine_kafka_error(self.kafka_consumer.client(), e)
     }
 
+    /// Report `data` to `consumer` along with the position of the endpoint
+    /// after consuming messages with the given `offsets`.
+    fn input_with_offsets(
+        endpoint: &Arc<KafkaInputEndpointInner>,
+        consumer: &mut Box<dyn InputConsumer>,
+        data: &[u8],
+        offsets: Vec<((String, i32), i64)>,
+    ) {
+        let offsets = {
+            let mut all_offsets = endpoint.offsets.lock().unwrap();
+            all_offsets.extend(offsets);
+            all_offsets.clone()
+        };
+        let position = KafkaInputPosition {
+            endpoint: Arc::downgrade(endpoint),
+            offsets,
+        };
+        consumer.input_with_position(data, Arc::new(position));
+    }
+
     fn worker_thread(endpoint: Arc<KafkaInputEndpointInner>, mut consumer: Box<dyn InputConsumer>) {
         let mut actual_state = PipelineState::Paused;
+
         loop {
             // endpoint.debug_consumer();
             match endpoint.state() {
@@ -377,31 +483,106 @@ impl KafkaInputEndpointInner {
             //
             // `POLL_TIMEOUT` makes sure that the thread will periodically
             // check for termination and pause commands.
-            match endpoint.kafka_consumer.poll(POLL_TIMEOUT) {
+            let error = match endpoint.kafka_consumer.poll(POLL_TIMEOUT) {
                 None => {
                     // println!("poll returned None");
+                    None
                 }
                 Some(Err(e)) => {
                     // println!("poll returned error");
-                    let (fatal, e) = endpoint.refine_error(e);
-                    consumer.error(fatal, e);
-                    if fatal {
-                        return;
+                    Some(e)
+                }
+                Some(Ok(message)) if endpoint.exactly_once => {
+                    // Combine messages that are already available into a
+                    // single buffer, so that the position of the endpoint is
+                    // reported once per buffer rather than once per message.
+                    let mut data = Vec::new();
+                    let mut offsets = Vec::new();
+                    let mut error = None;
+                    let mut next = Some(message);
+
+                    while let Some(message) = next.take() {
+                        data.extend_from_slice(message.payload().unwrap_or_default());
+                        offsets.push((
+                            (message.topic().to_string(), message.partition()),
+                            message.offset() + 1,
+                        ));
+                        if data.len() >= MAX_BATCH_BYTES {
+                            break;
+                        }
+                        match endpoint.kafka_consumer.poll(Duration::ZERO) {
+                            None => {}
+                            Some(Err(e)) => error = Some(e),
+                            Some(Ok(message)) => next = Some(message),
+                        }
                     }
+
+                    Self::input_with_offsets(&endpoint, &mut consumer, &data, offsets);
+                    error
                 }
                 Some(Ok(message)) => {
                     // println!("received {} bytes", message.payload().unwrap().len());
-                    // message.payload().map(|payload| consumer.input(payload));
-
                     if let Some(payload) = message.payload() {
                         consumer.input(payload);
                     }
+                    None
+                }
+            };
+
+            if let Some(e) = error {
+                let (fatal, e) = endpoint.refine_error(e);
+                consumer.error(fatal, e);
+                if fatal {
+                    return;
                 }
             }
         }
     }
 }
 
+/// Position of a Kafka input endpoint in exactly-once mode, reported to the
+/// controller via [`InputConsumer::input_with_position`].
+///
+/// Committed by Kafka output endpoints in exactly-once mode as part of the
+/// transaction that contains the outputs of a step.
+pub(super) struct KafkaInputPosition {
+    /// The endpoint that reported the position.
+    endpoint: Weak<KafkaInputEndpointInner>,
+
+    /// Offset of the next message to consume, indexed by topic and partition.
+    offsets: BTreeMap<(String, i32), i64>,
+}
+
+impl KafkaInputPosition {
+    /// Returns the offsets to commit along with the consumer group metadata.
+    ///
+    /// The metadata is retrieved at commit time, since it changes with every
+    /// group rebalance.  Only offsets of partitions currently assigned to the
+    /// consumer are returned, since the consumer can no longer commit offsets
+    /// of partitions revoked by a rebalance.
+    pub(super) fn offsets_to_commit(
+        &self,
+    ) -> AnyResult<(TopicPartitionList, ConsumerGroupMetadata)> {
+        let endpoint = self
+            .endpoint
+            .upgrade()
+            .ok_or_else(|| AnyError::msg("Kafka input endpoint has been disconnected"))?;
+        let group_metadata = endpoint
+            .kafka_consumer
+            .group_metadata()
+            .ok_or_else(|| AnyError::msg("failed to retrieve Kafka consumer group metadata"))?;
+        let assignment = endpoint.kafka_consumer.assignment()?;
+
+        let mut list = TopicPartitionList::new();
+        for ((topic, partition), offset) in self.offsets.iter() {
+            if assignment.find_partition(topic, *partition).is_some() {
+                list.add_partition_offset(topic, *partition, Offset::Offset(*offset))?;
+            }
+        }
+        Ok((list, group_metadata))
+    }
+}
+
 impl InputEndpoint for KafkaInputEndpoint {
     fn pause(&self) -> AnyResult<()> {
         // Notify worker thread via the state flag.  The worker may
@@ -418,6 +599,10 @@ impl InputEndpoint for KafkaInputEndpoint {
     fn disconnect(&self) {
         self.0.set_state(PipelineState::Terminated);
     }
+
+    fn is_exactly_once(&self) -> bool {
+        self.0.exactly_once
+    }
 }
 
 impl Drop for KafkaInputEndpoint {
@@ -6,6 +6,7 @@ use rdkafka::{
     types::RDKafkaErrorCode,
 };
 use serde::Deserialize;
+use std::time::Duration;
 use utoipa::ToSchema;
 
 mod input;
@@ -68,6 +69,9 @@ impl From<KafkaLogLevel> for RDKafkaLogLevel {
     }
 }
 
+/// Timeout of Kafka transaction operations in exactly-once mode.
+const TRANSACTION_TIMEOUT: Duration = Duration::from_secs(10);
+
 /// If `e` is an error of type `RDKafkaErrorCode::Fatal`, replace
 /// it with the result of calling `client.fatal_error()` (which
 /// should return the actual cause of the failure).  Otherwise,
@@ -1,8 +1,8 @@
-use super::KafkaLogLevel;
-use crate::{OutputEndpoint, OutputTransport};
+use super::{input::KafkaInputPosition, KafkaLogLevel, TRANSACTION_TIMEOUT};
+use crate::{InputPositions, OutputEndpoint, OutputTransport, Step};
 use anyhow::{Error as AnyError, Result as AnyResult};
 use crossbeam::sync::{Parker, Unparker};
-use log::debug;
+use log::{debug, error};
 use rdkafka::{
     config::{FromClientConfigAndContext, RDKafkaLogLevel},
     producer::{BaseRecord, DeliveryResult, Producer, ProducerContext, ThreadedProducer},
@@ -86,6 +86,23 @@ pub struct KafkaOutputConfig {
     /// Defaults to 1000.
     #[serde(default = "default_max_inflight_messages")]
     pub max_inflight_messages: u32,
+
+    /// Enable exactly-once delivery.
+    ///
+    /// When `true`, the outputs of each step of the circuit are written in a
+    /// Kafka transaction, which also commits the offsets consumed by the step
+    /// from Kafka input endpoints in exactly-once mode.  Consumers of the
+    /// output topic must use the `read_committed` isolation level to only
+    /// observe committed outputs.
+    ///
+    /// Requires the `transactional.id` option, which must remain the same
+    /// across pipeline restarts.  Input offsets should be committed by a
+    /// single output endpoint in exactly-once mode per pipeline: if several
+    /// endpoints commit them, a restarted pipeline resumes from the offsets
+    /// of the endpoint that committed last, and other endpoints may miss
+    /// outputs of the steps they had not committed.
+    #[serde(default)]
+    pub exactly_once: bool,
 }
 
 impl KafkaOutputConfig {
@@ -103,6 +120,12 @@ impl KafkaOutputConfig {
             "bootstrap.servers",
             &env::var("REDPANDA_BROKERS").unwrap_or_else(|_| "localhost".to_string()),
         );
+
+        if self.exactly_once && !self.kafka_options.contains_key("transactional.id") {
+            return Err(AnyError::msg(
+                "'transactional.id' must be specified in exactly-once mode",
+            ));
+        }
         Ok(())
     }
 }
@@ -138,6 +161,17 @@ unacknowledged messages reaches this limit, sending of a new message
 blocks until additional acknowledgements arrive from the broker.
 
 Defaults to 1000."#)),
+                )
+                .property(
+                    "exactly_once",
+                    ObjectBuilder::new()
+                        .schema_type(SchemaType::Boolean)
+                        .description(Some(r#"Enable exactly-once delivery.
+
+When `true`, the outputs of each step of the circuit are written in a
+Kafka transaction, which also commits the offsets consumed by the step
+from Kafka input endpoints in exactly-once mode.  Requires the
+`transactional.id` option."#)),
                 )
                 .additional_properties(Some(
                         ObjectBuilder::new()
@@ -198,6 +232,7 @@ struct KafkaOutputEndpoint {
     topic: String,
     max_inflight_messages: u32,
     parker: Parker,
+    exactly_once: bool,
 }
 
 impl KafkaOutputEndpoint {
@@ -227,13 +262,37 @@ impl KafkaOutputEndpoint {
         // Create Kafka producer.
         let kafka_producer = ThreadedProducer::from_config_and_context(&client_config, context)?;
 
+        // Fence off previous instances of the producer with the same
+        // `transactional.id` and abort their incomplete transactions.
+        if config.exactly_once {
+            kafka_producer.init_transactions(TRANSACTION_TIMEOUT)?;
+        }
+
         Ok(Self {
             kafka_producer,
             topic: config.topic,
             max_inflight_messages: config.max_inflight_messages,
             parker,
+            exactly_once: config.exactly_once,
         })
     }
+
+    /// Commit the current transaction along with input offsets.
+    fn commit_transaction(&self, input_positions: &InputPositions) -> AnyResult<()> {
+        for position in input_positions.values() {
+            if let Some(position) = position.downcast_ref::<KafkaInputPosition>() {
+                let (offsets, group_metadata) = position.offsets_to_commit()?;
+                self.kafka_producer.send_offsets_to_transaction(
+                    &offsets,
+                    &group_metadata,
+                    TRANSACTION_TIMEOUT,
+                )?;
+            }
+        }
+        self.kafka_producer
+            .commit_transaction(TRANSACTION_TIMEOUT)?;
+        Ok(())
+    }
 }
 
 impl OutputEndpoint for KafkaOutputEndpoint {
@@ -256,4 +315,26 @@ impl OutputEndpoint for KafkaOutputEndpoint {
             .map_err(|(err, _record)| err)?;
         Ok(())
     }
+
+    fn batch_start(&mut self, _step: Step) -> AnyResult<()> {
+        if self.exactly_once {
+            self.kafka_producer.begin_transaction()?;
+        }
+        Ok(())
+    }
+
+    fn batch_end(&mut self, step: Step, input_positions: &InputPositions) -> AnyResult<()> {
+        if !self.exactly_once {
+            return Ok(());
+        }
+
+        self.commit_transaction(input_positions).map_err(|e| {
+            if let Err(abort_error) = self.kafka_producer.abort_transaction(TRANSACTION_TIMEOUT) {
+                error!("failed to abort Kafka transaction: {abort_error}");
+            }
+            AnyError::msg(format!(
+                "failed to commit Kafka transaction for step {step}: {e}"
+            ))
+        })
+    }
 }
@@ -4,11 +4,15 @@ use crate::{
         kafka::{BufferConsumer, KafkaResources, TestProducer},
         mock_input_pipeline, test_circuit, wait, MockDeZSet, TestStruct, TEST_LOGGER,
     },
-    Controller, PipelineConfig,
+    Catalog, Controller, PipelineConfig,
 };
+use dbsp::{circuit::CircuitConfig, Runtime};
 use log::LevelFilter;
 use proptest::prelude::*;
-use std::{thread::sleep, time::Duration};
+use std::{
+    thread::sleep,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 /// Wait to receive all records in `data` in the same order.
 fn wait_for_output_ordered(zset: &MockDeZSet<TestStruct>, data: &[Vec<TestStruct>]) {
@@ -180,7 +184,7 @@ outputs:
 "#;
 
         println!("Creating circuit");
-        let (circuit, catalog) = test_circuit(4);
+        let (circuit, catalog) = test_circuit(CircuitConfig::from(4));
 
         println!("Starting controller");
         let config: PipelineConfig = serde_yaml::from_str(config_str).unwrap();
@@ -213,3 +217,160 @@ outputs:
         drop(kafka_resources);
     }
 }
+
+/// Generate a batch of records with ids in `ids`.
+fn test_batch(ids: std::ops::Range<u32>) -> Vec<TestStruct> {
+    ids.map(|id| TestStruct {
+        id,
+        b: id % 2 == 0,
+        i: Some(id as i64),
+        s: format!("record {id}"),
+    })
+    .collect()
+}
+
+#[test]
+fn test_kafka_exactly_once() {
+    let _ = log::set_logger(&TEST_LOGGER);
+    log::set_max_level(LevelFilter::Debug);
+
+    let kafka_resources = KafkaResources::create_topics(&[
+        ("exactly_once_test_input_topic", 1),
+        ("exactly_once_test_output_topic", 1),
+    ]);
+
+    // Committed offsets are stored by the consumer group, which must be the
+    // same across restarts, but fresh for each test run.
+    let group_id = format!(
+        "exactly_once_test_group_{}",
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis()
+    );
+
+    let config_str = format!(
+        r#"
+name: test
+inputs:
+    test_input1:
+        stream: test_input1
+        transport:
+            name: kafka
+            config:
+                bootstrap.servers: "localhost"
+                auto.offset.reset: "earliest"
+                group.id: "{group_id}"
+                topics: [exactly_once_test_input_topic]
+                exactly_once: true
+                log_level: debug
+        format:
+            name: csv
+outputs:
+    test_output1:
+        stream: test_output1
+        transport:
+            name: kafka
+            config:
+                bootstrap.servers: "localhost"
+                topic: exactly_once_test_output_topic
+                transactional.id: "{group_id}"
+                exactly_once: true
+        format:
+            name: csv
+"#
+    );
+    let config: PipelineConfig = serde_yaml::from_str(&config_str).unwrap();
+
+    let buffer_consumer = BufferConsumer::new("exactly_once_test_output_topic");
+    let producer = TestProducer::new();
+
+    let data1 = vec![test_batch(0..100)];
+    let data2 = vec![test_batch(100..200)];
+
+    // Process `data1`.
+    let (circuit, catalog) = test_circuit(CircuitConfig::from(4));
+    let controller = Controller::with_config(
+        circuit,
+        catalog,
+        &config,
+        Box::new(|e| panic!("error: {e}")),
+    )
+    .unwrap();
+    producer.send_to_topic(&data1, "exactly_once_test_input_topic");
+    controller.start();
+    buffer_consumer.wait_for_output_unordered(&data1);
+    controller.stop().unwrap();
+
+    // Restart the pipeline.  It must resume after the last committed step,
+    // without reprocessing `data1`.
+    producer.send_to_topic(&data2, "exactly_once_test_input_topic");
+    let (circuit, catalog) = test_circuit(CircuitConfig::from(4));
+    let controller = Controller::with_config(
+        circuit,
+        catalog,
+        &config,
+        Box::new(|e| panic!("error: {e}")),
+    )
+    .unwrap();
+    controller.start();
+
+    let all_data = vec![test_batch(0..200)];
+    buffer_consumer.wait_for_output_unordered(&all_data);
+    sleep(Duration::from_millis(1000));
+    assert_eq!(buffer_consumer.len(), 200);
+
+    drop(buffer_consumer);
+    controller.stop().unwrap();
+    sleep(Duration::from_millis(100));
+
+    println!("Delete Kafka resources");
+    drop(kafka_resources);
+}
+
+#[test]
+fn test_kafka_exactly_once_stateful_circuit() {
+    let kafka_resources =
+        KafkaResources::create_topics(&[("exactly_once_stateful_test_input_topic", 1)]);
+
+    let config_str = r#"
+name: test
+inputs:
+    test_input1:
+        stream: test_input1
+        transport:
+            name: kafka
+            config:
+                bootstrap.servers: "localhost"
+                auto.offset.reset: "earliest"
+                group.id: "exactly_once_stateful_test_group"
+                topics: [exactly_once_stateful_test_input_topic]
+                exactly_once: true
+        format:
+            name: csv
+"#;
+    let config: PipelineConfig = serde_yaml::from_str(config_str).unwrap();
+
+    // The integral carries state from one step to the next, which a
+    // restarted pipeline would lose.
+    let (circuit, (input, output)) = Runtime::init_circuit(1, |circuit| {
+        let (input, hinput) = circuit.add_input_zset::<TestStruct, i32>();
+        (hinput, input.integrate().output())
+    })
+    .unwrap();
+    let mut catalog = Catalog::new();
+    catalog.register_input_zset_handle("test_input1", input);
+    catalog.register_output_batch_handle("test_output1", output);
+
+    let error = Controller::with_config(
+        circuit,
+        catalog,
+        &config,
+        Box::new(|e| panic!("error: {e}")),
+    )
+    .err()
+    .unwrap();
+    assert!(error.to_string().contains("exactly-once"), "{error}");
+
+    drop(kafka_resources);
+}
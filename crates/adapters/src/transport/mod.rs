@@ -19,25 +19,44 @@
 //!   * `file`, for input from a file via [`FileInputTransport`] or output to a
 //!     file via [`FileOutputTransport`].
 //!
-//!   * `http`, for input from a websocket via [`HttpInputTransport`] or output
-//!     to a websocket via [`HttpOutputTransport`], if the `server` feature is
+//!   * `http`, for input from a websocket or POST requests via
+//!     [`HttpInputTransport`] or output to a websocket or a streaming HTTP
+//!     response via [`HttpOutputTransport`], if the `server` feature is
 //!     enabled.
 //!
 //!   * `kafka`, for input from [Kafka](https://kafka.apache.org/) via
 //!     [`KafkaInputTransport`] or output to Kafka via [`KafkaOutputTransport`],
 //!     if the `with-kafka` feature is enabled.
 //!
+//!   * `postgres`, for output to a PostgreSQL table via
+//!     [`PostgresOutputTransport`], if the `with-postgres` feature is enabled.
+//!
+//!   * `s3`, for input from an S3-compatible object store via
+//!     [`S3InputTransport`] or output to an object store via
+//!     [`S3OutputTransport`], if the `with-s3` feature is enabled.
+//!
+//! Additional transports implemented outside of this crate can be registered
+//! at runtime using [`register_transport`](`InputTransport::register_transport`)
+//! before creating a [`Controller`](`crate::Controller`) that uses them:
+//!
+//! ```ignore
+//! <dyn InputTransport>::register_transport(Box::new(MyInputTransport))?;
+//! ```
+//!
 //! To obtain a transport and create an endpoint with it:
 //!
 //! ```ignore
 //! let transport = <dyn InputTransport>::get_transport(transport_name).unwrap();
 //! let endpoint = transport.new_endpoint(endpoint_name, &config, consumer);
 //! ```
+use crate::registry::register;
 use anyhow::{Error as AnyError, Result as AnyResult};
 use once_cell::sync::Lazy;
 use serde_yaml::Value as YamlValue;
+use std::any::Any;
 use std::borrow::Cow;
 use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
 
 mod file;
 
@@ -47,57 +66,82 @@ mod http;
 #[cfg(feature = "with-kafka")]
 mod kafka;
 
-pub use file::{FileInputConfig, FileInputTransport, FileOutputConfig, FileOutputTransport};
+#[cfg(feature = "with-postgres")]
+mod postgres;
+
+#[cfg(feature = "with-s3")]
+mod s3;
+
+pub use file::{
+    FileAfterRead, FileInputConfig, FileInputTransport, FileOutputConfig, FileOutputTransport,
+};
 
 #[cfg(feature = "server")]
 pub use http::{HttpInputTransport, HttpOutputTransport};
 
+#[cfg(feature = "server")]
+pub(crate) use http::HttpInputError;
+
 #[cfg(feature = "with-kafka")]
 pub use kafka::{
     KafkaInputConfig, KafkaInputTransport, KafkaLogLevel, KafkaOutputConfig, KafkaOutputTransport,
 };
 
-/// Static map of supported input transports.
-// TODO: support for registering new transports at runtime in order to allow
-// external crates to implement new transports.
-static INPUT_TRANSPORT: Lazy<BTreeMap<&'static str, Box<dyn InputTransport>>> = Lazy::new(|| {
-    BTreeMap::from([
-        (
-            "file",
-            Box::new(FileInputTransport) as Box<dyn InputTransport>,
-        ),
-        #[cfg(feature = "server")]
-        (
-            "http",
-            Box::new(HttpInputTransport) as Box<dyn InputTransport>,
-        ),
-        #[cfg(feature = "with-kafka")]
-        (
-            "kafka",
-            Box::new(KafkaInputTransport) as Box<dyn InputTransport>,
-        ),
-    ])
-});
-
-/// Static map of supported output transports.
-static OUTPUT_TRANSPORT: Lazy<BTreeMap<&'static str, Box<dyn OutputTransport>>> = Lazy::new(|| {
-    BTreeMap::from([
-        (
-            "file",
-            Box::new(FileOutputTransport) as Box<dyn OutputTransport>,
-        ),
-        #[cfg(feature = "server")]
-        (
-            "http",
-            Box::new(HttpOutputTransport) as Box<dyn OutputTransport>,
-        ),
-        #[cfg(feature = "with-kafka")]
-        (
-            "kafka",
-            Box::new(KafkaOutputTransport) as Box<dyn OutputTransport>,
-        ),
-    ])
-});
+#[cfg(feature = "with-postgres")]
+pub use self::postgres::{PostgresOutputConfig, PostgresOutputTransport};
+
+#[cfg(feature = "with-s3")]
+pub use self::s3::{
+    S3Connection, S3InputConfig, S3InputTransport, S3OutputConfig, S3OutputTransport,
+};
+
+/// Registry of supported input transports.
+///
+/// Initialized with the transports built into this crate; additional
+/// transports can be added with [`InputTransport::register_transport`].
+static INPUT_TRANSPORT: Lazy<RwLock<BTreeMap<String, &'static dyn InputTransport>>> =
+    Lazy::new(|| {
+        let transports: Vec<&'static dyn InputTransport> = vec![
+            &FileInputTransport,
+            #[cfg(feature = "server")]
+            &HttpInputTransport,
+            #[cfg(feature = "with-kafka")]
+            &KafkaInputTransport,
+            #[cfg(feature = "with-s3")]
+            &S3InputTransport,
+        ];
+        RwLock::new(
+            transports
+                .into_iter()
+                .map(|transport| (transport.name().into_owned(), transport))
+                .collect(),
+        )
+    });
+
+/// Registry of supported output transports.
+///
+/// Initialized with the transports built into this crate; additional
+/// transports can be added with [`OutputTransport::register_transport`].
+static OUTPUT_TRANSPORT: Lazy<RwLock<BTreeMap<String, &'static dyn OutputTransport>>> =
+    Lazy::new(|| {
+        let transports: Vec<&'static dyn OutputTransport> = vec![
+            &FileOutputTransport,
+            #[cfg(feature = "server")]
+            &HttpOutputTransport,
+            #[cfg(feature = "with-kafka")]
+            &KafkaOutputTransport,
+            #[cfg(feature = "with-postgres")]
+            &PostgresOutputTransport,
+            #[cfg(feature = "with-s3")]
+            &S3OutputTransport,
+        ];
+        RwLock::new(
+            transports
+                .into_iter()
+                .map(|transport| (transport.name().into_owned(), transport))
+                .collect(),
+        )
+    });
 
 /// Trait that represents a specific data transport.
 ///
@@ -138,7 +182,21 @@ impl dyn InputTransport {
     /// Lookup input transport by `name`, which should be e.g. `file` for a file
     /// transport.
     pub fn get_transport(name: &str) -> Option<&'static dyn InputTransport> {
-        INPUT_TRANSPORT.get(name).map(|f| &**f)
+        INPUT_TRANSPORT.read().unwrap().get(name).copied()
+    }
+
+    /// Make `transport` available to [`get_transport`](`Self::get_transport`)
+    /// under the name returned by its [`name`](`InputTransport::name`) method.
+    ///
+    /// Allows crates that depend on this one to implement new transports.
+    /// Transports must be registered before creating the controller that uses
+    /// them.
+    ///
+    /// # Errors
+    ///
+    /// Fails if a transport with the same name is already registered.
+    pub fn register_transport(transport: Box<dyn InputTransport>) -> AnyResult<()> {
+        register(&INPUT_TRANSPORT, "transport", transport.name(), transport)
     }
 }
 
@@ -165,6 +223,13 @@ pub trait InputEndpoint: Send {
     /// data buffers may be pushed downstream before the endpoint gets
     /// disconnected.
     fn disconnect(&self);
+
+    /// Returns `true` if the endpoint is configured for exactly-once
+    /// processing, i.e., a restarted pipeline resumes reading from the
+    /// positions it reports via [`InputConsumer::input_with_position`].
+    fn is_exactly_once(&self) -> bool {
+        false
+    }
 }
 
 /// Input stream consumer.
@@ -176,6 +241,22 @@ pub trait InputConsumer: Send {
     /// Push a chunk of data to the consumer.
     fn input(&mut self, data: &[u8]);
 
+    /// Push a chunk of data to the consumer along with the position of the
+    /// endpoint in the input stream after this chunk.
+    ///
+    /// Used by transports that support exactly-once processing.  The consumer
+    /// processes `data` like [`input`](`Self::input`) and records `position`
+    /// atomically with respect to the steps of the circuit, so that each step
+    /// is labeled with the exact input positions it has consumed.  These
+    /// positions are passed to output endpoints along with the outputs of the
+    /// step (see [`OutputEndpoint::batch_end`]).
+    ///
+    /// The default implementation ignores `position`.
+    fn input_with_position(&mut self, data: &[u8], position: InputPosition) {
+        let _ = position;
+        self.input(data);
+    }
+
     /// Endpoint failed.
     ///
     /// Endpoint failed; no more data will be received from this endpoint.
@@ -186,6 +267,16 @@ pub trait InputConsumer: Send {
     /// No more data will be received from the endpoint.
     fn eoi(&mut self);
 
+    /// End of a self-contained fragment of the input stream, e.g., a file
+    /// read by an endpoint that ingests a directory of files.
+    ///
+    /// The consumer completes parsing the data received so far, as if it
+    /// were at the end of input, and parses subsequent data independently
+    /// from the preceding fragment, e.g., expecting a new header or file
+    /// magic.  Unlike [`eoi`](`Self::eoi`), more data can be received after
+    /// this notification.
+    fn end_of_fragment(&mut self);
+
     /// Create a new consumer instance.
     ///
     /// Used by multithreaded transport endpoints to create multiple parallel
@@ -238,10 +329,160 @@ pub trait OutputTransport: Send + Sync {
 impl dyn OutputTransport {
     /// Lookup output transport by name.
     pub fn get_transport(name: &str) -> Option<&'static dyn OutputTransport> {
-        OUTPUT_TRANSPORT.get(name).map(|f| &**f)
+        OUTPUT_TRANSPORT.read().unwrap().get(name).copied()
+    }
+
+    /// Make `transport` available to [`get_transport`](`Self::get_transport`)
+    /// under the name returned by its [`name`](`OutputTransport::name`)
+    /// method.
+    ///
+    /// See [`InputTransport::register_transport`].
+    pub fn register_transport(transport: Box<dyn OutputTransport>) -> AnyResult<()> {
+        register(&OUTPUT_TRANSPORT, "transport", transport.name(), transport)
     }
 }
 
+/// Opaque position of an input endpoint in its input stream, e.g., the Kafka
+/// offsets consumed by the endpoint.
+///
+/// The position is only interpreted by the transport that created it and by
+/// output transports that commit it together with their outputs, which
+/// downcast it to the transport-specific type.
+pub type InputPosition = Arc<dyn Any + Send + Sync>;
+
+/// Positions of all input endpoints that report them, indexed by endpoint
+/// name.
+pub type InputPositions = BTreeMap<String, InputPosition>;
+
+/// Sequence number of a step of the circuit.
+pub type Step = u64;
+
 pub trait OutputEndpoint: Send {
     fn push_buffer(&mut self, buffer: &[u8]) -> AnyResult<()>;
+
+    /// Notifies the endpoint that the following buffers contain outputs of
+    /// step `step` of the circuit.
+    ///
+    /// Each step is bracketed by `batch_start` and
+    /// [`batch_end`](`Self::batch_end`) calls, even if it produced no outputs
+    /// for this endpoint.  The default implementation does nothing.
+    fn batch_start(&mut self, step: Step) -> AnyResult<()> {
+        let _ = step;
+        Ok(())
+    }
+
+    /// Notifies the endpoint that all outputs of step `step` have been pushed
+    /// to it.
+    ///
+    /// `input_positions` contains the positions of input endpoints that
+    /// report them (see [`InputConsumer::input_with_position`]), reached
+    /// before the step.  A transport that supports exactly-once delivery
+    /// commits the outputs of the step atomically with these positions, so
+    /// that a restarted pipeline resumes from the last step whose outputs
+    /// were fully emitted.  An error returned by this method is fatal, since
+    /// the outputs of the step may have been lost.
+    ///
+    /// The default implementation does nothing.
+    fn batch_end(&mut self, step: Step, input_positions: &InputPositions) -> AnyResult<()> {
+        let _ = (step, input_positions);
+        Ok(())
+    }
+
+    /// Returns `true` if the endpoint can deliver snapshots of the output
+    /// stream.
+    ///
+    /// For such endpoints the controller maintains the integrated contents of
+    /// the stream and checks
+    /// [`snapshot_requested`](`Self::snapshot_requested`) between steps and
+    /// whenever the endpoint invokes the callback passed to
+    /// [`set_snapshot_notifier`](`Self::set_snapshot_notifier`).  This method
+    /// is invoked once, when the endpoint is connected.  The default
+    /// implementation returns `false`.
+    fn supports_snapshots(&self) -> bool {
+        false
+    }
+
+    /// Sets the callback that the endpoint must invoke after it starts
+    /// returning `true` from [`snapshot_requested`](`Self::snapshot_requested`),
+    /// so that the controller serves the request without waiting for the
+    /// next step.
+    ///
+    /// Only invoked for endpoints that
+    /// [`support snapshots`](`Self::supports_snapshots`).  The default
+    /// implementation drops the callback.
+    fn set_snapshot_notifier(&mut self, notifier: Box<dyn Fn() + Send + Sync>) {
+        let _ = notifier;
+    }
+
+    /// Returns `true` if the endpoint wants to receive a snapshot of the
+    /// output stream.
+    ///
+    /// The controller responds to the request before sending the outputs of
+    /// the next step by calling [`snapshot_start`](`Self::snapshot_start`),
+    /// pushing the current contents of the stream as a sequence of
+    /// insertions, and calling [`snapshot_end`](`Self::snapshot_end`).  The
+    /// default implementation returns `false`.
+    fn snapshot_requested(&mut self) -> bool {
+        false
+    }
+
+    /// Notifies the endpoint that the following buffers contain a snapshot of
+    /// the output stream.  The default implementation does nothing.
+    fn snapshot_start(&mut self) -> AnyResult<()> {
+        Ok(())
+    }
+
+    /// Notifies the endpoint that the entire snapshot has been pushed to it.
+    /// Buffers pushed after this call contain changes to the snapshot
+    /// produced by subsequent steps.  The default implementation does
+    /// nothing.
+    fn snapshot_end(&mut self) -> AnyResult<()> {
+        Ok(())
+    }
+
+    /// Notifies the endpoint that it has been disconnected from the pipeline.
+    /// This is the last method invoked before the endpoint object is dropped.
+    /// The default implementation does nothing.
+    fn disconnect(&mut self) {}
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{FileOutputTransport, OutputEndpoint, OutputTransport};
+    use anyhow::{Error as AnyError, Result as AnyResult};
+    use serde_yaml::Value as YamlValue;
+    use std::borrow::Cow;
+
+    /// Output transport implemented outside of the built-in set, which
+    /// delegates to the `file` transport.
+    struct TestOutputTransport;
+
+    impl OutputTransport for TestOutputTransport {
+        fn name(&self) -> Cow<'static, str> {
+            Cow::Borrowed("test_registered_output")
+        }
+
+        fn new_endpoint(
+            &self,
+            name: &str,
+            config: &YamlValue,
+            async_error_callback: Box<dyn Fn(bool, AnyError) + Send + Sync>,
+        ) -> AnyResult<Box<dyn OutputEndpoint>> {
+            FileOutputTransport.new_endpoint(name, config, async_error_callback)
+        }
+    }
+
+    #[test]
+    fn test_register_transport() {
+        assert!(<dyn OutputTransport>::get_transport("test_registered_output").is_none());
+
+        <dyn OutputTransport>::register_transport(Box::new(TestOutputTransport)).unwrap();
+        let transport = <dyn OutputTransport>::get_transport("test_registered_output").unwrap();
+        assert_eq!(transport.name(), "test_registered_output");
+
+        // Names must be unique, including the names of built-in transports.
+        assert!(<dyn OutputTransport>::register_transport(Box::new(TestOutputTransport)).is_err());
+        assert!(<dyn OutputTransport>::register_transport(Box::new(FileOutputTransport)).is_err());
+        assert!(<dyn OutputTransport>::get_transport("file").is_some());
+    }
 }
@@ -0,0 +1,970 @@
+//! Transports for S3-compatible object stores.
+//!
+//! The input transport reads all objects under a key prefix, optionally
+//! polling for new objects.  The output transport writes a sequence of
+//! objects, starting a new object when the current one exceeds a size or
+//! age threshold.
+//!
+//! Both transports work with AWS S3 as well as any S3-compatible service,
+//! such as MinIO, specified via the `endpoint_url` setting.
+
+use super::{InputConsumer, InputEndpoint, InputTransport, OutputEndpoint, OutputTransport};
+use crate::PipelineState;
+use anyhow::{Error as AnyError, Result as AnyResult};
+use crossbeam::sync::{Parker, Unparker};
+use log::error;
+use num_traits::FromPrimitive;
+use rand::random;
+use s3::{creds::Credentials, Bucket, Region};
+use serde::Deserialize;
+use serde_yaml::Value as YamlValue;
+use std::{
+    borrow::Cow,
+    cmp::min,
+    collections::{BTreeSet, VecDeque},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex, Weak,
+    },
+    thread::{sleep, spawn},
+    time::{Duration, Instant},
+};
+use utoipa::ToSchema;
+
+const SLEEP_MS: u64 = 200;
+
+/// Default size of a ranged read of an input object.
+const DEFAULT_BUFFER_SIZE_BYTES: usize = 8 * 1024 * 1024;
+
+/// Connection settings shared by S3 input and output endpoints.
+#[derive(Clone, Deserialize, ToSchema)]
+pub struct S3Connection {
+    /// Bucket name.
+    pub bucket: String,
+
+    /// Region, e.g., `us-east-1`.
+    #[serde(default = "default_region")]
+    pub region: String,
+
+    /// URL of an S3-compatible service, e.g., `http://localhost:9000`.
+    ///
+    /// When specified, objects are addressed using path-style URLs
+    /// (`<endpoint_url>/<bucket>/<key>`), which is what most self-hosted
+    /// services expect.  When not specified, the AWS endpoint for `region`
+    /// is used.
+    pub endpoint_url: Option<String>,
+
+    /// Access key id.
+    ///
+    /// When neither `access_key_id` nor `secret_access_key` is specified,
+    /// credentials are read from the standard `AWS_ACCESS_KEY_ID` and
+    /// `AWS_SECRET_ACCESS_KEY` environment variables, the AWS profile, or
+    /// the instance metadata service.
+    pub access_key_id: Option<String>,
+
+    /// Secret access key.
+    pub secret_access_key: Option<String>,
+}
+
+fn default_region() -> String {
+    "us-east-1".to_string()
+}
+
+impl S3Connection {
+    fn bucket(&self) -> AnyResult<Bucket> {
+        let credentials = if self.access_key_id.is_some() || self.secret_access_key.is_some() {
+            Credentials::new(
+                self.access_key_id.as_deref(),
+                self.secret_access_key.as_deref(),
+                None,
+                None,
+                None,
+            )
+        } else {
+            Credentials::default()
+        }
+        .map_err(|e| AnyError::msg(format!("Failed to obtain S3 credentials: {e}")))?;
+
+        Ok(match &self.endpoint_url {
+            Some(endpoint_url) => {
+                let region = Region::Custom {
+                    region: self.region.clone(),
+                    endpoint: endpoint_url.trim_end_matches('/').to_string(),
+                };
+                Bucket::new(&self.bucket, region, credentials)?.with_path_style()
+            }
+            None => Bucket::new(&self.bucket, self.region.parse()?, credentials)?,
+        })
+    }
+}
+
+/// Returns an error unless `status` is a 2xx HTTP status code.
+fn check_status(status: u16, body: &[u8], what: impl FnOnce() -> String) -> AnyResult<()> {
+    if (200..300).contains(&status) {
+        Ok(())
+    } else {
+        Err(AnyError::msg(format!(
+            "{} failed with HTTP status {status}: {}",
+            what(),
+            String::from_utf8_lossy(body)
+        )))
+    }
+}
+
+/// [`InputTransport`] implementation that reads objects from an S3-compatible
+/// object store.
+///
+/// The input transport factory gives this transport the name `s3`.
+pub struct S3InputTransport;
+
+impl InputTransport for S3InputTransport {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("s3")
+    }
+
+    /// Creates a new [`InputEndpoint`] for reading objects, interpreting
+    /// `config` as an [`S3InputConfig`].
+    ///
+    /// See [`InputTransport::new_endpoint()`] for more information.
+    fn new_endpoint(
+        &self,
+        _name: &str,
+        config: &YamlValue,
+        consumer: Box<dyn InputConsumer>,
+    ) -> AnyResult<Box<dyn InputEndpoint>> {
+        let config = S3InputConfig::deserialize(config)?;
+        let mut ep = S3InputEndpoint::new(config)?;
+        ep.connect(consumer);
+        Ok(Box::new(ep))
+    }
+}
+
+/// Configuration for reading objects with [`S3InputTransport`].
+#[derive(Deserialize, ToSchema)]
+pub struct S3InputConfig {
+    #[serde(flatten)]
+    #[schema(inline)]
+    pub connection: S3Connection,
+
+    /// Read all objects whose keys start with this prefix, e.g., `archive/`.
+    ///
+    /// Objects are read one by one, in lexicographic order of their keys.
+    /// Each object is parsed independently, e.g., a CSV header or a Parquet
+    /// footer is expected in every object.
+    #[serde(default)]
+    pub prefix: String,
+
+    /// Size of a single ranged read of an object.
+    ///
+    /// Default: 8 MiB.
+    pub buffer_size_bytes: Option<usize>,
+
+    /// Keep polling for new objects.
+    ///
+    /// When `false`, the endpoint outputs an [`eoi`](`InputConsumer::eoi`)
+    /// message and stops after reading all objects that exist under `prefix`
+    /// when it reaches the end of the last object.  When `true`, the endpoint
+    /// keeps listing the prefix every `poll_interval_ms` milliseconds and
+    /// reads any new objects.  Objects are assumed to be immutable: changes
+    /// to an object after it has been read are ignored.
+    #[serde(default)]
+    pub follow: bool,
+
+    /// Interval between listings of `prefix` in follow mode.
+    #[serde(default = "default_poll_interval_ms")]
+    pub poll_interval_ms: u64,
+}
+
+fn default_poll_interval_ms() -> u64 {
+    10_000
+}
+
+struct S3InputEndpoint {
+    config: Arc<S3InputConfig>,
+    bucket: Arc<Bucket>,
+    status: Arc<AtomicU32>,
+    unparker: Option<Unparker>,
+}
+
+impl S3InputEndpoint {
+    fn new(config: S3InputConfig) -> AnyResult<Self> {
+        let bucket = config.connection.bucket()?;
+
+        Ok(Self {
+            config: Arc::new(config),
+            bucket: Arc::new(bucket),
+            status: Arc::new(AtomicU32::new(PipelineState::Paused as u32)),
+            unparker: None,
+        })
+    }
+
+    fn connect(&mut self, consumer: Box<dyn InputConsumer>) {
+        let parker = Parker::new();
+        self.unparker = Some(parker.unparker().clone());
+        let config = self.config.clone();
+        let bucket = self.bucket.clone();
+        let status = self.status.clone();
+        let _worker = spawn(move || Self::worker_thread(config, bucket, consumer, parker, status));
+    }
+
+    fn unpark(&self) {
+        if let Some(unparker) = &self.unparker {
+            unparker.unpark();
+        }
+    }
+
+    /// List objects under `prefix` in lexicographic order of their keys.
+    ///
+    /// Returns `(key, size)` pairs.  Keys that end in `/`, which by
+    /// convention denote directories, are skipped.
+    fn list(config: &S3InputConfig, bucket: &Bucket) -> AnyResult<Vec<(String, u64)>> {
+        let mut objects: Vec<(String, u64)> = bucket
+            .list(config.prefix.clone(), None)
+            .map_err(|e| {
+                AnyError::msg(format!(
+                    "Failed to list objects in bucket '{}' under prefix '{}': {e}",
+                    config.connection.bucket, config.prefix
+                ))
+            })?
+            .into_iter()
+            .flat_map(|page| page.contents.into_iter())
+            .filter(|object| !object.key.ends_with('/'))
+            .map(|object| (object.key, object.size))
+            .collect();
+        objects.sort();
+
+        Ok(objects)
+    }
+
+    /// Read bytes `start..end` of object `key`.
+    fn read(bucket: &Bucket, key: &str, start: u64, end: u64) -> AnyResult<Vec<u8>> {
+        let what = || format!("Reading object '{key}' from bucket '{}'", bucket.name());
+        let response = bucket
+            .get_object_range(key, start, Some(end - 1))
+            .map_err(|e| AnyError::msg(format!("{} failed: {e}", what())))?;
+        check_status(response.status_code(), response.bytes(), what)?;
+
+        Ok(response.bytes().to_vec())
+    }
+
+    fn worker_thread(
+        config: Arc<S3InputConfig>,
+        bucket: Arc<Bucket>,
+        mut consumer: Box<dyn InputConsumer>,
+        parker: Parker,
+        status: Arc<AtomicU32>,
+    ) {
+        let buffer_size = match config.buffer_size_bytes {
+            Some(buffer_size) if buffer_size > 0 => buffer_size as u64,
+            _ => DEFAULT_BUFFER_SIZE_BYTES as u64,
+        };
+        let poll_interval = Duration::from_millis(config.poll_interval_ms);
+
+        // Objects that have been read or queued for reading.
+        let mut seen = BTreeSet::new();
+
+        // Objects waiting to be read.
+        let mut queue = VecDeque::new();
+
+        // Time of the last listing of the prefix.
+        let mut last_poll: Option<Instant> = None;
+
+        // Object currently being read: key, size, and the number of bytes read
+        // so far.
+        let mut current: Option<(String, u64, u64)> = None;
+
+        loop {
+            match PipelineState::from_u32(status.load(Ordering::Acquire)) {
+                Some(PipelineState::Paused) => parker.park(),
+                Some(PipelineState::Running) => {
+                    if current.is_none() {
+                        if queue.is_empty()
+                            && last_poll.map_or(true, |last_poll| {
+                                config.follow && last_poll.elapsed() >= poll_interval
+                            })
+                        {
+                            last_poll = Some(Instant::now());
+                            match Self::list(&config, &bucket) {
+                                Ok(objects) => {
+                                    for (key, size) in objects.into_iter() {
+                                        if seen.insert(key.clone()) {
+                                            queue.push_back((key, size));
+                                        }
+                                    }
+                                }
+                                // Retry at the next poll in follow mode.
+                                Err(e) if config.follow => consumer.error(false, e),
+                                Err(e) => {
+                                    consumer.error(true, e);
+                                    return;
+                                }
+                            }
+                        }
+                        match queue.pop_front() {
+                            Some((key, size)) => current = Some((key, size, 0)),
+                            None if config.follow => sleep(Duration::from_millis(SLEEP_MS)),
+                            None => {
+                                consumer.eoi();
+                                return;
+                            }
+                        }
+                        continue;
+                    }
+
+                    let (key, size, offset) = current.as_mut().unwrap();
+                    if *offset < *size {
+                        let end = min(*offset + buffer_size, *size);
+                        match Self::read(&bucket, key, *offset, end) {
+                            Ok(data) if data.is_empty() => {
+                                consumer.error(
+                                    true,
+                                    AnyError::msg(format!(
+                                        "Object '{key}' is shorter than its listed size ({size} bytes)"
+                                    )),
+                                );
+                                return;
+                            }
+                            Ok(data) => {
+                                consumer.input(&data);
+                                *offset += data.len() as u64;
+                            }
+                            Err(e) => {
+                                consumer.error(true, e);
+                                return;
+                            }
+                        }
+                    }
+
+                    if *offset >= *size {
+                        consumer.end_of_fragment();
+                        current = None;
+                    }
+                }
+                Some(PipelineState::Terminated) => return,
+                _ => unreachable!(),
+            }
+        }
+    }
+}
+
+impl InputEndpoint for S3InputEndpoint {
+    fn pause(&self) -> AnyResult<()> {
+        // Notify worker thread via the status flag.  The worker may
+        // send another buffer downstream before the flag takes effect.
+        self.status
+            .store(PipelineState::Paused as u32, Ordering::Release);
+        Ok(())
+    }
+
+    fn start(&self) -> AnyResult<()> {
+        self.status
+            .store(PipelineState::Running as u32, Ordering::Release);
+
+        // Wake up the worker if it's paused.
+        self.unpark();
+        Ok(())
+    }
+
+    fn disconnect(&self) {
+        self.status
+            .store(PipelineState::Terminated as u32, Ordering::Release);
+
+        // Wake up the worker if it's paused.
+        self.unpark();
+    }
+}
+
+impl Drop for S3InputEndpoint {
+    fn drop(&mut self) {
+        self.disconnect();
+    }
+}
+
+/// [`OutputTransport`] implementation that writes objects to an S3-compatible
+/// object store.
+///
+/// The output transport factory gives this transport the name `s3`.
+pub struct S3OutputTransport;
+
+impl OutputTransport for S3OutputTransport {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("s3")
+    }
+
+    /// Creates a new [`OutputEndpoint`] for writing objects, interpreting
+    /// `config` as an [`S3OutputConfig`].
+    ///
+    /// See [`OutputTransport::new_endpoint()`] for more information.
+    fn new_endpoint(
+        &self,
+        name: &str,
+        config: &YamlValue,
+        async_error_callback: Box<dyn Fn(bool, AnyError) + Send + Sync>,
+    ) -> AnyResult<Box<dyn OutputEndpoint>> {
+        let config = S3OutputConfig::deserialize(config)?;
+        let ep = S3OutputEndpoint::new(name, config, async_error_callback)?;
+
+        Ok(Box::new(ep))
+    }
+}
+
+/// Configuration for writing objects with [`S3OutputTransport`].
+#[derive(Deserialize, ToSchema)]
+pub struct S3OutputConfig {
+    #[serde(flatten)]
+    #[schema(inline)]
+    pub connection: S3Connection,
+
+    /// Prefix of object keys, e.g., `archive/orders-`.
+    ///
+    /// Objects are named `<prefix><name>-<timestamp>-<id>-<seq><suffix>`,
+    /// where `name` is the name of the endpoint, `timestamp` is the UTC time
+    /// when the endpoint was created, e.g., `20230601T120000Z`, `id` is a
+    /// random identifier of the endpoint instance, which prevents endpoints
+    /// created within the same second from overwriting each other's objects,
+    /// and `seq` is a zero-padded sequence number, so that lexicographic
+    /// order of keys written by an endpoint matches the order in which they
+    /// were written.
+    #[serde(default)]
+    pub prefix: String,
+
+    /// Suffix of object keys, e.g., `.csv`.
+    #[serde(default)]
+    pub suffix: String,
+
+    /// Upload the current object once it reaches this size.
+    ///
+    /// When `0`, each buffer received from the encoder is uploaded as a
+    /// separate object.  This is required by formats like Parquet, whose
+    /// encoder produces a complete file per buffer.
+    ///
+    /// Default: 64 MiB.
+    #[serde(default = "default_max_object_size_bytes")]
+    pub max_object_size_bytes: usize,
+
+    /// Upload the current object once this many milliseconds have passed
+    /// since the first buffer was written to it, even if it hasn't reached
+    /// `max_object_size_bytes`.
+    ///
+    /// Default: 60 seconds.
+    #[serde(default = "default_max_object_age_ms")]
+    pub max_object_age_ms: u64,
+}
+
+fn default_max_object_size_bytes() -> usize {
+    64 * 1024 * 1024
+}
+
+fn default_max_object_age_ms() -> u64 {
+    60_000
+}
+
+/// Object being assembled by [`S3OutputEndpoint`].
+#[derive(Default)]
+struct S3Object {
+    data: Vec<u8>,
+
+    /// Time when the first buffer was written to the object.
+    created: Option<Instant>,
+
+    /// Sequence number of the next object.
+    seq: u64,
+}
+
+struct S3Writer {
+    config: S3OutputConfig,
+    bucket: Bucket,
+
+    /// Prefix of object keys, including the endpoint name, creation
+    /// timestamp and instance id.
+    key_prefix: String,
+    object: Mutex<S3Object>,
+}
+
+impl S3Writer {
+    /// Upload `data` as object number `seq`.
+    fn upload(&self, seq: u64, data: &[u8]) -> AnyResult<()> {
+        let key = format!("{}{seq:06}{}", self.key_prefix, self.config.suffix);
+        let what = || format!("Writing object '{key}' to bucket '{}'", self.bucket.name());
+        let response = self
+            .bucket
+            .put_object(&key, data)
+            .map_err(|e| AnyError::msg(format!("{} failed: {e}", what())))?;
+        check_status(response.status_code(), response.bytes(), what)
+    }
+
+    /// Upload the current object if it is not empty.
+    ///
+    /// The data is kept in `object` if the upload fails, so that it is
+    /// retried by the next flush.
+    fn flush(&self, object: &mut S3Object) -> AnyResult<()> {
+        if object.data.is_empty() {
+            return Ok(());
+        }
+        self.upload(object.seq, &object.data)?;
+        object.seq += 1;
+        object.data = Vec::new();
+        object.created = None;
+        Ok(())
+    }
+
+    /// Upload the current object if it is older than `max_object_age_ms`.
+    fn flush_expired(&self) -> AnyResult<()> {
+        let mut object = self.object.lock().unwrap();
+        match object.created {
+            Some(created)
+                if created.elapsed() >= Duration::from_millis(self.config.max_object_age_ms) =>
+            {
+                self.flush(&mut object)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+struct S3OutputEndpoint {
+    writer: Arc<S3Writer>,
+}
+
+impl S3OutputEndpoint {
+    fn new(
+        name: &str,
+        config: S3OutputConfig,
+        async_error_callback: Box<dyn Fn(bool, AnyError) + Send + Sync>,
+    ) -> AnyResult<Self> {
+        let bucket = config.connection.bucket()?;
+        let key_prefix = format!(
+            "{}{name}-{}-{:08x}-",
+            config.prefix,
+            chrono::Utc::now().format("%Y%m%dT%H%M%SZ"),
+            random::<u32>()
+        );
+        let age_check_interval =
+            Duration::from_millis(min(config.max_object_age_ms, SLEEP_MS).max(1));
+
+        let writer = Arc::new(S3Writer {
+            config,
+            bucket,
+            key_prefix,
+            object: Mutex::new(S3Object::default()),
+        });
+
+        // Upload objects that exceed the age threshold in the background.  The
+        // thread exits when the endpoint is dropped.
+        let weak_writer = Arc::downgrade(&writer);
+        let _timer = spawn(move || {
+            Self::timer_thread(weak_writer, age_check_interval, async_error_callback)
+        });
+
+        Ok(Self { writer })
+    }
+
+    fn timer_thread(
+        writer: Weak<S3Writer>,
+        interval: Duration,
+        async_error_callback: Box<dyn Fn(bool, AnyError) + Send + Sync>,
+    ) {
+        loop {
+            sleep(interval);
+            match writer.upgrade() {
+                Some(writer) => {
+                    if let Err(e) = writer.flush_expired() {
+                        async_error_callback(false, e);
+                    }
+                }
+                None => return,
+            }
+        }
+    }
+}
+
+impl OutputEndpoint for S3OutputEndpoint {
+    fn push_buffer(&mut self, buffer: &[u8]) -> AnyResult<()> {
+        let writer = &self.writer;
+        let mut object = writer.object.lock().unwrap();
+
+        if writer.config.max_object_size_bytes == 0 {
+            // Upload any data left over from a failed upload first.
+            writer.flush(&mut object)?;
+            writer.upload(object.seq, buffer)?;
+            object.seq += 1;
+            return Ok(());
+        }
+
+        if object.created.is_none() {
+            object.created = Some(Instant::now());
+        }
+        object.data.extend_from_slice(buffer);
+        if object.data.len() >= writer.config.max_object_size_bytes {
+            writer.flush(&mut object)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for S3OutputEndpoint {
+    fn drop(&mut self) {
+        let mut object = self.writer.object.lock().unwrap();
+        if let Err(e) = self.writer.flush(&mut object) {
+            error!("S3 output endpoint failed to upload the last object: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        test::{mock_input_pipeline, wait},
+        OutputTransport,
+    };
+    use serde::{Deserialize, Serialize};
+    use std::{
+        collections::BTreeMap,
+        io::{BufRead, BufReader, Read, Write},
+        net::{TcpListener, TcpStream},
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex,
+        },
+        thread::{sleep, spawn},
+        time::Duration,
+    };
+
+    #[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+    struct TestStruct {
+        s: String,
+        b: bool,
+        i: i64,
+    }
+
+    impl TestStruct {
+        fn new(s: &str, b: bool, i: i64) -> Self {
+            Self {
+                s: s.to_string(),
+                b,
+                i,
+            }
+        }
+    }
+
+    /// In-process stand-in for an S3-compatible service.
+    ///
+    /// Supports path-style `ListObjectsV2`, `GetObject` (including ranged
+    /// reads), and `PutObject` requests against a single bucket, without
+    /// authentication.
+    #[derive(Clone)]
+    struct MockS3 {
+        url: String,
+        objects: Arc<Mutex<BTreeMap<String, Vec<u8>>>>,
+
+        /// Reject PUT requests while set.
+        fail_puts: Arc<AtomicBool>,
+    }
+
+    impl MockS3 {
+        fn start(bucket: &str) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let mock = Self {
+                url: format!("http://{}", listener.local_addr().unwrap()),
+                objects: Arc::new(Mutex::new(BTreeMap::new())),
+                fail_puts: Arc::new(AtomicBool::new(false)),
+            };
+
+            let objects = mock.objects.clone();
+            let fail_puts = mock.fail_puts.clone();
+            let bucket = format!("/{bucket}");
+            spawn(move || {
+                for stream in listener.incoming() {
+                    Self::handle(&bucket, &objects, &fail_puts, stream.unwrap());
+                }
+            });
+
+            mock
+        }
+
+        fn put(&self, key: &str, data: &str) {
+            self.objects
+                .lock()
+                .unwrap()
+                .insert(key.to_string(), data.as_bytes().to_vec());
+        }
+
+        fn objects(&self) -> BTreeMap<String, Vec<u8>> {
+            self.objects.lock().unwrap().clone()
+        }
+
+        fn handle(
+            bucket: &str,
+            objects: &Mutex<BTreeMap<String, Vec<u8>>>,
+            fail_puts: &AtomicBool,
+            stream: TcpStream,
+        ) {
+            let mut reader = BufReader::new(&stream);
+
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut parts = request_line.split_whitespace();
+            let method = parts.next().unwrap_or_default().to_string();
+            let target = parts.next().unwrap_or_default().to_string();
+
+            let mut content_length = 0;
+            let mut range = None;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let line = line.trim_end();
+                if line.is_empty() {
+                    break;
+                }
+                let (name, value) = line.split_once(':').unwrap();
+                match name.to_ascii_lowercase().as_str() {
+                    "content-length" => content_length = value.trim().parse().unwrap(),
+                    "range" => {
+                        let (start, end) = value
+                            .trim()
+                            .trim_start_matches("bytes=")
+                            .split_once('-')
+                            .unwrap();
+                        range = Some((start.parse::<usize>().unwrap(), end.parse::<usize>().ok()));
+                    }
+                    _ => {}
+                }
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+
+            let (path, query) = target.split_once('?').unwrap_or((&target, ""));
+            let key = decode(path.strip_prefix(bucket).unwrap().trim_start_matches('/'));
+            let params: BTreeMap<String, String> = query
+                .split('&')
+                .filter_map(|param| param.split_once('='))
+                .map(|(name, value)| (name.to_string(), decode(value)))
+                .collect();
+
+            let mut objects = objects.lock().unwrap();
+            let (status, response) = match method.as_str() {
+                "GET" if key.is_empty() => {
+                    let prefix = params.get("prefix").cloned().unwrap_or_default();
+                    let contents: String = objects
+                        .iter()
+                        .filter(|(key, _)| key.starts_with(&prefix))
+                        .map(|(key, data)| {
+                            format!(
+                                "<Contents><Key>{key}</Key><LastModified>2023-06-01T00:00:00.000Z</LastModified>\
+                                 <ETag>\"0\"</ETag><Size>{}</Size><StorageClass>STANDARD</StorageClass></Contents>",
+                                data.len()
+                            )
+                        })
+                        .collect();
+                    let list = format!(
+                        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+                         <ListBucketResult xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\">\
+                         <Name>{}</Name><Prefix>{prefix}</Prefix><MaxKeys>1000</MaxKeys>\
+                         <IsTruncated>false</IsTruncated>{contents}</ListBucketResult>",
+                        &bucket[1..]
+                    );
+                    ("200 OK", list.into_bytes())
+                }
+                "GET" => match (objects.get(&key), range) {
+                    (None, _) => ("404 Not Found", Vec::new()),
+                    (Some(data), None) => ("200 OK", data.clone()),
+                    (Some(data), Some((start, end))) => {
+                        let end = end.map_or(data.len(), |end| (end + 1).min(data.len()));
+                        ("206 Partial Content", data[start..end].to_vec())
+                    }
+                },
+                "PUT" if fail_puts.load(Ordering::Acquire) => ("403 Forbidden", Vec::new()),
+                "PUT" => {
+                    objects.insert(key, body);
+                    ("200 OK", Vec::new())
+                }
+                _ => ("405 Method Not Allowed", Vec::new()),
+            };
+            drop(objects);
+
+            let mut stream = &stream;
+            write!(
+                stream,
+                "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                response.len()
+            )
+            .unwrap();
+            stream.write_all(&response).unwrap();
+        }
+    }
+
+    /// Decode a percent-encoded URL component.
+    fn decode(s: &str) -> String {
+        let bytes = s.as_bytes();
+        let mut result = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+        while i < bytes.len() {
+            if bytes[i] == b'%' && i + 2 < bytes.len() {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap();
+                result.push(u8::from_str_radix(hex, 16).unwrap());
+                i += 3;
+            } else {
+                result.push(bytes[i]);
+                i += 1;
+            }
+        }
+        String::from_utf8(result).unwrap()
+    }
+
+    /// Connection settings for `mock`, as Yaml lines indented by `indent`.
+    fn connection_config(mock: &MockS3, indent: &str) -> String {
+        [
+            "bucket: test-bucket".to_string(),
+            format!("endpoint_url: {:?}", mock.url),
+            "access_key_id: test".to_string(),
+            "secret_access_key: test".to_string(),
+        ]
+        .iter()
+        .map(|line| format!("\n{indent}{line}"))
+        .collect()
+    }
+
+    #[test]
+    fn test_s3_input() {
+        let mock = MockS3::start("test-bucket");
+        mock.put("archive/2.csv", "bar,false,-10\n");
+        mock.put("archive/1.csv", "foo,true,10\n");
+        mock.put("other/1.csv", "xxx\n");
+
+        let config_str = format!(
+            r#"
+stream: test_input
+transport:
+    name: s3
+    config:{}
+        prefix: archive/
+        buffer_size_bytes: 5
+        follow: true
+        poll_interval_ms: 100
+format:
+    name: csv
+"#,
+            connection_config(&mock, "        ")
+        );
+
+        let (endpoint, consumer, zset) =
+            mock_input_pipeline::<TestStruct>(serde_yaml::from_str(&config_str).unwrap());
+
+        sleep(Duration::from_millis(10));
+        assert!(zset.state().flushed.is_empty());
+
+        // Objects are read in key order, in small ranged chunks.
+        endpoint.start().unwrap();
+        wait(|| consumer.state().num_fragments == 2, None);
+        assert_eq!(
+            zset.state().flushed,
+            vec![
+                (TestStruct::new("foo", true, 10), true),
+                (TestStruct::new("bar", false, -10), true)
+            ]
+        );
+
+        // New objects are picked up by polling.
+        mock.put("archive/3.csv", "baz,true,0\n");
+        wait(|| zset.state().flushed.len() == 3, None);
+        assert_eq!(
+            zset.state().flushed[2],
+            (TestStruct::new("baz", true, 0), true)
+        );
+        assert!(!consumer.state().eoi);
+
+        endpoint.disconnect();
+    }
+
+    #[test]
+    fn test_s3_input_nofollow() {
+        let mock = MockS3::start("test-bucket");
+        mock.put("1.csv", "foo,true,10\n");
+
+        let config_str = format!(
+            r#"
+stream: test_input
+transport:
+    name: s3
+    config:{}
+format:
+    name: csv
+"#,
+            connection_config(&mock, "        ")
+        );
+
+        let (endpoint, consumer, zset) =
+            mock_input_pipeline::<TestStruct>(serde_yaml::from_str(&config_str).unwrap());
+        endpoint.start().unwrap();
+
+        wait(|| consumer.state().eoi, None);
+        assert_eq!(
+            zset.state().flushed,
+            vec![(TestStruct::new("foo", true, 10), true)]
+        );
+    }
+
+    #[test]
+    fn test_s3_output() {
+        let mock = MockS3::start("test-bucket");
+        let config = serde_yaml::from_str(&format!(
+            "{}\nprefix: out/part-\nsuffix: .csv\nmax_object_size_bytes: 8\nmax_object_age_ms: 300",
+            connection_config(&mock, "")
+        ))
+        .unwrap();
+
+        let mut endpoint = <dyn OutputTransport>::get_transport("s3")
+            .unwrap()
+            .new_endpoint("test_output", &config, Box::new(|_, _| {}))
+            .unwrap();
+
+        // The size threshold is reached by the second buffer.
+        endpoint.push_buffer(b"foo\n").unwrap();
+        endpoint.push_buffer(b"bar\n").unwrap();
+        assert_eq!(mock.objects().len(), 1);
+
+        // The age threshold flushes an object in the background.
+        endpoint.push_buffer(b"baz\n").unwrap();
+        wait(|| mock.objects().len() == 2, None);
+
+        // Data is kept until it is uploaded successfully.
+        mock.fail_puts.store(true, Ordering::Release);
+        endpoint.push_buffer(b"qux\n").unwrap();
+        assert!(endpoint.push_buffer(b"quux\n").is_err());
+        mock.fail_puts.store(false, Ordering::Release);
+        wait(|| mock.objects().len() == 3, None);
+
+        // The last object is flushed when the endpoint is dropped.
+        endpoint.push_buffer(b"corge\n").unwrap();
+        drop(endpoint);
+
+        let objects = mock.objects();
+        let keys: Vec<&String> = objects.keys().collect();
+        assert_eq!(keys.len(), 4);
+        for (seq, key) in keys.iter().enumerate() {
+            assert!(key.starts_with("out/part-test_output-"));
+            assert!(key.ends_with(&format!("-{seq:06}.csv")));
+        }
+        let data: Vec<&[u8]> = objects.values().map(|data| data.as_slice()).collect();
+        assert_eq!(
+            data,
+            vec![&b"foo\nbar\n"[..], b"baz\n", b"qux\nquux\n", b"corge\n"]
+        );
+
+        // Endpoints created at the same time write to different keys.
+        let mut endpoints: Vec<_> = (0..2)
+            .map(|_| {
+                <dyn OutputTransport>::get_transport("s3")
+                    .unwrap()
+                    .new_endpoint("test_output", &config, Box::new(|_, _| {}))
+                    .unwrap()
+            })
+            .collect();
+        for endpoint in endpoints.iter_mut() {
+            endpoint.push_buffer(b"grault\n").unwrap();
+        }
+        drop(endpoints);
+        assert_eq!(mock.objects().len(), 6);
+    }
+}
@@ -40,7 +40,7 @@ use dbsp::{
             ordered::OrderedBuilder,
             Builder as LayerBuilder, MergeBuilder, OrdOffset, TupleBuilder,
         },
-        spine_fueled::{MergeState, MergeVariant, Spine},
+        spine_fueled::{MergeState, MergeVariant, Spine, SpineBatch},
         Batch, BatchReader, Batcher, Builder, Consumer, Cursor, Merger, ValueConsumer,
     },
     Circuit, DBData, DBWeight, NumEntries, OrdIndexedZSet, OrdZSet, RootCircuit, Stream,
@@ -276,6 +276,17 @@ where
     }
 }
 
+/// Returns the batch stored in `batch`.  Spines in this benchmark are not
+/// configured to spill to disk, so all their batches are in memory.
+fn in_memory<B>(batch: &SpineBatch<B>) -> &B
+where
+    B: Batch,
+{
+    batch
+        .as_memory()
+        .expect("cannot probe a batch spilled to disk")
+}
+
 struct SpineProbes<'a, K, V, R, O = usize> {
     probes: Vec<HashedKVBatchProbe<'a, K, V, R, O>>,
     contains_key: BitVec,
@@ -294,18 +305,18 @@ where
             match merge_state {
                 MergeState::Double(MergeVariant::InProgress(batch1, batch2, _)) => {
                     if !batch1.is_empty() {
-                        probes.push(batch1.probe());
+                        probes.push(in_memory(batch1).probe());
                     }
 
                     if !batch2.is_empty() {
-                        probes.push(batch2.probe());
+                        probes.push(in_memory(batch2).probe());
                     }
                 }
 
                 MergeState::Double(MergeVariant::Complete(Some(batch)))
                 | MergeState::Single(Some(batch)) => {
                     if !batch.is_empty() {
-                        probes.push(batch.probe());
+                        probes.push(in_memory(batch).probe());
                     }
                 }
 
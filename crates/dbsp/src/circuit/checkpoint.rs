@@ -0,0 +1,356 @@
+//! Checkpointing circuit state.
+//!
+//! A checkpoint captures the internal state of all stateful operators in a
+//! circuit (traces, `z^-1` delays, integrals) at a clock cycle boundary, so
+//! that an identically constructed circuit can later resume from that point
+//! instead of replaying its entire input history.
+//!
+//! Operators participate in checkpointing by implementing
+//! [`Operator::checkpoint`](`crate::circuit::operator_traits::Operator::checkpoint`)
+//! and [`Operator::restore`](`crate::circuit::operator_traits::Operator::restore`).
+//! Operators that store arbitrary user values (e.g., [`Z1`](`crate::operator::Z1`))
+//! require these values to implement the [`Checkpoint`] trait.
+//!
+//! Checkpointing relational data (batches, traces, and timestamps) requires
+//! the `with-bincode` feature (enabled by default), which makes
+//! [`DBData`](`crate::DBData`) types encodable.  Without it, checkpointing a
+//! circuit that contains such state fails with an error.
+//!
+//! On disk, the checkpoint of a single circuit is a directory that contains
+//! one file per stateful operator, named after the operator's global node id.
+
+use crate::{
+    circuit::{circuit_builder::Node, GlobalNodeId},
+    trace::{cursor::Cursor, Batch, BatchReader, Batcher, Trace},
+    Error, Timestamp,
+};
+use bincode::{config::standard, decode_from_slice, encode_to_vec, Decode, Encode};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Values that can be saved to and restored from a checkpoint.
+///
+/// Exchange operators also use this trait to serialize values sent to
+/// workers on other hosts in a multihost runtime.
+pub trait Checkpoint: Sized {
+    /// Serialize `self` into a byte array.
+    fn checkpoint(&self) -> Result<Vec<u8>, Error>;
+
+    /// Deserialize a value serialized by [`Self::checkpoint`].
+    fn restore(data: &[u8]) -> Result<Self, Error>;
+}
+
+/// Serialize a value using the encoding shared by all checkpoint files.
+pub(crate) fn encode_state<T>(value: &T) -> Result<Vec<u8>, Error>
+where
+    T: Encode,
+{
+    encode_to_vec(value, standard())
+        .map_err(|e| Error::Custom(format!("failed to encode checkpoint: {e}")))
+}
+
+/// Deserialize a value written by [`encode_state`].
+pub(crate) fn decode_state<T>(data: &[u8]) -> Result<T, Error>
+where
+    T: Decode,
+{
+    decode_from_slice(data, standard())
+        .map(|(value, _len)| value)
+        .map_err(|e| Error::Custom(format!("failed to decode checkpoint: {e}")))
+}
+
+/// Serialize a value built from [`DBData`](`crate::DBData`) types, such as the
+/// updates in a batch.
+///
+/// `DBData` only requires `Encode` with the `with-bincode` feature; without
+/// it, this function fails.
+#[cfg(feature = "with-bincode")]
+pub(crate) fn encode_data<T>(value: &T) -> Result<Vec<u8>, Error>
+where
+    T: Encode,
+{
+    encode_state(value)
+}
+
+/// Serialize a value built from [`DBData`](`crate::DBData`) types, such as the
+/// updates in a batch.
+///
+/// `DBData` only requires `Encode` with the `with-bincode` feature; without
+/// it, this function fails.
+#[cfg(not(feature = "with-bincode"))]
+pub(crate) fn encode_data<T>(_value: &T) -> Result<Vec<u8>, Error> {
+    Err(Error::Custom(
+        "checkpointing relational data requires the `with-bincode` feature".to_string(),
+    ))
+}
+
+/// Deserialize a value written by [`encode_data`].
+#[cfg(feature = "with-bincode")]
+pub(crate) fn decode_data<T>(data: &[u8]) -> Result<T, Error>
+where
+    T: Decode,
+{
+    decode_state(data)
+}
+
+/// Deserialize a value written by [`encode_data`].
+#[cfg(not(feature = "with-bincode"))]
+pub(crate) fn decode_data<T>(_data: &[u8]) -> Result<T, Error> {
+    Err(Error::Custom(
+        "restoring relational data from a checkpoint requires the `with-bincode` feature"
+            .to_string(),
+    ))
+}
+
+macro_rules! checkpoint_via_bincode {
+    ($($type:ty),* $(,)?) => {
+        $(
+            impl Checkpoint for $type {
+                fn checkpoint(&self) -> Result<Vec<u8>, Error> {
+                    encode_state(self)
+                }
+
+                fn restore(data: &[u8]) -> Result<Self, Error> {
+                    decode_state(data)
+                }
+            }
+        )*
+    };
+}
+
+checkpoint_via_bincode! {
+    (), bool, char, String,
+    u8, u16, u32, u64, u128, usize,
+    i8, i16, i32, i64, i128, isize,
+    f32, f64,
+}
+
+impl<T> Checkpoint for Option<T>
+where
+    T: Checkpoint,
+{
+    fn checkpoint(&self) -> Result<Vec<u8>, Error> {
+        let state = self.as_ref().map(T::checkpoint).transpose()?;
+        encode_state(&state)
+    }
+
+    fn restore(data: &[u8]) -> Result<Self, Error> {
+        let state: Option<Vec<u8>> = decode_state(data)?;
+        state.as_deref().map(T::restore).transpose()
+    }
+}
+
+impl<T> Checkpoint for Vec<T>
+where
+    T: Checkpoint,
+{
+    fn checkpoint(&self) -> Result<Vec<u8>, Error> {
+        let state = self
+            .iter()
+            .map(T::checkpoint)
+            .collect::<Result<Vec<_>, _>>()?;
+        encode_state(&state)
+    }
+
+    fn restore(data: &[u8]) -> Result<Self, Error> {
+        let state: Vec<Vec<u8>> = decode_state(data)?;
+        state.iter().map(|data| T::restore(data)).collect()
+    }
+}
+
+/// Checkpoints a tuple as the list of its fields' checkpoints.
+macro_rules! checkpoint_tuple {
+    ($($len:literal => ($($name:ident $index:tt),+)),* $(,)?) => {
+        $(
+            impl<$($name),+> Checkpoint for ($($name,)+)
+            where
+                $($name: Checkpoint,)+
+            {
+                fn checkpoint(&self) -> Result<Vec<u8>, Error> {
+                    let state: Vec<Vec<u8>> = vec![$(self.$index.checkpoint()?),+];
+                    encode_state(&state)
+                }
+
+                fn restore(data: &[u8]) -> Result<Self, Error> {
+                    let state: Vec<Vec<u8>> = decode_state(data)?;
+                    if state.len() != $len {
+                        return Err(Error::Custom(format!(
+                            "failed to decode checkpoint: expected a tuple of {} fields, found {}",
+                            $len,
+                            state.len()
+                        )));
+                    }
+                    Ok(($($name::restore(&state[$index])?,)+))
+                }
+            }
+        )*
+    };
+}
+
+checkpoint_tuple! {
+    1 => (A 0),
+    2 => (A 0, B 1),
+    3 => (A 0, B 1, C 2),
+    4 => (A 0, B 1, C 2, D 3),
+    5 => (A 0, B 1, C 2, D 3, E 4),
+    6 => (A 0, B 1, C 2, D 3, E 4, F 5),
+    7 => (A 0, B 1, C 2, D 3, E 4, F 5, G 6),
+    8 => (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7),
+}
+
+/// Updates in a batch or trace, flattened into `(key, value, time, weight)`
+/// tuples.
+type Updates<K, V, T, R> = Vec<(K, V, T, R)>;
+
+/// Serialize all updates in `batch`.
+pub(crate) fn checkpoint_batch<B>(batch: &B) -> Result<Vec<u8>, Error>
+where
+    B: BatchReader,
+{
+    let mut updates: Updates<B::Key, B::Val, B::Time, B::R> = Vec::with_capacity(batch.len());
+
+    let mut cursor = batch.cursor();
+    while cursor.key_valid() {
+        while cursor.val_valid() {
+            let key = cursor.key().clone();
+            let val = cursor.val().clone();
+            cursor.map_times(|time, weight| {
+                updates.push((key.clone(), val.clone(), time.clone(), weight.clone()));
+            });
+            cursor.step_val();
+        }
+        cursor.step_key();
+    }
+
+    encode_data(&updates)
+}
+
+/// Deserialize updates written by [`checkpoint_batch`], grouped into one batch
+/// per distinct timestamp.
+fn restore_batches<B>(data: &[u8]) -> Result<Vec<B>, Error>
+where
+    B: Batch,
+{
+    let mut updates: Updates<B::Key, B::Val, B::Time, B::R> = decode_data(data)?;
+    updates.sort_by(|(_, _, t1, _), (_, _, t2, _)| t1.cmp(t2));
+
+    let mut batches = Vec::new();
+    let mut updates = updates.into_iter().peekable();
+
+    while let Some((key, val, time, weight)) = updates.next() {
+        let mut tuples = vec![(B::item_from(key, val), weight)];
+        while let Some((key, val, _, weight)) = updates.next_if(|(_, _, t, _)| t == &time) {
+            tuples.push((B::item_from(key, val), weight));
+        }
+
+        let mut batcher = B::Batcher::new_batcher(time);
+        batcher.push_batch(&mut tuples);
+        batches.push(batcher.seal());
+    }
+
+    Ok(batches)
+}
+
+/// Restore a batch serialized with [`checkpoint_batch`].
+pub(crate) fn restore_batch<B>(data: &[u8]) -> Result<B, Error>
+where
+    B: Batch,
+{
+    let batches = restore_batches::<B>(data)?;
+
+    Ok(batches
+        .iter()
+        .fold(None, |acc: Option<B>, batch| match acc {
+            None => Some(batch.clone()),
+            Some(acc) => Some(acc.merge(batch)),
+        })
+        .unwrap_or_else(|| B::empty(B::Time::minimum())))
+}
+
+/// Restore a trace serialized with [`checkpoint_batch`] into an empty `trace`.
+pub(crate) fn restore_trace<T>(mut trace: T, data: &[u8]) -> Result<T, Error>
+where
+    T: Trace,
+{
+    for batch in restore_batches::<T::Batch>(data)? {
+        trace.insert(batch);
+    }
+
+    Ok(trace)
+}
+
+impl<B> Checkpoint for B
+where
+    B: Batch,
+{
+    fn checkpoint(&self) -> Result<Vec<u8>, Error> {
+        checkpoint_batch(self)
+    }
+
+    fn restore(data: &[u8]) -> Result<Self, Error> {
+        restore_batch(data)
+    }
+}
+
+/// Name of the file that stores the state of node `id` within a circuit
+/// checkpoint directory.
+fn node_file_name(id: &GlobalNodeId) -> String {
+    let path = id
+        .path()
+        .iter()
+        .map(|node_id| node_id.id().to_string())
+        .collect::<Vec<_>>()
+        .join(".");
+    format!("node-{path}.dat")
+}
+
+/// Write the state of `node` to the checkpoint directory `dir`.
+pub(crate) fn write_node_checkpoint(dir: &Path, node: &dyn Node) -> Result<(), Error> {
+    if let Some(state) = node.checkpoint()? {
+        fs::write(dir.join(node_file_name(node.global_id())), state)?;
+    }
+    Ok(())
+}
+
+/// Restore the state of `node` from the checkpoint directory `dir`.
+///
+/// Nodes without a matching file are left in their initial state: they were
+/// stateless when the checkpoint was taken.
+pub(crate) fn read_node_checkpoint(dir: &Path, node: &mut dyn Node) -> Result<(), Error> {
+    let path: PathBuf = dir.join(node_file_name(node.global_id()));
+    if path.exists() {
+        node.restore(&fs::read(path)?)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::Checkpoint;
+
+    fn round_trip<T>(value: T)
+    where
+        T: Checkpoint + PartialEq + std::fmt::Debug,
+    {
+        let state = value.checkpoint().unwrap();
+        assert_eq!(T::restore(&state).unwrap(), value);
+    }
+
+    #[test]
+    fn checkpoint_compound_values() {
+        round_trip((5u32, "foo".to_string()));
+        round_trip((1i64, (true, 'x'), -2.5f64));
+        round_trip(Some(vec![Some(1u8), None, Some(3)]));
+        round_trip(None::<String>);
+        round_trip(vec![(1u64, "a".to_string()), (2, "b".to_string())]);
+        round_trip(Vec::<u32>::new());
+    }
+
+    #[test]
+    fn restore_tuple_with_wrong_arity() {
+        let state = (1u32, 2u32, 3u32).checkpoint().unwrap();
+        assert!(<(u32, u32)>::restore(&state).is_err());
+    }
+}
@@ -35,6 +35,7 @@ use crate::{
 use crate::{
     circuit::{
         cache::{CircuitCache, CircuitStoreMarker},
+        checkpoint::{read_node_checkpoint, write_node_checkpoint},
         metadata::OperatorMeta,
         operator_traits::{
             BinaryOperator, Data, ImportOperator, NaryOperator, QuaternaryOperator, SinkOperator,
@@ -49,7 +50,7 @@ use crate::{
     circuit_cache_key,
     operator::communication::Exchange,
     time::{Timestamp, UnitTimestamp},
-    Runtime,
+    Error, Runtime,
 };
 use std::{
     borrow::Cow,
@@ -60,6 +61,7 @@ use std::{
     iter::repeat,
     marker::PhantomData,
     panic::Location,
+    path::Path,
     rc::Rc,
     thread::panicking,
 };
@@ -769,7 +771,24 @@ pub trait Node {
 
     fn fixedpoint(&self, scope: Scope) -> bool;
 
+    /// Serialize the state of the inner operator (see
+    /// [`Operator::checkpoint`](super::operator_traits::Operator::checkpoint)).
+    ///
+    /// Subcircuits return `None`; the state of their nodes is checkpointed
+    /// individually.
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
+
+    /// Restore the state of the inner operator (see
+    /// [`Operator::restore`](super::operator_traits::Operator::restore)).
+    fn restore(&mut self, _state: &[u8]) -> Result<(), Error> {
+        Ok(())
+    }
+
     fn map_nodes_recursive(&self, _f: &mut dyn FnMut(&dyn Node)) {}
+
+    fn map_nodes_recursive_mut(&mut self, _f: &mut dyn FnMut(&mut dyn Node)) {}
 }
 
 /// Id of an operator, guaranteed to be unique within a circuit.
@@ -2103,6 +2122,15 @@ where
         }
     }
 
+    /// Recursively apply `f` to all nodes in `self` and its children, allowing
+    /// `f` to modify the nodes.
+    pub(crate) fn map_nodes_recursive_mut(&self, f: &mut dyn FnMut(&mut dyn Node)) {
+        for node in self.inner_mut().nodes.iter_mut() {
+            f(node.as_mut());
+            node.map_nodes_recursive_mut(f);
+        }
+    }
+
     fn clear(&mut self) {
         self.inner_mut().clear();
     }
@@ -2855,6 +2883,9 @@ where
                             if Runtime::kill_in_progress() {
                                 return Err(SchedulerError::Killed);
                             }
+                            if let Some(error) = Runtime::network_failure() {
+                                return Err(SchedulerError::NetworkFailure(error));
+                            }
                             Runtime::parker().with(|parker| parker.park());
                         }
                         // Receive the fixed point status of each peer, compute global fixedpoint
@@ -2865,6 +2896,9 @@ where
                             if Runtime::kill_in_progress() {
                                 return Err(SchedulerError::Killed);
                             }
+                            if let Some(error) = Runtime::network_failure() {
+                                return Err(SchedulerError::NetworkFailure(error));
+                            }
                             // Sleep if other threads are still working.
                             Runtime::parker().with(|parker| parker.park());
                         }
@@ -3066,6 +3100,14 @@ where
     fn fixedpoint(&self, scope: Scope) -> bool {
         self.operator.fixedpoint(scope)
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        self.operator.checkpoint()
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Result<(), Error> {
+        self.operator.restore(state)
+    }
 }
 
 struct SourceNode<C, O, Op> {
@@ -3142,6 +3184,14 @@ where
     fn fixedpoint(&self, scope: Scope) -> bool {
         self.operator.fixedpoint(scope)
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        self.operator.checkpoint()
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Result<(), Error> {
+        self.operator.restore(state)
+    }
 }
 
 struct UnaryNode<C, I, O, Op> {
@@ -3224,6 +3274,14 @@ where
     fn fixedpoint(&self, scope: Scope) -> bool {
         self.operator.fixedpoint(scope)
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        self.operator.checkpoint()
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Result<(), Error> {
+        self.operator.restore(state)
+    }
 }
 
 struct SinkNode<C, I, Op> {
@@ -3299,6 +3357,14 @@ where
     fn fixedpoint(&self, scope: Scope) -> bool {
         self.operator.fixedpoint(scope)
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        self.operator.checkpoint()
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Result<(), Error> {
+        self.operator.restore(state)
+    }
 }
 
 struct BinaryNode<C, I1, I2, O, Op> {
@@ -3414,6 +3480,14 @@ where
     fn fixedpoint(&self, scope: Scope) -> bool {
         self.operator.fixedpoint(scope)
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        self.operator.checkpoint()
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Result<(), Error> {
+        self.operator.restore(state)
+    }
 }
 
 struct TernaryNode<C, I1, I2, I3, O, Op> {
@@ -3533,6 +3607,14 @@ where
     fn fixedpoint(&self, scope: Scope) -> bool {
         self.operator.fixedpoint(scope)
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        self.operator.checkpoint()
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Result<(), Error> {
+        self.operator.restore(state)
+    }
 }
 
 struct QuaternaryNode<C, I1, I2, I3, I4, O, Op> {
@@ -3670,6 +3752,14 @@ where
     fn fixedpoint(&self, scope: Scope) -> bool {
         self.operator.fixedpoint(scope)
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        self.operator.checkpoint()
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Result<(), Error> {
+        self.operator.restore(state)
+    }
 }
 
 struct NaryNode<C, I, O, Op>
@@ -3792,6 +3882,14 @@ where
     fn fixedpoint(&self, scope: Scope) -> bool {
         self.operator.fixedpoint(scope)
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        self.operator.checkpoint()
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Result<(), Error> {
+        self.operator.restore(state)
+    }
 }
 
 // The output half of a feedback node.  We implement a feedback node using a
@@ -3889,6 +3987,14 @@ where
     fn fixedpoint(&self, scope: Scope) -> bool {
         unsafe { (*self.operator.get()).fixedpoint(scope) }
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        unsafe { (*self.operator.get()).checkpoint() }
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Result<(), Error> {
+        unsafe { (*self.operator.get()).restore(state) }
+    }
 }
 
 /// The input half of a feedback node
@@ -3953,7 +4059,8 @@ where
     }
 
     // Don't call `clock_start`/`clock_end` on the operator.  `FeedbackOutputNode`
-    // will do that.
+    // will do that.  Likewise, checkpointing the operator's state is left to
+    // `FeedbackOutputNode`.
     fn clock_start(&mut self, _scope: Scope) {}
 
     unsafe fn clock_end(&mut self, _scope: Scope) {}
@@ -4108,6 +4215,10 @@ where
     fn map_nodes_recursive(&self, f: &mut dyn FnMut(&dyn Node)) {
         self.circuit.map_nodes_recursive(f);
     }
+
+    fn map_nodes_recursive_mut(&mut self, f: &mut dyn FnMut(&mut dyn Node)) {
+        self.circuit.map_nodes_recursive_mut(f);
+    }
 }
 
 /// Top-level circuit with executor.
@@ -4155,6 +4266,47 @@ impl CircuitHandle {
         self.executor.run(&self.circuit)
     }
 
+    /// Write the state of all stateful operators in the circuit to `dir`.
+    ///
+    /// Must be invoked between calls to [`Self::step`].  `dir` must exist.
+    /// See [`checkpoint`](`crate::circuit::checkpoint`) module documentation.
+    pub fn checkpoint(&self, dir: &Path) -> Result<(), Error> {
+        let mut result = Ok(());
+        self.circuit.map_nodes_recursive(&mut |node| {
+            if result.is_ok() {
+                result = write_node_checkpoint(dir, node);
+            }
+        });
+        result
+    }
+
+    /// Returns `true` if the circuit contains stateful operators, i.e.,
+    /// operators whose state is included in a [checkpoint](`Self::checkpoint`)
+    /// and carries over from one step to the next.
+    pub fn is_stateful(&self) -> bool {
+        let mut stateful = false;
+        self.circuit.map_nodes_recursive(&mut |node| {
+            // An operator that fails to serialize its state has state too.
+            stateful = stateful || !matches!(node.checkpoint(), Ok(None));
+        });
+        stateful
+    }
+
+    /// Restore the state of all stateful operators in the circuit from a
+    /// checkpoint written to `dir` by [`Self::checkpoint`].
+    ///
+    /// The circuit must be constructed identically to the circuit that wrote
+    /// the checkpoint and must not have been stepped yet.
+    pub fn restore(&self, dir: &Path) -> Result<(), Error> {
+        let mut result = Ok(());
+        self.circuit.map_nodes_recursive_mut(&mut |node| {
+            if result.is_ok() {
+                result = read_node_checkpoint(dir, node);
+            }
+        });
+        result
+    }
+
     /// Attach a scheduler event handler to the circuit.
     ///
     /// This method is identical to
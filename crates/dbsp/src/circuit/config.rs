@@ -0,0 +1,288 @@
+//! Runtime configuration of a circuit.
+//!
+//! A [`CircuitConfig`] describes how a circuit is instantiated by
+//! [`Runtime::init_circuit_with_config`](`crate::Runtime::init_circuit_with_config`):
+//! how its workers are distributed across hosts ([`Layout`]) and, optionally,
+//! where stateful operators keep their state ([`StorageConfig`]).
+
+use crate::{circuit::layout::Layout, trace::spine_fueled::DEFAULT_SPILL_THRESHOLD};
+use std::{collections::BTreeSet, path::PathBuf};
+
+#[cfg(feature = "with-serde")]
+use serde::{Deserialize, Serialize};
+
+/// Default size of the in-memory cache of the persistent storage.
+const fn default_cache_size() -> usize {
+    1024 * 1024 * 1024
+}
+
+const fn default_backend() -> StorageBackend {
+    StorageBackend::Spill
+}
+
+const fn default_spill_threshold() -> usize {
+    DEFAULT_SPILL_THRESHOLD
+}
+
+const fn default_compression() -> StorageCompression {
+    StorageCompression::None
+}
+
+fn default_persistent_operators() -> BTreeSet<StatefulOperator> {
+    StatefulOperator::ALL.into_iter().collect()
+}
+
+/// Configuration of a circuit instantiated in a [`Runtime`](`crate::Runtime`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CircuitConfig {
+    /// Distribution of the workers of the circuit across hosts.
+    pub layout: Layout,
+
+    /// Persistent storage for the state of stateful operators.
+    ///
+    /// When `None`, all operators keep their state in memory.
+    pub storage: Option<StorageConfig>,
+}
+
+impl CircuitConfig {
+    /// Configuration with the given `layout` and all state kept in memory.
+    pub fn new(layout: Layout) -> Self {
+        Self {
+            layout,
+            storage: None,
+        }
+    }
+
+    /// Keep the state of stateful operators in persistent storage configured
+    /// by `storage`.
+    pub fn with_storage(mut self, storage: StorageConfig) -> Self {
+        self.storage = Some(storage);
+        self
+    }
+}
+
+impl From<Layout> for CircuitConfig {
+    fn from(layout: Layout) -> Self {
+        Self::new(layout)
+    }
+}
+
+impl From<usize> for CircuitConfig {
+    fn from(n_workers: usize) -> Self {
+        Self::new(Layout::new_solo(n_workers))
+    }
+}
+
+/// Stateful operators that can keep their state in persistent storage.
+///
+/// Used in [`StorageConfig::persistent_operators`] to select the operators
+/// whose traces are stored on disk.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "with-serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "with-serde", serde(rename_all = "snake_case"))]
+pub enum StatefulOperator {
+    /// Traces created by [`Stream::integrate_trace`](`crate::Stream::integrate_trace`),
+    /// [`Stream::trace`](`crate::Stream::trace`) and stateful operators not
+    /// listed below.
+    Trace,
+
+    /// Input traces of join operators.
+    Join,
+
+    /// Input traces of aggregation operators.
+    Aggregate,
+
+    /// Input traces of the [`distinct`](`crate::Stream::distinct`) operator.
+    Distinct,
+}
+
+impl StatefulOperator {
+    /// All stateful operators.
+    pub const ALL: [Self; 4] = [Self::Trace, Self::Join, Self::Aggregate, Self::Distinct];
+}
+
+/// Implementation of the persistent storage.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "with-serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "with-serde", serde(rename_all = "snake_case"))]
+pub enum StorageBackend {
+    /// Traces are kept in memory, but large batches are spilled to files in
+    /// [`StorageConfig::path`] (see
+    /// [`Spine::with_spill`](`crate::trace::spine_fueled::Spine::with_spill`)).
+    /// Requires the `with-bincode` feature.
+    Spill,
+
+    /// Traces are stored in a RocksDB database at [`StorageConfig::path`].
+    /// Requires the `persistence` feature.
+    RocksDb,
+}
+
+/// Compression algorithm applied to data in persistent storage.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "with-serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "with-serde", serde(rename_all = "snake_case"))]
+pub enum StorageCompression {
+    None,
+    Snappy,
+    Lz4,
+    Zstd,
+}
+
+/// Configuration of the persistent storage for operator state.
+///
+/// Stateful operators selected by [`Self::persistent_operators`] keep their
+/// traces on disk, at [`Self::path`], so that their state can grow larger
+/// than RAM.  All other operators keep their state in memory.  By default,
+/// these traces spill large batches to files, which requires the
+/// `with-bincode` feature.  Alternatively, [`StorageBackend::RocksDb`] keeps
+/// them in a RocksDB database, which requires the `persistence` feature.
+///
+/// Several traces can share the same underlying trace, e.g., when a stream
+/// is joined with several other streams.  The operator that creates the trace
+/// first determines where it is stored.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "with-serde", derive(Serialize, Deserialize))]
+pub struct StorageConfig {
+    /// Directory that contains the spilled batches or the database.  It is
+    /// created if it does not exist.
+    pub path: PathBuf,
+
+    /// Implementation of the storage.  Defaults to [`StorageBackend::Spill`].
+    #[cfg_attr(feature = "with-serde", serde(default = "default_backend"))]
+    pub backend: StorageBackend,
+
+    /// Minimal number of updates in a batch for it to be spilled to disk by
+    /// [`StorageBackend::Spill`].  Defaults to
+    /// [`DEFAULT_SPILL_THRESHOLD`].
+    #[cfg_attr(feature = "with-serde", serde(default = "default_spill_threshold"))]
+    pub spill_threshold: usize,
+
+    /// Size of the in-memory cache shared by all traces in the database of
+    /// [`StorageBackend::RocksDb`], in bytes.  Defaults to 1 GiB.
+    #[cfg_attr(feature = "with-serde", serde(default = "default_cache_size"))]
+    pub cache_size: usize,
+
+    /// Compression applied to data in the database of
+    /// [`StorageBackend::RocksDb`].  Defaults to no compression.
+    #[cfg_attr(feature = "with-serde", serde(default = "default_compression"))]
+    pub compression: StorageCompression,
+
+    /// Operators whose state is kept in persistent storage.  Defaults to all
+    /// stateful operators.
+    #[cfg_attr(
+        feature = "with-serde",
+        serde(default = "default_persistent_operators")
+    )]
+    pub persistent_operators: BTreeSet<StatefulOperator>,
+}
+
+impl StorageConfig {
+    /// Storage at `path` that spills batches with at least
+    /// [`DEFAULT_SPILL_THRESHOLD`] updates, and keeps all stateful operators in
+    /// persistent storage.
+    pub fn new<P>(path: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self {
+            path: path.into(),
+            backend: default_backend(),
+            spill_threshold: default_spill_threshold(),
+            cache_size: default_cache_size(),
+            compression: default_compression(),
+            persistent_operators: default_persistent_operators(),
+        }
+    }
+
+    /// Set the implementation of the storage.
+    pub fn with_backend(mut self, backend: StorageBackend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Set the minimal number of updates in a spilled batch.
+    pub fn with_spill_threshold(mut self, spill_threshold: usize) -> Self {
+        self.spill_threshold = spill_threshold;
+        self
+    }
+
+    /// Set the size of the in-memory cache in bytes.
+    pub fn with_cache_size(mut self, cache_size: usize) -> Self {
+        self.cache_size = cache_size;
+        self
+    }
+
+    /// Set the compression algorithm.
+    pub fn with_compression(mut self, compression: StorageCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Keep only the state of `operators` in persistent storage.
+    pub fn with_persistent_operators<I>(mut self, operators: I) -> Self
+    where
+        I: IntoIterator<Item = StatefulOperator>,
+    {
+        self.persistent_operators = operators.into_iter().collect();
+        self
+    }
+
+    /// Returns `true` if the state of `operator` is kept in persistent
+    /// storage.
+    pub fn is_persistent(&self, operator: StatefulOperator) -> bool {
+        self.persistent_operators.contains(&operator)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        CircuitConfig, StatefulOperator, StorageBackend, StorageCompression, StorageConfig,
+    };
+    use crate::circuit::Layout;
+
+    #[test]
+    fn storage_config() {
+        let storage = StorageConfig::new("/tmp/dbsp-storage");
+        assert_eq!(storage.backend, StorageBackend::Spill);
+        for operator in StatefulOperator::ALL {
+            assert!(storage.is_persistent(operator));
+        }
+
+        let storage = storage
+            .with_backend(StorageBackend::RocksDb)
+            .with_spill_threshold(100)
+            .with_cache_size(1024)
+            .with_compression(StorageCompression::Zstd)
+            .with_persistent_operators([StatefulOperator::Join]);
+        assert_eq!(storage.backend, StorageBackend::RocksDb);
+        assert_eq!(storage.spill_threshold, 100);
+        assert_eq!(storage.cache_size, 1024);
+        assert!(storage.is_persistent(StatefulOperator::Join));
+        assert!(!storage.is_persistent(StatefulOperator::Aggregate));
+
+        let config = CircuitConfig::from(4).with_storage(storage.clone());
+        assert_eq!(config.layout, Layout::new_solo(4));
+        assert_eq!(config.storage, Some(storage));
+    }
+
+    #[test]
+    #[cfg(feature = "with-serde")]
+    fn storage_config_defaults() {
+        let storage: StorageConfig = serde_json::from_str(
+            r#"{"path": "/tmp/dbsp-storage", "persistent_operators": ["join", "aggregate"]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            storage,
+            StorageConfig::new("/tmp/dbsp-storage")
+                .with_persistent_operators([StatefulOperator::Join, StatefulOperator::Aggregate])
+        );
+
+        let storage: StorageConfig =
+            serde_json::from_str(r#"{"path": "/tmp/dbsp-storage", "backend": "rocks_db"}"#)
+                .unwrap();
+        assert_eq!(storage.backend, StorageBackend::RocksDb);
+    }
+}
@@ -1,6 +1,7 @@
 use crate::{
-    circuit::runtime::RuntimeHandle, profile::Profiler, Error as DBSPError, RootCircuit, Runtime,
-    RuntimeError, SchedulerError,
+    circuit::{config::CircuitConfig, runtime::RuntimeHandle},
+    profile::Profiler,
+    Error as DBSPError, RootCircuit, Runtime, RuntimeError, SchedulerError,
 };
 use crossbeam::channel::{bounded, Receiver, Sender, TryRecvError};
 use std::{
@@ -12,7 +13,7 @@ use std::{
 };
 
 #[cfg(doc)]
-use crate::circuit::circuit_builder::Stream;
+use crate::circuit::{circuit_builder::Stream, layout::Layout};
 
 impl Runtime {
     /// Instantiate a circuit in a multithreaded runtime.
@@ -50,6 +51,93 @@ impl Runtime {
         F: FnOnce(&mut RootCircuit) -> T + Clone + Send + 'static,
         T: Clone + Send + 'static,
     {
+        Self::init_circuit_inner(CircuitConfig::from(nworkers), None, constructor)
+    }
+
+    /// Instantiate a circuit in a runtime configured by `config`.
+    ///
+    /// Behaves like [`Self::init_circuit`], but takes a [`CircuitConfig`]
+    /// (or anything that converts into one, such as a [`Layout`]) instead of
+    /// the number of workers.
+    ///
+    /// With a [`Layout::Multihost`] layout the workers of the circuit run in
+    /// several processes.  Every process must call this function with the
+    /// same list of hosts and the same `constructor`.  The call blocks until
+    /// the process has connected to all of its peers (see
+    /// [`Runtime::run_with_config`]).  The returned [`DBSPHandle`] controls
+    /// the workers in the current process only.  All processes must call
+    /// [`DBSPHandle::step`] in lockstep, since exchange operators in each
+    /// clock cycle wait for data from all workers.  Input and output handles
+    /// likewise only cover local workers.
+    ///
+    /// If `config` specifies persistent [storage](`CircuitConfig::storage`),
+    /// operators selected by the storage configuration keep their traces on
+    /// disk.
+    pub fn init_circuit_with_config<F, T>(
+        config: impl Into<CircuitConfig>,
+        constructor: F,
+    ) -> Result<(DBSPHandle, T), DBSPError>
+    where
+        F: FnOnce(&mut RootCircuit) -> T + Clone + Send + 'static,
+        T: Clone + Send + 'static,
+    {
+        Self::init_circuit_inner(config.into(), None, constructor)
+    }
+
+    /// Instantiate a circuit in a multithreaded runtime and restore its state
+    /// from a checkpoint.
+    ///
+    /// Behaves like [`Self::init_circuit`], but before returning, restores
+    /// the state of all stateful operators in the circuit from the checkpoint
+    /// written by [`DBSPHandle::checkpoint`] to `checkpoint_dir`.  The
+    /// `constructor` must build the same circuit that wrote the checkpoint,
+    /// and `nworkers` must match the number of workers at the time the
+    /// checkpoint was taken.
+    pub fn init_circuit_from_checkpoint<F, T, P>(
+        nworkers: usize,
+        checkpoint_dir: P,
+        constructor: F,
+    ) -> Result<(DBSPHandle, T), DBSPError>
+    where
+        F: FnOnce(&mut RootCircuit) -> T + Clone + Send + 'static,
+        T: Clone + Send + 'static,
+        P: AsRef<Path>,
+    {
+        let checkpoint_dir = checkpoint_dir.as_ref().to_path_buf();
+
+        let metadata = fs::read_to_string(checkpoint_dir.join(CHECKPOINT_METADATA_FILE))?;
+        let checkpoint_workers = metadata.trim().parse::<usize>().map_err(|_| {
+            DBSPError::Custom(format!(
+                "invalid checkpoint metadata in '{}'",
+                checkpoint_dir.display()
+            ))
+        })?;
+        if checkpoint_workers != nworkers {
+            return Err(DBSPError::Custom(format!(
+                "checkpoint in '{}' was written by {checkpoint_workers} workers, but the circuit has {nworkers} workers",
+                checkpoint_dir.display()
+            )));
+        }
+
+        Self::init_circuit_inner(
+            CircuitConfig::from(nworkers),
+            Some(checkpoint_dir),
+            constructor,
+        )
+    }
+
+    fn init_circuit_inner<F, T>(
+        config: CircuitConfig,
+        checkpoint_dir: Option<PathBuf>,
+        constructor: F,
+    ) -> Result<(DBSPHandle, T), DBSPError>
+    where
+        F: FnOnce(&mut RootCircuit) -> T + Clone + Send + 'static,
+        T: Clone + Send + 'static,
+    {
+        // Channels below are indexed by the local worker index.
+        let nworkers = config.layout.local_workers().len();
+
         // When a worker finishes building the circuit, it sends completion status back
         // to us via this channel.  The function returns after receiving a
         // notification from each worker.
@@ -64,14 +152,18 @@ impl Runtime {
         let (status_senders, status_receivers): (Vec<_>, Vec<_>) =
             (0..nworkers).map(|_| bounded(1)).unzip();
 
-        let runtime = Self::run(nworkers, move || {
+        let runtime = Self::run_with_config(config, move || {
             let worker_index = Runtime::worker_index();
+            let local_worker_index = Runtime::local_worker_index();
 
             // Drop all but one channels.  This makes sure that if one of the worker panics
             // or exits, its channel will become disconnected.
-            let init_sender = init_senders.into_iter().nth(worker_index).unwrap();
-            let status_sender = status_senders.into_iter().nth(worker_index).unwrap();
-            let command_receiver = command_receivers.into_iter().nth(worker_index).unwrap();
+            let init_sender = init_senders.into_iter().nth(local_worker_index).unwrap();
+            let status_sender = status_senders.into_iter().nth(local_worker_index).unwrap();
+            let command_receiver = command_receivers
+                .into_iter()
+                .nth(local_worker_index)
+                .unwrap();
 
             let (circuit, profiler) = match RootCircuit::build(|circuit| {
                 let profiler = Profiler::new(circuit);
@@ -79,13 +171,21 @@ impl Runtime {
                 (res, profiler)
             }) {
                 Ok((circuit, (res, profiler))) => {
-                    if init_sender.send(Ok(res)).is_err() {
+                    if let Some(checkpoint_dir) = &checkpoint_dir {
+                        if let Err(e) =
+                            circuit.restore(&worker_checkpoint_dir(checkpoint_dir, worker_index))
+                        {
+                            let _ = init_sender.send(Err(e));
+                            return;
+                        }
+                    }
+                    if init_sender.send(Ok((res, circuit.is_stateful()))).is_err() {
                         return;
                     }
                     (circuit, profiler)
                 }
                 Err(e) => {
-                    let _ = init_sender.send(Err(e));
+                    let _ = init_sender.send(Err(DBSPError::Scheduler(e)));
                     return;
                 }
             };
@@ -119,6 +219,15 @@ impl Runtime {
                             return;
                         }
                     }
+                    Ok(Command::Checkpoint(dir)) => {
+                        let result = circuit.checkpoint(&worker_checkpoint_dir(&dir, worker_index));
+                        if status_sender
+                            .send(Ok(Response::Checkpoint(result)))
+                            .is_err()
+                        {
+                            return;
+                        }
+                    }
                     // Nothing to do: do some housekeeping and relinquish the CPU if there's none
                     // left.
                     Err(TryRecvError::Empty) => {
@@ -134,7 +243,7 @@ impl Runtime {
                     }
                 }
             }
-        });
+        })?;
 
         // Receive initialization status from all workers.
 
@@ -142,9 +251,7 @@ impl Runtime {
 
         for (worker, receiver) in init_receivers.iter().enumerate() {
             match receiver.recv() {
-                Ok(Err(scheduler_error)) => {
-                    init_status.push(Err(DBSPError::Scheduler(scheduler_error)))
-                }
+                Ok(Err(error)) => init_status.push(Err(error)),
                 Ok(Ok(ret)) => init_status.push(Ok(ret)),
                 Err(_) => {
                     init_status.push(Err(DBSPError::Runtime(RuntimeError::WorkerPanic(worker))))
@@ -162,24 +269,37 @@ impl Runtime {
             return Err(error);
         }
 
-        let dbsp = DBSPHandle::new(runtime, command_senders, status_receivers);
+        let stateful = init_status.iter().any(|status| status.as_ref().unwrap().1);
+        let dbsp = DBSPHandle::new(runtime, command_senders, status_receivers, stateful);
 
         // `constructor` should return identical results in all workers.  Use
         // worker 0 output.
-        Ok((dbsp, init_status[0].as_ref().unwrap().clone()))
+        Ok((dbsp, init_status[0].as_ref().unwrap().0.clone()))
     }
 }
 
+/// Name of the file in a checkpoint directory that records the number of
+/// workers that wrote the checkpoint.  The file is written last, so its
+/// presence indicates that the checkpoint is complete.
+const CHECKPOINT_METADATA_FILE: &str = "CHECKPOINT";
+
+/// Directory that stores the state of worker `worker` within a checkpoint.
+fn worker_checkpoint_dir(checkpoint_dir: &Path, worker: usize) -> PathBuf {
+    checkpoint_dir.join(worker.to_string())
+}
+
 #[derive(Clone)]
 enum Command {
     Step,
     EnableProfiler,
     DumpProfile,
+    Checkpoint(PathBuf),
 }
 
 enum Response {
     Unit,
     Profile(String),
+    Checkpoint(Result<(), DBSPError>),
 }
 
 /// A handle to control the execution of a circuit in a multithreaded runtime.
@@ -193,6 +313,8 @@ pub struct DBSPHandle {
     // Channels used to receive command completion status from
     // workers.
     status_receivers: Vec<Receiver<Result<Response, SchedulerError>>>,
+    // `true` if the circuit contains stateful operators.
+    stateful: bool,
 }
 
 impl DBSPHandle {
@@ -200,12 +322,14 @@ impl DBSPHandle {
         runtime: RuntimeHandle,
         command_senders: Vec<Sender<Command>>,
         status_receivers: Vec<Receiver<Result<Response, SchedulerError>>>,
+        stateful: bool,
     ) -> Self {
         Self {
             start_time: Instant::now(),
             runtime: Some(runtime),
             command_senders,
             status_receivers,
+            stateful,
         }
     }
 
@@ -250,10 +374,19 @@ impl DBSPHandle {
         Ok(())
     }
 
+    /// Returns the number of workers controlled by this handle, i.e., the
+    /// workers that run in the current process.
     pub fn num_workers(&self) -> usize {
         self.status_receivers.len()
     }
 
+    /// Returns `true` if the circuit contains stateful operators, whose
+    /// outputs depend on the inputs of previous steps (see
+    /// [`CircuitHandle::is_stateful`](`crate::circuit::CircuitHandle::is_stateful`)).
+    pub fn is_stateful(&self) -> bool {
+        self.stateful
+    }
+
     /// Evaluate the circuit for one clock cycle.
     pub fn step(&mut self) -> Result<(), DBSPError> {
         self.broadcast_command(Command::Step, |_| {})
@@ -297,6 +430,59 @@ impl DBSPHandle {
         Ok(dir_path)
     }
 
+    /// Write a checkpoint of the circuit's state to `dir`.
+    ///
+    /// Creates `dir` if it doesn't exist.  Each worker writes the state of
+    /// all stateful operators in its copy of the circuit (traces, integrals,
+    /// `z^-1` delays, etc.) to the `dir/<worker>` subdirectory.  Since the
+    /// circuit is only checkpointed between clock cycles, the checkpoint is
+    /// consistent across all workers.
+    ///
+    /// Use [`Runtime::init_circuit_from_checkpoint`] to instantiate a new
+    /// circuit from the checkpoint.
+    ///
+    /// Checkpointing relational state requires the `with-bincode` feature (see
+    /// [`checkpoint`](`crate::circuit::checkpoint`)).
+    pub fn checkpoint<P: AsRef<Path>>(&mut self, dir: P) -> Result<(), DBSPError> {
+        let dir = dir.as_ref().to_path_buf();
+
+        // Remove metadata from a previous checkpoint to the same directory
+        // so that a partially written checkpoint is never mistaken for a
+        // complete one.
+        let metadata_path = dir.join(CHECKPOINT_METADATA_FILE);
+        if metadata_path.exists() {
+            fs::remove_file(&metadata_path)?;
+        }
+
+        let runtime = self
+            .runtime
+            .as_ref()
+            .ok_or(DBSPError::Runtime(RuntimeError::Killed))?
+            .runtime()
+            .clone();
+
+        for worker in runtime.layout().local_workers() {
+            let worker_dir = worker_checkpoint_dir(&dir, worker);
+            if worker_dir.exists() {
+                fs::remove_dir_all(&worker_dir)?;
+            }
+            create_dir_all(&worker_dir)?;
+        }
+
+        let mut result = Ok(());
+        self.broadcast_command(Command::Checkpoint(dir), |resp| {
+            if let Response::Checkpoint(Err(e)) = resp {
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        })?;
+        result?;
+
+        fs::write(metadata_path, runtime.num_workers().to_string())?;
+        Ok(())
+    }
+
     /// Terminate the execution of the circuit, exiting all worker threads.
     ///
     /// If one or more of the worker threads panics, returns the argument the
@@ -324,7 +510,24 @@ impl Drop for DBSPHandle {
 
 #[cfg(test)]
 mod tests {
+    #[cfg(feature = "with-bincode")]
+    use crate::{
+        circuit::{CircuitConfig, Layout, StatefulOperator, StorageBackend, StorageConfig},
+        trace::{Batch, BatchReader},
+        OrdZSet, RootCircuit, SchedulerError,
+    };
     use crate::{operator::Generator, Circuit, Error as DBSPError, Runtime, RuntimeError};
+    #[cfg(feature = "with-bincode")]
+    use std::{
+        fs,
+        io::{Read, Write},
+        net::{SocketAddr, TcpListener, TcpStream},
+        thread,
+        thread::sleep,
+        time::Duration,
+    };
+    #[cfg(feature = "with-bincode")]
+    use tempfile::TempDir;
 
     // Panic during initialization in worker thread.
     #[test]
@@ -431,4 +634,294 @@ mod tests {
 
         handle.step().unwrap();
     }
+
+    #[test]
+    fn test_is_stateful() {
+        let (handle, ()) = Runtime::init_circuit(2, |circuit| {
+            let (input, _input_handle) = circuit.add_input_zset::<u64, isize>();
+            input.map(|x| x + 1).output();
+        })
+        .unwrap();
+        assert!(!handle.is_stateful());
+
+        let (handle, ()) = Runtime::init_circuit(2, |circuit| {
+            let (input, _input_handle) = circuit.add_input_zset::<u64, isize>();
+            input.map(|x| x + 1).integrate().output();
+        })
+        .unwrap();
+        assert!(handle.is_stateful());
+    }
+
+    // Checkpoint a circuit and restore its state in a new instance.
+    #[test]
+    #[cfg(feature = "with-bincode")]
+    fn test_checkpoint1() {
+        test_checkpoint(1);
+    }
+
+    #[test]
+    #[cfg(feature = "with-bincode")]
+    fn test_checkpoint4() {
+        test_checkpoint(4);
+    }
+
+    // Keep the state of joins and `distinct` in persistent storage.
+    #[test]
+    #[cfg(feature = "with-bincode")]
+    fn test_storage_spill() {
+        test_storage(StorageBackend::Spill);
+    }
+
+    #[test]
+    #[cfg(feature = "persistence")]
+    fn test_storage_rocksdb() {
+        test_storage(StorageBackend::RocksDb);
+    }
+
+    #[cfg(feature = "with-bincode")]
+    fn test_storage(backend: StorageBackend) {
+        let tempdir = TempDir::new().unwrap();
+
+        let config = CircuitConfig::from(2).with_storage(
+            StorageConfig::new(tempdir.path())
+                .with_backend(backend)
+                .with_spill_threshold(16)
+                .with_cache_size(16 * 1024 * 1024)
+                .with_persistent_operators([StatefulOperator::Join, StatefulOperator::Distinct]),
+        );
+
+        let (mut handle, (mut input, pairs, distinct)) =
+            Runtime::init_circuit_with_config(config, |circuit| {
+                let (input, input_handle) = circuit.add_input_zset::<u64, isize>();
+                let indexed = input.index_with(|k| (k % 2, *k));
+                let pairs = indexed
+                    .join(&indexed, |_parity, k1, k2| (*k1, *k2))
+                    .output();
+                let distinct = input.distinct().output();
+
+                (input_handle, pairs, distinct)
+            })
+            .unwrap();
+
+        input.append(&mut vec![(1, 1), (2, 1), (3, 1)]);
+        handle.step().unwrap();
+        assert_eq!(
+            pairs.consolidate(),
+            OrdZSet::from_keys(
+                (),
+                vec![
+                    ((1, 1), 1),
+                    ((1, 3), 1),
+                    ((2, 2), 1),
+                    ((3, 1), 1),
+                    ((3, 3), 1)
+                ]
+            )
+        );
+
+        input.append(&mut vec![(2, 1), (4, 1)]);
+        handle.step().unwrap();
+        assert_eq!(
+            pairs.consolidate(),
+            OrdZSet::from_keys((), vec![((2, 2), 3), ((2, 4), 2), ((4, 2), 2), ((4, 4), 1)])
+        );
+        assert_eq!(distinct.consolidate(), OrdZSet::from_keys((), vec![(4, 1)]));
+
+        // Grow the state of `distinct` until its trace writes files to the
+        // storage directory.
+        for step in 0..100 {
+            input.append(&mut (0..10).map(|i| (1000 + step * 10 + i, 1)).collect());
+            handle.step().unwrap();
+            assert_eq!(distinct.consolidate().len(), 10);
+        }
+        assert!(fs::read_dir(tempdir.path()).unwrap().count() > 0);
+
+        handle.kill().unwrap();
+    }
+
+    #[cfg(feature = "with-bincode")]
+    fn test_checkpoint(nworkers: usize) {
+        let tempdir = TempDir::new().unwrap();
+        let dir = tempdir.path();
+
+        let constructor = |circuit: &mut RootCircuit| {
+            let (input, input_handle) = circuit.add_input_zset::<u64, isize>();
+            let integral = input.integrate().output();
+            let distinct = input.distinct().output();
+
+            (input_handle, integral, distinct)
+        };
+
+        let (mut handle, (mut input, _, _)) = Runtime::init_circuit(nworkers, constructor).unwrap();
+
+        input.append(&mut vec![(1, 1), (2, 1), (3, 1)]);
+        handle.step().unwrap();
+        input.append(&mut vec![(2, 1), (4, 1)]);
+        handle.step().unwrap();
+        handle.checkpoint(dir).unwrap();
+        handle.kill().unwrap();
+
+        // A checkpoint can only be restored by the same number of workers.
+        assert!(Runtime::init_circuit_from_checkpoint(nworkers + 1, dir, constructor).is_err());
+
+        let (mut handle, (mut input, integral, distinct)) =
+            Runtime::init_circuit_from_checkpoint(nworkers, dir, constructor).unwrap();
+
+        input.append(&mut vec![(1, -1), (5, 1)]);
+        handle.step().unwrap();
+
+        assert_eq!(
+            integral.consolidate(),
+            OrdZSet::from_keys((), vec![(2, 2), (3, 1), (4, 1), (5, 1)])
+        );
+        assert_eq!(
+            distinct.consolidate(),
+            OrdZSet::from_keys((), vec![(1, -1), (5, 1)])
+        );
+
+        handle.kill().unwrap();
+    }
+
+    // Run a circuit with workers split across two hosts, simulated by two
+    // runtimes in the same process that communicate over TCP on localhost.
+    #[test]
+    #[cfg(feature = "with-bincode")]
+    #[cfg_attr(miri, ignore)]
+    fn test_multihost() {
+        const ROUNDS: u64 = 3;
+
+        // Pick two free ports.
+        let addresses: Vec<SocketAddr> = (0..2)
+            .map(|_| {
+                TcpListener::bind("127.0.0.1:0")
+                    .unwrap()
+                    .local_addr()
+                    .unwrap()
+            })
+            .collect();
+        let hosts = vec![(addresses[0], 2), (addresses[1], 3)];
+
+        let threads = addresses
+            .iter()
+            .enumerate()
+            .map(|(host, address)| {
+                let layout = Layout::new_multihost(&hosts, *address).unwrap();
+                let local_workers = hosts[host].1;
+
+                thread::spawn(move || {
+                    let (mut handle, (mut input, output)) =
+                        Runtime::init_circuit_with_config(layout, |circuit| {
+                            let (input, input_handle) = circuit.add_input_zset::<u64, isize>();
+                            let output = input.distinct().gather(0).output();
+
+                            (input_handle, output)
+                        })
+                        .unwrap();
+                    assert_eq!(handle.num_workers(), local_workers);
+
+                    let mut outputs = Vec::new();
+                    for round in 0..ROUNDS {
+                        // Each host feeds its own share of the input.  Both
+                        // hosts insert key 1000, which `distinct` must only
+                        // output once.
+                        let mut tuples: Vec<_> = (0..10)
+                            .map(|i| (round * 100 + host as u64 * 10 + i, 1))
+                            .collect();
+                        tuples.push((1000, 1));
+                        input.append(&mut tuples);
+
+                        handle.step().unwrap();
+                        outputs.push(output.consolidate());
+                    }
+
+                    handle.kill().unwrap();
+                    outputs
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let outputs: Vec<_> = threads
+            .into_iter()
+            .map(|thread| thread.join().unwrap())
+            .collect();
+
+        // Worker 0 runs on the first host, which receives all output.
+        for round in 0..ROUNDS {
+            let mut expected: Vec<_> = (0..20).map(|i| (round * 100 + i, 1)).collect();
+            if round == 0 {
+                expected.push((1000, 1));
+            }
+
+            assert_eq!(outputs[0][round as usize], OrdZSet::from_keys((), expected));
+            assert_eq!(outputs[1][round as usize], OrdZSet::empty(()));
+        }
+    }
+
+    // A peer that closes its connection without shutting down makes the next
+    // step fail instead of blocking forever.
+    #[test]
+    #[cfg(feature = "with-bincode")]
+    #[cfg_attr(miri, ignore)]
+    fn test_multihost_peer_disconnect() {
+        test_multihost_peer_failure(&[]);
+    }
+
+    // So does a peer that sends a message that can't be decoded.
+    #[test]
+    #[cfg(feature = "with-bincode")]
+    #[cfg_attr(miri, ignore)]
+    fn test_multihost_bad_message() {
+        test_multihost_peer_failure(&[0xff; 16]);
+    }
+
+    /// Run a circuit on the first of two hosts.  The second host is simulated
+    /// by a thread that connects to the first one, sends `payload`, and
+    /// closes the connection.
+    #[cfg(feature = "with-bincode")]
+    fn test_multihost_peer_failure(payload: &'static [u8]) {
+        let addresses: Vec<SocketAddr> = (0..2)
+            .map(|_| {
+                TcpListener::bind("127.0.0.1:0")
+                    .unwrap()
+                    .local_addr()
+                    .unwrap()
+            })
+            .collect();
+        let hosts = vec![(addresses[0], 1), (addresses[1], 1)];
+
+        let peer_address = addresses[1];
+        let host_address = addresses[0];
+        let peer = thread::spawn(move || {
+            let listener = TcpListener::bind(peer_address).unwrap();
+            let mut outgoing = loop {
+                match TcpStream::connect(host_address) {
+                    Ok(stream) => break stream,
+                    Err(_) => sleep(Duration::from_millis(50)),
+                }
+            };
+            outgoing.write_all(&1u64.to_le_bytes()).unwrap();
+            let (mut incoming, _) = listener.accept().unwrap();
+            let mut host = [0u8; 8];
+            incoming.read_exact(&mut host).unwrap();
+
+            outgoing.write_all(payload).unwrap();
+            incoming
+        });
+
+        let layout = Layout::new_multihost(&hosts, addresses[0]).unwrap();
+        let (mut handle, mut input) = Runtime::init_circuit_with_config(layout, |circuit| {
+            let (input, input_handle) = circuit.add_input_zset::<u64, isize>();
+            input.distinct().gather(0).output();
+            input_handle
+        })
+        .unwrap();
+        let _incoming = peer.join().unwrap();
+
+        input.append(&mut vec![(1, 1), (2, 1)]);
+        assert!(matches!(
+            handle.step(),
+            Err(DBSPError::Scheduler(SchedulerError::NetworkFailure(_)))
+        ));
+        handle.kill().unwrap();
+    }
 }
@@ -0,0 +1,158 @@
+//! Assignment of workers to hosts.
+//!
+//! By default, all workers in a [`Runtime`](`crate::Runtime`) run as threads
+//! in the current process ([`Layout::Solo`]).  A [`Layout::Multihost`]
+//! runtime instead spreads the workers of a single logical circuit across
+//! several processes, possibly running on different hosts.  Each process runs
+//! an identical circuit with a subset of the workers, and exchange operators
+//! ([`shard`](`crate::Stream::shard`), [`gather`](`crate::Stream::gather`),
+//! etc.) serialize data sent to workers in other processes over TCP.
+//!
+//! All processes must be started with the same list of hosts.  Workers are
+//! assigned to hosts in order: the first host runs workers `0..n0`, the second
+//! host runs workers `n0..n0+n1`, and so on.
+
+use crate::Error;
+use std::{net::SocketAddr, ops::Range};
+
+/// A host in a multihost runtime.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Host {
+    /// Address at which the host listens for connections from its peers.
+    pub address: SocketAddr,
+
+    /// Range of worker indexes that run on this host.
+    pub workers: Range<usize>,
+}
+
+/// Describes how the workers of a runtime are distributed across hosts.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Layout {
+    /// All workers run in the current process.
+    Solo { n_workers: usize },
+
+    /// Workers are distributed across several processes.
+    Multihost {
+        /// All hosts participating in the computation.
+        hosts: Vec<Host>,
+        /// Index of the current process in `hosts`.
+        local_host: usize,
+    },
+}
+
+impl Layout {
+    /// Layout with `n_workers` workers, all running in the current process.
+    pub fn new_solo(n_workers: usize) -> Self {
+        Self::Solo { n_workers }
+    }
+
+    /// Layout with workers distributed across multiple hosts.
+    ///
+    /// `hosts` lists the address of each host and the number of workers it
+    /// runs.  `local_address` is the address of the current process, which
+    /// must be one of the addresses in `hosts`.
+    pub fn new_multihost(
+        hosts: &[(SocketAddr, usize)],
+        local_address: SocketAddr,
+    ) -> Result<Self, Error> {
+        let mut next_worker = 0;
+        let mut layout_hosts: Vec<Host> = Vec::with_capacity(hosts.len());
+
+        for (address, n_workers) in hosts.iter() {
+            if *n_workers == 0 {
+                return Err(Error::Custom(format!("host {address} has no workers")));
+            }
+            if layout_hosts.iter().any(|host| &host.address == address) {
+                return Err(Error::Custom(format!(
+                    "host {address} appears more than once in the layout"
+                )));
+            }
+
+            layout_hosts.push(Host {
+                address: *address,
+                workers: next_worker..next_worker + n_workers,
+            });
+            next_worker += n_workers;
+        }
+
+        let local_host = layout_hosts
+            .iter()
+            .position(|host| host.address == local_address)
+            .ok_or_else(|| {
+                Error::Custom(format!(
+                    "local address {local_address} is not one of the hosts in the layout"
+                ))
+            })?;
+
+        Ok(Self::Multihost {
+            hosts: layout_hosts,
+            local_host,
+        })
+    }
+
+    /// Total number of workers across all hosts.
+    pub fn n_workers(&self) -> usize {
+        match self {
+            Self::Solo { n_workers } => *n_workers,
+            Self::Multihost { hosts, .. } => hosts.last().map_or(0, |host| host.workers.end),
+        }
+    }
+
+    /// Range of indexes of the workers that run in the current process.
+    pub fn local_workers(&self) -> Range<usize> {
+        match self {
+            Self::Solo { n_workers } => 0..*n_workers,
+            Self::Multihost { hosts, local_host } => hosts[*local_host].workers.clone(),
+        }
+    }
+
+    /// Returns `true` if `worker` runs in the current process.
+    pub fn is_local_worker(&self, worker: usize) -> bool {
+        self.local_workers().contains(&worker)
+    }
+
+    /// Returns `true` if workers run in more than one process.
+    pub fn is_multihost(&self) -> bool {
+        matches!(self, Self::Multihost { hosts, .. } if hosts.len() > 1)
+    }
+
+    /// Index of the host that runs `worker`.
+    pub(crate) fn host_of(&self, worker: usize) -> usize {
+        match self {
+            Self::Solo { .. } => 0,
+            Self::Multihost { hosts, .. } => hosts
+                .iter()
+                .position(|host| host.workers.contains(&worker))
+                .unwrap_or_else(|| panic!("worker {worker} is not assigned to any host")),
+        }
+    }
+}
+
+impl From<usize> for Layout {
+    fn from(n_workers: usize) -> Self {
+        Self::new_solo(n_workers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Layout;
+
+    #[test]
+    fn multihost_layout() {
+        let hosts = [
+            ("127.0.0.1:5000".parse().unwrap(), 2),
+            ("127.0.0.1:5001".parse().unwrap(), 3),
+        ];
+
+        let layout = Layout::new_multihost(&hosts, hosts[1].0).unwrap();
+        assert!(layout.is_multihost());
+        assert_eq!(layout.n_workers(), 5);
+        assert_eq!(layout.local_workers(), 2..5);
+        assert_eq!(layout.host_of(0), 0);
+        assert_eq!(layout.host_of(4), 1);
+
+        assert!(Layout::new_multihost(&hosts, "127.0.0.1:5002".parse().unwrap()).is_err());
+        assert!(Layout::new_multihost(&[(hosts[0].0, 1), (hosts[0].0, 1)], hosts[0].0).is_err());
+    }
+}
@@ -17,22 +17,31 @@
 mod activations;
 mod dbsp_handle;
 
+pub(crate) mod network;
 pub(crate) mod runtime;
 
 #[macro_use]
 pub mod metadata;
 pub mod cache;
+pub mod checkpoint;
 pub mod circuit_builder;
+pub mod config;
+pub mod layout;
 pub mod operator_traits;
 pub mod schedule;
 pub mod trace;
 
 pub use activations::{Activations, Activator};
+pub use checkpoint::Checkpoint;
 pub use circuit_builder::{
     ChildCircuit, Circuit, CircuitHandle, ExportId, ExportStream, FeedbackConnector, GlobalNodeId,
     NodeId, OwnershipPreference, RootCircuit, Scope, Stream, WithClock,
 };
+pub use config::{
+    CircuitConfig, StatefulOperator, StorageBackend, StorageCompression, StorageConfig,
+};
 pub use dbsp_handle::DBSPHandle;
+pub use layout::{Host, Layout};
 pub use runtime::{Error as RuntimeError, LocalStore, LocalStoreMarker, Runtime, RuntimeHandle};
 
 pub use schedule::Error as SchedulerError;
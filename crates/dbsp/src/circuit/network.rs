@@ -0,0 +1,387 @@
+//! TCP transport between the hosts of a multihost runtime.
+//!
+//! Every host listens on its address from the [`Layout`] and opens one
+//! connection to each peer.  A host only writes to the connections it opened
+//! and only reads from the connections it accepted, so each connection
+//! carries messages in one direction.  A dedicated thread per accepted
+//! connection dispatches incoming messages to their destination
+//! [`Exchange`](`crate::operator::communication::Exchange`) objects.
+//!
+//! Messages can arrive before the receiving host has created the exchange
+//! they are addressed to, e.g., because its workers are still building the
+//! circuit.  Such messages are buffered until the exchange is registered.
+//!
+//! A host that drops its network announces it to its peers with
+//! [`Message::Shutdown`].  A connection that is closed without this message,
+//! a message that cannot be decoded or delivered, and a failure to send data
+//! to a peer put the network into a failed state (see [`Network::failure`]).
+//! Schedulers check this state before waiting for remote workers and fail
+//! the current step instead of waiting forever.
+
+use crate::{circuit::layout::Layout, Error};
+use bincode::{config::standard, decode_from_std_read, encode_into_std_write, Decode, Encode};
+use std::{
+    collections::HashMap,
+    io::{BufReader, BufWriter, Error as IoError, ErrorKind, Read, Result as IoResult, Write},
+    mem::take,
+    net::{Shutdown, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, Weak,
+    },
+    thread::{sleep, Builder},
+    time::{Duration, Instant},
+};
+
+/// How long to wait for all peers to come up.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Delay between attempts to connect to a peer.
+const RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The receiving end of an exchange in a multihost runtime.
+pub(crate) trait RemoteExchange: Send + Sync {
+    /// Deliver serialized `data` sent by remote worker `sender` to local
+    /// worker `receiver`.
+    ///
+    /// Fails if `data` cannot be deserialized.
+    fn deliver(&self, sender: usize, receiver: usize, data: &[u8]) -> Result<(), Error>;
+
+    /// Notify local worker `sender` that remote worker `receiver` has
+    /// consumed the value previously sent to it.
+    fn acknowledge(&self, sender: usize, receiver: usize);
+
+    /// Wake up all local workers that may be waiting on this exchange, so
+    /// that they notice that the network has failed.
+    fn wake(&self);
+}
+
+#[derive(Debug, Encode, Decode)]
+pub(crate) enum Message {
+    /// Value sent by `sender` to `receiver` in the current round.
+    Data {
+        exchange_id: usize,
+        sender: usize,
+        receiver: usize,
+        data: Vec<u8>,
+    },
+    /// `receiver` has retrieved the value sent to it by `sender`.
+    Ack {
+        exchange_id: usize,
+        sender: usize,
+        receiver: usize,
+    },
+    /// The sending host is shutting down and will close the connection.
+    Shutdown,
+}
+
+impl Message {
+    fn exchange_id(&self) -> usize {
+        match self {
+            Self::Data { exchange_id, .. } | Self::Ack { exchange_id, .. } => *exchange_id,
+            Self::Shutdown => unreachable!("shutdown messages are not addressed to an exchange"),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Directory {
+    exchanges: HashMap<usize, Weak<dyn RemoteExchange>>,
+    /// Messages received for exchanges that have not been registered yet.
+    pending: HashMap<usize, Vec<Message>>,
+}
+
+impl Directory {
+    fn dispatch(&mut self, message: Message) -> Result<(), Error> {
+        let exchange_id = message.exchange_id();
+
+        match self.exchanges.get(&exchange_id) {
+            Some(exchange) => {
+                // The exchange may have been dropped together with the circuit.
+                if let Some(exchange) = exchange.upgrade() {
+                    deliver(&*exchange, message)?;
+                }
+            }
+            None => self.pending.entry(exchange_id).or_default().push(message),
+        }
+        Ok(())
+    }
+}
+
+fn deliver(exchange: &dyn RemoteExchange, message: Message) -> Result<(), Error> {
+    match message {
+        Message::Data {
+            sender,
+            receiver,
+            data,
+            ..
+        } => exchange.deliver(sender, receiver, &data)?,
+        Message::Ack {
+            sender, receiver, ..
+        } => exchange.acknowledge(sender, receiver),
+        Message::Shutdown => unreachable!("shutdown messages are not addressed to an exchange"),
+    }
+    Ok(())
+}
+
+/// Connections from the current host to all of its peers.
+pub(crate) struct Network {
+    layout: Layout,
+    /// Outgoing connections indexed by host; `None` for the local host.
+    peers: Vec<Option<Mutex<BufWriter<TcpStream>>>>,
+    /// Incoming connections, kept so that we can shut them down when the
+    /// network is dropped, which terminates the reader threads.
+    incoming: Vec<TcpStream>,
+    /// Hosts that have announced that they are shutting down, indexed by
+    /// host.
+    departed: Vec<AtomicBool>,
+    directory: Mutex<Directory>,
+    /// Description of the first failure of the network, if any.
+    failure: Mutex<Option<String>>,
+}
+
+impl Network {
+    /// Connect to all peers in `layout`.
+    ///
+    /// Blocks until connections in both directions have been established
+    /// with every peer or until `CONNECT_TIMEOUT` expires.
+    pub(crate) fn connect(layout: &Layout) -> IoResult<Arc<Self>> {
+        let (hosts, local_host) = match layout {
+            Layout::Multihost { hosts, local_host } => (hosts, *local_host),
+            Layout::Solo { .. } => panic!("Network::connect called on a single-host layout"),
+        };
+        let deadline = Instant::now() + CONNECT_TIMEOUT;
+
+        let listener = TcpListener::bind(hosts[local_host].address)?;
+        let npeers = hosts.len() - 1;
+        let acceptor = Builder::new()
+            .name("dbsp-network-accept".to_string())
+            .spawn(move || accept_peers(listener, npeers, deadline))?;
+
+        let mut peers = Vec::with_capacity(hosts.len());
+        for (index, host) in hosts.iter().enumerate() {
+            if index == local_host {
+                peers.push(None);
+                continue;
+            }
+
+            let mut stream = loop {
+                match TcpStream::connect(host.address) {
+                    Ok(stream) => break stream,
+                    Err(error) if Instant::now() >= deadline => {
+                        return Err(IoError::new(
+                            error.kind(),
+                            format!("failed to connect to peer {}: {error}", host.address),
+                        ))
+                    }
+                    Err(_) => sleep(RETRY_INTERVAL),
+                }
+            };
+            stream.set_nodelay(true)?;
+            // Identify ourselves to the peer.
+            stream.write_all(&(local_host as u64).to_le_bytes())?;
+            peers.push(Some(Mutex::new(BufWriter::new(stream))));
+        }
+
+        let incoming = acceptor
+            .join()
+            .map_err(|_| IoError::new(ErrorKind::Other, "network acceptor thread panicked"))??;
+
+        let network = Arc::new(Self {
+            layout: layout.clone(),
+            peers,
+            incoming: incoming
+                .iter()
+                .map(|(_, stream)| stream.try_clone())
+                .collect::<IoResult<_>>()?,
+            departed: hosts.iter().map(|_| AtomicBool::new(false)).collect(),
+            directory: Mutex::new(Directory::default()),
+            failure: Mutex::new(None),
+        });
+
+        for (host, stream) in incoming {
+            let network = Arc::downgrade(&network);
+            Builder::new()
+                .name(format!("dbsp-network-{host}"))
+                .spawn(move || receive_messages(network, host, stream))?;
+        }
+
+        Ok(network)
+    }
+
+    /// Register `exchange` to receive messages addressed to `exchange_id`
+    /// and deliver any messages received before the exchange was created.
+    pub(crate) fn register(&self, exchange_id: usize, exchange: Weak<dyn RemoteExchange>) {
+        let result = {
+            let mut directory = self.directory.lock().unwrap();
+
+            let pending = directory.pending.remove(&exchange_id).unwrap_or_default();
+            directory.exchanges.insert(exchange_id, exchange);
+            pending
+                .into_iter()
+                .try_for_each(|message| directory.dispatch(message))
+        };
+
+        if let Err(error) = result {
+            self.fail(error.to_string());
+        }
+    }
+
+    /// Send `message` to the host that runs `worker`.
+    ///
+    /// Sending data to a host that has shut down fails.  Acknowledgements are
+    /// never reported as failed: a lost acknowledgement only blocks a peer
+    /// that has either shut down or sees its end of the broken connection
+    /// fail.
+    pub(crate) fn send(&self, worker: usize, message: &Message) -> IoResult<()> {
+        let host = self.layout.host_of(worker);
+        let result = if self.departed[host].load(Ordering::Acquire) {
+            Err(IoError::new(
+                ErrorKind::NotConnected,
+                format!("host {host} has shut down"),
+            ))
+        } else {
+            self.send_to_host(host, message)
+        };
+
+        match message {
+            Message::Ack { .. } => Ok(()),
+            _ => result,
+        }
+    }
+
+    /// Send `message` to `host`.  Shuts the connection down on failure, so
+    /// that the peer notices.
+    fn send_to_host(&self, host: usize, message: &Message) -> IoResult<()> {
+        let mut stream = self.peers[host]
+            .as_ref()
+            .unwrap_or_else(|| panic!("host {host} is local"))
+            .lock()
+            .unwrap();
+
+        let result = encode_into_std_write(message, &mut *stream, standard())
+            .map_err(|e| IoError::new(ErrorKind::Other, e.to_string()))
+            .and_then(|_| stream.flush());
+        if result.is_err() {
+            let _ = stream.get_ref().shutdown(Shutdown::Both);
+        }
+        result
+    }
+
+    /// Put the network into a failed state described by `error`, unless it
+    /// has already failed, and wake up all local workers waiting on an
+    /// exchange.
+    pub(crate) fn fail(&self, error: String) {
+        {
+            let mut failure = self.failure.lock().unwrap();
+            if failure.is_some() {
+                return;
+            }
+            *failure = Some(error);
+        }
+
+        // Collect the exchanges first: waking them up may call back into the
+        // scheduler, which must not run while we hold the directory lock.
+        let exchanges: Vec<_> = self
+            .directory
+            .lock()
+            .unwrap()
+            .exchanges
+            .values()
+            .filter_map(Weak::upgrade)
+            .collect();
+        for exchange in exchanges {
+            exchange.wake();
+        }
+    }
+
+    /// Returns a description of the first failure of the network, or `None`
+    /// if the network is healthy.
+    pub(crate) fn failure(&self) -> Option<String> {
+        self.failure.lock().unwrap().clone()
+    }
+}
+
+impl Drop for Network {
+    fn drop(&mut self) {
+        for (host, peer) in self.peers.iter().enumerate() {
+            if peer.is_some() {
+                let _ = self.send_to_host(host, &Message::Shutdown);
+            }
+        }
+        for stream in take(&mut self.incoming) {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+/// Accept connections from `npeers` peers.  Returns the index of each peer
+/// host along with the stream.
+fn accept_peers(
+    listener: TcpListener,
+    npeers: usize,
+    deadline: Instant,
+) -> IoResult<Vec<(usize, TcpStream)>> {
+    listener.set_nonblocking(true)?;
+
+    let mut streams = Vec::with_capacity(npeers);
+    while streams.len() < npeers {
+        match listener.accept() {
+            Ok((mut stream, _)) => {
+                stream.set_nonblocking(false)?;
+                let mut host = [0u8; 8];
+                stream.read_exact(&mut host)?;
+                streams.push((u64::from_le_bytes(host) as usize, stream));
+            }
+            Err(error) if error.kind() == ErrorKind::WouldBlock => {
+                if Instant::now() >= deadline {
+                    return Err(IoError::new(
+                        ErrorKind::TimedOut,
+                        format!(
+                            "timed out waiting for peers to connect ({} out of {npeers} connected)",
+                            streams.len()
+                        ),
+                    ));
+                }
+                sleep(RETRY_INTERVAL);
+            }
+            Err(error) => return Err(error),
+        }
+    }
+
+    Ok(streams)
+}
+
+/// Read messages sent by `host` from `stream` until the connection is closed.
+///
+/// Fails the network if the connection is closed before `host` announced
+/// that it is shutting down, or if a message cannot be decoded or delivered.
+fn receive_messages(network: Weak<Network>, host: usize, stream: TcpStream) {
+    let mut reader = BufReader::new(stream);
+
+    loop {
+        let message = decode_from_std_read::<Message, _, _>(&mut reader, standard());
+
+        let network = match network.upgrade() {
+            Some(network) => network,
+            // The network has been dropped together with the runtime.
+            None => return,
+        };
+
+        let result = match message {
+            Ok(Message::Shutdown) => {
+                network.departed[host].store(true, Ordering::Release);
+                return;
+            }
+            Ok(message) => network.directory.lock().unwrap().dispatch(message),
+            Err(error) => Err(Error::Custom(format!(
+                "connection to host {host} failed: {error}"
+            ))),
+        };
+
+        if let Err(error) = result {
+            network.fail(error.to_string());
+            return;
+        }
+    }
+}
@@ -3,9 +3,12 @@
 //! Operators are the building blocks of DBSP circuits.  An operator
 //! consumes one or more input streams and produces an output stream.
 
-use crate::circuit::{
-    metadata::{OperatorLocation, OperatorMeta},
-    OwnershipPreference, Scope,
+use crate::{
+    circuit::{
+        metadata::{OperatorLocation, OperatorMeta},
+        OwnershipPreference, Scope,
+    },
+    Error,
 };
 use std::borrow::Cow;
 
@@ -194,6 +197,27 @@ pub trait Operator: 'static {
     /// of the fixed point computation, but not as part of an integrator circuit
     /// ([`Stream::integrate`](`crate::circuit::Stream::integrate`)).
     fn fixedpoint(&self, scope: Scope) -> bool;
+
+    /// Serialize the internal state of the operator.
+    ///
+    /// Invoked between clock cycles of the root circuit to write a
+    /// [checkpoint](`crate::circuit::checkpoint`).  Returns `None` if the
+    /// operator is stateless, i.e., a freshly created instance of the operator
+    /// is indistinguishable from `self`.  Stateful operators must return a
+    /// value that [`Self::restore`] can later use to reconstruct their current
+    /// state.
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
+
+    /// Restore the internal state of the operator from a checkpoint.
+    ///
+    /// `state` is the value returned by [`Self::checkpoint`] for the same
+    /// operator in an identically constructed circuit.  Invoked after the
+    /// circuit has been constructed, before its first clock cycle.
+    fn restore(&mut self, _state: &[u8]) -> Result<(), Error> {
+        Ok(())
+    }
 }
 
 /// A source operator that injects data from the outside world or from the
@@ -1,8 +1,18 @@
 //! A multithreaded runtime for evaluating DBSP circuits in a data-parallel
 //! fashion.
 
+use crate::{
+    circuit::{
+        config::{CircuitConfig, StatefulOperator, StorageBackend, StorageConfig},
+        layout::Layout,
+        network::Network,
+    },
+    Error as DBSPError,
+};
 use crossbeam::channel::bounded;
 use crossbeam_utils::sync::{Parker, Unparker};
+#[cfg(feature = "with-bincode")]
+use std::fs;
 use std::{
     cell::{Cell, RefCell},
     fmt,
@@ -50,9 +60,9 @@ thread_local! {
     // if the current thread is not running in a multithreaded runtime.
     static RUNTIME: RefCell<Option<Runtime>> = RefCell::new(None);
 
-    // 0-based index of the current worker thread within its runtime.
-    // Returns `0` if the current thread in not running in a multithreaded
-    // runtime.
+    // 0-based index of the current worker thread within its runtime (across
+    // all hosts).  Returns `0` if the current thread in not running in a
+    // multithreaded runtime.
     pub(crate) static WORKER_INDEX: Cell<usize> = Cell::new(0);
 }
 
@@ -62,23 +72,29 @@ pub struct LocalStoreMarker;
 pub type LocalStore = TypedDashMap<LocalStoreMarker>;
 
 struct RuntimeInner {
-    nworkers: usize,
+    layout: Layout,
+    storage: Option<StorageConfig>,
     store: LocalStore,
+    // Connections to peer hosts in a multihost runtime.
+    network: Option<Arc<Network>>,
 }
 
 impl Debug for RuntimeInner {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         f.debug_struct("RuntimeInner")
-            .field("nworkers", &self.nworkers)
+            .field("layout", &self.layout)
+            .field("storage", &self.storage)
             .finish()
     }
 }
 
 impl RuntimeInner {
-    fn new(nworkers: usize) -> Self {
+    fn new(config: CircuitConfig, network: Option<Arc<Network>>) -> Self {
         Self {
-            nworkers,
+            layout: config.layout,
+            storage: config.storage,
             store: TypedDashMap::new(),
+            network,
         }
     }
 }
@@ -90,6 +106,46 @@ impl RuntimeInner {
 #[derive(Clone, Debug)]
 pub struct Runtime(Arc<RuntimeInner>);
 
+/// Open the persistent storage configured by `storage`, reporting any error
+/// before the runtime starts.
+fn open_storage(storage: &StorageConfig) -> Result<(), DBSPError> {
+    match storage.backend {
+        StorageBackend::Spill => open_spill_storage(storage),
+        StorageBackend::RocksDb => open_rocksdb_storage(storage),
+    }
+}
+
+#[cfg(feature = "with-bincode")]
+fn open_spill_storage(storage: &StorageConfig) -> Result<(), DBSPError> {
+    fs::create_dir_all(&storage.path).map_err(|e| {
+        DBSPError::Custom(format!(
+            "failed to create storage directory '{}': {e}",
+            storage.path.display()
+        ))
+    })
+}
+
+#[cfg(not(feature = "with-bincode"))]
+fn open_spill_storage(storage: &StorageConfig) -> Result<(), DBSPError> {
+    Err(DBSPError::Custom(format!(
+        "cannot spill traces to '{}': DBSP was built without the 'with-bincode' feature",
+        storage.path.display()
+    )))
+}
+
+#[cfg(feature = "persistence")]
+fn open_rocksdb_storage(storage: &StorageConfig) -> Result<(), DBSPError> {
+    crate::trace::persistent::open_db(storage).map(|_| ())
+}
+
+#[cfg(not(feature = "persistence"))]
+fn open_rocksdb_storage(storage: &StorageConfig) -> Result<(), DBSPError> {
+    Err(DBSPError::Custom(format!(
+        "cannot open persistent storage at '{}': DBSP was built without the 'persistence' feature",
+        storage.path.display()
+    )))
+}
+
 impl Runtime {
     /// Create a new runtime with `nworkers` worker threads and run a
     /// user-provided closure in each thread.  The closure takes a reference
@@ -138,10 +194,70 @@ impl Runtime {
     where
         F: FnOnce() + Clone + Send + 'static,
     {
-        let runtime = Self(Arc::new(RuntimeInner::new(workers)));
+        Self::spawn_workers(CircuitConfig::from(workers), None, circuit)
+    }
+
+    /// Create a new runtime configured by `config` and run a user-provided
+    /// closure in each local worker thread.
+    ///
+    /// With a [`Layout::Multihost`] layout, this function runs in each
+    /// participating process.  It first connects to all peer hosts over TCP,
+    /// blocking until all of them are up, and then spawns threads for the
+    /// workers assigned to the current host.  Exchange operators
+    /// instantiated by these workers transparently send data to workers in
+    /// other processes over the network.
+    ///
+    /// If `config` specifies persistent storage, the storage is opened before
+    /// starting the workers.  Stateful operators selected by the storage
+    /// configuration then keep their traces on disk.
+    ///
+    /// Fails if the runtime cannot listen on the local host's address or
+    /// cannot connect to one of its peers, or if the storage cannot be
+    /// opened.  Persistent storage requires the feature of its
+    /// [`StorageBackend`], and multihost layouts require the `with-bincode`
+    /// feature, which makes data serializable.
+    pub fn run_with_config<F>(
+        config: impl Into<CircuitConfig>,
+        circuit: F,
+    ) -> Result<RuntimeHandle, DBSPError>
+    where
+        F: FnOnce() + Clone + Send + 'static,
+    {
+        let config = config.into();
+
+        #[cfg(not(feature = "with-bincode"))]
+        if config.layout.is_multihost() {
+            return Err(DBSPError::Custom(
+                "multihost runtimes require the 'with-bincode' feature".to_string(),
+            ));
+        }
+
+        if let Some(storage) = &config.storage {
+            open_storage(storage)?;
+        }
+
+        let network = if config.layout.is_multihost() {
+            Some(Network::connect(&config.layout)?)
+        } else {
+            None
+        };
+
+        Ok(Self::spawn_workers(config, network, circuit))
+    }
+
+    fn spawn_workers<F>(
+        config: CircuitConfig,
+        network: Option<Arc<Network>>,
+        circuit: F,
+    ) -> RuntimeHandle
+    where
+        F: FnOnce() + Clone + Send + 'static,
+    {
+        let local_workers = config.layout.local_workers();
+        let runtime = Self(Arc::new(RuntimeInner::new(config, network)));
 
-        let mut handles = Vec::with_capacity(workers);
-        handles.extend((0..workers).map(|worker_index| {
+        let mut handles = Vec::with_capacity(local_workers.len());
+        handles.extend(local_workers.map(|worker_index| {
             let runtime = runtime.clone();
             let build_circuit = circuit.clone();
 
@@ -172,7 +288,7 @@ impl Runtime {
             (join_handle, init_receiver)
         }));
 
-        let mut workers = Vec::with_capacity(workers);
+        let mut workers = Vec::with_capacity(handles.len());
         workers.extend(handles.into_iter().map(|(handle, recv)| {
             let (unparker, kill_signal) = recv.recv().unwrap();
             WorkerHandle::new(handle, unparker, kill_signal)
@@ -198,17 +314,64 @@ impl Runtime {
     /// Returns 0-based index of the current worker thread within its
     /// runtime.  For threads that run without a runtime, this method
     /// returns `0`.
+    ///
+    /// In a multihost runtime, worker indexes are unique across all hosts.
     pub fn worker_index() -> usize {
         WORKER_INDEX.with(|index| index.get())
     }
 
+    /// Returns 0-based index of the current worker thread among the workers
+    /// running in the current process.
+    ///
+    /// This is the same as [`Self::worker_index`], unless the worker belongs
+    /// to a multihost runtime.
+    pub fn local_worker_index() -> usize {
+        Self::worker_index()
+            - Self::runtime().map_or(0, |runtime| runtime.layout().local_workers().start)
+    }
+
     fn inner(&self) -> &RuntimeInner {
         &self.0
     }
 
-    /// Returns the number of workers in this runtime.
+    /// Returns the number of workers in this runtime, across all hosts.
     pub fn num_workers(&self) -> usize {
-        self.inner().nworkers
+        self.inner().layout.n_workers()
+    }
+
+    /// Returns the number of workers that run in the current process.
+    pub fn num_local_workers(&self) -> usize {
+        self.inner().layout.local_workers().len()
+    }
+
+    /// Returns the assignment of workers to hosts in this runtime.
+    pub fn layout(&self) -> &Layout {
+        &self.inner().layout
+    }
+
+    /// Returns the persistent storage configuration of this runtime, or
+    /// `None` if all operators keep their state in memory.
+    pub fn storage(&self) -> Option<&StorageConfig> {
+        self.inner().storage.as_ref()
+    }
+
+    /// Returns `true` if `operator` instantiated in the current worker thread
+    /// should keep its state in persistent storage.
+    ///
+    /// Always returns `false` outside of a runtime or if the runtime was
+    /// created without persistent storage.
+    pub fn persistent_state(operator: StatefulOperator) -> bool {
+        Self::runtime().map_or(false, |runtime| {
+            runtime
+                .storage()
+                .map_or(false, |storage| storage.is_persistent(operator))
+        })
+    }
+
+    /// Returns connections to peer hosts, or `None` if all workers in this
+    /// runtime run in the current process.
+    pub(crate) fn network(&self) -> Option<&Arc<Network>> {
+        self.inner().network.as_ref()
     }
 
     /// Returns reference to the data store shared by all workers within the
@@ -230,7 +393,7 @@ impl Runtime {
     /// same across all worker threads.  Repeated calls to this function
     /// with the same worker index generate numbers 0, 1, 2, ...
     pub fn sequence_next(&self, worker_index: usize) -> usize {
-        debug_assert!(worker_index < self.inner().nworkers);
+        debug_assert!(worker_index < self.num_workers());
         let mut entry = self
             .local_store()
             .entry(WorkerId(worker_index))
@@ -257,6 +420,14 @@ impl Runtime {
     pub fn kill_in_progress() -> bool {
         KILL_SIGNAL.with(|signal| signal.load(Ordering::SeqCst))
     }
+
+    /// Returns a description of the failure of the network that connects
+    /// the hosts of the current multihost runtime, if it has failed.
+    /// Schedulers should use this method before parking, since remote
+    /// workers that an operator is waiting for may never respond.
+    pub(crate) fn network_failure() -> Option<String> {
+        Runtime::runtime()?.network()?.failure()
+    }
 }
 
 /// Per-worker controls.
@@ -298,6 +469,9 @@ impl RuntimeHandle {
     /// Workers release the CPU by parking when they have no work to do.
     /// This method unparks a thread after sending a command to it or
     /// when killing a circuit.
+    ///
+    /// `worker` is the index of the worker among workers in the current
+    /// process (see [`Runtime::local_worker_index`]).
     pub(super) fn unpark_worker(&self, worker: usize) {
         self.workers[worker].unpark();
     }
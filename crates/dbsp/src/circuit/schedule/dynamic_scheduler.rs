@@ -356,8 +356,12 @@ impl Inner {
                     self.process_notifications(circuit);
 
                     // Still nothing to do -- sleep waiting for a notification to
-                    // unpark us.
+                    // unpark us, unless we are waiting for remote workers
+                    // that we can no longer reach.
                     if self.runnable.is_empty() {
+                        if let Some(error) = Runtime::network_failure() {
+                            return Err(Error::NetworkFailure(error));
+                        }
                         circuit.log_scheduler_event(&SchedulerEvent::wait_start(
                             circuit.global_id().deref(),
                         ));
@@ -28,6 +28,10 @@ pub enum Error {
     /// Execution of the circuit interrupted by the user (via
     /// [`RuntimeHandle::kill`](`crate::circuit::RuntimeHandle::kill`)).
     Killed,
+    /// Communication with workers on other hosts of a multihost runtime
+    /// failed, e.g., because a peer closed its connection or sent data that
+    /// could not be deserialized.
+    NetworkFailure(String),
 }
 
 impl Display for Error {
@@ -41,6 +45,7 @@ impl Display for Error {
                 write!(f, "unschedulable circuit due to a cyclic topology: cycle through node '{node_id}'")
             }
             Self::Killed => f.write_str("circuit has been killed by the user"),
+            Self::NetworkFailure(error) => write!(f, "network failure: {error}"),
         }
     }
 }
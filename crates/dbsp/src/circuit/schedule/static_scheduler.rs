@@ -70,6 +70,9 @@ impl Scheduler for StaticScheduler {
                         circuit.eval_node(*node_id)?;
                         break;
                     }
+                    if let Some(error) = Runtime::network_failure() {
+                        return Err(Error::NetworkFailure(error));
+                    }
                     circuit.log_scheduler_event(&SchedulerEvent::wait_start(
                         circuit.global_id().deref(),
                     ));
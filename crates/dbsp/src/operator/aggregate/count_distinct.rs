@@ -0,0 +1,91 @@
+use crate::{
+    algebra::{MonoidValue, Semigroup},
+    operator::aggregate::Aggregator,
+    trace::Cursor,
+    DBData, Timestamp,
+};
+use std::{cmp::Ordering, marker::PhantomData};
+
+/// An [aggregator](`crate::operator::Aggregator`) that counts distinct
+/// values with non-zero weight.
+///
+/// The accumulator is the sorted list of distinct values in the group, so
+/// that partial aggregates over overlapping subsets of the group (e.g.,
+/// partitions of a rolling window) can be combined without double-counting.
+/// Its size is proportional to the number of distinct values; use
+/// [`ApproxCountDistinct`](`crate::operator::ApproxCountDistinct`) to count
+/// distinct values in large groups in constant space.
+///
+/// Like all aggregators, `CountDistinct` is evaluated over the net weights
+/// of the group, so retracting the last occurrence of a value removes it
+/// from the count.
+#[derive(Clone)]
+pub struct CountDistinct;
+
+#[derive(Clone)]
+pub struct CountDistinctSemigroup<V>(PhantomData<V>);
+
+impl<V> Semigroup<Vec<V>> for CountDistinctSemigroup<V>
+where
+    V: Ord + Clone,
+{
+    fn combine(left: &Vec<V>, right: &Vec<V>) -> Vec<V> {
+        let mut result = Vec::with_capacity(left.len() + right.len());
+        let (mut left, mut right) = (left.iter().peekable(), right.iter().peekable());
+
+        while let (Some(l), Some(r)) = (left.peek(), right.peek()) {
+            match l.cmp(r) {
+                Ordering::Less => result.push(left.next().unwrap().clone()),
+                Ordering::Greater => result.push(right.next().unwrap().clone()),
+                Ordering::Equal => {
+                    result.push(left.next().unwrap().clone());
+                    right.next();
+                }
+            }
+        }
+        result.extend(left.cloned());
+        result.extend(right.cloned());
+
+        result
+    }
+}
+
+impl<V, T, R> Aggregator<V, T, R> for CountDistinct
+where
+    V: DBData,
+    T: Timestamp,
+    R: MonoidValue,
+{
+    type Accumulator = Vec<V>;
+    type Output = u64;
+    type Semigroup = CountDistinctSemigroup<V>;
+
+    fn aggregate<C>(&self, cursor: &mut C) -> Option<Self::Accumulator>
+    where
+        C: Cursor<V, (), T, R>,
+    {
+        let mut values = Vec::new();
+
+        while cursor.key_valid() {
+            let weight = cursor.fold_times(R::zero(), |mut acc, _, weight| {
+                acc.add_assign_by_ref(weight);
+                acc
+            });
+            if !weight.is_zero() {
+                values.push(cursor.key().clone());
+            }
+
+            cursor.step_key();
+        }
+
+        if values.is_empty() {
+            None
+        } else {
+            Some(values)
+        }
+    }
+
+    fn finalize(&self, accumulator: Self::Accumulator) -> Self::Output {
+        accumulator.len() as u64
+    }
+}
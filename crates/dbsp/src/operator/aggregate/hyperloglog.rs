@@ -0,0 +1,291 @@
+use crate::{
+    algebra::{MonoidValue, Semigroup},
+    operator::aggregate::Aggregator,
+    trace::Cursor,
+    DBData, Timestamp,
+};
+use bincode::{Decode, Encode};
+use size_of::SizeOf;
+use std::hash::{Hash, Hasher};
+use xxhash_rust::xxh3::Xxh3;
+
+/// Seed of the hash function applied to values.  Deliberately different
+/// from the seed used to shard records across workers, so that the
+/// values in a shard are not biased towards a subset of registers.
+const HLL_SEED: u64 = 0x2f1c_3a5e_97d4_b061u64;
+
+/// An approximate distinct-count sketch.
+///
+/// This is the accumulator of the [`ApproxCountDistinct`] aggregator.  It
+/// consists of `2^precision` registers that record the maximum number of
+/// leading zeros observed in the hashes of the values assigned to each
+/// register.  Sketches form a semigroup with register-wise maximum as the
+/// `+` operation: combining the sketches of two sets yields the sketch of
+/// their union.
+///
+/// The default sketch has no registers and is the neutral element of this
+/// semigroup.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, SizeOf, Encode, Decode)]
+pub struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl HyperLogLog {
+    /// Create an empty sketch with `2^precision` registers.
+    pub fn new(precision: u8) -> Self {
+        assert!(
+            (ApproxCountDistinct::MIN_PRECISION..=ApproxCountDistinct::MAX_PRECISION)
+                .contains(&precision),
+            "HyperLogLog precision must be between {} and {}",
+            ApproxCountDistinct::MIN_PRECISION,
+            ApproxCountDistinct::MAX_PRECISION,
+        );
+
+        Self {
+            registers: vec![0; 1 << precision],
+        }
+    }
+
+    /// Add `value` to the sketch.
+    pub fn insert<V>(&mut self, value: &V)
+    where
+        V: Hash,
+    {
+        let mut hasher = Xxh3::with_seed(HLL_SEED);
+        value.hash(&mut hasher);
+        self.insert_hash(hasher.finish());
+    }
+
+    fn insert_hash(&mut self, hash: u64) {
+        let precision = self.registers.len().trailing_zeros();
+        let index = (hash >> (64 - precision)) as usize;
+
+        // Set the highest bit below the index bits to bound the rank by
+        // `64 - precision + 1`.
+        let rest = (hash << precision) | (1 << (precision - 1));
+        let rank = rest.leading_zeros() as u8 + 1;
+
+        if rank > self.registers[index] {
+            self.registers[index] = rank;
+        }
+    }
+
+    /// Merge the registers of `other` into `self`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if both sketches are non-empty and have different precisions.
+    pub fn merge(&mut self, other: &Self) {
+        if other.registers.is_empty() {
+            return;
+        }
+        if self.registers.is_empty() {
+            self.registers = other.registers.clone();
+            return;
+        }
+
+        assert_eq!(
+            self.registers.len(),
+            other.registers.len(),
+            "cannot merge HyperLogLog sketches of different precisions"
+        );
+        for (register, other) in self.registers.iter_mut().zip(other.registers.iter()) {
+            *register = (*register).max(*other);
+        }
+    }
+
+    /// Estimated number of distinct values added to the sketch.
+    pub fn estimate(&self) -> u64 {
+        if self.registers.is_empty() {
+            return 0;
+        }
+
+        let m = self.registers.len() as f64;
+        let alpha = match self.registers.len() {
+            16 => 0.673,
+            32 => 0.697,
+            64 => 0.709,
+            _ => 0.7213 / (1.0 + 1.079 / m),
+        };
+
+        let mut sum = 0.0;
+        let mut zeros = 0usize;
+        for register in self.registers.iter() {
+            sum += 1.0 / (1u64 << *register) as f64;
+            if *register == 0 {
+                zeros += 1;
+            }
+        }
+
+        let estimate = alpha * m * m / sum;
+
+        // Small range correction: fall back to linear counting while there
+        // are empty registers.
+        if estimate <= 2.5 * m && zeros > 0 {
+            (m * (m / zeros as f64).ln()).round() as u64
+        } else {
+            estimate.round() as u64
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct HyperLogLogSemigroup;
+
+impl Semigroup<HyperLogLog> for HyperLogLogSemigroup {
+    fn combine(left: &HyperLogLog, right: &HyperLogLog) -> HyperLogLog {
+        let mut result = left.clone();
+        result.merge(right);
+        result
+    }
+}
+
+/// An [aggregator](`crate::operator::Aggregator`) that estimates the number
+/// of distinct values with non-zero weight using the HyperLogLog algorithm.
+///
+/// Unlike [`CountDistinct`](`crate::operator::CountDistinct`), the
+/// accumulator of this aggregator has a fixed size of `2^precision` bytes,
+/// regardless of the number of values in the group.  The relative standard
+/// error of the estimate is about `1.04 / sqrt(2^precision)`, i.e., 1.6% for
+/// the default precision of 12.
+#[derive(Clone)]
+pub struct ApproxCountDistinct {
+    precision: u8,
+}
+
+impl ApproxCountDistinct {
+    /// Smallest supported precision.
+    pub const MIN_PRECISION: u8 = 4;
+
+    /// Largest supported precision.
+    pub const MAX_PRECISION: u8 = 18;
+
+    /// Precision used by [`Self::default`].
+    pub const DEFAULT_PRECISION: u8 = 12;
+
+    /// Create an aggregator that uses sketches with `2^precision` registers.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `precision` is not between [`Self::MIN_PRECISION`] and
+    /// [`Self::MAX_PRECISION`].
+    pub fn new(precision: u8) -> Self {
+        // Validate the precision eagerly rather than when the first group is
+        // aggregated.
+        HyperLogLog::new(precision);
+        Self { precision }
+    }
+
+    /// Precision of the sketches computed by this aggregator.
+    pub fn precision(&self) -> u8 {
+        self.precision
+    }
+}
+
+impl Default for ApproxCountDistinct {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_PRECISION)
+    }
+}
+
+impl<V, T, R> Aggregator<V, T, R> for ApproxCountDistinct
+where
+    V: DBData,
+    T: Timestamp,
+    R: MonoidValue,
+{
+    type Accumulator = HyperLogLog;
+    type Output = u64;
+    type Semigroup = HyperLogLogSemigroup;
+
+    fn aggregate<C>(&self, cursor: &mut C) -> Option<Self::Accumulator>
+    where
+        C: Cursor<V, (), T, R>,
+    {
+        let mut sketch = None;
+
+        while cursor.key_valid() {
+            let weight = cursor.fold_times(R::zero(), |mut acc, _, weight| {
+                acc.add_assign_by_ref(weight);
+                acc
+            });
+            if !weight.is_zero() {
+                sketch
+                    .get_or_insert_with(|| HyperLogLog::new(self.precision))
+                    .insert(cursor.key());
+            }
+
+            cursor.step_key();
+        }
+
+        sketch
+    }
+
+    fn finalize(&self, accumulator: Self::Accumulator) -> Self::Output {
+        accumulator.estimate()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ApproxCountDistinct, HyperLogLog};
+
+    fn sketch<I>(precision: u8, values: I) -> HyperLogLog
+    where
+        I: IntoIterator<Item = u64>,
+    {
+        let mut sketch = HyperLogLog::new(precision);
+        for value in values {
+            sketch.insert(&value);
+        }
+        sketch
+    }
+
+    fn assert_close(estimate: u64, expected: u64, tolerance: f64) {
+        let error = (estimate as f64 - expected as f64).abs() / expected as f64;
+        assert!(
+            error <= tolerance,
+            "estimate {estimate} too far from {expected}"
+        );
+    }
+
+    #[test]
+    fn estimate() {
+        assert_eq!(HyperLogLog::default().estimate(), 0);
+        assert_eq!(HyperLogLog::new(12).estimate(), 0);
+
+        // Small cardinalities are counted almost exactly.
+        assert_close(sketch(12, 0..10).estimate(), 10, 0.1);
+
+        // Duplicates don't affect the estimate.
+        assert_close(sketch(12, (0..1000).chain(0..1000)).estimate(), 1000, 0.05);
+
+        for precision in [
+            ApproxCountDistinct::MIN_PRECISION,
+            ApproxCountDistinct::DEFAULT_PRECISION,
+            ApproxCountDistinct::MAX_PRECISION,
+        ] {
+            let tolerance = 5.0 * 1.04 / ((1u64 << precision) as f64).sqrt();
+            assert_close(sketch(precision, 0..100_000).estimate(), 100_000, tolerance);
+        }
+    }
+
+    #[test]
+    fn merge() {
+        let mut left = sketch(10, 0..6000);
+        left.merge(&sketch(10, 4000..10_000));
+        assert_eq!(left, sketch(10, 0..10_000));
+
+        let mut empty = HyperLogLog::default();
+        empty.merge(&left);
+        assert_eq!(empty, left);
+        left.merge(&HyperLogLog::default());
+        assert_eq!(empty, left);
+    }
+
+    #[test]
+    #[should_panic]
+    fn merge_precision_mismatch() {
+        sketch(10, 0..10).merge(&sketch(12, 0..10));
+    }
+}
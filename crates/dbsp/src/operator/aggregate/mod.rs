@@ -8,6 +8,8 @@ use std::{
     marker::PhantomData,
 };
 
+use num::PrimInt;
+
 use crate::{
     algebra::{
         DefaultSemigroup, GroupValue, HasOne, HasZero, IndexedZSet, Lattice, MulByRef,
@@ -15,8 +17,9 @@ use crate::{
     },
     circuit::{
         operator_traits::{BinaryOperator, Operator, UnaryOperator},
-        Circuit, Scope, Stream, WithClock,
+        Circuit, RootCircuit, Scope, StatefulOperator, Stream, WithClock,
     },
+    operator::trace::TraceBound,
     time::Timestamp,
     trace::{
         cursor::{Cursor, CursorGroup},
@@ -27,14 +30,20 @@ use crate::{
 
 // Some standard aggregators.
 mod average;
+mod count_distinct;
 mod fold;
+mod hyperloglog;
 mod max;
 mod min;
+mod percentile;
 
 pub use average::Avg;
+pub use count_distinct::{CountDistinct, CountDistinctSemigroup};
 pub use fold::Fold;
+pub use hyperloglog::{ApproxCountDistinct, HyperLogLog, HyperLogLogSemigroup};
 pub use max::{Max, MaxSemigroup};
 pub use min::{Min, MinSemigroup};
+pub use percentile::{Median, Percentile, PercentileSemigroup};
 
 /// A trait for aggregator objects.  An aggregator summarizes the contents
 /// of a Z-set into a single value.
@@ -245,6 +254,24 @@ where
 
     /// Like [`Self::aggregate`], but can return any batch type.
     pub fn aggregate_generic<A, O>(&self, aggregator: A) -> Stream<C, O>
+    where
+        Z: IndexedZSet + Send,
+        A: Aggregator<Z::Val, <C as WithClock>::Time, Z::R>,
+        O: Batch<Key = Z::Key, Val = A::Output, Time = ()>,
+        O::R: ZRingValue,
+    {
+        self.aggregate_generic_with_key_bound(aggregator, TraceBound::new())
+    }
+
+    /// Like [`Self::aggregate_generic`], but allows the input and output
+    /// traces of the operator to be truncated below `key_bound`.  The caller
+    /// must guarantee that `self` won't contain keys below the bound in the
+    /// future.
+    fn aggregate_generic_with_key_bound<A, O>(
+        &self,
+        aggregator: A,
+        key_bound: TraceBound<Z::Key>,
+    ) -> Stream<C, O>
     where
         Z: IndexedZSet + Send,
         A: Aggregator<Z::Val, <C as WithClock>::Time, Z::R>,
@@ -270,9 +297,15 @@ where
             .add_binary_operator(
                 AggregateIncremental::new(aggregator, circuit.clone()),
                 &stream,
-                &stream.trace::<Spine<<<C as WithClock>::Time as Timestamp>::OrdValBatch<Z::Key, Z::Val, Z::R>>>(),
+                &stream.trace_inner::<Spine<
+                    <<C as WithClock>::Time as Timestamp>::OrdValBatch<Z::Key, Z::Val, Z::R>,
+                >>(
+                    StatefulOperator::Aggregate,
+                    key_bound.clone(),
+                    TraceBound::new(),
+                ),
             )
-            .upsert::<O>()
+            .upsert_with_key_bound::<O>(key_bound)
             .mark_sharded()
     }
 
@@ -358,6 +391,51 @@ where
     }
 }
 
+impl<Z> Stream<RootCircuit, Z>
+where
+    Z: IndexedZSet + Send,
+    Z::Key: PrimInt,
+    Z::R: ZRingValue,
+{
+    /// Incremental aggregation of a collection indexed by timestamp, which
+    /// discards state older than the watermark.
+    ///
+    /// Behaves like [`aggregate`](`Self::aggregate`), except that records
+    /// whose keys are more than `lateness` behind `watermark` are considered
+    /// late and are discarded.  Since no updates to such keys can arrive
+    /// after the watermark has moved past them, the operator drops its input
+    /// and output state for these keys, allowing unbounded streams to be
+    /// aggregated in bounded memory.
+    ///
+    /// At each clock cycle, the operator applies the watermark from the
+    /// previous clock cycle to filter late inputs.
+    ///
+    /// # Arguments
+    ///
+    /// * `aggregator` - aggregator applied to each key.
+    /// * `watermark` - monotonically growing lower bound on keys in `self`,
+    ///   e.g., computed using
+    ///   [`watermark_monotonic`](`Stream::watermark_monotonic`).
+    /// * `lateness` - how far behind the watermark a record can arrive without
+    ///   being discarded.
+    #[allow(clippy::type_complexity)]
+    pub fn aggregate_with_watermark<A>(
+        &self,
+        aggregator: A,
+        watermark: &Stream<RootCircuit, Z::Key>,
+        lateness: Z::Key,
+    ) -> Stream<RootCircuit, OrdIndexedZSet<Z::Key, A::Output, Z::R>>
+    where
+        A: Aggregator<Z::Val, (), Z::R>,
+    {
+        let key_bound = TraceBound::new();
+
+        self.shard()
+            .discard_late_keys(watermark, lateness, key_bound.clone())
+            .aggregate_generic_with_key_bound(aggregator, key_bound)
+    }
+}
+
 /// Non-incremental aggregation operator.
 struct Aggregate<Z, A, O> {
     aggregator: A,
@@ -731,7 +809,7 @@ mod test {
         algebra::DefaultSemigroup,
         indexed_zset,
         operator::GeneratorNested,
-        operator::{Fold, Min},
+        operator::{ApproxCountDistinct, CountDistinct, Fold, Max, Median, Min, Percentile},
         trace::{cursor::Cursor, Batch, BatchReader},
         zset, Circuit, OrdIndexedZSet, OrdZSet, RootCircuit, Runtime, Stream,
     };
@@ -872,6 +950,48 @@ mod test {
                         assert_eq!(d1, d2);
                     });
 
+                let count_distinct_inc = input.aggregate(CountDistinct).gather(0);
+                let count_distinct_noninc = input
+                    .integrate_nested()
+                    .integrate()
+                    .stream_aggregate(CountDistinct)
+                    .differentiate()
+                    .differentiate_nested()
+                    .gather(0);
+
+                count_distinct_inc
+                    .apply2(
+                        &count_distinct_noninc,
+                        |d1: &OrdIndexedZSet<usize, u64, isize>,
+                         d2: &OrdIndexedZSet<usize, u64, isize>| {
+                            (d1.clone(), d2.clone())
+                        },
+                    )
+                    .inspect(|(d1, d2)| {
+                        assert_eq!(d1, d2);
+                    });
+
+                let median_inc = input.aggregate(Median).gather(0);
+                let median_noninc = input
+                    .integrate_nested()
+                    .integrate()
+                    .stream_aggregate(Median)
+                    .differentiate()
+                    .differentiate_nested()
+                    .gather(0);
+
+                median_inc
+                    .apply2(
+                        &median_noninc,
+                        |d1: &OrdIndexedZSet<usize, isize, isize>,
+                         d2: &OrdIndexedZSet<usize, isize, isize>| {
+                            (d1.clone(), d2.clone())
+                        },
+                    )
+                    .inspect(|(d1, d2)| {
+                        assert_eq!(d1, d2);
+                    });
+
                 Ok((
                     move || {
                         *counter.borrow_mut() += 1;
@@ -1062,4 +1182,160 @@ mod test {
     fn count_test4() {
         count_test(4);
     }
+
+    fn distinct_and_percentile_test(workers: usize) {
+        type Output<V> = Arc<Mutex<OrdIndexedZSet<usize, V, isize>>>;
+
+        let count_distinct_output: Output<u64> = Arc::new(Mutex::new(indexed_zset! {}));
+        let approx_count_distinct_output: Output<u64> = Arc::new(Mutex::new(indexed_zset! {}));
+        let median_output: Output<usize> = Arc::new(Mutex::new(indexed_zset! {}));
+        let p90_output: Output<usize> = Arc::new(Mutex::new(indexed_zset! {}));
+
+        let count_distinct_output_clone = count_distinct_output.clone();
+        let approx_count_distinct_output_clone = approx_count_distinct_output.clone();
+        let median_output_clone = median_output.clone();
+        let p90_output_clone = p90_output.clone();
+
+        let (mut dbsp, mut input_handle) = Runtime::init_circuit(workers, move |circuit| {
+            let (input_stream, input_handle) = circuit.add_input_indexed_zset();
+
+            fn export<V: crate::DBData>(
+                stream: Stream<RootCircuit, OrdIndexedZSet<usize, V, isize>>,
+                output: Output<V>,
+            ) {
+                stream.integrate().gather(0).inspect(move |batch| {
+                    if Runtime::worker_index() == 0 {
+                        *output.lock().unwrap() = batch.clone();
+                    }
+                });
+            }
+
+            export(input_stream.aggregate(CountDistinct), count_distinct_output);
+            export(
+                input_stream.aggregate(ApproxCountDistinct::default()),
+                approx_count_distinct_output,
+            );
+            export(input_stream.aggregate(Median), median_output);
+            export(input_stream.aggregate(Percentile::new(0.9)), p90_output);
+
+            input_handle
+        })
+        .unwrap();
+
+        input_handle.append(&mut vec![
+            (1, (1, 1)),
+            (1, (2, 3)),
+            (1, (3, 1)),
+            (1, (10, 1)),
+            (2, (5, 1)),
+        ]);
+        dbsp.step().unwrap();
+        assert_eq!(
+            &*count_distinct_output_clone.lock().unwrap(),
+            &indexed_zset! {1 => {4 => 1}, 2 => {1 => 1}}
+        );
+        assert_eq!(
+            &*approx_count_distinct_output_clone.lock().unwrap(),
+            &indexed_zset! {1 => {4 => 1}, 2 => {1 => 1}}
+        );
+        assert_eq!(
+            &*median_output_clone.lock().unwrap(),
+            &indexed_zset! {1 => {2 => 1}, 2 => {5 => 1}}
+        );
+        assert_eq!(
+            &*p90_output_clone.lock().unwrap(),
+            &indexed_zset! {1 => {10 => 1}, 2 => {5 => 1}}
+        );
+
+        // Retract some of the values.
+        input_handle.append(&mut vec![(1, (2, -3)), (1, (10, -1)), (2, (5, -1))]);
+        dbsp.step().unwrap();
+        assert_eq!(
+            &*count_distinct_output_clone.lock().unwrap(),
+            &indexed_zset! {1 => {2 => 1}}
+        );
+        assert_eq!(
+            &*approx_count_distinct_output_clone.lock().unwrap(),
+            &indexed_zset! {1 => {2 => 1}}
+        );
+        assert_eq!(
+            &*median_output_clone.lock().unwrap(),
+            &indexed_zset! {1 => {1 => 1}}
+        );
+        assert_eq!(
+            &*p90_output_clone.lock().unwrap(),
+            &indexed_zset! {1 => {3 => 1}}
+        );
+
+        dbsp.kill().unwrap();
+    }
+
+    #[test]
+    fn distinct_and_percentile_test1() {
+        distinct_and_percentile_test(1);
+    }
+
+    #[test]
+    fn distinct_and_percentile_test4() {
+        distinct_and_percentile_test(4);
+    }
+
+    fn aggregate_with_watermark_test(workers: usize) {
+        let output = Arc::new(Mutex::new(indexed_zset! {}));
+        let output_clone = output.clone();
+
+        let (mut dbsp, mut input_handle) = Runtime::init_circuit(workers, move |circuit| {
+            let (input_stream, input_handle) = circuit.add_input_indexed_zset::<u64, u64, isize>();
+            let watermark = input_stream.watermark_monotonic(|ts| *ts);
+
+            input_stream
+                .aggregate_with_watermark(Max, &watermark, 5)
+                .integrate()
+                .gather(0)
+                .inspect(move |batch| {
+                    if Runtime::worker_index() == 0 {
+                        *output_clone.lock().unwrap() = batch.clone();
+                    }
+                });
+
+            input_handle
+        })
+        .unwrap();
+
+        input_handle.append(&mut vec![(10, (1, 1)), (12, (2, 1)), (12, (3, 1))]);
+        dbsp.step().unwrap();
+        assert_eq!(
+            &*output.lock().unwrap(),
+            &indexed_zset! {10 => {1 => 1}, 12 => {3 => 1}}
+        );
+
+        // Watermark: 12, records with timestamps below 7 are late.
+        input_handle.append(&mut vec![(5, (9, 1)), (12, (7, 1)), (20, (1, 1))]);
+        dbsp.step().unwrap();
+        assert_eq!(
+            &*output.lock().unwrap(),
+            &indexed_zset! {10 => {1 => 1}, 12 => {7 => 1}, 20 => {1 => 1}}
+        );
+
+        // Watermark: 20, records with timestamps below 15 are late,
+        // including retractions.
+        input_handle.append(&mut vec![(12, (100, 1)), (10, (1, -1)), (18, (4, 1))]);
+        dbsp.step().unwrap();
+        assert_eq!(
+            &*output.lock().unwrap(),
+            &indexed_zset! {10 => {1 => 1}, 12 => {7 => 1}, 18 => {4 => 1}, 20 => {1 => 1}}
+        );
+
+        dbsp.kill().unwrap();
+    }
+
+    #[test]
+    fn aggregate_with_watermark_test1() {
+        aggregate_with_watermark_test(1);
+    }
+
+    #[test]
+    fn aggregate_with_watermark_test4() {
+        aggregate_with_watermark_test(4);
+    }
 }
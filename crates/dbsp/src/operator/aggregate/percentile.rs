@@ -0,0 +1,209 @@
+use crate::{
+    algebra::{MonoidValue, Semigroup},
+    operator::aggregate::Aggregator,
+    trace::Cursor,
+    DBData, Timestamp,
+};
+use num::ToPrimitive;
+use std::{cmp::Ordering, marker::PhantomData};
+
+/// An [aggregator](`crate::operator::Aggregator`) that returns the value at
+/// a given percentile of the group.
+///
+/// The percentile is computed using the nearest-rank method over the
+/// multiset of values with positive weight, where each value occurs as many
+/// times as its weight: the `p`-th percentile of `N` values is the smallest
+/// value such that at least `ceil(p * N)` values are less than or equal to
+/// it.  The result is always one of the values in the group; values with
+/// non-positive weights are ignored.
+///
+/// The accumulator is the sorted list of distinct values in the group with
+/// their multiplicities, so that partial aggregates over subsets of the
+/// group (e.g., partitions of a rolling window) can be combined.  Its size
+/// is proportional to the number of distinct values in the group.
+#[derive(Clone)]
+pub struct Percentile {
+    percentile: f64,
+}
+
+impl Percentile {
+    /// Create an aggregator that computes the `percentile`-th percentile,
+    /// where `percentile` is a fraction between `0.0` and `1.0`, e.g.,
+    /// `0.99` for the 99th percentile.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `percentile` is not between `0.0` and `1.0`.
+    pub fn new(percentile: f64) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&percentile),
+            "percentile must be between 0.0 and 1.0, found {percentile}"
+        );
+
+        Self { percentile }
+    }
+
+    /// The percentile computed by this aggregator.
+    pub fn percentile(&self) -> f64 {
+        self.percentile
+    }
+}
+
+/// An [aggregator](`crate::operator::Aggregator`) that returns the median of
+/// the group.
+///
+/// This is equivalent to [`Percentile::new(0.5)`](`Percentile::new`), i.e.,
+/// it returns the lower median of groups with an even number of values.
+#[derive(Clone)]
+pub struct Median;
+
+#[derive(Clone)]
+pub struct PercentileSemigroup<V>(PhantomData<V>);
+
+impl<V> Semigroup<Vec<(V, u64)>> for PercentileSemigroup<V>
+where
+    V: Ord + Clone,
+{
+    fn combine(left: &Vec<(V, u64)>, right: &Vec<(V, u64)>) -> Vec<(V, u64)> {
+        let mut result = Vec::with_capacity(left.len() + right.len());
+        let (mut left, mut right) = (left.iter().peekable(), right.iter().peekable());
+
+        while let (Some((l, _)), Some((r, _))) = (left.peek(), right.peek()) {
+            match l.cmp(r) {
+                Ordering::Less => result.push(left.next().unwrap().clone()),
+                Ordering::Greater => result.push(right.next().unwrap().clone()),
+                Ordering::Equal => {
+                    let (value, count1) = left.next().unwrap();
+                    let (_, count2) = right.next().unwrap();
+                    result.push((value.clone(), count1 + count2));
+                }
+            }
+        }
+        result.extend(left.cloned());
+        result.extend(right.cloned());
+
+        result
+    }
+}
+
+/// Collect values with positive weights and their multiplicities.
+fn weighted_values<V, T, R, C>(cursor: &mut C) -> Option<Vec<(V, u64)>>
+where
+    V: DBData,
+    T: Timestamp,
+    R: MonoidValue + ToPrimitive,
+    C: Cursor<V, (), T, R>,
+{
+    let mut values = Vec::new();
+
+    while cursor.key_valid() {
+        let weight = cursor.fold_times(R::zero(), |mut acc, _, weight| {
+            acc.add_assign_by_ref(weight);
+            acc
+        });
+        match weight.to_i64() {
+            Some(count) if count > 0 => values.push((cursor.key().clone(), count as u64)),
+            _ => {}
+        }
+
+        cursor.step_key();
+    }
+
+    if values.is_empty() {
+        None
+    } else {
+        Some(values)
+    }
+}
+
+/// Nearest-rank `percentile` of a non-empty sorted multiset.
+fn nearest_rank<V>(values: Vec<(V, u64)>, percentile: f64) -> V {
+    let total: u64 = values.iter().map(|(_, count)| count).sum();
+    let rank = ((percentile * total as f64).ceil() as u64).clamp(1, total);
+
+    let mut seen = 0;
+    for (value, count) in values {
+        seen += count;
+        if seen >= rank {
+            return value;
+        }
+    }
+
+    unreachable!("rank {rank} exceeds the number of values {total}")
+}
+
+impl<V, T, R> Aggregator<V, T, R> for Percentile
+where
+    V: DBData,
+    T: Timestamp,
+    R: MonoidValue + ToPrimitive,
+{
+    type Accumulator = Vec<(V, u64)>;
+    type Output = V;
+    type Semigroup = PercentileSemigroup<V>;
+
+    fn aggregate<C>(&self, cursor: &mut C) -> Option<Self::Accumulator>
+    where
+        C: Cursor<V, (), T, R>,
+    {
+        weighted_values(cursor)
+    }
+
+    fn finalize(&self, accumulator: Self::Accumulator) -> Self::Output {
+        nearest_rank(accumulator, self.percentile)
+    }
+}
+
+impl<V, T, R> Aggregator<V, T, R> for Median
+where
+    V: DBData,
+    T: Timestamp,
+    R: MonoidValue + ToPrimitive,
+{
+    type Accumulator = Vec<(V, u64)>;
+    type Output = V;
+    type Semigroup = PercentileSemigroup<V>;
+
+    fn aggregate<C>(&self, cursor: &mut C) -> Option<Self::Accumulator>
+    where
+        C: Cursor<V, (), T, R>,
+    {
+        weighted_values(cursor)
+    }
+
+    fn finalize(&self, accumulator: Self::Accumulator) -> Self::Output {
+        nearest_rank(accumulator, 0.5)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{nearest_rank, PercentileSemigroup};
+    use crate::algebra::Semigroup;
+
+    #[test]
+    fn nearest_rank_test() {
+        let values = vec![(10, 1), (20, 2), (30, 1)];
+
+        assert_eq!(nearest_rank(values.clone(), 0.0), 10);
+        assert_eq!(nearest_rank(values.clone(), 0.25), 10);
+        assert_eq!(nearest_rank(values.clone(), 0.5), 20);
+        assert_eq!(nearest_rank(values.clone(), 0.75), 20);
+        assert_eq!(nearest_rank(values.clone(), 0.76), 30);
+        assert_eq!(nearest_rank(values, 1.0), 30);
+
+        assert_eq!(nearest_rank(vec![(5, 1)], 0.99), 5);
+    }
+
+    #[test]
+    fn combine() {
+        assert_eq!(
+            PercentileSemigroup::combine(&vec![(1, 1), (3, 2)], &vec![(2, 1), (3, 1), (4, 5)]),
+            vec![(1, 1), (2, 1), (3, 3), (4, 5)]
+        );
+        assert_eq!(
+            PercentileSemigroup::combine(&vec![], &vec![(1, 1)]),
+            vec![(1, 1)]
+        );
+    }
+}
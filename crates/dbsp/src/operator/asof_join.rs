@@ -0,0 +1,987 @@
+//! As-of join operator.
+
+use crate::{
+    algebra::{HasZero, IndexedZSet, MulByRef, ZRingValue},
+    circuit::{
+        checkpoint::{decode_data, encode_data},
+        operator_traits::{Operator, QuaternaryOperator},
+        RootCircuit, Scope, StatefulOperator, Stream,
+    },
+    operator::{trace::TraceBound, FilterMap, Map},
+    trace::{consolidation::consolidate, cursor::Cursor, BatchReader, Trace},
+    DBData, DBWeight, Error, OrdIndexedZSet, OrdZSet,
+};
+use num::PrimInt;
+use std::{borrow::Cow, cmp::Ordering, marker::PhantomData, ops::Neg};
+
+/// Input of the as-of join operator: records indexed by key, with `(timestamp,
+/// Some(value))` pairs as values, so that the values of each key are ordered
+/// by timestamp.
+///
+/// Values are wrapped in `Option` so that `(ts, None)` can serve as a lower
+/// bound on all values with timestamp `ts` when truncating traces.
+type Timestamped<K, TS, V, R> = OrdIndexedZSet<K, (TS, Option<V>), R>;
+
+impl<I1> Stream<RootCircuit, I1>
+where
+    I1: IndexedZSet + Send,
+    I1::R: ZRingValue,
+{
+    /// Incremental as-of join of two streams of batches.
+    ///
+    /// For each record in the left input with key `k` and timestamp `t`,
+    /// finds the right records with key `k` and the greatest timestamp that
+    /// does not exceed `t`, and applies `join_func` to each such pair of
+    /// records.  Left records without a matching right record don't
+    /// contribute to the output.  The weight of each output record is the
+    /// product of the weights of the left and right records that produced
+    /// it.
+    ///
+    /// This is the standard way to enrich a time series with the latest
+    /// value of another time series, e.g., to match each trade with the last
+    /// quote for the same instrument at the time of the trade.
+    ///
+    /// The operator is maintained incrementally under insertions and
+    /// retractions in both inputs: a change to the right input updates the
+    /// output for all left records with the same key whose match changed as
+    /// a result.
+    ///
+    /// If multiple right records with the same key share the greatest
+    /// timestamp, the left record is joined with each of them.
+    ///
+    /// The operator indexes both inputs by timestamp within each key, so
+    /// looking up the match of a left record doesn't require scanning the
+    /// history of the key.  However, it retains the complete history of both
+    /// inputs.  Use
+    /// [`asof_join_with_watermark`](`Self::asof_join_with_watermark`) to
+    /// discard old records.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - the right input stream.
+    /// * `join_func` - maps key and a pair of matching values from the left
+    ///   and right inputs to an output value.
+    /// * `ts_func1` - extracts timestamp from a value in the left input.
+    /// * `ts_func2` - extracts timestamp from a value in the right input.
+    ///
+    /// # Type arguments
+    ///
+    /// * `I2` - batch type in the right input stream.
+    /// * `TS` - timestamp type.
+    /// * `V` - output value type.
+    pub fn asof_join<I2, TS, F, TSF1, TSF2, V>(
+        &self,
+        other: &Stream<RootCircuit, I2>,
+        join_func: F,
+        ts_func1: TSF1,
+        ts_func2: TSF2,
+    ) -> Stream<RootCircuit, OrdZSet<V, I1::R>>
+    where
+        I2: IndexedZSet<Key = I1::Key, R = I1::R> + Send,
+        TS: DBData,
+        F: Fn(&I1::Key, &I1::Val, &I2::Val) -> V + 'static,
+        TSF1: Fn(&I1::Val) -> TS + 'static,
+        TSF2: Fn(&I2::Val) -> TS + 'static,
+        V: DBData,
+    {
+        self.circuit().region("asof_join", || {
+            let left = self.index_by_timestamp(ts_func1);
+            let right = other.index_by_timestamp(ts_func2);
+
+            left.asof_join_inner(&right, join_func, |_: &TS| None, TraceBound::new())
+        })
+    }
+
+    /// As-of join that uses a watermark to bound the state of the operator.
+    ///
+    /// Like [`asof_join`](`Self::asof_join`), with two differences that
+    /// allow the operator to discard old records:
+    ///
+    /// * A left record with timestamp `t` only matches right records with
+    ///   timestamps in `[t - tolerance, t]`.
+    /// * Left records with timestamps more than `lateness` behind the
+    ///   watermark are late: new late records are discarded, and the outputs
+    ///   produced for existing ones are no longer updated.  Right records
+    ///   that could only match late left records, i.e., records with
+    ///   timestamps more than `lateness + tolerance` behind the watermark,
+    ///   are discarded.
+    ///
+    /// As in [`join_with_watermark`](`Self::join_with_watermark`), the
+    /// operator uses the value of `watermark` at the previous clock cycle to
+    /// filter late inputs.  Left records with timestamps below `watermark -
+    /// lateness` and right records with timestamps below `watermark -
+    /// lateness - tolerance` are eventually removed from the traces of the
+    /// operator.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - the right input stream.
+    /// * `join_func` - maps key and a pair of matching values from the left
+    ///   and right inputs to an output value.
+    /// * `ts_func1` - extracts timestamp from a value in the left input.
+    /// * `ts_func2` - extracts timestamp from a value in the right input.
+    /// * `watermark` - monotonically growing lower bound on timestamps in
+    ///   both inputs, e.g., computed using
+    ///   [`watermark_monotonic`](`Stream::watermark_monotonic`).
+    /// * `lateness` - how far behind the watermark a record can arrive without
+    ///   being discarded.
+    /// * `tolerance` - how far behind a left record its matching right record
+    ///   can be.
+    #[allow(clippy::too_many_arguments)]
+    pub fn asof_join_with_watermark<I2, TS, F, TSF1, TSF2, V>(
+        &self,
+        other: &Stream<RootCircuit, I2>,
+        join_func: F,
+        ts_func1: TSF1,
+        ts_func2: TSF2,
+        watermark: &Stream<RootCircuit, TS>,
+        lateness: TS,
+        tolerance: TS,
+    ) -> Stream<RootCircuit, OrdZSet<V, I1::R>>
+    where
+        I2: IndexedZSet<Key = I1::Key, R = I1::R> + Send,
+        TS: DBData + PrimInt,
+        F: Fn(&I1::Key, &I1::Val, &I2::Val) -> V + 'static,
+        TSF1: Fn(&I1::Val) -> TS + 'static,
+        TSF2: Fn(&I2::Val) -> TS + 'static,
+        V: DBData,
+    {
+        self.circuit().region("asof_join_with_watermark", || {
+            let bound = TraceBound::new();
+
+            let left = self.index_by_timestamp_with_watermark(
+                ts_func1,
+                watermark,
+                lateness,
+                bound.clone(),
+            );
+            let right = other.index_by_timestamp_with_watermark(
+                ts_func2,
+                watermark,
+                lateness.saturating_add(tolerance),
+                TraceBound::new(),
+            );
+
+            left.asof_join_inner(
+                &right,
+                join_func,
+                move |ts: &TS| Some(ts.saturating_sub(tolerance)),
+                bound,
+            )
+        })
+    }
+}
+
+impl<B> Stream<RootCircuit, B>
+where
+    B: IndexedZSet,
+{
+    /// Index values by timestamp within each key.
+    fn index_by_timestamp<TS, TSF>(
+        &self,
+        ts_func: TSF,
+    ) -> Stream<RootCircuit, Timestamped<B::Key, TS, B::Val, B::R>>
+    where
+        TS: DBData,
+        TSF: Fn(&B::Val) -> TS + 'static,
+    {
+        self.circuit().add_unary_operator(
+            Map::new(move |(key, val): (&B::Key, &B::Val)| {
+                (key.clone(), (ts_func(val), Some(val.clone())))
+            }),
+            self,
+        )
+    }
+
+    /// Index values by timestamp within each key, discarding records with
+    /// timestamps more than `lateness` behind `watermark`.
+    ///
+    /// See [`Stream::discard_late_keys`] for the meaning of `bound`.
+    fn index_by_timestamp_with_watermark<TS, TSF>(
+        &self,
+        ts_func: TSF,
+        watermark: &Stream<RootCircuit, TS>,
+        lateness: TS,
+        bound: TraceBound<TS>,
+    ) -> Stream<RootCircuit, Timestamped<B::Key, TS, B::Val, B::R>>
+    where
+        TS: DBData + PrimInt,
+        TSF: Fn(&B::Val) -> TS + 'static,
+    {
+        let by_ts: Stream<RootCircuit, OrdIndexedZSet<TS, (B::Key, B::Val), B::R>> =
+            self.circuit().add_unary_operator(
+                Map::new(move |(key, val): (&B::Key, &B::Val)| {
+                    (ts_func(val), (key.clone(), val.clone()))
+                }),
+                self,
+            );
+
+        by_ts
+            .discard_late_keys(watermark, lateness, bound)
+            .map_index(|(ts, (key, val))| (key.clone(), (*ts, Some(val.clone()))))
+    }
+}
+
+impl<K, TS, V1, R> Stream<RootCircuit, Timestamped<K, TS, V1, R>>
+where
+    K: DBData,
+    TS: DBData,
+    V1: DBData,
+    R: DBWeight + ZRingValue,
+{
+    /// As-of join of inputs indexed by timestamp.
+    ///
+    /// `horizon` returns the earliest right timestamp that a left record
+    /// with the given timestamp can match, if any.  When set, `bound` is the
+    /// lower bound on timestamps of new left records, used to truncate the
+    /// traces of both inputs.
+    fn asof_join_inner<V2, F, H, V>(
+        &self,
+        other: &Stream<RootCircuit, Timestamped<K, TS, V2, R>>,
+        join_func: F,
+        horizon: H,
+        bound: TraceBound<TS>,
+    ) -> Stream<RootCircuit, OrdZSet<V, R>>
+    where
+        V2: DBData,
+        F: Fn(&K, &V1, &V2) -> V + 'static,
+        H: Fn(&TS) -> Option<TS> + 'static,
+        V: DBData,
+    {
+        let left = self.shard();
+        let right = other.shard();
+
+        let left_trace_bound = TraceBound::new();
+        let right_trace_bound = TraceBound::new();
+
+        let left_trace = left
+            .integrate_trace_inner(
+                StatefulOperator::Join,
+                TraceBound::new(),
+                left_trace_bound.clone(),
+            )
+            .delay_trace();
+        let right_trace = right
+            .integrate_trace_inner(
+                StatefulOperator::Join,
+                TraceBound::new(),
+                right_trace_bound.clone(),
+            )
+            .delay_trace();
+
+        self.circuit().add_quaternary_operator(
+            AsofJoin::new(
+                join_func,
+                horizon,
+                bound,
+                left_trace_bound,
+                right_trace_bound,
+            ),
+            &left,
+            &right,
+            &left_trace,
+            &right_trace,
+        )
+    }
+}
+
+/// `((timestamp, value), weight)` tuples under one key, ordered by
+/// timestamp.
+type Group<TS, V, R> = Vec<((TS, Option<V>), R)>;
+
+/// Records in `group` with the greatest timestamp that does not exceed `ts`
+/// and is not below `horizon`.
+fn matches<'a, TS, V, R>(
+    group: &'a [((TS, Option<V>), R)],
+    ts: &TS,
+    horizon: Option<&TS>,
+) -> &'a [((TS, Option<V>), R)]
+where
+    TS: Ord,
+{
+    let end = group.partition_point(|((right_ts, _), _)| right_ts <= ts);
+    if end == 0 {
+        return &[];
+    }
+
+    let latest = &group[end - 1].0 .0;
+    if horizon.map_or(false, |horizon| latest < horizon) {
+        return &[];
+    }
+
+    let start = group.partition_point(|((right_ts, _), _)| right_ts < latest);
+    &group[start..end]
+}
+
+/// Collect `(value, weight)` pairs with non-zero weights from the current
+/// position of `cursor` to the last value under the current key.
+fn collect_vals<K, V, R, C>(cursor: &mut C) -> Vec<(V, R)>
+where
+    V: Clone,
+    R: HasZero,
+    C: Cursor<K, V, (), R>,
+{
+    let mut vals = Vec::new();
+
+    while cursor.val_valid() {
+        let weight = cursor.weight();
+        if !weight.is_zero() {
+            vals.push((cursor.val().clone(), weight));
+        }
+        cursor.step_val();
+    }
+
+    vals
+}
+
+/// Merge old left records with changes to the left relation into `(value,
+/// old weight, delta weight)` tuples.  Both inputs are sorted by value.
+fn merge_left<V, R>(old: Vec<(V, R)>, changes: Vec<(V, R)>) -> Vec<(V, R, R)>
+where
+    V: Ord,
+    R: HasZero,
+{
+    let mut merged = Vec::with_capacity(old.len() + changes.len());
+    let mut old = old.into_iter().peekable();
+    let mut changes = changes.into_iter().peekable();
+
+    loop {
+        let next = match (old.peek(), changes.peek()) {
+            (None, None) => break,
+            (Some(_), None) => {
+                let (val, weight) = old.next().unwrap();
+                (val, weight, R::zero())
+            }
+            (None, Some(_)) => {
+                let (val, weight) = changes.next().unwrap();
+                (val, R::zero(), weight)
+            }
+            (Some((old_val, _)), Some((delta_val, _))) => match old_val.cmp(delta_val) {
+                Ordering::Less => {
+                    let (val, weight) = old.next().unwrap();
+                    (val, weight, R::zero())
+                }
+                Ordering::Greater => {
+                    let (val, weight) = changes.next().unwrap();
+                    (val, R::zero(), weight)
+                }
+                Ordering::Equal => {
+                    let (val, old_weight) = old.next().unwrap();
+                    let (_, delta_weight) = changes.next().unwrap();
+                    (val, old_weight, delta_weight)
+                }
+            },
+        };
+        merged.push(next);
+    }
+
+    merged
+}
+
+/// As-of join operator.
+///
+/// Inputs are changes to the left and right relations at the current clock
+/// cycle and traces of both relations as of the previous clock cycle, all
+/// indexed by timestamp within each key.  For each key modified in either
+/// input, the operator determines the range of timestamps of left records
+/// whose matches may have changed, reads the part of the right trace that
+/// these records can match, and updates their outputs.
+///
+/// See [`Stream::asof_join`] and [`Stream::asof_join_with_watermark`].
+pub struct AsofJoin<K, TS, V1, V2, R, F, H, V> {
+    join_func: F,
+    /// Earliest right timestamp that a left record with the given timestamp
+    /// can match, if any.
+    horizon: H,
+    /// Lower bound on timestamps of new left records, maintained by the
+    /// operator that discards late left records.
+    bound: TraceBound<TS>,
+    /// Value of `bound` at the previous clock cycle.  Outputs of left records
+    /// below this timestamp are no longer updated.
+    left_bound: Option<TS>,
+    /// Lower bound on values retained in the left trace.
+    left_trace_bound: TraceBound<(TS, Option<V1>)>,
+    /// Lower bound on values retained in the right trace.
+    right_trace_bound: TraceBound<(TS, Option<V2>)>,
+    _types: PhantomData<(K, R, V)>,
+}
+
+impl<K, TS, V1, V2, R, F, H, V> AsofJoin<K, TS, V1, V2, R, F, H, V> {
+    pub fn new(
+        join_func: F,
+        horizon: H,
+        bound: TraceBound<TS>,
+        left_trace_bound: TraceBound<(TS, Option<V1>)>,
+        right_trace_bound: TraceBound<(TS, Option<V2>)>,
+    ) -> Self {
+        Self {
+            join_func,
+            horizon,
+            bound,
+            left_bound: None,
+            left_trace_bound,
+            right_trace_bound,
+            _types: PhantomData,
+        }
+    }
+}
+
+impl<K, TS, V1, V2, R, F, H, V> AsofJoin<K, TS, V1, V2, R, F, H, V>
+where
+    K: DBData,
+    TS: DBData,
+    V1: DBData,
+    V2: DBData,
+    R: DBWeight,
+    H: Fn(&TS) -> Option<TS>,
+{
+    /// Allow the traces to discard records that can no longer affect the
+    /// output once left records below `bound` are no longer updated.
+    fn advance_trace_bounds(&self, bound: &TS) {
+        if self
+            .left_trace_bound
+            .get()
+            .map_or(true, |(old, _)| &old < bound)
+        {
+            self.left_trace_bound.set((bound.clone(), None));
+        }
+
+        if let Some(horizon) = (self.horizon)(bound) {
+            if self
+                .right_trace_bound
+                .get()
+                .map_or(true, |(old, _)| old < horizon)
+            {
+                self.right_trace_bound.set((horizon, None));
+            }
+        }
+    }
+
+    /// Read the right records under `key` that left records with timestamps
+    /// in `[min_ts, max_ts]` can match, before or after applying `changes`.
+    ///
+    /// Scans back from `min_ts` to the latest timestamp whose records are not
+    /// all retracted by `changes`: records below it can't be the latest
+    /// match of any left record in the range, before or after the update.
+    fn right_window<C>(
+        &self,
+        cursor: &mut C,
+        key: &K,
+        changes: &[((TS, Option<V2>), R)],
+        min_ts: &TS,
+        max_ts: &TS,
+    ) -> Group<TS, V2, R>
+    where
+        C: Cursor<K, (TS, Option<V2>), (), R>,
+    {
+        let mut window = Vec::new();
+
+        cursor.seek_key(key);
+        if !cursor.key_valid() || cursor.key() != key {
+            return window;
+        }
+
+        let horizon = (self.horizon)(min_ts);
+
+        cursor.fast_forward_vals();
+        cursor.seek_val_with_reverse(|(ts, _)| ts <= min_ts);
+        while cursor.val_valid() {
+            let ts = cursor.val().0.clone();
+            if horizon.as_ref().map_or(false, |horizon| &ts < horizon) {
+                break;
+            }
+
+            let group_start = window.len();
+            while cursor.val_valid() && cursor.val().0 == ts {
+                let weight = cursor.weight();
+                if !weight.is_zero() {
+                    window.push((cursor.val().clone(), weight));
+                }
+                cursor.step_val_reverse();
+            }
+
+            let changes_start = changes.partition_point(|((change_ts, _), _)| change_ts < &ts);
+            let changes_end = changes.partition_point(|((change_ts, _), _)| change_ts <= &ts);
+
+            let mut new_group = window[group_start..].to_vec();
+            new_group.extend_from_slice(&changes[changes_start..changes_end]);
+            consolidate(&mut new_group);
+            if !new_group.is_empty() {
+                break;
+            }
+        }
+        window.reverse();
+
+        cursor.rewind_vals();
+        cursor.seek_val_with(|(ts, _)| ts > min_ts);
+        while cursor.val_valid() && &cursor.val().0 <= max_ts {
+            let weight = cursor.weight();
+            if !weight.is_zero() {
+                window.push((cursor.val().clone(), weight));
+            }
+            cursor.step_val();
+        }
+
+        window
+    }
+}
+
+impl<K, TS, V1, V2, R, F, H, V> Operator for AsofJoin<K, TS, V1, V2, R, F, H, V>
+where
+    K: DBData,
+    TS: DBData,
+    V1: DBData,
+    V2: DBData,
+    R: DBWeight,
+    F: 'static,
+    H: Fn(&TS) -> Option<TS> + 'static,
+    V: 'static,
+{
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("AsofJoin")
+    }
+
+    fn fixedpoint(&self, _scope: Scope) -> bool {
+        true
+    }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(Some(encode_data(&self.left_bound)?))
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Result<(), Error> {
+        self.left_bound = decode_data(state)?;
+        if let Some(bound) = &self.left_bound {
+            self.advance_trace_bounds(bound);
+        }
+        Ok(())
+    }
+}
+
+impl<K, TS, V1, V2, R, F, H, V, T1, T2>
+    QuaternaryOperator<Timestamped<K, TS, V1, R>, Timestamped<K, TS, V2, R>, T1, T2, OrdZSet<V, R>>
+    for AsofJoin<K, TS, V1, V2, R, F, H, V>
+where
+    K: DBData,
+    TS: DBData,
+    V1: DBData,
+    V2: DBData,
+    R: DBWeight + ZRingValue,
+    T1: Trace<Key = K, Val = (TS, Option<V1>), Time = (), R = R> + Clone,
+    T2: Trace<Key = K, Val = (TS, Option<V2>), Time = (), R = R> + Clone,
+    F: Fn(&K, &V1, &V2) -> V + 'static,
+    H: Fn(&TS) -> Option<TS> + 'static,
+    V: DBData,
+{
+    fn eval<'a>(
+        &mut self,
+        left_delta: Cow<'a, Timestamped<K, TS, V1, R>>,
+        right_delta: Cow<'a, Timestamped<K, TS, V2, R>>,
+        left_trace: Cow<'a, T1>,
+        right_trace: Cow<'a, T2>,
+    ) -> OrdZSet<V, R> {
+        let mut left_delta_cursor = left_delta.cursor();
+        let mut right_delta_cursor = right_delta.cursor();
+        let mut left_trace_cursor = left_trace.cursor();
+        let mut right_trace_cursor = right_trace.cursor();
+
+        let mut output = Vec::with_capacity(left_delta.len() + right_delta.len());
+
+        loop {
+            let key = match (
+                left_delta_cursor.key_valid(),
+                right_delta_cursor.key_valid(),
+            ) {
+                (false, false) => break,
+                (true, false) => left_delta_cursor.key().clone(),
+                (false, true) => right_delta_cursor.key().clone(),
+                (true, true) => {
+                    if left_delta_cursor.key() <= right_delta_cursor.key() {
+                        left_delta_cursor.key().clone()
+                    } else {
+                        right_delta_cursor.key().clone()
+                    }
+                }
+            };
+
+            let left_changes = if left_delta_cursor.key_valid() && left_delta_cursor.key() == &key {
+                let vals = collect_vals(&mut left_delta_cursor);
+                left_delta_cursor.step_key();
+                vals
+            } else {
+                Vec::new()
+            };
+
+            let right_changes =
+                if right_delta_cursor.key_valid() && right_delta_cursor.key() == &key {
+                    let vals = collect_vals(&mut right_delta_cursor);
+                    right_delta_cursor.step_key();
+                    vals
+                } else {
+                    Vec::new()
+                };
+
+            // A change to the right relation at time `t` can only change the
+            // matches of left records at or after `t`.  Records below
+            // `left_bound` are no longer updated.
+            let mut old_left_vals = Vec::new();
+            if let Some(((first_ts, _), _)) = right_changes.first() {
+                let start_ts = match &self.left_bound {
+                    Some(left_bound) if left_bound > first_ts => left_bound,
+                    _ => first_ts,
+                };
+
+                left_trace_cursor.seek_key(&key);
+                if left_trace_cursor.key_valid() && left_trace_cursor.key() == &key {
+                    left_trace_cursor.seek_val_with(|(ts, _)| ts >= start_ts);
+                    old_left_vals = collect_vals(&mut left_trace_cursor);
+                }
+            }
+
+            // Left records are sorted by timestamp.
+            let left = merge_left(old_left_vals, left_changes);
+            let (min_ts, max_ts) = match (left.first(), left.last()) {
+                (Some(((min_ts, _), _, _)), Some(((max_ts, _), _, _))) => (min_ts, max_ts),
+                _ => continue,
+            };
+
+            let old_right = self.right_window(
+                &mut right_trace_cursor,
+                &key,
+                &right_changes,
+                min_ts,
+                max_ts,
+            );
+            let new_right = if right_changes.is_empty() {
+                None
+            } else {
+                let mut new_right = old_right.clone();
+                new_right.extend(right_changes);
+                consolidate(&mut new_right);
+                Some(new_right)
+            };
+
+            for ((ts, left_val), old_weight, delta_weight) in left.into_iter() {
+                let left_val = left_val.as_ref().unwrap();
+                let horizon = (self.horizon)(&ts);
+                let old_matches = matches(&old_right, &ts, horizon.as_ref());
+                let new_matches = new_right.as_ref().map_or(old_matches, |new_right| {
+                    matches(new_right, &ts, horizon.as_ref())
+                });
+
+                if old_matches == new_matches {
+                    // `old_weight` is only known for left records whose
+                    // matches may have changed, but isn't needed here.
+                    if delta_weight.is_zero() {
+                        continue;
+                    }
+                    for ((_, right_val), right_weight) in new_matches {
+                        output.push((
+                            (self.join_func)(&key, left_val, right_val.as_ref().unwrap()),
+                            delta_weight.mul_by_ref(right_weight),
+                        ));
+                    }
+                    continue;
+                }
+
+                if !old_weight.is_zero() {
+                    for ((_, right_val), right_weight) in old_matches {
+                        output.push((
+                            (self.join_func)(&key, left_val, right_val.as_ref().unwrap()),
+                            old_weight.mul_by_ref(right_weight).neg(),
+                        ));
+                    }
+                }
+
+                let new_weight = old_weight + delta_weight;
+                if new_weight.is_zero() {
+                    continue;
+                }
+                for ((_, right_val), right_weight) in new_matches {
+                    output.push((
+                        (self.join_func)(&key, left_val, right_val.as_ref().unwrap()),
+                        new_weight.mul_by_ref(right_weight),
+                    ));
+                }
+            }
+        }
+
+        if let Some(bound) = self.bound.get() {
+            self.advance_trace_bounds(&bound);
+            self.left_bound = Some(bound);
+        }
+
+        OrdZSet::from_keys((), output)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        indexed_zset, operator::Generator, zset, Circuit, OrdIndexedZSet, OrdZSet, RootCircuit,
+        Runtime, Stream,
+    };
+    use proptest::{collection::vec, prelude::*};
+    use std::{
+        collections::BTreeMap,
+        sync::{Arc, Mutex},
+    };
+
+    #[test]
+    fn asof_join_test() {
+        let circuit = RootCircuit::build(move |circuit| {
+            // Trades: instrument => (time, quantity).
+            let mut trades = vec![
+                indexed_zset! {
+                    1 => { (10, 100) => 1, (20, 200) => 1 },
+                    2 => { (5, 50) => 1 }
+                },
+                // No new trades; quotes change.
+                indexed_zset! {},
+                // Retract a trade.
+                indexed_zset! { 1 => { (20, 200) => -1 } },
+                indexed_zset! { 1 => { (30, 300) => 1 } },
+            ]
+            .into_iter();
+
+            // Quotes: instrument => (time, price).
+            let mut quotes = vec![
+                indexed_zset! {
+                    1 => { (8, 1) => 1, (15, 2) => 1 },
+                    2 => { (6, 7) => 1 }
+                },
+                // New quote takes over one of the matches; a late quote
+                // gives instrument 2 a match.
+                indexed_zset! {
+                    1 => { (18, 3) => 1 },
+                    2 => { (1, 4) => 1 }
+                },
+                // Retract the latest quote: the remaining trade falls back
+                // to an earlier quote.
+                indexed_zset! { 1 => { (8, 1) => -1 } },
+                indexed_zset! {},
+            ]
+            .into_iter();
+
+            let mut outputs = vec![
+                zset! {
+                    (1, 10, 100, 1) => 1,
+                    (1, 20, 200, 2) => 1,
+                },
+                zset! {
+                    (1, 20, 200, 2) => -1,
+                    (1, 20, 200, 3) => 1,
+                    (2, 5, 50, 4) => 1,
+                },
+                zset! {
+                    (1, 10, 100, 1) => -1,
+                    (1, 20, 200, 3) => -1,
+                },
+                zset! {
+                    (1, 30, 300, 3) => 1,
+                },
+            ]
+            .into_iter();
+
+            let trades: Stream<_, OrdIndexedZSet<i32, (i32, i32), isize>> =
+                circuit.add_source(Generator::new(move || trades.next().unwrap()));
+            let quotes: Stream<_, OrdIndexedZSet<i32, (i32, i32), isize>> =
+                circuit.add_source(Generator::new(move || quotes.next().unwrap()));
+
+            trades
+                .asof_join(
+                    &quotes,
+                    |instrument, (time, quantity), (_, price)| {
+                        (*instrument, *time, *quantity, *price)
+                    },
+                    |(time, _)| *time,
+                    |(time, _)| *time,
+                )
+                .inspect(move |output| assert_eq!(output, &outputs.next().unwrap()));
+        })
+        .unwrap()
+        .0;
+
+        for _ in 0..4 {
+            circuit.step().unwrap();
+        }
+    }
+
+    fn asof_join_with_watermark_test(workers: usize) {
+        let output = Arc::new(Mutex::new(OrdZSet::empty(())));
+        let output_clone = output.clone();
+
+        let (mut circuit, (mut trades, mut quotes)) =
+            Runtime::init_circuit(workers, move |circuit| {
+                // Instrument => (time, value).
+                let (trades, trades_handle) =
+                    circuit.add_input_indexed_zset::<u64, (u64, u64), isize>();
+                let (quotes, quotes_handle) =
+                    circuit.add_input_indexed_zset::<u64, (u64, u64), isize>();
+
+                let mut watermarks = vec![20, 30, 30].into_iter();
+                let watermark =
+                    circuit.add_source(Generator::new(move || watermarks.next().unwrap()));
+
+                trades
+                    .asof_join_with_watermark(
+                        &quotes,
+                        |instrument, (time, trade), (_, quote)| {
+                            (*instrument, *time, *trade, *quote)
+                        },
+                        |(time, _)| *time,
+                        |(time, _)| *time,
+                        &watermark,
+                        5,
+                        10,
+                    )
+                    .gather(0)
+                    .inspect(move |batch| {
+                        if Runtime::worker_index() == 0 {
+                            *output_clone.lock().unwrap() = batch.clone();
+                        }
+                    });
+
+                (trades_handle, quotes_handle)
+            })
+            .unwrap();
+
+        // Instrument 2 has no quote within the tolerance of its trade.
+        trades.append(&mut vec![(1, ((15, 1), 1)), (2, ((20, 2), 1))]);
+        quotes.append(&mut vec![
+            (1, ((5, 50), 1)),
+            (1, ((12, 120), 1)),
+            (2, ((1, 10), 1)),
+        ]);
+        circuit.step().unwrap();
+        assert_eq!(&*output.lock().unwrap(), &zset! { (1, 15, 1, 120) => 1 });
+
+        // Watermark: 20.  Trades below 15 and quotes below 5 are late.
+        trades.append(&mut vec![(1, ((14, 3), 1)), (1, ((16, 4), 1))]);
+        quotes.append(&mut vec![(1, ((4, 40), 1)), (1, ((13, 130), 1))]);
+        circuit.step().unwrap();
+        assert_eq!(
+            &*output.lock().unwrap(),
+            &zset! {
+                (1, 15, 1, 120) => -1,
+                (1, 15, 1, 130) => 1,
+                (1, 16, 4, 130) => 1,
+            }
+        );
+
+        // Watermark: 30.  Trades below 25 and quotes below 15 are late.  The
+        // new quote at 16 doesn't update the output for the trade at 16,
+        // which is late.
+        trades.append(&mut vec![(1, ((26, 5), 1)), (2, ((30, 6), 1))]);
+        quotes.append(&mut vec![
+            (1, ((13, 130), -1)),
+            (1, ((16, 160), 1)),
+            (1, ((20, 200), 1)),
+        ]);
+        circuit.step().unwrap();
+        assert_eq!(&*output.lock().unwrap(), &zset! { (1, 26, 5, 200) => 1 });
+
+        circuit.kill().unwrap();
+    }
+
+    #[test]
+    fn asof_join_with_watermark_test1() {
+        asof_join_with_watermark_test(1);
+    }
+
+    #[test]
+    fn asof_join_with_watermark_test4() {
+        asof_join_with_watermark_test(4);
+    }
+
+    type Relation = BTreeMap<(i32, i32), isize>;
+
+    fn apply(relation: &mut Relation, batch: &[(i32, i32, isize)]) {
+        for (k, v, w) in batch {
+            let weight = relation.entry((*k, *v)).or_insert(0);
+            *weight += w;
+            if *weight == 0 {
+                relation.remove(&(*k, *v));
+            }
+        }
+    }
+
+    /// Reference as-of join, where the timestamp of a value is `v / 10`.
+    fn reference(left: &Relation, right: &Relation) -> OrdZSet<(i32, i32, i32), isize> {
+        let mut tuples = Vec::new();
+
+        for ((k, v1), w1) in left.iter() {
+            let ts = v1 / 10;
+            let latest = right
+                .keys()
+                .filter(|(k2, v2)| k2 == k && v2 / 10 <= ts)
+                .map(|(_, v2)| v2 / 10)
+                .max();
+
+            if let Some(latest) = latest {
+                for ((_, v2), w2) in right
+                    .iter()
+                    .filter(|((k2, v2), _)| k2 == k && v2 / 10 == latest)
+                {
+                    tuples.push(((*k, *v1, *v2), w1 * w2));
+                }
+            }
+        }
+
+        OrdZSet::from_keys((), tuples)
+    }
+
+    fn input_trace(
+        max_key: i32,
+        max_val: i32,
+        max_batch_size: usize,
+        max_batches: usize,
+    ) -> impl Strategy<Value = Vec<(Vec<(i32, i32, isize)>, Vec<(i32, i32, isize)>)>> {
+        vec(
+            (
+                vec((0..max_key, 0..max_val, -1..2isize), 0..max_batch_size),
+                vec((0..max_key, 0..max_val, -1..2isize), 0..max_batch_size),
+            ),
+            0..max_batches,
+        )
+    }
+
+    proptest! {
+        #[test]
+        fn proptest_asof_join(trace in input_trace(5, 200, 30, 20)) {
+            let (mut dbsp, (left_handle, right_handle, output_handle)) =
+                Runtime::init_circuit(4, |circuit| {
+                    let (left, left_handle) = circuit.add_input_indexed_zset::<i32, i32, isize>();
+                    let (right, right_handle) = circuit.add_input_indexed_zset::<i32, i32, isize>();
+
+                    let output_handle = left
+                        .asof_join(&right, |k, v1, v2| (*k, *v1, *v2), |v| v / 10, |v| v / 10)
+                        .integrate()
+                        .output();
+
+                    (left_handle, right_handle, output_handle)
+                })
+                .unwrap();
+
+            let mut left = Relation::new();
+            let mut right = Relation::new();
+
+            for (left_batch, right_batch) in trace.into_iter() {
+                apply(&mut left, &left_batch);
+                apply(&mut right, &right_batch);
+
+                for (k, v, w) in left_batch.into_iter() {
+                    left_handle.push(k, (v, w));
+                }
+                for (k, v, w) in right_batch.into_iter() {
+                    right_handle.push(k, (v, w));
+                }
+                dbsp.step().unwrap();
+
+                assert_eq!(output_handle.consolidate(), reference(&left, &right));
+            }
+
+            dbsp.kill().unwrap();
+        }
+    }
+}
@@ -1,6 +1,13 @@
 //! Exchange operators implement a N-to-N communication pattern where
 //! each participant sends exactly one value to and receives exactly one
 //! value from each peer at every clock cycle.
+//!
+//! In a multihost runtime (see [`Layout`](`crate::circuit::Layout`)), values
+//! sent to workers in other processes are serialized using the
+//! [`Checkpoint`] trait and transmitted over TCP.  Since batches can only be
+//! serialized with the `with-bincode` feature, multihost runtimes require it.
+//! Failures to serialize, send, or deserialize a value put the network into a
+//! failed state, which makes the current step of the circuit fail.
 
 // TODO: We may want to generalize these operators to implement N-to-M
 // communication, including 1-to-N and N-to-1.
@@ -8,16 +15,18 @@
 use crate::{
     circuit::{
         metadata::OperatorLocation,
+        network::{Message, Network, RemoteExchange},
         operator_traits::{Operator, SinkOperator, SourceOperator},
-        OwnershipPreference, Runtime, Scope,
+        Checkpoint, OwnershipPreference, Runtime, Scope,
     },
-    circuit_cache_key,
+    circuit_cache_key, Error,
 };
 use crossbeam_utils::CachePadded;
 use once_cell::sync::OnceCell;
 use std::{
     borrow::Cow,
     marker::PhantomData,
+    ops::Range,
     sync::{
         atomic::{AtomicUsize, Ordering},
         Arc, Mutex,
@@ -42,9 +51,21 @@ circuit_cache_key!(local ExchangeId<T>(usize => Arc<Exchange<T>>));
 /// The send operation can only proceed when all peers have retrieved data
 /// produced at the previous round.  Likewise, the receive operation can proceed
 /// once all incoming values are ready for the current round.
+///
+/// In a multihost runtime, only mailboxes of local receivers are used.  Values
+/// for remote receivers are sent over the network and stored in the
+/// receiver's mailbox by the remote host.  The remote host acknowledges each
+/// value once the receiver has retrieved it, which frees up the corresponding
+/// slot in the sender's counter.
 pub(crate) struct Exchange<T> {
+    /// Id of the exchange, identical across all workers and hosts.
+    exchange_id: usize,
     /// The number of communicating peers.
     npeers: usize,
+    /// Peers that run in the current process.
+    local_workers: Range<usize>,
+    /// Connections to remote hosts, `None` if all peers are local.
+    network: Option<Arc<Network>>,
     /// `npeers^2` mailboxes, one for each sender/receiver pair.  Note that each
     /// mailbox is accessed by exactly two threads, so contention is low.
     mailboxes: Vec<Mutex<Option<T>>>,
@@ -65,12 +86,20 @@ pub(crate) struct Exchange<T> {
 
 impl<T> Exchange<T>
 where
-    T: Send + 'static,
+    T: Checkpoint + Send + 'static,
 {
     /// Create a new exchange operator for `npeers` communicating threads.
-    fn new(npeers: usize) -> Self {
+    fn new(
+        exchange_id: usize,
+        npeers: usize,
+        local_workers: Range<usize>,
+        network: Option<Arc<Network>>,
+    ) -> Self {
         Self {
+            exchange_id,
             npeers,
+            local_workers,
+            network,
             mailboxes: (0..npeers * npeers).map(|_| Mutex::new(None)).collect(),
             receiver_counters: (0..npeers)
                 .map(|_| CachePadded::new(AtomicUsize::new(0)))
@@ -86,15 +115,79 @@ where
     /// Create a new `Exchange` instance if an instance with the same id
     /// (created by another thread) does not yet exist within `runtime`.
     /// The number of peers will be set to `runtime.num_workers()`.
+    ///
+    /// In a multihost runtime, the new instance registers with the network
+    /// to receive values sent by remote peers.
     pub(crate) fn with_runtime(runtime: &Runtime, exchange_id: usize) -> Arc<Self> {
         runtime
             .local_store()
             .entry(ExchangeId::new(exchange_id))
-            .or_insert_with(|| Arc::new(Exchange::new(runtime.num_workers())))
+            .or_insert_with(|| {
+                let exchange = Arc::new(Exchange::new(
+                    exchange_id,
+                    runtime.num_workers(),
+                    runtime.layout().local_workers(),
+                    runtime.network().cloned(),
+                ));
+                if let Some(network) = runtime.network() {
+                    network.register(exchange_id, Arc::downgrade(&exchange));
+                }
+                exchange
+            })
             .value()
             .clone()
     }
 
+    /// True if `worker` runs in the current process.
+    fn is_local(&self, worker: usize) -> bool {
+        self.local_workers.contains(&worker)
+    }
+
+    /// Send `message` to the host that runs remote `worker`.
+    ///
+    /// On failure, puts the network into a failed state instead of blocking
+    /// the peers of `worker` forever.
+    fn send_remote(&self, worker: usize, message: &Message) {
+        let network = self.network.as_ref().unwrap();
+        if let Err(e) = network.send(worker, message) {
+            network.fail(format!(
+                "failed to send data to remote worker {worker}: {e}"
+            ));
+        }
+    }
+
+    /// Store `data` in the mailbox for the sender/receiver pair and notify
+    /// the receiver if this was the last value it was waiting for.
+    fn deliver_local(&self, sender: usize, receiver: usize, data: Option<T>) {
+        *self.mailbox(sender, receiver).lock().unwrap() = data;
+        let old_counter = self.receiver_counters[receiver].fetch_add(1, Ordering::AcqRel);
+        if old_counter >= self.npeers - 1 {
+            // This can be a spurious callback (see detailed comment in
+            // `release_mailbox`).
+            if let Some(cb) = self.receiver_callbacks[receiver].get() {
+                cb()
+            }
+        }
+    }
+
+    /// Mark one of `sender`'s outgoing mailboxes as free and notify the
+    /// sender if all its mailboxes are now available.
+    fn release_mailbox(&self, sender: usize) {
+        let old_counter = self.sender_counters[sender].fetch_add(1, Ordering::AcqRel);
+        if old_counter >= self.npeers - 1 {
+            // This can be a spurious callback if the following thread interleaving occurs:
+            // 1. Another receiver increments the sender's counter to `npeers`.
+            // 2. The sender starts transmitting messages, writing `receiver`'s mailbox
+            // first    (counter drops to `npeers-1`)
+            // 3. `receiver` is unblocked and retrieves its message, bumping the counter
+            //    back to `npeers` and generating a spurious sender callback in the
+            // following    line.
+            if let Some(cb) = self.sender_callbacks[sender].get() {
+                cb()
+            }
+        }
+    }
+
     /// Returns a reference to a mailbox for the sender/receiver pair.
     fn mailbox(&self, sender: usize, receiver: usize) -> &Mutex<Option<T>> {
         debug_assert!(sender < self.npeers);
@@ -134,14 +227,25 @@ where
         }
 
         for receiver in 0..self.npeers {
-            *self.mailbox(sender, receiver).lock().unwrap() = data.next();
-            self.sender_counters[sender].fetch_sub(1, Ordering::AcqRel);
-            let old_counter = self.receiver_counters[receiver].fetch_add(1, Ordering::AcqRel);
-            if old_counter >= self.npeers - 1 {
-                // This can be a spurious callback (see detailed comment in `try_receive_all`)
-                // below.
-                if let Some(cb) = self.receiver_callbacks[receiver].get() {
-                    cb()
+            if self.is_local(receiver) {
+                self.sender_counters[sender].fetch_sub(1, Ordering::AcqRel);
+                self.deliver_local(sender, receiver, data.next());
+            } else {
+                let value = data.next().unwrap();
+                self.sender_counters[sender].fetch_sub(1, Ordering::AcqRel);
+                match value.checkpoint() {
+                    Ok(bytes) => self.send_remote(
+                        receiver,
+                        &Message::Data {
+                            exchange_id: self.exchange_id,
+                            sender,
+                            receiver,
+                            data: bytes,
+                        },
+                    ),
+                    Err(e) => self.network.as_ref().unwrap().fail(format!(
+                        "failed to serialize data for remote worker {receiver}: {e}"
+                    )),
                 }
             }
         }
@@ -181,18 +285,17 @@ where
                 .unwrap();
             cb(data);
             self.receiver_counters[receiver].fetch_sub(1, Ordering::Release);
-            let old_counter = self.sender_counters[sender].fetch_add(1, Ordering::AcqRel);
-            if old_counter >= self.npeers - 1 {
-                // This can be a spurious callback if the following thread interleaving occurs:
-                // 1. Another receiver increments the sender's counter to `npeers`.
-                // 2. The sender starts transmitting messages, writing `receiver`'s mailbox
-                // first    (counter drops to `npeers-1`)
-                // 3. `receiver` is unblocked and retrieves its message, bumping the counter
-                //    back to `npeers` and generating a spurious sender callback in the
-                // following    line.
-                if let Some(cb) = self.sender_callbacks[sender].get() {
-                    cb()
-                }
+            if self.is_local(sender) {
+                self.release_mailbox(sender);
+            } else {
+                self.send_remote(
+                    sender,
+                    &Message::Ack {
+                        exchange_id: self.exchange_id,
+                        sender,
+                        receiver,
+                    },
+                );
             }
         }
 
@@ -250,6 +353,42 @@ where
     }
 }
 
+impl<T> RemoteExchange for Exchange<T>
+where
+    T: Checkpoint + Send + 'static,
+{
+    fn deliver(&self, sender: usize, receiver: usize, data: &[u8]) -> Result<(), Error> {
+        debug_assert!(!self.is_local(sender));
+        debug_assert!(self.is_local(receiver));
+
+        let value = T::restore(data).map_err(|e| {
+            Error::Custom(format!(
+                "failed to deserialize data received from remote worker {sender}: {e}"
+            ))
+        })?;
+        self.deliver_local(sender, receiver, Some(value));
+        Ok(())
+    }
+
+    fn acknowledge(&self, sender: usize, receiver: usize) {
+        debug_assert!(self.is_local(sender));
+        debug_assert!(!self.is_local(receiver));
+
+        self.release_mailbox(sender);
+    }
+
+    fn wake(&self) {
+        for worker in self.local_workers.clone() {
+            if let Some(cb) = self.sender_callbacks[worker].get() {
+                cb()
+            }
+            if let Some(cb) = self.receiver_callbacks[worker].get() {
+                cb()
+            }
+        }
+    }
+}
+
 /// Operator that partitions incoming data across all workers.
 ///
 /// This operator works in tandem with [`ExchangeReceiver`], which reassembles
@@ -384,7 +523,7 @@ pub struct ExchangeSender<D, T, L> {
 
 impl<D, T, L> ExchangeSender<D, T, L>
 where
-    T: Send + 'static,
+    T: Checkpoint + Send + 'static,
 {
     fn new(
         runtime: &Runtime,
@@ -408,7 +547,7 @@ where
 impl<D, T, L> Operator for ExchangeSender<D, T, L>
 where
     D: 'static,
-    T: Send + 'static,
+    T: Checkpoint + Send + 'static,
     L: 'static,
 {
     fn name(&self) -> Cow<'static, str> {
@@ -446,7 +585,7 @@ where
 impl<D, T, L> SinkOperator<D> for ExchangeSender<D, T, L>
 where
     D: Clone + 'static,
-    T: Clone + Send + 'static,
+    T: Checkpoint + Clone + Send + 'static,
     L: FnMut(D, &mut Vec<T>) + 'static,
 {
     fn eval(&mut self, input: &D) {
@@ -488,7 +627,7 @@ pub struct ExchangeReceiver<T, L> {
 
 impl<T, L> ExchangeReceiver<T, L>
 where
-    T: Send + 'static,
+    T: Checkpoint + Send + 'static,
 {
     fn new(
         runtime: &Runtime,
@@ -510,7 +649,7 @@ where
 
 impl<T, L> Operator for ExchangeReceiver<T, L>
 where
-    T: Send + 'static,
+    T: Checkpoint + Send + 'static,
     L: 'static,
 {
     fn name(&self) -> Cow<'static, str> {
@@ -545,7 +684,7 @@ where
 impl<D, T, L> SourceOperator<D> for ExchangeReceiver<T, L>
 where
     D: Default + Clone,
-    T: Clone + Send + 'static,
+    T: Checkpoint + Clone + Send + 'static,
     L: Fn(&mut D, T) + 'static,
 {
     fn eval(&mut self) -> D {
@@ -571,6 +710,8 @@ where
 /// * `worker_index` - index of the current worker.
 /// * `partition` - partitioning logic that, for each element of the input
 ///   stream, returns an iterator with exactly `runtime.num_workers()` values.
+///   In a multihost runtime, values sent to workers in other processes are
+///   serialized using the [`Checkpoint`] trait.
 /// * `combine` - re-assemble logic that combines values received from all peers
 ///   into a single output value.
 ///
@@ -592,7 +733,7 @@ pub fn new_exchange_operators<TI, TO, TE, PL, CL>(
 ) -> (ExchangeSender<TI, TE, PL>, ExchangeReceiver<TE, CL>)
 where
     TO: Default + Clone,
-    TE: Send + 'static,
+    TE: Checkpoint + Send + 'static,
     PL: FnMut(TI, &mut Vec<TE>) + 'static,
     CL: Fn(&mut TO, TE) + 'static,
 {
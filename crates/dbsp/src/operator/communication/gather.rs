@@ -5,6 +5,7 @@ use crate::{
         GlobalNodeId, OwnershipPreference, Scope,
     },
     circuit_cache_key,
+    operator::communication::new_exchange_operators,
     trace::{spine_fueled::Spine, Batch, Trace},
     Circuit, Runtime, Stream,
 };
@@ -58,6 +59,14 @@ where
                         .cache_get_or_insert_with(
                             GatherId::new((self.origin_node_id().clone(), receiver_worker)),
                             move || {
+                                if runtime.layout().is_multihost() {
+                                    return self.gather_multihost(
+                                        &runtime,
+                                        receiver_worker,
+                                        location,
+                                    );
+                                }
+
                                 let current_worker = Runtime::worker_index();
                                 let gather_id = runtime.sequence_next(current_worker);
 
@@ -101,6 +110,36 @@ where
             }
         }
     }
+
+    /// Implementation of `gather` for multihost runtimes, where `GatherData`
+    /// cannot be shared by all workers.  Sends batches to the receiver
+    /// through an exchange, which serializes them for remote workers.
+    fn gather_multihost(
+        &self,
+        runtime: &Runtime,
+        receiver_worker: usize,
+        location: &'static Location<'static>,
+    ) -> Stream<C, B>
+    where
+        B: Batch<Time = ()> + Send,
+    {
+        let workers = runtime.num_workers();
+
+        let (sender, receiver) = new_exchange_operators(
+            runtime,
+            Runtime::worker_index(),
+            Some(location),
+            move |batch: B, batches: &mut Vec<B>| {
+                batches.extend((0..workers).map(|_| B::empty(())));
+                batches[receiver_worker] = batch;
+            },
+            |trace: &mut Spine<B>, batch: B| trace.insert(batch),
+        );
+
+        self.circuit()
+            .add_exchange(sender, receiver, self)
+            .consolidate()
+    }
 }
 
 struct GatherData<T> {
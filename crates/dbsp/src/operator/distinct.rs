@@ -5,7 +5,7 @@ use crate::{
     circuit::{
         metadata::{MetaItem, OperatorMeta},
         operator_traits::{BinaryOperator, Operator, UnaryOperator},
-        Circuit, GlobalNodeId, Scope, Stream, WithClock,
+        Circuit, GlobalNodeId, Scope, StatefulOperator, Stream, WithClock,
     },
     circuit_cache_key,
     trace::{ord::OrdValSpine, Batch, BatchReader, Builder, Cursor as TraceCursor, Trace},
@@ -80,7 +80,9 @@ where
                             circuit.add_binary_operator(
                                 DistinctIncrementalTotal::new(),
                                 &stream,
-                                &stream.integrate_trace().delay_trace(),
+                                &stream
+                                    .integrate_trace_for(StatefulOperator::Distinct)
+                                    .delay_trace(),
                             )
                         } else {
                             // ```
@@ -95,7 +97,7 @@ where
                                 DistinctIncremental::new(circuit.clone()),
                                 &stream,
                                 // TODO use OrdIndexedZSetSpine if `Z::Val = ()`
-                                &stream.trace::<OrdValSpine<Z::Key, Z::Val, <C as WithClock>::Time, Z::R>>(),
+                                &stream.trace_for::<OrdValSpine<Z::Key, Z::Val, <C as WithClock>::Time, Z::R>>(StatefulOperator::Distinct),
                             )
                         }
                         .mark_sharded()
@@ -1,8 +1,9 @@
-use super::{GroupTransformer, Monotonicity};
+use super::{GroupTransformer, Monotonicity, NonIncrementalGroupTransformer};
 use crate::{
     algebra::{HasZero, ZRingValue},
+    circuit::{ChildCircuit, WithClock},
     trace::{cursor::CursorPair, Cursor},
-    DBData, DBWeight, IndexedZSet, OrdIndexedZSet, RootCircuit, Stream,
+    Circuit, DBData, DBTimestamp, DBWeight, IndexedZSet, OrdIndexedZSet, RootCircuit, Stream,
 };
 use std::{cmp::Ordering, marker::PhantomData};
 
@@ -78,6 +79,78 @@ where
     }
 }
 
+impl<P, B> Stream<ChildCircuit<P>, B>
+where
+    P: Circuit,
+    <ChildCircuit<P> as WithClock>::Time: DBTimestamp,
+    B: IndexedZSet + Send,
+{
+    /// Lag operator matches each row in a group with a previous row in the
+    /// same group, in a nested circuit.
+    ///
+    /// For each key in the input stream, it matches each associated value with
+    /// a previous value (i.e., value with a smaller index according to
+    /// ascending order of values), applies projection function `project` to
+    /// it and outputs the input value along with this projection.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - offset to the previous value.
+    /// * `project` - projection function to apply to the delayed row.
+    #[allow(clippy::type_complexity)]
+    pub fn lag<OV, PF>(
+        &self,
+        offset: usize,
+        project: PF,
+    ) -> Stream<ChildCircuit<P>, OrdIndexedZSet<B::Key, (B::Val, OV), B::R>>
+    where
+        B::R: ZRingValue,
+        OV: DBData,
+        PF: Fn(Option<&B::Val>) -> OV + 'static,
+    {
+        self.group_transform(Lag::new(
+            offset,
+            true,
+            project,
+            |k1, k2| k1.cmp(k2),
+            |v1, v2| v1.cmp(v2),
+        ))
+    }
+
+    /// Lead operator matches each row in a group with a subsequent row in the
+    /// same group, in a nested circuit.
+    ///
+    /// For each key in the input stream, matches each associated value with
+    /// a subsequent value (i.e., value with a larger index according to
+    /// ascending order of values), applies projection function `project` to
+    /// it and outputs the input value along with this projection.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - offset to the subsequent value.
+    /// * `project` - projection function to apply to the subsequent row. The
+    ///   argument is `None` for out-of-range values.
+    #[allow(clippy::type_complexity)]
+    pub fn lead<OV, PF>(
+        &self,
+        offset: usize,
+        project: PF,
+    ) -> Stream<ChildCircuit<P>, OrdIndexedZSet<B::Key, (B::Val, OV), B::R>>
+    where
+        B::R: ZRingValue,
+        OV: DBData,
+        PF: Fn(Option<&B::Val>) -> OV + 'static,
+    {
+        self.group_transform(Lag::new(
+            offset,
+            false,
+            project,
+            |k1, k2| k2.cmp(k1),
+            |v1, v2| v2.cmp(v1),
+        ))
+    }
+}
+
 /// Implement both `lag` and `lead` operators.
 struct Lag<I, O, R, PF, KCF, VCF> {
     name: String,
@@ -525,6 +598,54 @@ where
     }
 }
 
+/// Non-incremental implementation of `lag` and `lead`, used in nested
+/// scopes.
+impl<I, O, R, PF, KCF, VCF> NonIncrementalGroupTransformer<I, (I, O), R>
+    for Lag<I, O, R, PF, KCF, VCF>
+where
+    I: DBData,
+    O: DBData,
+    R: DBWeight + ZRingValue,
+    PF: Fn(Option<&I>) -> O + 'static,
+    KCF: Fn(&I, &I) -> Ordering + 'static,
+    VCF: Fn(&O, &O) -> Ordering + 'static,
+{
+    fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    fn monotonicity(&self) -> Monotonicity {
+        Monotonicity::Ascending
+    }
+
+    fn transform<C, CB>(&mut self, cursor: &mut C, mut output_cb: CB)
+    where
+        C: Cursor<I, (), (), R>,
+        CB: FnMut((I, O), R),
+    {
+        let mut vals = Vec::new();
+
+        while cursor.key_valid() {
+            let w = cursor.weight();
+            if !w.is_zero() {
+                vals.push((cursor.key().clone(), w));
+            }
+            cursor.step_key();
+        }
+
+        for i in 0..vals.len() {
+            let delayed = if self.asc {
+                i.checked_sub(self.lag).map(|j| &vals[j].0)
+            } else {
+                vals.get(i + self.lag).map(|(v, _)| v)
+            };
+            let projection = (self.project)(delayed);
+
+            output_cb((vals[i].0.clone(), projection), vals[i].1.clone());
+        }
+    }
+}
+
 fn step_key_skip_zeros<C, I, R>(cursor: &mut C)
 where
     C: Cursor<I, (), (), R>,
@@ -534,7 +655,7 @@ where
     skip_zeros(cursor)
 }
 
-fn step_key_reverse_skip_zeros<C, I, R>(cursor: &mut C)
+pub(super) fn step_key_reverse_skip_zeros<C, I, R>(cursor: &mut C)
 where
     C: Cursor<I, (), (), R>,
     R: DBWeight,
@@ -553,7 +674,7 @@ where
     }
 }
 
-fn skip_zeros_reverse<C, I, R>(cursor: &mut C)
+pub(super) fn skip_zeros_reverse<C, I, R>(cursor: &mut C)
 where
     C: Cursor<I, (), (), R>,
     R: DBWeight,
@@ -566,11 +687,12 @@ where
 /// Cursor that reverses the direction of the underlying
 /// cursor.
 ///
-/// Enables a single implementation for `lag` and `lead`.
+/// Enables a single implementation for `lag` and `lead`, and for
+/// ascending and descending ranking.
 /// This is not a clean abstraction, as DBSP normally assumes
 /// that keys grow monotonically, therefore we keep it
-/// private to this module.
-struct ReverseCursor<'a, C, K, R> {
+/// private to the `group` module.
+pub(super) struct ReverseCursor<'a, C, K, R> {
     cursor: &'a mut C,
     _phantom: PhantomData<(K, R)>,
 }
@@ -579,7 +701,7 @@ impl<'a, C, K, R> ReverseCursor<'a, C, K, R>
 where
     C: Cursor<K, (), (), R>,
 {
-    fn new(cursor: &'a mut C) -> Self {
+    pub(super) fn new(cursor: &'a mut C) -> Self {
         cursor.fast_forward_keys();
 
         Self {
@@ -2,21 +2,30 @@
 //! into multiple output records.
 
 use crate::{
-    algebra::ZRingValue,
+    algebra::{AddAssignByRef, HasZero, Lattice, MonoidValue, PartialOrder, ZRingValue},
     circuit::{
         operator_traits::{Operator, TernaryOperator},
-        Scope,
+        ChildCircuit, ExportId, ExportStream, OwnershipPreference, Scope, WithClock,
     },
-    operator::trace::{TraceBounds, TraceFeedback},
+    operator::trace::{DelayedTraceId, TraceAppend, TraceBounds, TraceFeedback, TraceId, Z1Trace},
     trace::{
+        consolidation::consolidate,
         cursor::{CursorEmpty, CursorGroup, CursorPair},
-        Builder, Cursor, Spine, Trace,
+        Batch, BatchReader, Builder, Cursor, Spine, Trace,
     },
-    Circuit, DBData, DBWeight, IndexedZSet, OrdIndexedZSet, RootCircuit, Stream,
+    Circuit, DBData, DBTimestamp, DBWeight, IndexedZSet, OrdIndexedZSet, RootCircuit, Stream,
+    Timestamp,
+};
+use std::{
+    borrow::Cow,
+    cmp::{min, Ordering},
+    collections::{BTreeMap, BTreeSet},
+    marker::PhantomData,
+    ops::Neg,
 };
-use std::{borrow::Cow, marker::PhantomData, ops::Neg};
 
 mod lag;
+mod rank;
 mod topk;
 
 #[cfg(test)]
@@ -304,6 +313,111 @@ where
     }
 }
 
+impl<P, B> Stream<ChildCircuit<P>, B>
+where
+    P: Circuit,
+    <ChildCircuit<P> as WithClock>::Time: DBTimestamp,
+    B: IndexedZSet + Send,
+{
+    /// Apply group `transformer` to each partition in the input stream of a
+    /// nested circuit.
+    ///
+    /// Unlike the root-circuit version of this operator, recomputes each
+    /// affected group at the current timestamp using the non-incremental
+    /// `transformer`.
+    fn group_transform<GT, OV>(
+        &self,
+        transformer: GT,
+    ) -> Stream<ChildCircuit<P>, OrdIndexedZSet<B::Key, OV, B::R>>
+    where
+        GT: NonIncrementalGroupTransformer<B::Val, OV, B::R>,
+        OV: DBData,
+        B::R: ZRingValue,
+    {
+        self.group_transform_generic(transformer)
+    }
+
+    /// Like [`group_transform`](`Self::group_transform`), but can output any
+    /// indexed Z-set, not just [`OrdIndexedZSet`]
+    fn group_transform_generic<GT, OB>(&self, transformer: GT) -> Stream<ChildCircuit<P>, OB>
+    where
+        OB: IndexedZSet<Key = B::Key, R = B::R>,
+        OB::R: ZRingValue,
+        GT: NonIncrementalGroupTransformer<B::Val, OB::Val, B::R>,
+    {
+        let circuit = self.circuit();
+        let stream = self.shard();
+
+        // Same shape as the root-scope circuit, except that the input and
+        // output traces are timed and the output trace is maintained like in
+        // `upsert`.
+        //
+        // ```
+        //       ┌──────────────────────────────────────┐
+        //       │                                      │
+        //       │                                      ▼
+        // stream│  ┌───────┐  input_trace  ┌────────────────────┐   output  ┌───────────┐
+        // ──────┴─►│ trace ├──────────────►│GroupTransformNested├─────┬────►│TraceAppend├───┐
+        //          └───────┘               └────────────────────┘     │     └───────────┘   │
+        //                                            ▲                │           ▲         │
+        //                                            │                ▼           │         │
+        //                                            │   delayed_trace         ┌──┴───┐     │
+        //                                            └─────────────────────────┤Z1Trace│◄───┘
+        //                                                                      └──────┘
+        // ```
+        circuit.region("group_transform", || {
+            let bounds = <TraceBounds<B::Key, OB::Val>>::unbounded();
+
+            let (ExportStream { local, export }, z1feedback) = circuit.add_feedback_with_export(
+                Z1Trace::new(false, circuit.root_scope(), bounds.clone()),
+            );
+            local.mark_sharded();
+
+            let output = circuit
+                .add_ternary_operator(
+                    GroupTransformNested::new(transformer, circuit.clone()),
+                    &stream,
+                    &stream.trace::<Spine<
+                        <<ChildCircuit<P> as WithClock>::Time as Timestamp>::OrdValBatch<
+                            B::Key,
+                            B::Val,
+                            B::R,
+                        >,
+                    >>(),
+                    &local,
+                )
+                .mark_sharded();
+
+            let trace = circuit.add_binary_operator_with_preference(
+                <TraceAppend<
+                    Spine<
+                        <<ChildCircuit<P> as WithClock>::Time as Timestamp>::OrdValBatch<
+                            B::Key,
+                            OB::Val,
+                            B::R,
+                        >,
+                    >,
+                    OB,
+                    ChildCircuit<P>,
+                >>::new(circuit.clone()),
+                (&local, OwnershipPreference::STRONGLY_PREFER_OWNED),
+                (&output, OwnershipPreference::PREFER_OWNED),
+            );
+            trace.mark_sharded();
+
+            z1feedback.connect_with_preference(&trace, OwnershipPreference::STRONGLY_PREFER_OWNED);
+            circuit.cache_insert(DelayedTraceId::new(trace.origin_node_id().clone()), local);
+            circuit.cache_insert(ExportId::new(trace.origin_node_id().clone()), export);
+            circuit.cache_insert(
+                TraceId::new(output.origin_node_id().clone()),
+                (trace, bounds),
+            );
+
+            output
+        })
+    }
+}
+
 struct GroupTransform<B, OB, T, OT, GT>
 where
     B: IndexedZSet,
@@ -472,3 +586,381 @@ where
         builder.done()
     }
 }
+
+/// Group transform operator for nested scopes.
+///
+/// This is a ternary operator with the following inputs:
+/// * `delta` - stream of changes to the input indexed Z-set, only used to
+///   compute the set of affected keys.
+/// * `input_trace` - a timed trace of the input indexed Z-set.
+/// * `output_trace` - a timed trace of the output of the operator, delayed by
+///   one clock cycle.
+///
+/// For each affected key, the operator applies the non-incremental
+/// transformer to the contents of the input group at the current time and
+/// outputs the difference between the result and the contents of the output
+/// group at the current time.  Like
+/// [`AggregateIncremental`](`crate::operator::aggregate`), it tracks keys
+/// whose groups may change at future times due to updates at times that
+/// are not comparable with the current time.
+struct GroupTransformNested<B, T, OT, OB, GT, Clk>
+where
+    B: IndexedZSet,
+    T: BatchReader,
+    OB: IndexedZSet,
+{
+    clock: Clk,
+    transformer: GT,
+    // The last input batch was empty - used in fixedpoint computation.
+    empty_input: bool,
+    // The last output batch was empty - used in fixedpoint computation.
+    empty_output: bool,
+    // Keys that may need updating at future times.
+    keys_of_interest: BTreeMap<T::Time, BTreeSet<B::Key>>,
+    // Updates to the current output group.  Keep it here to reuse allocation
+    // across multiple operations.
+    buffer: Vec<(OB::Val, B::R)>,
+    _phantom: PhantomData<(B, T, OT, OB)>,
+}
+
+impl<B, T, OT, OB, GT, Clk> GroupTransformNested<B, T, OT, OB, GT, Clk>
+where
+    Clk: WithClock<Time = T::Time>,
+    B: IndexedZSet,
+    T: BatchReader<Key = B::Key, Val = B::Val, R = B::R>,
+    OT: BatchReader<Key = B::Key, Val = OB::Val, Time = T::Time, R = B::R>,
+    OB: IndexedZSet<Key = B::Key, R = B::R>,
+    OB::R: ZRingValue,
+    GT: NonIncrementalGroupTransformer<B::Val, OB::Val, B::R>,
+{
+    fn new(transformer: GT, clock: Clk) -> Self {
+        Self {
+            clock,
+            transformer,
+            empty_input: false,
+            empty_output: false,
+            keys_of_interest: BTreeMap::new(),
+            buffer: Vec::new(),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Compute updates to the output group for `key` at `time`.
+    fn eval_key(
+        &mut self,
+        key: &B::Key,
+        input_cursor: &mut T::Cursor<'_>,
+        output_cursor: &mut OT::Cursor<'_>,
+        builder: &mut OB::Builder,
+        time: &T::Time,
+    ) {
+        input_cursor.seek_key(key);
+
+        if input_cursor.key_valid() && input_cursor.key() == key {
+            let buffer = &mut self.buffer;
+            self.transformer.transform(
+                &mut UntimedCursor::new(CursorGroup::new(input_cursor, time.clone())),
+                |val, w| buffer.push((val, w)),
+            );
+
+            // Compute the closest future timestamp when we may need to reevaluate
+            // this key (see `AggregateIncremental::eval_key`).
+            input_cursor.rewind_vals();
+
+            let mut time_of_interest = None;
+            while input_cursor.val_valid() {
+                time_of_interest =
+                    input_cursor.fold_times(time_of_interest, |time_of_interest, ts, _| {
+                        if !ts.less_equal(time) {
+                            match time_of_interest {
+                                None => Some(time.join(ts)),
+                                Some(time_of_interest) => {
+                                    Some(min(time_of_interest, time.join(ts)))
+                                }
+                            }
+                        } else {
+                            time_of_interest
+                        }
+                    });
+                input_cursor.step_val();
+            }
+
+            if let Some(t) = time_of_interest {
+                self.keys_of_interest
+                    .entry(t)
+                    .or_insert_with(BTreeSet::new)
+                    .insert(key.clone());
+            }
+        }
+
+        // Retract the current contents of the output group.
+        output_cursor.seek_key(key);
+
+        if output_cursor.key_valid() && output_cursor.key() == key {
+            while output_cursor.val_valid() {
+                let mut weight = B::R::zero();
+                output_cursor.map_times(|t, w| {
+                    if t.less_equal(time) {
+                        weight.add_assign_by_ref(w);
+                    }
+                });
+
+                if !weight.is_zero() {
+                    self.buffer
+                        .push((output_cursor.val().clone(), weight.neg()));
+                }
+
+                output_cursor.step_val();
+            }
+        }
+
+        consolidate(&mut self.buffer);
+        for (val, w) in self.buffer.drain(..) {
+            builder.push((OB::item_from(key.clone(), val), w));
+        }
+    }
+}
+
+impl<B, T, OT, OB, GT, Clk> Operator for GroupTransformNested<B, T, OT, OB, GT, Clk>
+where
+    Clk: WithClock<Time = T::Time> + 'static,
+    B: IndexedZSet + 'static,
+    T: BatchReader + 'static,
+    OT: 'static,
+    OB: IndexedZSet + 'static,
+    GT: NonIncrementalGroupTransformer<B::Val, OB::Val, B::R>,
+{
+    fn name(&self) -> Cow<'static, str> {
+        Cow::from(format!("GroupTransformNested({})", self.transformer.name()))
+    }
+
+    fn clock_start(&mut self, scope: Scope) {
+        if scope == 0 {
+            self.empty_input = false;
+            self.empty_output = false;
+        }
+    }
+
+    fn fixedpoint(&self, scope: Scope) -> bool {
+        let epoch_end = self.clock.time().epoch_end(scope);
+
+        self.empty_input
+            && self.empty_output
+            && self
+                .keys_of_interest
+                .keys()
+                .all(|ts| !ts.less_equal(&epoch_end))
+    }
+}
+
+impl<B, T, OT, OB, GT, Clk> TernaryOperator<B, T, OT, OB>
+    for GroupTransformNested<B, T, OT, OB, GT, Clk>
+where
+    Clk: WithClock<Time = T::Time> + 'static,
+    B: IndexedZSet,
+    T: BatchReader<Key = B::Key, Val = B::Val, R = B::R> + Clone,
+    OT: BatchReader<Key = B::Key, Val = OB::Val, Time = T::Time, R = B::R> + Clone,
+    OB: IndexedZSet<Key = B::Key, R = B::R>,
+    OB::R: ZRingValue,
+    GT: NonIncrementalGroupTransformer<B::Val, OB::Val, B::R>,
+{
+    fn eval<'a>(
+        &mut self,
+        delta: Cow<'a, B>,
+        input_trace: Cow<'a, T>,
+        output_trace: Cow<'a, OT>,
+    ) -> OB {
+        self.empty_input = delta.is_empty();
+
+        let mut delta_cursor = delta.cursor();
+        let mut input_trace_cursor = input_trace.cursor();
+        let mut output_trace_cursor = output_trace.cursor();
+
+        let mut builder = OB::Builder::with_capacity((), delta.len());
+
+        let time = self.clock.time();
+
+        // Previously encountered keys that may affect output at the
+        // current time.
+        let keys_of_interest = self.keys_of_interest.remove(&time).unwrap_or_default();
+        let mut keys_of_interest = keys_of_interest.into_iter().peekable();
+
+        // Iterate over all keys in `delta_cursor` and `keys_of_interest`.
+        loop {
+            let key = match (delta_cursor.get_key(), keys_of_interest.peek()) {
+                (None, None) => break,
+                (Some(delta_key), None) => {
+                    let key = delta_key.clone();
+                    delta_cursor.step_key();
+                    key
+                }
+                (None, Some(_)) => keys_of_interest.next().unwrap(),
+                (Some(delta_key), Some(key_of_interest)) => match delta_key.cmp(key_of_interest) {
+                    Ordering::Less => {
+                        let key = delta_key.clone();
+                        delta_cursor.step_key();
+                        key
+                    }
+                    Ordering::Greater => keys_of_interest.next().unwrap(),
+                    Ordering::Equal => {
+                        delta_cursor.step_key();
+                        keys_of_interest.next().unwrap()
+                    }
+                },
+            };
+
+            self.eval_key(
+                &key,
+                &mut input_trace_cursor,
+                &mut output_trace_cursor,
+                &mut builder,
+                &time,
+            );
+        }
+
+        let result = builder.done();
+        self.empty_output = result.is_empty();
+        result
+    }
+}
+
+/// Cursor over a group of a timed trace that exposes the weights of its
+/// values as of the time of the underlying [`CursorGroup`] as untimed
+/// weights.
+///
+/// Used to apply [`NonIncrementalGroupTransformer`]s, which expect untimed
+/// cursors, in nested scopes.
+struct UntimedCursor<C, T> {
+    cursor: C,
+    _phantom: PhantomData<T>,
+}
+
+impl<C, T> UntimedCursor<C, T> {
+    fn new(cursor: C) -> Self {
+        Self {
+            cursor,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<V, T, R, C> Cursor<V, (), (), R> for UntimedCursor<C, T>
+where
+    T: Timestamp,
+    R: MonoidValue,
+    C: Cursor<V, (), T, R>,
+{
+    fn key_valid(&self) -> bool {
+        self.cursor.key_valid()
+    }
+
+    fn val_valid(&self) -> bool {
+        self.cursor.val_valid()
+    }
+
+    fn key(&self) -> &V {
+        self.cursor.key()
+    }
+
+    fn val(&self) -> &() {
+        self.cursor.val()
+    }
+
+    fn fold_times<F, U>(&mut self, init: U, mut fold: F) -> U
+    where
+        F: FnMut(U, &(), &R) -> U,
+    {
+        let weight = self.weight();
+        fold(init, &(), &weight)
+    }
+
+    fn fold_times_through<F, U>(&mut self, _upper: &(), init: U, fold: F) -> U
+    where
+        F: FnMut(U, &(), &R) -> U,
+    {
+        self.fold_times(init, fold)
+    }
+
+    fn weight(&mut self) -> R {
+        self.cursor.fold_times(R::zero(), |mut weight, _, w| {
+            weight.add_assign_by_ref(w);
+            weight
+        })
+    }
+
+    fn step_key(&mut self) {
+        self.cursor.step_key()
+    }
+
+    fn step_key_reverse(&mut self) {
+        self.cursor.step_key_reverse()
+    }
+
+    fn seek_key(&mut self, key: &V) {
+        self.cursor.seek_key(key)
+    }
+
+    fn seek_key_with<P>(&mut self, predicate: P)
+    where
+        P: Fn(&V) -> bool + Clone,
+    {
+        self.cursor.seek_key_with(predicate)
+    }
+
+    fn seek_key_with_reverse<P>(&mut self, predicate: P)
+    where
+        P: Fn(&V) -> bool + Clone,
+    {
+        self.cursor.seek_key_with_reverse(predicate)
+    }
+
+    fn seek_key_reverse(&mut self, key: &V) {
+        self.cursor.seek_key_reverse(key)
+    }
+
+    fn step_val(&mut self) {
+        self.cursor.step_val()
+    }
+
+    fn step_val_reverse(&mut self) {
+        self.cursor.step_val_reverse()
+    }
+
+    fn seek_val(&mut self, val: &()) {
+        self.cursor.seek_val(val)
+    }
+
+    fn seek_val_reverse(&mut self, val: &()) {
+        self.cursor.seek_val_reverse(val)
+    }
+
+    fn seek_val_with<P>(&mut self, predicate: P)
+    where
+        P: Fn(&()) -> bool + Clone,
+    {
+        self.cursor.seek_val_with(predicate)
+    }
+
+    fn seek_val_with_reverse<P>(&mut self, predicate: P)
+    where
+        P: Fn(&()) -> bool + Clone,
+    {
+        self.cursor.seek_val_with_reverse(predicate)
+    }
+
+    fn rewind_keys(&mut self) {
+        self.cursor.rewind_keys()
+    }
+
+    fn fast_forward_keys(&mut self) {
+        self.cursor.fast_forward_keys()
+    }
+
+    fn rewind_vals(&mut self) {
+        self.cursor.rewind_vals()
+    }
+
+    fn fast_forward_vals(&mut self) {
+        self.cursor.fast_forward_vals()
+    }
+}
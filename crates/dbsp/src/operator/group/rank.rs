@@ -0,0 +1,741 @@
+use super::{
+    lag::{skip_zeros_reverse, step_key_reverse_skip_zeros, ReverseCursor},
+    DiffGroupTransformer, GroupTransformer, Monotonicity, NonIncrementalGroupTransformer,
+};
+use crate::{
+    algebra::{HasOne, ZRingValue},
+    circuit::{ChildCircuit, WithClock},
+    trace::{cursor::CursorPair, Cursor},
+    Circuit, DBData, DBTimestamp, DBWeight, IndexedZSet, OrdIndexedZSet, RootCircuit, Stream,
+};
+use num::ToPrimitive;
+use std::{cmp::Ordering, marker::PhantomData};
+
+impl<B> Stream<RootCircuit, B>
+where
+    B: IndexedZSet + Send,
+{
+    /// Number the rows in each group in ascending order of values.
+    ///
+    /// For each key in the input stream, outputs each associated value
+    /// paired with its 1-based position in the group.  A value with weight
+    /// `n` represents `n` rows, which receive `n` consecutive row numbers.
+    /// Values with non-positive weights are ignored.
+    ///
+    /// This is the equivalent of SQL's
+    /// `ROW_NUMBER() OVER (PARTITION BY key ORDER BY value)`.  The operator
+    /// is incremental: a change to the group only updates the row numbers of
+    /// values that follow the smallest modified value.
+    #[allow(clippy::type_complexity)]
+    pub fn row_number_asc(&self) -> Stream<RootCircuit, OrdIndexedZSet<B::Key, (B::Val, i64), B::R>>
+    where
+        B::R: ZRingValue + ToPrimitive,
+    {
+        self.group_transform(Rank::new(RankKind::RowNumber, true))
+    }
+
+    /// Number the rows in each group in descending order of values.
+    ///
+    /// See [`Self::row_number_asc`].
+    #[allow(clippy::type_complexity)]
+    pub fn row_number_desc(
+        &self,
+    ) -> Stream<RootCircuit, OrdIndexedZSet<B::Key, (B::Val, i64), B::R>>
+    where
+        B::R: ZRingValue + ToPrimitive,
+    {
+        self.group_transform(Rank::new(RankKind::RowNumber, false))
+    }
+
+    /// Number the rows in each group in the order defined by `cmp`.
+    ///
+    /// Like [`Self::row_number_asc`], but orders values using `cmp`.  Values
+    /// that compare equal are numbered in ascending order.
+    ///
+    /// Unlike [`Self::row_number_asc`], this operator recomputes the entire
+    /// group whenever it changes.
+    #[allow(clippy::type_complexity)]
+    pub fn row_number_by<F>(
+        &self,
+        cmp: F,
+    ) -> Stream<RootCircuit, OrdIndexedZSet<B::Key, (B::Val, i64), B::R>>
+    where
+        B::R: ZRingValue + ToPrimitive,
+        F: Fn(&B::Val, &B::Val) -> Ordering + 'static,
+    {
+        self.group_transform(DiffGroupTransformer::new(RankBy::new(
+            RankKind::RowNumber,
+            cmp,
+        )))
+    }
+
+    /// Rank the values in each group in ascending order.
+    ///
+    /// For each key in the input stream, outputs each associated value
+    /// paired with its rank, i.e., one plus the number of rows with smaller
+    /// values in the group.  Duplicate values receive the same rank, leaving
+    /// gaps in the sequence of ranks.  Values with non-positive weights are
+    /// ignored.
+    ///
+    /// This is the equivalent of SQL's
+    /// `RANK() OVER (PARTITION BY key ORDER BY value)`.  The operator is
+    /// incremental: a change to the group only updates the ranks of values
+    /// that follow the smallest modified value.
+    #[allow(clippy::type_complexity)]
+    pub fn rank_asc(&self) -> Stream<RootCircuit, OrdIndexedZSet<B::Key, (B::Val, i64), B::R>>
+    where
+        B::R: ZRingValue + ToPrimitive,
+    {
+        self.group_transform(Rank::new(RankKind::Rank, true))
+    }
+
+    /// Rank the values in each group in descending order.
+    ///
+    /// See [`Self::rank_asc`].
+    #[allow(clippy::type_complexity)]
+    pub fn rank_desc(&self) -> Stream<RootCircuit, OrdIndexedZSet<B::Key, (B::Val, i64), B::R>>
+    where
+        B::R: ZRingValue + ToPrimitive,
+    {
+        self.group_transform(Rank::new(RankKind::Rank, false))
+    }
+
+    /// Rank the values in each group in the order defined by `cmp`.
+    ///
+    /// Like [`Self::rank_asc`], but orders values using `cmp`.  Values that
+    /// compare equal receive the same rank.
+    ///
+    /// Unlike [`Self::rank_asc`], this operator recomputes the entire group
+    /// whenever it changes.
+    #[allow(clippy::type_complexity)]
+    pub fn rank_by<F>(
+        &self,
+        cmp: F,
+    ) -> Stream<RootCircuit, OrdIndexedZSet<B::Key, (B::Val, i64), B::R>>
+    where
+        B::R: ZRingValue + ToPrimitive,
+        F: Fn(&B::Val, &B::Val) -> Ordering + 'static,
+    {
+        self.group_transform(DiffGroupTransformer::new(RankBy::new(RankKind::Rank, cmp)))
+    }
+
+    /// Rank the values in each group in ascending order without gaps.
+    ///
+    /// Like [`Self::rank_asc`], but ranks form a contiguous sequence: the
+    /// rank of a value is one plus the number of distinct smaller values in
+    /// the group.
+    ///
+    /// This is the equivalent of SQL's
+    /// `DENSE_RANK() OVER (PARTITION BY key ORDER BY value)`.
+    #[allow(clippy::type_complexity)]
+    pub fn dense_rank_asc(&self) -> Stream<RootCircuit, OrdIndexedZSet<B::Key, (B::Val, i64), B::R>>
+    where
+        B::R: ZRingValue + ToPrimitive,
+    {
+        self.group_transform(Rank::new(RankKind::DenseRank, true))
+    }
+
+    /// Rank the values in each group in descending order without gaps.
+    ///
+    /// See [`Self::dense_rank_asc`].
+    #[allow(clippy::type_complexity)]
+    pub fn dense_rank_desc(
+        &self,
+    ) -> Stream<RootCircuit, OrdIndexedZSet<B::Key, (B::Val, i64), B::R>>
+    where
+        B::R: ZRingValue + ToPrimitive,
+    {
+        self.group_transform(Rank::new(RankKind::DenseRank, false))
+    }
+
+    /// Rank the values in each group in the order defined by `cmp` without
+    /// gaps.
+    ///
+    /// Like [`Self::dense_rank_asc`], but orders values using `cmp`.  Values
+    /// that compare equal receive the same rank.
+    ///
+    /// Unlike [`Self::dense_rank_asc`], this operator recomputes the entire
+    /// group whenever it changes.
+    #[allow(clippy::type_complexity)]
+    pub fn dense_rank_by<F>(
+        &self,
+        cmp: F,
+    ) -> Stream<RootCircuit, OrdIndexedZSet<B::Key, (B::Val, i64), B::R>>
+    where
+        B::R: ZRingValue + ToPrimitive,
+        F: Fn(&B::Val, &B::Val) -> Ordering + 'static,
+    {
+        self.group_transform(DiffGroupTransformer::new(RankBy::new(
+            RankKind::DenseRank,
+            cmp,
+        )))
+    }
+}
+
+impl<P, B> Stream<ChildCircuit<P>, B>
+where
+    P: Circuit,
+    <ChildCircuit<P> as WithClock>::Time: DBTimestamp,
+    B: IndexedZSet + Send,
+{
+    /// Nested-circuit version of the `row_number_asc` operator.
+    ///
+    /// Recomputes each modified group in full.
+    #[allow(clippy::type_complexity)]
+    pub fn row_number_asc(
+        &self,
+    ) -> Stream<ChildCircuit<P>, OrdIndexedZSet<B::Key, (B::Val, i64), B::R>>
+    where
+        B::R: ZRingValue + ToPrimitive,
+    {
+        self.group_transform(Rank::new(RankKind::RowNumber, true))
+    }
+
+    /// Nested-circuit version of the `row_number_desc` operator.
+    ///
+    /// Recomputes each modified group in full.
+    #[allow(clippy::type_complexity)]
+    pub fn row_number_desc(
+        &self,
+    ) -> Stream<ChildCircuit<P>, OrdIndexedZSet<B::Key, (B::Val, i64), B::R>>
+    where
+        B::R: ZRingValue + ToPrimitive,
+    {
+        self.group_transform(Rank::new(RankKind::RowNumber, false))
+    }
+
+    /// Nested-circuit version of the `row_number_by` operator.
+    ///
+    /// Recomputes each modified group in full.
+    #[allow(clippy::type_complexity)]
+    pub fn row_number_by<F>(
+        &self,
+        cmp: F,
+    ) -> Stream<ChildCircuit<P>, OrdIndexedZSet<B::Key, (B::Val, i64), B::R>>
+    where
+        B::R: ZRingValue + ToPrimitive,
+        F: Fn(&B::Val, &B::Val) -> Ordering + 'static,
+    {
+        self.group_transform(RankBy::new(RankKind::RowNumber, cmp))
+    }
+
+    /// Nested-circuit version of the `rank_asc` operator.
+    ///
+    /// Recomputes each modified group in full.
+    #[allow(clippy::type_complexity)]
+    pub fn rank_asc(&self) -> Stream<ChildCircuit<P>, OrdIndexedZSet<B::Key, (B::Val, i64), B::R>>
+    where
+        B::R: ZRingValue + ToPrimitive,
+    {
+        self.group_transform(Rank::new(RankKind::Rank, true))
+    }
+
+    /// Nested-circuit version of the `rank_desc` operator.
+    ///
+    /// Recomputes each modified group in full.
+    #[allow(clippy::type_complexity)]
+    pub fn rank_desc(&self) -> Stream<ChildCircuit<P>, OrdIndexedZSet<B::Key, (B::Val, i64), B::R>>
+    where
+        B::R: ZRingValue + ToPrimitive,
+    {
+        self.group_transform(Rank::new(RankKind::Rank, false))
+    }
+
+    /// Nested-circuit version of the `rank_by` operator.
+    ///
+    /// Recomputes each modified group in full.
+    #[allow(clippy::type_complexity)]
+    pub fn rank_by<F>(
+        &self,
+        cmp: F,
+    ) -> Stream<ChildCircuit<P>, OrdIndexedZSet<B::Key, (B::Val, i64), B::R>>
+    where
+        B::R: ZRingValue + ToPrimitive,
+        F: Fn(&B::Val, &B::Val) -> Ordering + 'static,
+    {
+        self.group_transform(RankBy::new(RankKind::Rank, cmp))
+    }
+
+    /// Nested-circuit version of the `dense_rank_asc` operator.
+    ///
+    /// Recomputes each modified group in full.
+    #[allow(clippy::type_complexity)]
+    pub fn dense_rank_asc(
+        &self,
+    ) -> Stream<ChildCircuit<P>, OrdIndexedZSet<B::Key, (B::Val, i64), B::R>>
+    where
+        B::R: ZRingValue + ToPrimitive,
+    {
+        self.group_transform(Rank::new(RankKind::DenseRank, true))
+    }
+
+    /// Nested-circuit version of the `dense_rank_desc` operator.
+    ///
+    /// Recomputes each modified group in full.
+    #[allow(clippy::type_complexity)]
+    pub fn dense_rank_desc(
+        &self,
+    ) -> Stream<ChildCircuit<P>, OrdIndexedZSet<B::Key, (B::Val, i64), B::R>>
+    where
+        B::R: ZRingValue + ToPrimitive,
+    {
+        self.group_transform(Rank::new(RankKind::DenseRank, false))
+    }
+
+    /// Nested-circuit version of the `dense_rank_by` operator.
+    ///
+    /// Recomputes each modified group in full.
+    #[allow(clippy::type_complexity)]
+    pub fn dense_rank_by<F>(
+        &self,
+        cmp: F,
+    ) -> Stream<ChildCircuit<P>, OrdIndexedZSet<B::Key, (B::Val, i64), B::R>>
+    where
+        B::R: ZRingValue + ToPrimitive,
+        F: Fn(&B::Val, &B::Val) -> Ordering + 'static,
+    {
+        self.group_transform(RankBy::new(RankKind::DenseRank, cmp))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum RankKind {
+    RowNumber,
+    Rank,
+    DenseRank,
+}
+
+impl RankKind {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::RowNumber => "row_number",
+            Self::Rank => "rank",
+            Self::DenseRank => "dense_rank",
+        }
+    }
+}
+
+/// Number of rows represented by a value with weight `weight`, or 0 for
+/// non-positive weights.
+fn row_count<R>(weight: &R) -> i64
+where
+    R: ToPrimitive,
+{
+    weight.to_i64().unwrap_or(0).max(0)
+}
+
+/// Compare `a` and `b` in ascending or descending order.
+fn order<T>(asc: bool, a: &T, b: &T) -> Ordering
+where
+    T: Ord,
+{
+    if asc {
+        a.cmp(b)
+    } else {
+        b.cmp(a)
+    }
+}
+
+/// Ranking state of a group scanned in sort order.
+struct RankState {
+    kind: RankKind,
+    /// Number of rows scanned so far.
+    rows: i64,
+    /// Rank of the last value scanned.
+    rank: i64,
+}
+
+impl RankState {
+    fn new(kind: RankKind) -> Self {
+        Self {
+            kind,
+            rows: 0,
+            rank: 0,
+        }
+    }
+
+    /// Assign a rank to the next value in sort order.
+    ///
+    /// `count` is the number of rows represented by `val`, and `tied` is
+    /// `true` if `val` compares equal to the previous value.  Output tuples
+    /// are produced in ascending or descending order, depending on `asc`.
+    fn push<I, R, CB>(&mut self, val: I, weight: R, count: i64, tied: bool, asc: bool, mut cb: CB)
+    where
+        I: Clone,
+        R: HasOne,
+        CB: FnMut((I, i64), R),
+    {
+        match self.kind {
+            RankKind::RowNumber => {
+                let first = self.rows + 1;
+                self.rows += count;
+                if asc {
+                    for row in first..=self.rows {
+                        cb((val.clone(), row), R::one());
+                    }
+                } else {
+                    for row in (first..=self.rows).rev() {
+                        cb((val.clone(), row), R::one());
+                    }
+                }
+            }
+            RankKind::Rank => {
+                if !tied {
+                    self.rank = self.rows + 1;
+                }
+                self.rows += count;
+                cb((val, self.rank), weight);
+            }
+            RankKind::DenseRank => {
+                if !tied {
+                    self.rank += 1;
+                }
+                cb((val, self.rank), weight);
+            }
+        }
+    }
+}
+
+/// Incremental implementation of ranking functions in ascending or
+/// descending order of values.
+///
+/// Ranks of values that precede the first modified value in sort order
+/// don't change.  The transformer recovers the ranking state at that value
+/// from the last preceding record in the output trace and recomputes the
+/// rest of the group from there.
+struct Rank<I, R> {
+    name: String,
+    kind: RankKind,
+    asc: bool,
+    _phantom: PhantomData<(I, R)>,
+}
+
+impl<I, R> Rank<I, R>
+where
+    I: DBData,
+    R: DBWeight + ZRingValue + ToPrimitive,
+{
+    fn new(kind: RankKind, asc: bool) -> Self {
+        Self {
+            name: format!("{}-{}", kind.name(), if asc { "asc" } else { "desc" }),
+            kind,
+            asc,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Recover the ranking state right before `first` from `output_trace`
+    /// and position the cursor at the first output record for `first` or a
+    /// later value.
+    fn initial_state<C>(&self, output_trace: &mut C, first: &I) -> RankState
+    where
+        C: Cursor<(I, i64), (), (), R>,
+    {
+        let asc = self.asc;
+        let mut state = RankState::new(self.kind);
+
+        output_trace.seek_key_with(|(val, _)| order(asc, val, first) != Ordering::Less);
+        if output_trace.key_valid() {
+            step_key_reverse_skip_zeros(output_trace);
+        } else {
+            output_trace.fast_forward_keys();
+            skip_zeros_reverse(output_trace);
+        }
+
+        if output_trace.key_valid() {
+            let (prev, rank) = output_trace.key().clone();
+
+            match self.kind {
+                RankKind::RowNumber => {
+                    // Rows that represent the same value are numbered consecutively;
+                    // find the largest row number assigned to `prev`.
+                    while output_trace.key_valid() && output_trace.key().0 == prev {
+                        if !output_trace.weight().is_zero() {
+                            state.rows = state.rows.max(output_trace.key().1);
+                        }
+                        output_trace.step_key_reverse();
+                    }
+                }
+                RankKind::Rank => {
+                    state.rows = rank + row_count(&output_trace.weight()) - 1;
+                }
+                RankKind::DenseRank => {
+                    state.rank = rank;
+                }
+            }
+        }
+
+        output_trace.rewind_keys();
+        output_trace.seek_key_with(|(val, _)| order(asc, val, first) != Ordering::Less);
+
+        state
+    }
+
+    /// Recompute the ranks of values starting from `first`.
+    ///
+    /// `input_cursor` iterates over the new contents of the input group and
+    /// `output_trace` over the old contents of the output group, both in the
+    /// sort order of the transformer.
+    fn update<C1, C2, CB>(
+        &self,
+        first: &I,
+        input_cursor: &mut C1,
+        output_trace: &mut C2,
+        mut output_cb: CB,
+    ) where
+        C1: Cursor<I, (), (), R>,
+        C2: Cursor<(I, i64), (), (), R>,
+        CB: FnMut((I, i64), R),
+    {
+        let asc = self.asc;
+        let mut state = self.initial_state(output_trace, first);
+
+        // Merge new outputs with retractions of old outputs to produce updates
+        // in sort order.
+        let mut insert = |val: (I, i64), w: R| {
+            while output_trace.key_valid() && order(asc, output_trace.key(), &val) == Ordering::Less
+            {
+                retract(output_trace, &mut output_cb);
+            }
+
+            if output_trace.key_valid() && output_trace.key() == &val {
+                let w = w + output_trace.weight().neg();
+                if !w.is_zero() {
+                    output_cb(val, w);
+                }
+                output_trace.step_key();
+            } else {
+                output_cb(val, w);
+            }
+        };
+
+        input_cursor.seek_key(first);
+        while input_cursor.key_valid() {
+            let weight = input_cursor.weight();
+            let count = row_count(&weight);
+            if count > 0 {
+                state.push(
+                    input_cursor.key().clone(),
+                    weight,
+                    count,
+                    false,
+                    asc,
+                    &mut insert,
+                );
+            }
+            input_cursor.step_key();
+        }
+
+        // Retract the remaining old outputs.
+        while output_trace.key_valid() {
+            retract(output_trace, &mut output_cb);
+        }
+    }
+}
+
+/// Retract the current record of `output_trace` and advance the cursor.
+fn retract<I, R, C, CB>(output_trace: &mut C, output_cb: &mut CB)
+where
+    I: Clone,
+    R: ZRingValue,
+    C: Cursor<(I, i64), (), (), R>,
+    CB: FnMut((I, i64), R),
+{
+    let w = output_trace.weight();
+    if !w.is_zero() {
+        output_cb(output_trace.key().clone(), w.neg());
+    }
+    output_trace.step_key();
+}
+
+impl<I, R> GroupTransformer<I, (I, i64), R> for Rank<I, R>
+where
+    I: DBData,
+    R: DBWeight + ZRingValue + ToPrimitive,
+{
+    fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    fn monotonicity(&self) -> Monotonicity {
+        if self.asc {
+            Monotonicity::Ascending
+        } else {
+            Monotonicity::Descending
+        }
+    }
+
+    fn transform<C1, C2, C3, CB>(
+        &mut self,
+        input_delta: &mut C1,
+        input_trace: &mut C2,
+        output_trace: &mut C3,
+        output_cb: CB,
+    ) where
+        C1: Cursor<I, (), (), R>,
+        C2: Cursor<I, (), (), R>,
+        C3: Cursor<(I, i64), (), (), R>,
+        CB: FnMut((I, i64), R),
+    {
+        if self.asc {
+            if !input_delta.key_valid() {
+                return;
+            }
+            let first = input_delta.key().clone();
+
+            self.update(
+                &first,
+                &mut CursorPair::new(input_delta, input_trace),
+                output_trace,
+                output_cb,
+            );
+        } else {
+            input_delta.fast_forward_keys();
+            if !input_delta.key_valid() {
+                return;
+            }
+            let first = input_delta.key().clone();
+
+            self.update(
+                &first,
+                &mut ReverseCursor::new(&mut CursorPair::new(input_delta, input_trace)),
+                &mut ReverseCursor::new(output_trace),
+                output_cb,
+            );
+        }
+    }
+}
+
+/// Non-incremental implementation of ranking functions in ascending or
+/// descending order of values, used in nested scopes.
+impl<I, R> NonIncrementalGroupTransformer<I, (I, i64), R> for Rank<I, R>
+where
+    I: DBData,
+    R: DBWeight + ZRingValue + ToPrimitive,
+{
+    fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    fn monotonicity(&self) -> Monotonicity {
+        if self.asc {
+            Monotonicity::Ascending
+        } else {
+            Monotonicity::Descending
+        }
+    }
+
+    fn transform<C, CB>(&mut self, cursor: &mut C, mut output_cb: CB)
+    where
+        C: Cursor<I, (), (), R>,
+        CB: FnMut((I, i64), R),
+    {
+        let asc = self.asc;
+        let mut state = RankState::new(self.kind);
+
+        if !asc {
+            cursor.fast_forward_keys();
+        }
+
+        while cursor.key_valid() {
+            let weight = cursor.weight();
+            let count = row_count(&weight);
+            if count > 0 {
+                state.push(
+                    cursor.key().clone(),
+                    weight,
+                    count,
+                    false,
+                    asc,
+                    &mut output_cb,
+                );
+            }
+
+            if asc {
+                cursor.step_key();
+            } else {
+                cursor.step_key_reverse();
+            }
+        }
+    }
+}
+
+/// Non-incremental implementation of ranking functions with a custom
+/// comparator.
+struct RankBy<I, R, F> {
+    name: String,
+    kind: RankKind,
+    cmp: F,
+    /// Values in the group with their weights and row counts.  Reused
+    /// across invocations of the transformer.
+    values: Vec<(I, R, i64)>,
+    /// Output tuples.  Reused across invocations of the transformer.
+    outputs: Vec<((I, i64), R)>,
+}
+
+impl<I, R, F> RankBy<I, R, F> {
+    fn new(kind: RankKind, cmp: F) -> Self {
+        Self {
+            name: format!("{}-by", kind.name()),
+            kind,
+            cmp,
+            values: Vec::new(),
+            outputs: Vec::new(),
+        }
+    }
+}
+
+impl<I, R, F> NonIncrementalGroupTransformer<I, (I, i64), R> for RankBy<I, R, F>
+where
+    I: DBData,
+    R: DBWeight + ZRingValue + ToPrimitive,
+    F: Fn(&I, &I) -> Ordering + 'static,
+{
+    fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    fn monotonicity(&self) -> Monotonicity {
+        Monotonicity::Ascending
+    }
+
+    fn transform<C, CB>(&mut self, cursor: &mut C, mut output_cb: CB)
+    where
+        C: Cursor<I, (), (), R>,
+        CB: FnMut((I, i64), R),
+    {
+        while cursor.key_valid() {
+            let weight = cursor.weight();
+            let count = row_count(&weight);
+            if count > 0 {
+                self.values.push((cursor.key().clone(), weight, count));
+            }
+            cursor.step_key();
+        }
+
+        // Stable sort: values that compare equal remain in ascending order.
+        let cmp = &self.cmp;
+        self.values.sort_by(|(v1, _, _), (v2, _, _)| cmp(v1, v2));
+
+        let mut state = RankState::new(self.kind);
+        for i in 0..self.values.len() {
+            let tied = i > 0 && cmp(&self.values[i - 1].0, &self.values[i].0) == Ordering::Equal;
+            let (val, weight, count) = &self.values[i];
+            state.push(
+                val.clone(),
+                weight.clone(),
+                *count,
+                tied,
+                true,
+                |tuple, w| self.outputs.push((tuple, w)),
+            );
+        }
+        self.values.clear();
+
+        // Output tuples in ascending order.
+        self.outputs.sort_by(|(t1, _), (t2, _)| t1.cmp(t2));
+        for (tuple, w) in self.outputs.drain(..) {
+            output_cb(tuple, w);
+        }
+    }
+}
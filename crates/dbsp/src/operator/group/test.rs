@@ -1,13 +1,24 @@
 use crate::{
     algebra::ZRingValue,
+    operator::GeneratorNested,
     trace::{
         cursor::Cursor,
         test_batch::{assert_batch_eq, TestBatch},
-        BatchReader, Trace,
+        Batch, BatchReader, Trace,
     },
-    CollectionHandle, DBData, DBWeight, OrdIndexedZSet, OutputHandle, RootCircuit, Runtime,
+    ChildCircuit, Circuit, CollectionHandle, DBData, DBWeight, OrdIndexedZSet, OrdZSet,
+    OutputHandle, RootCircuit, Runtime, Stream,
 };
+use num::ToPrimitive;
 use proptest::{collection::vec, prelude::*};
+use std::{cell::RefCell, cmp::Ordering, rc::Rc};
+
+#[derive(Clone, Copy)]
+enum Ranking {
+    RowNumber,
+    Rank,
+    DenseRank,
+}
 
 fn input_trace(
     max_key: i32,
@@ -123,6 +134,66 @@ where
 
         TestBatch::from_data(&result)
     }
+
+    fn ranking<F>(&self, ranking: Ranking, cmp: F) -> TestBatch<K, (V, i64), (), R>
+    where
+        R: ToPrimitive,
+        F: Fn(&V, &V) -> Ordering,
+    {
+        let mut result = Vec::new();
+        let mut cursor = self.cursor();
+
+        while cursor.key_valid() {
+            let mut vals = Vec::new();
+
+            while cursor.val_valid() {
+                let w = cursor.weight();
+                if w.to_i64().unwrap() > 0 {
+                    vals.push((cursor.val().clone(), w));
+                }
+                cursor.step_val();
+            }
+            vals.sort_by(|(v1, _), (v2, _)| cmp(v1, v2));
+
+            let mut rows = 0;
+            let mut rank = 0;
+            for i in 0..vals.len() {
+                let (v, w) = vals[i].clone();
+                let tied = i > 0 && cmp(&vals[i - 1].0, &v) == Ordering::Equal;
+                let count = w.to_i64().unwrap();
+
+                match ranking {
+                    Ranking::RowNumber => {
+                        for row in rows + 1..=rows + count {
+                            result.push(((cursor.key().clone(), (v.clone(), row), ()), R::one()));
+                        }
+                    }
+                    Ranking::Rank => {
+                        if !tied {
+                            rank = rows + 1;
+                        }
+                        result.push(((cursor.key().clone(), (v, rank), ()), w));
+                    }
+                    Ranking::DenseRank => {
+                        if !tied {
+                            rank += 1;
+                        }
+                        result.push(((cursor.key().clone(), (v, rank), ()), w));
+                    }
+                }
+                rows += count;
+            }
+
+            cursor.step_key();
+        }
+
+        TestBatch::from_data(&result)
+    }
+}
+
+/// Comparator with ties used to test `*_by` ranking operators.
+fn by_tens_desc(v1: &i32, v2: &i32) -> Ordering {
+    (v2 / 10).cmp(&(v1 / 10))
 }
 
 fn topk_test_circuit(
@@ -166,6 +237,154 @@ fn lead_test_circuit(
     (input_handle, lead_handle)
 }
 
+type RankOutputHandle = OutputHandle<OrdIndexedZSet<i32, (i32, i64), i32>>;
+
+/// Returns handles to `row_number`, `rank` and `dense_rank` outputs in
+/// ascending, descending and custom (`by_tens_desc`) order.
+fn rank_test_circuit(
+    circuit: &mut RootCircuit,
+) -> (CollectionHandle<i32, (i32, i32)>, Vec<RankOutputHandle>) {
+    let (input_stream, input_handle) = circuit.add_input_indexed_zset::<i32, i32, i32>();
+
+    let outputs = vec![
+        input_stream.row_number_asc().integrate().output(),
+        input_stream.row_number_desc().integrate().output(),
+        input_stream
+            .row_number_by(by_tens_desc)
+            .integrate()
+            .output(),
+        input_stream.rank_asc().integrate().output(),
+        input_stream.rank_desc().integrate().output(),
+        input_stream.rank_by(by_tens_desc).integrate().output(),
+        input_stream.dense_rank_asc().integrate().output(),
+        input_stream.dense_rank_desc().integrate().output(),
+        input_stream
+            .dense_rank_by(by_tens_desc)
+            .integrate()
+            .output(),
+    ];
+
+    (input_handle, outputs)
+}
+
+/// Apply reference implementation `transform` to the contents of `batch`.
+fn reference<V, F>(
+    batch: &OrdIndexedZSet<i32, i32, i32>,
+    transform: F,
+) -> OrdIndexedZSet<i32, V, i32>
+where
+    V: DBData,
+    F: Fn(&TestBatch<i32, i32, (), i32>) -> TestBatch<i32, V, (), i32>,
+{
+    let mut records = Vec::new();
+    let mut cursor = batch.cursor();
+    while cursor.key_valid() {
+        while cursor.val_valid() {
+            records.push(((*cursor.key(), *cursor.val(), ()), cursor.weight()));
+            cursor.step_val();
+        }
+        cursor.step_key();
+    }
+
+    let output = transform(&TestBatch::from_data(&records));
+
+    let mut tuples = Vec::new();
+    let mut cursor = output.cursor();
+    while cursor.key_valid() {
+        while cursor.val_valid() {
+            tuples.push(((*cursor.key(), cursor.val().clone()), cursor.weight()));
+            cursor.step_val();
+        }
+        cursor.step_key();
+    }
+
+    OrdIndexedZSet::from_tuples((), tuples)
+}
+
+type NestedStream<V> = Stream<ChildCircuit<RootCircuit>, OrdIndexedZSet<i32, V, i32>>;
+
+/// Check that `output`, computed from `input` inside a nested circuit,
+/// matches the result of applying `transform` to the complete contents of
+/// `input` at every timestamp.
+fn check_nested<V, F>(input: &NestedStream<i32>, output: &NestedStream<V>, transform: F)
+where
+    V: DBData,
+    F: Fn(&TestBatch<i32, i32, (), i32>) -> TestBatch<i32, V, (), i32> + 'static,
+{
+    let expected = input
+        .integrate_nested()
+        .integrate()
+        .apply(move |batch| reference(batch, &transform))
+        .differentiate()
+        .differentiate_nested();
+
+    output.apply2(&expected, |output, expected| {
+        assert_batch_eq(output, expected)
+    });
+}
+
+/// Number of nested clock ticks per parent clock tick in `nested_test_circuit`.
+/// Exceeds the number of input batches per epoch, so that operators also run
+/// after the input has been exhausted.
+const NESTED_ITERATIONS: usize = 8;
+
+/// Evaluate group operators inside an `iterate` child circuit.  `inputs`
+/// contains a sequence of input batches for each nested clock epoch.
+fn nested_test_circuit(circuit: &mut RootCircuit, inputs: Vec<Vec<Vec<(i32, i32, i32)>>>) {
+    let mut inputs = inputs.into_iter();
+
+    circuit
+        .iterate(|child| {
+            let counter = Rc::new(RefCell::new(0));
+            let counter_clone = counter.clone();
+
+            let input = child
+                .add_source(GeneratorNested::new(Box::new(move || {
+                    *counter_clone.borrow_mut() = 0;
+                    let mut deltas = if Runtime::worker_index() == 0 {
+                        inputs.next().unwrap_or_default()
+                    } else {
+                        Vec::new()
+                    }
+                    .into_iter();
+                    Box::new(move || {
+                        let tuples = deltas
+                            .next()
+                            .unwrap_or_default()
+                            .into_iter()
+                            .map(|(k, v, r)| ((k, v), r))
+                            .collect();
+                        <OrdZSet<(i32, i32), i32>>::from_tuples((), tuples)
+                    })
+                })))
+                .index()
+                .shard();
+
+            check_nested(&input, &input.topk_asc(5), |b| b.topk_asc(5));
+            check_nested(&input, &input.topk_desc(5), |b| b.topk_desc(5));
+            check_nested(&input, &input.lag(3, |v| v.cloned()), |b| b.lag(3));
+            check_nested(&input, &input.lead(3, |v| v.cloned()), |b| b.lead(3));
+            check_nested(&input, &input.row_number_asc(), |b| {
+                b.ranking(Ranking::RowNumber, |v1, v2| v1.cmp(v2))
+            });
+            check_nested(&input, &input.rank_desc(), |b| {
+                b.ranking(Ranking::Rank, |v1, v2| v2.cmp(v1))
+            });
+            check_nested(&input, &input.dense_rank_by(by_tens_desc), |b| {
+                b.ranking(Ranking::DenseRank, by_tens_desc)
+            });
+
+            Ok((
+                move || {
+                    *counter.borrow_mut() += 1;
+                    Ok(*counter.borrow() == NESTED_ITERATIONS)
+                },
+                (),
+            ))
+        })
+        .unwrap();
+}
+
 proptest! {
     #[test]
     fn test_topk(trace in input_trace(5, 1_000, 200, 20)) {
@@ -242,4 +461,46 @@ proptest! {
             assert_batch_eq(&lead_result, &ref_lead);
         }
     }
+
+    #[test]
+    fn test_rank(trace in input_trace(5, 100, 200, 20)) {
+        let (mut dbsp, (input_handle, output_handles)) = Runtime::init_circuit(4, |circuit| rank_test_circuit(circuit)).unwrap();
+
+        let mut ref_trace = TestBatch::new(None);
+
+        for batch in trace.into_iter() {
+            let records = batch.iter().map(|(k, v, r)| ((*k, *v, ()), *r)).collect::<Vec<_>>();
+
+            let ref_batch = TestBatch::from_data(&records);
+            ref_trace.insert(ref_batch);
+
+            for (k, v, r) in batch.into_iter() {
+                input_handle.push(k, (v, r));
+            }
+            dbsp.step().unwrap();
+
+            let mut output_handles = output_handles.iter();
+            for ranking in [Ranking::RowNumber, Ranking::Rank, Ranking::DenseRank] {
+                let asc_result = output_handles.next().unwrap().consolidate();
+                let desc_result = output_handles.next().unwrap().consolidate();
+                let by_result = output_handles.next().unwrap().consolidate();
+
+                assert_batch_eq(&asc_result, &ref_trace.ranking(ranking, |v1, v2| v1.cmp(v2)));
+                assert_batch_eq(&desc_result, &ref_trace.ranking(ranking, |v1, v2| v2.cmp(v1)));
+                assert_batch_eq(&by_result, &ref_trace.ranking(ranking, by_tens_desc));
+            }
+        }
+    }
+
+    #[test]
+    fn test_nested(inputs in vec(input_trace(5, 100, 20, NESTED_ITERATIONS - 2), 0..5), workers in (1..=4usize)) {
+        let rounds = inputs.len();
+        let mut dbsp = Runtime::init_circuit(workers, |circuit| nested_test_circuit(circuit, inputs)).unwrap().0;
+
+        for _ in 0..rounds {
+            dbsp.step().unwrap();
+        }
+
+        dbsp.kill().unwrap();
+    }
 }
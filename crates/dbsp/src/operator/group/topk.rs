@@ -1,7 +1,9 @@
 use super::{DiffGroupTransformer, Monotonicity, NonIncrementalGroupTransformer};
 use crate::{
-    algebra::ZRingValue, trace::Cursor, DBData, DBWeight, IndexedZSet, OrdIndexedZSet, RootCircuit,
-    Stream,
+    algebra::ZRingValue,
+    circuit::{ChildCircuit, WithClock},
+    trace::Cursor,
+    Circuit, DBData, DBTimestamp, DBWeight, IndexedZSet, OrdIndexedZSet, RootCircuit, Stream,
 };
 use std::marker::PhantomData;
 
@@ -32,6 +34,41 @@ where
     }
 }
 
+impl<P, B> Stream<ChildCircuit<P>, B>
+where
+    P: Circuit,
+    <ChildCircuit<P> as WithClock>::Time: DBTimestamp,
+    B: IndexedZSet + Send,
+{
+    /// Pick `k` smallest values in each group of a stream in a nested circuit.
+    ///
+    /// For each key in the input stream, removes all but `k` smallest values.
+    #[allow(clippy::type_complexity)]
+    pub fn topk_asc(
+        &self,
+        k: usize,
+    ) -> Stream<ChildCircuit<P>, OrdIndexedZSet<B::Key, B::Val, B::R>>
+    where
+        B::R: ZRingValue,
+    {
+        self.group_transform(TopK::asc(k))
+    }
+
+    /// Pick `k` largest values in each group of a stream in a nested circuit.
+    ///
+    /// For each key in the input stream, removes all but `k` largest values.
+    #[allow(clippy::type_complexity)]
+    pub fn topk_desc(
+        &self,
+        k: usize,
+    ) -> Stream<ChildCircuit<P>, OrdIndexedZSet<B::Key, B::Val, B::R>>
+    where
+        B::R: ZRingValue,
+    {
+        self.group_transform(TopK::desc(k))
+    }
+}
+
 struct TopK<I, R> {
     k: usize,
     name: String,
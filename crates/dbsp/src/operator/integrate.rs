@@ -2,7 +2,7 @@
 
 use crate::{
     algebra::{AddAssignByRef, AddByRef, HasZero},
-    circuit::{Circuit, GlobalNodeId, OwnershipPreference, Stream},
+    circuit::{Checkpoint, Circuit, GlobalNodeId, OwnershipPreference, Stream},
     circuit_cache_key,
     operator::{
         z1::{DelayedFeedback, DelayedNestedFeedback},
@@ -27,6 +27,7 @@ where
         + HasZero
         + SizeOf
         + NumEntries
+        + Checkpoint
         + 'static,
 {
     /// Integrate the input stream.
@@ -5,14 +5,15 @@ use crate::{
     circuit::{
         metadata::{MetaItem, OperatorLocation, OperatorMeta},
         operator_traits::{BinaryOperator, Operator},
-        Circuit, GlobalNodeId, RootCircuit, Scope, Stream, WithClock,
+        Circuit, GlobalNodeId, RootCircuit, Scope, StatefulOperator, Stream, WithClock,
     },
     circuit_cache_key,
-    operator::FilterMap,
+    operator::{trace::TraceBound, FilterMap},
     time::Timestamp,
     trace::{cursor::Cursor as TraceCursor, Batch, BatchReader, Batcher, Builder, Spine, Trace},
     DBData, DBTimestamp, OrdIndexedZSet, OrdZSet,
 };
+use num::PrimInt;
 use size_of::{Context, SizeOf};
 use std::{
     borrow::Cow,
@@ -155,10 +156,69 @@ impl<I1> Stream<RootCircuit, I1> {
         let left = self.shard();
         let right = other.shard();
 
-        left.integrate_trace()
+        left.integrate_trace_for(StatefulOperator::Join)
             .delay_trace()
             .stream_join_inner(&right, join_func.clone(), Location::caller())
-            .plus(&left.stream_join_inner(&right.integrate_trace(), join_func, Location::caller()))
+            .plus(&left.stream_join_inner(
+                &right.integrate_trace_for(StatefulOperator::Join),
+                join_func,
+                Location::caller(),
+            ))
+    }
+
+    /// Incrementally join two collections indexed by timestamp, discarding
+    /// state older than the watermark.
+    ///
+    /// Behaves like [`join`](`Self::join`), except that records in either
+    /// input whose keys are more than `lateness` behind `watermark` are
+    /// considered late and are discarded.  Since such records can no longer
+    /// affect the output, the operator drops the parts of its input traces
+    /// below `watermark - lateness`, allowing unbounded streams to be joined
+    /// in bounded memory.
+    ///
+    /// At each clock cycle, the operator applies the watermark from the
+    /// previous clock cycle to filter late inputs.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - the second input stream.
+    /// * `join_func` - maps key and a pair of values from input batches to an
+    ///   output value.
+    /// * `watermark` - monotonically growing lower bound on keys in both
+    ///   inputs, e.g., computed using
+    ///   [`watermark_monotonic`](`Stream::watermark_monotonic`).
+    /// * `lateness` - how far behind the watermark a record can arrive without
+    ///   being discarded.
+    #[track_caller]
+    pub fn join_with_watermark<I2, F, V>(
+        &self,
+        other: &Stream<RootCircuit, I2>,
+        join_func: F,
+        watermark: &Stream<RootCircuit, I1::Key>,
+        lateness: I1::Key,
+    ) -> Stream<RootCircuit, OrdZSet<V, I1::R>>
+    where
+        I1: IndexedZSet + Send,
+        I1::Key: PrimInt,
+        I1::R: ZRingValue,
+        I2: IndexedZSet<Key = I1::Key, R = I1::R> + Send,
+        F: Fn(&I1::Key, &I1::Val, &I2::Val) -> V + Clone + 'static,
+        V: DBData,
+    {
+        let key_bound = TraceBound::new();
+
+        let left = self
+            .shard()
+            .discard_late_keys(watermark, lateness, key_bound.clone());
+        let right = other
+            .shard()
+            .discard_late_keys(watermark, lateness, key_bound.clone());
+
+        left.join_generic_with_key_bound(
+            &right,
+            move |k, v1, v2| once((join_func(k, v1, v2), ())),
+            key_bound,
+        )
     }
 }
 
@@ -222,6 +282,26 @@ where
     /// Like [`Self::join_index`], but can return any indexed Z-set type.
     #[track_caller]
     pub fn join_generic<I2, F, Z, It>(&self, other: &Stream<C, I2>, join_func: F) -> Stream<C, Z>
+    where
+        I2: IndexedZSet<Key = I1::Key, R = I1::R> + Send,
+        Z: IndexedZSet<R = I1::R>,
+        Z::R: MulByRef<Output = Z::R>,
+        F: Fn(&I1::Key, &I1::Val, &I2::Val) -> It + Clone + 'static,
+        It: IntoIterator<Item = (Z::Key, Z::Val)> + 'static,
+    {
+        self.join_generic_with_key_bound(other, join_func, TraceBound::new())
+    }
+
+    /// Like [`Self::join_generic`], but allows the traces of both inputs to be
+    /// truncated below `key_bound`.  The caller must guarantee that neither
+    /// input will contain keys below the bound in the future.
+    #[track_caller]
+    fn join_generic_with_key_bound<I2, F, Z, It>(
+        &self,
+        other: &Stream<C, I2>,
+        join_func: F,
+        key_bound: TraceBound<I1::Key>,
+    ) -> Stream<C, Z>
     where
         I2: IndexedZSet<Key = I1::Key, R = I1::R> + Send,
         Z: IndexedZSet<R = I1::R>,
@@ -272,8 +352,14 @@ where
         let left = self.shard();
         let right = other.shard();
 
-        let left_trace = left.trace::<Spine<<<C as WithClock>::Time as Timestamp>::OrdValBatch<I1::Key, I1::Val, I1::R>>>();
-        let right_trace = right.trace::<Spine<<<C as WithClock>::Time as Timestamp>::OrdValBatch<I1::Key, I2::Val, I1::R>>>();
+        let left_trace = left.trace_inner::<Spine<
+            <<C as WithClock>::Time as Timestamp>::OrdValBatch<I1::Key, I1::Val, I1::R>,
+        >>(
+            StatefulOperator::Join, key_bound.clone(), TraceBound::new()
+        );
+        let right_trace = right.trace_inner::<Spine<
+            <<C as WithClock>::Time as Timestamp>::OrdValBatch<I1::Key, I2::Val, I1::R>,
+        >>(StatefulOperator::Join, key_bound, TraceBound::new());
 
         let left = self.circuit().add_binary_operator(
             JoinTrace::new(
@@ -1390,4 +1476,59 @@ mod test {
 
         circuit.kill().unwrap();
     }
+
+    fn join_with_watermark_test(workers: usize) {
+        let output = Arc::new(Mutex::new(OrdZSet::empty(())));
+        let output_clone = output.clone();
+
+        let (mut circuit, (mut input1, mut input2)) =
+            Runtime::init_circuit(workers, move |circuit| {
+                let (input1, input_handle1) = circuit.add_input_indexed_zset::<u64, u64, isize>();
+                let (input2, input_handle2) = circuit.add_input_indexed_zset::<u64, u64, isize>();
+
+                let watermark = input1.watermark_monotonic(|ts| *ts);
+
+                input1
+                    .join_with_watermark(&input2, |ts, v1, v2| (*ts, *v1, *v2), &watermark, 5)
+                    .gather(0)
+                    .inspect(move |batch| {
+                        if Runtime::worker_index() == 0 {
+                            *output_clone.lock().unwrap() = batch.clone();
+                        }
+                    });
+
+                (input_handle1, input_handle2)
+            })
+            .unwrap();
+
+        input1.append(&mut vec![(10, (1, 1))]);
+        input2.append(&mut vec![(10, (100, 1)), (3, (300, 1))]);
+        circuit.step().unwrap();
+        assert_eq!(&*output.lock().unwrap(), &zset! { (10, 1, 100) => 1 });
+
+        // Watermark: 10, records with timestamps below 5 are late.
+        input1.append(&mut vec![(4, (2, 1)), (3, (3, 1)), (20, (4, 1))]);
+        input2.append(&mut vec![(4, (400, 1)), (20, (200, 1))]);
+        circuit.step().unwrap();
+        assert_eq!(&*output.lock().unwrap(), &zset! { (20, 4, 200) => 1 });
+
+        // Watermark: 20, records with timestamps below 15 are late,
+        // including retractions.
+        input1.append(&mut vec![(10, (1, -1)), (10, (6, 1)), (16, (5, 1))]);
+        input2.append(&mut vec![(10, (600, 1)), (16, (500, 1))]);
+        circuit.step().unwrap();
+        assert_eq!(&*output.lock().unwrap(), &zset! { (16, 5, 500) => 1 });
+
+        circuit.kill().unwrap();
+    }
+
+    #[test]
+    fn join_with_watermark_test1() {
+        join_with_watermark_test(1);
+    }
+
+    #[test]
+    fn join_with_watermark_test4() {
+        join_with_watermark_test(4);
+    }
 }
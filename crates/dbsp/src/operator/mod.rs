@@ -9,6 +9,7 @@ pub(crate) mod inspect;
 pub(crate) mod upsert;
 
 mod aggregate;
+mod asof_join;
 mod condition;
 mod consolidate;
 mod count;
@@ -37,8 +38,13 @@ mod z1;
 
 #[cfg(feature = "with-csv")]
 pub use self::csv::CsvSource;
-pub use aggregate::{Aggregator, Avg, Fold, Max, MaxSemigroup, Min, MinSemigroup};
+pub use aggregate::{
+    Aggregator, ApproxCountDistinct, Avg, CountDistinct, CountDistinctSemigroup, Fold, HyperLogLog,
+    HyperLogLogSemigroup, Max, MaxSemigroup, Median, Min, MinSemigroup, Percentile,
+    PercentileSemigroup,
+};
 pub use apply::Apply;
+pub use asof_join::AsofJoin;
 pub use condition::Condition;
 pub use delta0::Delta0;
 pub use distinct::Distinct;
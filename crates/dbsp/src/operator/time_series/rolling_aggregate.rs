@@ -580,13 +580,14 @@ where
 mod test {
     use crate::{
         algebra::DefaultSemigroup,
+        indexed_zset,
         operator::{
             time_series::{
                 range::{Range, RelOffset, RelRange},
                 PartitionCursor,
             },
             trace::TraceBound,
-            FilterMap, Fold,
+            CountDistinct, FilterMap, Fold, Median,
         },
         trace::{Batch, BatchReader, Cursor},
         CollectionHandle, DBSPHandle, OrdIndexedZSet, RootCircuit, Runtime, Stream,
@@ -833,6 +834,73 @@ mod test {
         circuit.kill().unwrap();
     }
 
+    #[test]
+    fn test_partitioned_rolling_distinct_and_median() {
+        let (mut circuit, (mut input, count_distinct, median)) =
+            Runtime::init_circuit(4, |circuit| {
+                let (input_stream, input_handle) =
+                    circuit.add_input_indexed_zset::<u64, (u64, i64), isize>();
+
+                let range_spec = RelRange::new(RelOffset::Before(10), RelOffset::Before(0));
+                let count_distinct = input_stream
+                    .partitioned_rolling_aggregate::<u64, i64, _>(CountDistinct, range_spec)
+                    .integrate()
+                    .output();
+                let median = input_stream
+                    .partitioned_rolling_aggregate::<u64, i64, _>(Median, range_spec)
+                    .integrate()
+                    .output();
+
+                (input_handle, count_distinct, median)
+            })
+            .unwrap();
+
+        input.append(&mut vec![
+            (0, ((1, 100), 1)),
+            (0, ((5, 200), 1)),
+            (0, ((12, 100), 1)),
+            (0, ((30, 300), 1)),
+            (1, ((1, 100), 1)),
+        ]);
+        circuit.step().unwrap();
+
+        assert_eq!(
+            count_distinct.consolidate(),
+            indexed_zset! {
+                0 => {(1, Some(1)) => 1, (5, Some(2)) => 1, (12, Some(2)) => 1, (30, Some(1)) => 1},
+                1 => {(1, Some(1)) => 1}
+            }
+        );
+        assert_eq!(
+            median.consolidate(),
+            indexed_zset! {
+                0 => {(1, Some(100)) => 1, (5, Some(100)) => 1, (12, Some(100)) => 1, (30, Some(300)) => 1},
+                1 => {(1, Some(100)) => 1}
+            }
+        );
+
+        // Retracting a value only affects the windows that contain it.
+        input.append(&mut vec![(0, ((1, 100), -1))]);
+        circuit.step().unwrap();
+
+        assert_eq!(
+            count_distinct.consolidate(),
+            indexed_zset! {
+                0 => {(5, Some(1)) => 1, (12, Some(2)) => 1, (30, Some(1)) => 1},
+                1 => {(1, Some(1)) => 1}
+            }
+        );
+        assert_eq!(
+            median.consolidate(),
+            indexed_zset! {
+                0 => {(5, Some(200)) => 1, (12, Some(100)) => 1, (30, Some(300)) => 1},
+                1 => {(1, Some(100)) => 1}
+            }
+        );
+
+        circuit.kill().unwrap();
+    }
+
     use proptest::{collection, prelude::*};
 
     type InputTuple = (u64, ((u64, i64), isize));
@@ -1,10 +1,16 @@
 use crate::{
-    operator::communication::new_exchange_operators,
-    trace::{cursor::Cursor, BatchReader},
-    Circuit, NumEntries, RootCircuit, Runtime, Stream,
+    circuit::{
+        checkpoint::{decode_data, encode_data},
+        operator_traits::{BinaryOperator, Operator},
+        Checkpoint, Scope,
+    },
+    operator::{communication::new_exchange_operators, trace::TraceBound},
+    trace::{cursor::Cursor, Batch, BatchReader, Builder},
+    Circuit, DBData, Error, NumEntries, RootCircuit, Runtime, Stream,
 };
+use num::PrimInt;
 use size_of::SizeOf;
-use std::{cmp::max, panic::Location};
+use std::{borrow::Cow, cmp::max, marker::PhantomData, panic::Location};
 
 impl<B> Stream<RootCircuit, B>
 where
@@ -35,7 +41,7 @@ where
     pub fn watermark_monotonic<W, TS>(&self, watermark_func: W) -> Stream<RootCircuit, TS>
     where
         W: Fn(&B::Key) -> TS + 'static,
-        TS: Ord + Clone + Default + SizeOf + NumEntries + Send + 'static,
+        TS: Ord + Clone + Default + SizeOf + NumEntries + Checkpoint + Send + 'static,
     {
         let local_watermark = self.stream_fold(TS::default(), move |old_watermark, batch| {
             let mut cursor = batch.cursor();
@@ -76,6 +82,136 @@ where
     }
 }
 
+impl<B> Stream<RootCircuit, B>
+where
+    B: Batch<Time = ()>,
+    B::Key: DBData + PrimInt,
+{
+    /// Discard records whose keys are more than `lateness` behind
+    /// `watermark`.
+    ///
+    /// At each clock cycle, drops records with keys below `watermark -
+    /// lateness`, where `watermark` is the value observed at the previous
+    /// clock cycle, and advances `bound` to `watermark - lateness` for the
+    /// current clock cycle.  Operators that consume the output of this
+    /// operator can use `bound` as the lower key bound of their traces:
+    /// since no records with keys below the bound will arrive in the future,
+    /// such keys can be safely discarded.
+    pub(crate) fn discard_late_keys(
+        &self,
+        watermark: &Stream<RootCircuit, B::Key>,
+        lateness: B::Key,
+        bound: TraceBound<B::Key>,
+    ) -> Self {
+        let output = self.circuit().add_binary_operator(
+            DiscardLate::new(lateness, bound),
+            &self.try_sharded_version(),
+            watermark,
+        );
+        output.mark_sharded_if(self);
+        output
+    }
+}
+
+/// Drops records with keys that are too far behind the watermark.
+///
+/// See [`Stream::discard_late_keys`].
+struct DiscardLate<B>
+where
+    B: BatchReader,
+{
+    lateness: B::Key,
+    /// Keys below this bound are discarded.  Derived from the watermark at
+    /// the previous clock cycle.
+    bound: Option<B::Key>,
+    trace_bound: TraceBound<B::Key>,
+    _phantom: PhantomData<B>,
+}
+
+impl<B> DiscardLate<B>
+where
+    B: BatchReader,
+{
+    fn new(lateness: B::Key, trace_bound: TraceBound<B::Key>) -> Self {
+        Self {
+            lateness,
+            bound: None,
+            trace_bound,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<B> Operator for DiscardLate<B>
+where
+    B: BatchReader + 'static,
+    B::Key: DBData,
+{
+    fn name(&self) -> Cow<'static, str> {
+        Cow::from("DiscardLate")
+    }
+
+    fn fixedpoint(&self, _scope: Scope) -> bool {
+        true
+    }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(Some(encode_data(&self.bound)?))
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Result<(), Error> {
+        self.bound = decode_data(state)?;
+        if let Some(bound) = &self.bound {
+            if self.trace_bound.get().map_or(true, |old| &old < bound) {
+                self.trace_bound.set(bound.clone());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<B> BinaryOperator<B, B::Key, B> for DiscardLate<B>
+where
+    B: Batch<Time = ()>,
+    B::Key: DBData + PrimInt,
+{
+    fn eval(&mut self, batch: &B, watermark: &B::Key) -> B {
+        let mut cursor = batch.cursor();
+
+        let output = match &self.bound {
+            Some(bound) if cursor.key_valid() && cursor.key() < bound => {
+                cursor.seek_key(bound);
+
+                let mut builder = B::Builder::with_capacity((), batch.len());
+                while cursor.key_valid() {
+                    while cursor.val_valid() {
+                        builder.push((
+                            B::item_from(cursor.key().clone(), cursor.val().clone()),
+                            cursor.weight(),
+                        ));
+                        cursor.step_val();
+                    }
+                    cursor.step_key();
+                }
+                builder.done()
+            }
+            _ => batch.clone(),
+        };
+
+        let bound = watermark.saturating_sub(self.lateness);
+        if self.bound.map_or(true, |old| old < bound) {
+            self.bound = Some(bound);
+            // The trace bound may be shared by multiple operators driven by
+            // the same watermark.
+            if self.trace_bound.get().map_or(true, |old| old < bound) {
+                self.trace_bound.set(bound);
+            }
+        }
+
+        output
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::Runtime;
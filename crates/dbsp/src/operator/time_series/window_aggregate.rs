@@ -0,0 +1,703 @@
+//! Aggregation over tumbling, hopping, and session windows.
+
+use crate::{
+    algebra::{HasOne, HasZero, IndexedZSet, ZRingValue},
+    circuit::{
+        checkpoint::{decode_data, encode_data},
+        operator_traits::{BinaryOperator, Operator},
+        Scope,
+    },
+    operator::{trace::TraceBound, Aggregator, FilterMap},
+    trace::{consolidation::consolidate, Batch, BatchReader, Builder, Cursor},
+    Circuit, DBData, DBWeight, Error, OrdIndexedZSet, OrdZSet, RootCircuit, Stream,
+};
+use num::PrimInt;
+use std::{borrow::Cow, cmp::min, iter::successors, marker::PhantomData};
+
+/// Output of window aggregation operators.
+///
+/// For each partition key of type `PK`, contains a `(start, end, aggregate)`
+/// tuple per closed window, where `[start..end)` is the right-open time range
+/// covered by the window.
+pub type OrdWindowAggregateStream<PK, TS, A, R> =
+    Stream<RootCircuit, OrdIndexedZSet<PK, (TS, TS, A), R>>;
+
+impl<B> Stream<RootCircuit, B>
+where
+    B: IndexedZSet,
+{
+    /// Aggregate a partitioned time series over tumbling windows.
+    ///
+    /// Tumbling windows are non-overlapping windows of fixed `size` aligned
+    /// at multiples of `size`: `[0..size)`, `[size..2*size)`, etc.  This is a
+    /// special case of
+    /// [`hopping_window_aggregate`](`Self::hopping_window_aggregate`) with
+    /// `step = size`.
+    pub fn tumbling_window_aggregate<PK, TS, V, Agg, PF>(
+        &self,
+        watermark: &Stream<RootCircuit, TS>,
+        partition_func: PF,
+        aggregator: Agg,
+        size: TS,
+    ) -> OrdWindowAggregateStream<PK, TS, Agg::Output, B::R>
+    where
+        B: IndexedZSet<Key = TS>,
+        Self: for<'a> FilterMap<RootCircuit, ItemRef<'a> = (&'a B::Key, &'a B::Val), R = B::R>,
+        B::R: ZRingValue,
+        PK: DBData,
+        PF: Fn(&B::Val) -> (PK, V) + 'static,
+        Agg: Aggregator<V, (), B::R>,
+        TS: DBData + PrimInt,
+        V: DBData,
+    {
+        self.hopping_window_aggregate(watermark, partition_func, aggregator, size, size)
+    }
+
+    /// Aggregate a partitioned time series over hopping windows.
+    ///
+    /// Hopping windows are windows of fixed `size` that start at multiples
+    /// of `step`: `[0..size)`, `[step..step+size)`, etc.  When `step < size`,
+    /// windows overlap and each record is assigned to multiple windows.
+    ///
+    /// Splits the input stream into non-overlapping partitions using
+    /// `partition_func`, and computes an aggregate for each window within
+    /// each partition.  Unlike most DBSP operators, this operator does not
+    /// update its outputs incrementally: it outputs the aggregate for a
+    /// window exactly once, when the window closes, i.e., when `watermark`
+    /// reaches the end of the window.  Records that arrive after the
+    /// watermark has passed their timestamp are discarded, and the
+    /// operator discards its state for closed windows.
+    ///
+    /// # Arguments
+    ///
+    /// * `self` - time series data indexed by time.
+    /// * `watermark` - monotonically growing lower bound on timestamps in the
+    ///   input stream, e.g., computed using
+    ///   [`watermark_monotonic`](`Stream::watermark_monotonic`).
+    /// * `partition_func` - function used to split inputs into non-overlapping
+    ///   partitions indexed by partition key of type `PK`.
+    /// * `aggregator` - aggregator used to summarize values in each window.
+    /// * `size` - window size.
+    /// * `step` - distance between the starts of consecutive windows.
+    ///
+    /// # Output
+    ///
+    /// For each partition, outputs a `(start, end, aggregate)` tuple with
+    /// weight 1 per window that closed at the current clock cycle and
+    /// contains at least one record with non-zero weight.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` or `step` is not positive.
+    pub fn hopping_window_aggregate<PK, TS, V, Agg, PF>(
+        &self,
+        watermark: &Stream<RootCircuit, TS>,
+        partition_func: PF,
+        aggregator: Agg,
+        size: TS,
+        step: TS,
+    ) -> OrdWindowAggregateStream<PK, TS, Agg::Output, B::R>
+    where
+        B: IndexedZSet<Key = TS>,
+        Self: for<'a> FilterMap<RootCircuit, ItemRef<'a> = (&'a B::Key, &'a B::Val), R = B::R>,
+        B::R: ZRingValue,
+        PK: DBData,
+        PF: Fn(&B::Val) -> (PK, V) + 'static,
+        Agg: Aggregator<V, (), B::R>,
+        TS: DBData + PrimInt,
+        V: DBData,
+    {
+        assert!(size > TS::zero(), "window size must be positive");
+        assert!(step > TS::zero(), "window step must be positive");
+
+        self.circuit().region("hopping_window_aggregate", || {
+            let input_bound: TraceBound<TS> = TraceBound::new();
+            let input_bound_clone = input_bound.clone();
+
+            // Assign each record to all windows that contain it.  Windows are
+            // identified by their start time.
+            let windows = self.flat_map_index(move |(ts, v)| {
+                let (partition_key, val) = partition_func(v);
+                let ts = *ts;
+                let late = input_bound_clone.get().map_or(false, |bound| ts < bound);
+
+                successors(Some(window_start(ts, step)), move |start| {
+                    start.checked_sub(&step)
+                })
+                .take_while(move |start| !late && ts - *start < size)
+                .map(move |start| (partition_key.clone(), (start, Some(val.clone()))))
+            });
+
+            windows.window_aggregate_inner(
+                watermark,
+                WindowKind::Hopping { size },
+                aggregator,
+                input_bound,
+            )
+        })
+    }
+
+    /// Aggregate a partitioned time series over session windows.
+    ///
+    /// A session is a maximal sequence of records in a partition, such that
+    /// the distance between the timestamps of consecutive records is less
+    /// than `gap`.  A session that consists of records with timestamps
+    /// `first..=last` covers the time range `[first..last+gap)`.
+    ///
+    /// Like [`hopping_window_aggregate`](`Self::hopping_window_aggregate`),
+    /// this operator outputs the aggregate for each session exactly once,
+    /// when `watermark` reaches the end of the session, discards records
+    /// that arrive after the watermark has passed their timestamp, and
+    /// discards its state for closed sessions.
+    ///
+    /// # Arguments
+    ///
+    /// * `self` - time series data indexed by time.
+    /// * `watermark` - monotonically growing lower bound on timestamps in the
+    ///   input stream.
+    /// * `partition_func` - function used to split inputs into non-overlapping
+    ///   partitions indexed by partition key of type `PK`.
+    /// * `aggregator` - aggregator used to summarize values in each session.
+    /// * `gap` - minimal distance between the timestamps of records in
+    ///   different sessions.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `gap` is not positive.
+    pub fn session_window_aggregate<PK, TS, V, Agg, PF>(
+        &self,
+        watermark: &Stream<RootCircuit, TS>,
+        partition_func: PF,
+        aggregator: Agg,
+        gap: TS,
+    ) -> OrdWindowAggregateStream<PK, TS, Agg::Output, B::R>
+    where
+        B: IndexedZSet<Key = TS>,
+        Self: for<'a> FilterMap<RootCircuit, ItemRef<'a> = (&'a B::Key, &'a B::Val), R = B::R>,
+        B::R: ZRingValue,
+        PK: DBData,
+        PF: Fn(&B::Val) -> (PK, V) + 'static,
+        Agg: Aggregator<V, (), B::R>,
+        TS: DBData + PrimInt,
+        V: DBData,
+    {
+        assert!(gap > TS::zero(), "session gap must be positive");
+
+        self.circuit().region("session_window_aggregate", || {
+            let input_bound: TraceBound<TS> = TraceBound::new();
+            let input_bound_clone = input_bound.clone();
+
+            let records = self.flat_map_index(move |(ts, v)| {
+                let late = input_bound_clone.get().map_or(false, |bound| ts < &bound);
+                if late {
+                    None
+                } else {
+                    let (partition_key, val) = partition_func(v);
+                    Some((partition_key, (*ts, Some(val))))
+                }
+            });
+
+            records.window_aggregate_inner(
+                watermark,
+                WindowKind::Session { gap },
+                aggregator,
+                input_bound,
+            )
+        })
+    }
+}
+
+impl<PK, TS, V, R> Stream<RootCircuit, OrdIndexedZSet<PK, (TS, Option<V>), R>>
+where
+    PK: DBData,
+    TS: DBData + PrimInt,
+    V: DBData,
+    R: DBWeight + ZRingValue,
+{
+    /// Aggregate windows of a partitioned stream of `(timestamp, value)`
+    /// pairs, where timestamps are window start times for hopping windows
+    /// and record timestamps for session windows.
+    ///
+    /// `input_bound` is set to the current watermark at the end of each
+    /// clock cycle; upstream operators use it to discard late records.
+    fn window_aggregate_inner<Agg>(
+        &self,
+        watermark: &Stream<RootCircuit, TS>,
+        kind: WindowKind<TS>,
+        aggregator: Agg,
+        input_bound: TraceBound<TS>,
+    ) -> OrdWindowAggregateStream<PK, TS, Agg::Output, R>
+    where
+        Agg: Aggregator<V, (), R>,
+    {
+        // ```
+        //        ┌────────────────────────────┐  trace  ┌───────────────┐
+        // ──────►│integrate_trace_with_bound()├────────►│WindowAggregate├──────►
+        //        └────────────────────────────┘         └───────────────┘
+        //                      ▲                            ▲   │
+        //                      │        trace_bound         │   │
+        //                      └────────────────────────────┼───┘
+        // watermark                                         │
+        // ──────────────────────────────────────────────────┘
+        // ```
+        let trace_bound: TraceBound<(TS, Option<V>)> = TraceBound::new();
+        let trace = self
+            .shard()
+            .integrate_trace_with_bound(TraceBound::new(), trace_bound.clone());
+
+        self.circuit()
+            .add_binary_operator(
+                WindowAggregate::new(kind, aggregator, input_bound, trace_bound),
+                &trace,
+                watermark,
+            )
+            .mark_sharded()
+    }
+}
+
+/// Start of the latest window that contains `ts`, i.e., the largest multiple
+/// of `step` that does not exceed `ts`.
+fn window_start<TS>(ts: TS, step: TS) -> TS
+where
+    TS: PrimInt,
+{
+    let rem = ts % step;
+    if rem < TS::zero() {
+        // `%` rounds towards zero; round negative timestamps down instead.
+        ts.saturating_sub(rem).saturating_sub(step)
+    } else {
+        ts - rem
+    }
+}
+
+/// Aggregate the contents of a window.  Returns `None` if all values in the
+/// window have zero weight.
+fn aggregate_window<V, R, Agg>(aggregator: &Agg, mut values: Vec<(V, R)>) -> Option<Agg::Output>
+where
+    V: DBData,
+    R: DBWeight + ZRingValue,
+    Agg: Aggregator<V, (), R>,
+{
+    consolidate(&mut values);
+    if values.is_empty() {
+        return None;
+    }
+
+    aggregator.aggregate_and_finalize(&mut OrdZSet::from_tuples((), values).cursor())
+}
+
+/// Window assignment policy of the [`WindowAggregate`] operator.
+#[derive(Clone)]
+enum WindowKind<TS> {
+    /// Windows `[start..start+size)`.  Records are pre-assigned to windows
+    /// and indexed by window start time.
+    Hopping { size: TS },
+    /// Sessions separated by gaps of at least `gap`.  Records are indexed by
+    /// their own timestamps.
+    Session { gap: TS },
+}
+
+/// Outputs aggregates of windows that closed at the current clock cycle.
+///
+/// The first input is the trace of all records that may still contribute
+/// to open windows, the second is the current watermark.  Windows that end
+/// at or before the watermark are closed; the operator outputs the
+/// aggregates of windows that end after the previous watermark, but not
+/// after the current one, and advances the bound of the input trace past
+/// the closed windows.
+struct WindowAggregate<PK, TS, V, R, Agg>
+where
+    TS: DBData,
+    V: DBData,
+{
+    kind: WindowKind<TS>,
+    aggregator: Agg,
+    /// Watermark at the previous clock cycle.  Windows that closed at or
+    /// before this watermark have already been output.
+    watermark: Option<TS>,
+    /// Lower bound on timestamps of new input records.
+    input_bound: TraceBound<TS>,
+    /// Lower bound on values retained in the input trace.
+    trace_bound: TraceBound<(TS, Option<V>)>,
+    _phantom: PhantomData<(PK, R)>,
+}
+
+impl<PK, TS, V, R, Agg> WindowAggregate<PK, TS, V, R, Agg>
+where
+    PK: DBData,
+    TS: DBData + PrimInt,
+    V: DBData,
+    R: DBWeight + ZRingValue,
+    Agg: Aggregator<V, (), R>,
+{
+    fn new(
+        kind: WindowKind<TS>,
+        aggregator: Agg,
+        input_bound: TraceBound<TS>,
+        trace_bound: TraceBound<(TS, Option<V>)>,
+    ) -> Self {
+        Self {
+            kind,
+            aggregator,
+            watermark: None,
+            input_bound,
+            trace_bound,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Returns `true` if a window that ends at `end` closed after the
+    /// previous watermark, but no later than `watermark`.
+    fn closed_now(&self, end: &TS, watermark: &TS) -> bool {
+        end <= watermark
+            && self
+                .watermark
+                .as_ref()
+                .map_or(true, |prev_watermark| end > prev_watermark)
+    }
+
+    /// Output aggregates of hopping windows that closed at the current clock
+    /// cycle for the partition under `cursor`.
+    fn eval_hopping<C, CB>(&self, cursor: &mut C, size: TS, watermark: &TS, mut output_cb: CB)
+    where
+        C: Cursor<PK, (TS, Option<V>), (), R>,
+        CB: FnMut((TS, TS, Agg::Output)),
+    {
+        if let Some(prev_watermark) = &self.watermark {
+            cursor.seek_val_with(|(start, _)| &start.saturating_add(size) > prev_watermark);
+        }
+
+        while cursor.val_valid() {
+            let start = cursor.val().0;
+            let end = start.saturating_add(size);
+            if !self.closed_now(&end, watermark) {
+                break;
+            }
+
+            let mut values = Vec::new();
+            while cursor.val_valid() && cursor.val().0 == start {
+                values.push((cursor.val().1.clone().unwrap(), cursor.weight()));
+                cursor.step_val();
+            }
+
+            if let Some(aggregate) = aggregate_window(&self.aggregator, values) {
+                output_cb((start, end, aggregate));
+            }
+        }
+    }
+
+    /// Output aggregates of sessions that closed at the current clock cycle
+    /// for the partition under `cursor`.  Returns the start of the first
+    /// open session in the partition, if any.
+    fn eval_session<C, CB>(
+        &self,
+        cursor: &mut C,
+        gap: TS,
+        watermark: &TS,
+        mut output_cb: CB,
+    ) -> Option<TS>
+    where
+        C: Cursor<PK, (TS, Option<V>), (), R>,
+        CB: FnMut((TS, TS, Agg::Output)),
+    {
+        // Current session: `(first, last, values)`.
+        let mut session: Option<(TS, TS, Vec<(V, R)>)> = None;
+
+        loop {
+            let next = if cursor.val_valid() {
+                let weight = cursor.weight();
+                if weight.is_zero() {
+                    cursor.step_val();
+                    continue;
+                }
+                Some((cursor.val().0, weight))
+            } else {
+                None
+            };
+
+            let extends_session = match (&session, &next) {
+                (Some((_, last, _)), Some((ts, _))) => *ts - *last < gap,
+                _ => false,
+            };
+
+            if extends_session {
+                let (_, last, values) = session.as_mut().unwrap();
+                let (ts, weight) = next.unwrap();
+                *last = ts;
+                values.push((cursor.val().1.clone().unwrap(), weight));
+            } else {
+                if let Some((first, last, values)) = session.take() {
+                    let end = last.saturating_add(gap);
+                    if end > *watermark {
+                        // This and all subsequent sessions are still open.
+                        return Some(first);
+                    }
+                    if self.closed_now(&end, watermark) {
+                        if let Some(aggregate) = aggregate_window(&self.aggregator, values) {
+                            output_cb((first, end, aggregate));
+                        }
+                    }
+                }
+
+                match next {
+                    None => return None,
+                    Some((ts, weight)) => {
+                        session = Some((ts, ts, vec![(cursor.val().1.clone().unwrap(), weight)]));
+                    }
+                }
+            }
+
+            cursor.step_val();
+        }
+    }
+}
+
+impl<PK, TS, V, R, Agg> Operator for WindowAggregate<PK, TS, V, R, Agg>
+where
+    PK: 'static,
+    TS: DBData,
+    V: DBData,
+    R: 'static,
+    Agg: 'static,
+{
+    fn name(&self) -> Cow<'static, str> {
+        Cow::from("WindowAggregate")
+    }
+
+    fn fixedpoint(&self, _scope: Scope) -> bool {
+        true
+    }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(Some(encode_data(&self.watermark)?))
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Result<(), Error> {
+        self.watermark = decode_data(state)?;
+        if let Some(watermark) = &self.watermark {
+            self.input_bound.set(watermark.clone());
+        }
+        Ok(())
+    }
+}
+
+impl<PK, TS, V, R, Agg, T> BinaryOperator<T, TS, OrdIndexedZSet<PK, (TS, TS, Agg::Output), R>>
+    for WindowAggregate<PK, TS, V, R, Agg>
+where
+    PK: DBData,
+    TS: DBData + PrimInt,
+    V: DBData,
+    R: DBWeight + ZRingValue,
+    Agg: Aggregator<V, (), R>,
+    T: BatchReader<Key = PK, Val = (TS, Option<V>), Time = (), R = R>,
+{
+    fn eval(&mut self, trace: &T, watermark: &TS) -> OrdIndexedZSet<PK, (TS, TS, Agg::Output), R> {
+        let mut builder =
+            <OrdIndexedZSet<PK, (TS, TS, Agg::Output), R> as Batch>::Builder::with_capacity((), 0);
+        let mut cursor = trace.cursor();
+
+        // Smallest timestamp that must be retained in the trace.
+        let mut lower_bound = *watermark;
+
+        while cursor.key_valid() {
+            let key = cursor.key().clone();
+            let mut output_cb =
+                |window: (TS, TS, Agg::Output)| builder.push(((key.clone(), window), R::one()));
+
+            match self.kind {
+                WindowKind::Hopping { size } => {
+                    self.eval_hopping(&mut cursor, size, watermark, &mut output_cb);
+                }
+                WindowKind::Session { gap } => {
+                    if let Some(first) =
+                        self.eval_session(&mut cursor, gap, watermark, &mut output_cb)
+                    {
+                        lower_bound = min(lower_bound, first);
+                    }
+                }
+            }
+
+            cursor.step_key();
+        }
+
+        // Discard closed windows.  For hopping windows, the bound also
+        // retains windows that closed exactly at the current watermark, which
+        // have been output and won't be output again.
+        let trace_bound = match self.kind {
+            WindowKind::Hopping { size } => watermark.saturating_sub(size),
+            WindowKind::Session { .. } => lower_bound,
+        };
+        self.trace_bound.set((trace_bound, None));
+        self.input_bound.set(*watermark);
+        self.watermark = Some(*watermark);
+
+        builder.done()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        algebra::DefaultSemigroup,
+        indexed_zset,
+        operator::{Fold, Max},
+        OrdIndexedZSet, RootCircuit, Runtime, Stream,
+    };
+
+    type DataStream = Stream<RootCircuit, OrdIndexedZSet<u64, (u64, i64), isize>>;
+    type OutputBatch = OrdIndexedZSet<u64, (u64, u64, i64), isize>;
+
+    /// Feed the same input to the window aggregation operator built by
+    /// `window_aggregate` and compare its outputs with `expected`.
+    fn window_test<F>(workers: usize, window_aggregate: F, expected: Vec<OutputBatch>)
+    where
+        F: Fn(&DataStream, &Stream<RootCircuit, u64>) -> Stream<RootCircuit, OutputBatch>
+            + Clone
+            + Send
+            + 'static,
+    {
+        let (mut dbsp, mut input_handle) = Runtime::init_circuit(workers, move |circuit| {
+            let mut expected = expected.into_iter();
+
+            let (input, input_handle) = circuit.add_input_indexed_zset::<u64, (u64, i64), isize>();
+            let watermark = input.watermark_monotonic(|ts| ts.saturating_sub(5));
+
+            window_aggregate(&input, &watermark)
+                .gather(0)
+                .inspect(move |batch| {
+                    if Runtime::worker_index() == 0 {
+                        assert_eq!(batch, &expected.next().unwrap());
+                    }
+                });
+
+            input_handle
+        })
+        .unwrap();
+
+        // Watermark: 7.
+        input_handle.append(&mut vec![
+            (1, ((0, 1), 1)),
+            (5, ((0, 2), 1)),
+            (12, ((0, 3), 1)),
+            (3, ((1, 10), 1)),
+        ]);
+        dbsp.step().unwrap();
+
+        // Watermark: 20.
+        input_handle.append(&mut vec![
+            (12, ((0, 3), -1)),
+            (18, ((0, 4), 1)),
+            (25, ((1, 5), 1)),
+        ]);
+        dbsp.step().unwrap();
+
+        // Watermark: 35.  The record with timestamp 8 is late and gets
+        // discarded.
+        input_handle.append(&mut vec![
+            (8, ((0, 100), 1)),
+            (22, ((1, 1), 1)),
+            (40, ((0, 1), 1)),
+        ]);
+        dbsp.step().unwrap();
+
+        // Watermark: 100.
+        input_handle.append(&mut vec![(105, ((2, 7), 1))]);
+        dbsp.step().unwrap();
+
+        dbsp.kill().unwrap();
+    }
+
+    fn tumbling_test(workers: usize) {
+        window_test(
+            workers,
+            |input, watermark| {
+                input.tumbling_window_aggregate(
+                    watermark,
+                    |(pk, v)| (*pk, *v),
+                    <Fold<_, DefaultSemigroup<_>, _, _>>::new(
+                        0,
+                        |acc: &mut i64, v: &i64, w: isize| *acc += *v * w as i64,
+                    ),
+                    10,
+                )
+            },
+            vec![
+                indexed_zset! {},
+                indexed_zset! {
+                    0 => { (0, 10, 3) => 1, (10, 20, 4) => 1 },
+                    1 => { (0, 10, 10) => 1 }
+                },
+                indexed_zset! { 1 => { (20, 30, 6) => 1 } },
+                indexed_zset! { 0 => { (40, 50, 1) => 1 } },
+            ],
+        );
+    }
+
+    fn hopping_test(workers: usize) {
+        window_test(
+            workers,
+            |input, watermark| {
+                input.hopping_window_aggregate(watermark, |(pk, v)| (*pk, *v), Max, 10, 5)
+            },
+            vec![
+                indexed_zset! {},
+                indexed_zset! {
+                    0 => { (0, 10, 2) => 1, (5, 15, 2) => 1, (10, 20, 4) => 1 },
+                    1 => { (0, 10, 10) => 1 }
+                },
+                indexed_zset! {
+                    0 => { (15, 25, 4) => 1 },
+                    1 => { (15, 25, 1) => 1, (20, 30, 5) => 1, (25, 35, 5) => 1 }
+                },
+                indexed_zset! { 0 => { (35, 45, 1) => 1, (40, 50, 1) => 1 } },
+            ],
+        );
+    }
+
+    fn session_test(workers: usize) {
+        window_test(
+            workers,
+            |input, watermark| {
+                input.session_window_aggregate(
+                    watermark,
+                    |(pk, v)| (*pk, *v),
+                    <Fold<_, DefaultSemigroup<_>, _, _>>::new(
+                        0,
+                        |acc: &mut i64, v: &i64, w: isize| *acc += *v * w as i64,
+                    ),
+                    5,
+                )
+            },
+            vec![
+                indexed_zset! {},
+                indexed_zset! {
+                    0 => { (1, 10, 3) => 1 },
+                    1 => { (3, 8, 10) => 1 }
+                },
+                indexed_zset! {
+                    0 => { (18, 23, 4) => 1 },
+                    1 => { (22, 30, 6) => 1 }
+                },
+                indexed_zset! { 0 => { (40, 45, 1) => 1 } },
+            ],
+        );
+    }
+
+    #[test]
+    fn test_tumbling_window_aggregate() {
+        tumbling_test(1);
+        tumbling_test(4);
+    }
+
+    #[test]
+    fn test_hopping_window_aggregate() {
+        hopping_test(1);
+        hopping_test(4);
+    }
+
+    #[test]
+    fn test_session_window_aggregate() {
+        session_test(1);
+        session_test(4);
+    }
+}
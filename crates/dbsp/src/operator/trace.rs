@@ -1,13 +1,14 @@
 use crate::{
     circuit::{
+        checkpoint::{checkpoint_batch, decode_data, encode_data, restore_trace},
         metadata::{MetaItem, OperatorMeta},
         operator_traits::{BinaryOperator, Operator, StrictOperator, StrictUnaryOperator},
         Circuit, ExportId, ExportStream, FeedbackConnector, GlobalNodeId, OwnershipPreference,
-        Scope, Stream, WithClock,
+        Runtime, Scope, StatefulOperator, Stream, WithClock,
     },
     circuit_cache_key,
     trace::{cursor::Cursor, Batch, BatchReader, Builder, Spine, Trace},
-    DBData, Timestamp,
+    DBData, Error, Timestamp,
 };
 use size_of::SizeOf;
 use std::{borrow::Cow, cell::RefCell, marker::PhantomData, ops::DerefMut, rc::Rc};
@@ -183,6 +184,31 @@ where
         lower_key_bound: TraceBound<B::Key>,
         lower_val_bound: TraceBound<B::Val>,
     ) -> Stream<C, T>
+    where
+        B: BatchReader<Time = ()>,
+        T: Trace<Key = B::Key, Val = B::Val, R = B::R, Time = <C as WithClock>::Time> + Clone,
+    {
+        self.trace_inner(StatefulOperator::Trace, lower_key_bound, lower_val_bound)
+    }
+
+    /// Like [`Self::trace`], but creates the trace on behalf of `operator`,
+    /// which determines whether the trace is kept in persistent storage.
+    pub(crate) fn trace_for<T>(&self, operator: StatefulOperator) -> Stream<C, T>
+    where
+        B: BatchReader<Time = ()>,
+        T: Trace<Key = B::Key, Val = B::Val, R = B::R, Time = <C as WithClock>::Time> + Clone,
+    {
+        self.trace_inner(operator, TraceBound::new(), TraceBound::new())
+    }
+
+    /// Like [`Self::trace_with_bound`], but creates the trace on behalf of
+    /// `operator`.
+    pub(crate) fn trace_inner<T>(
+        &self,
+        operator: StatefulOperator,
+        lower_key_bound: TraceBound<B::Key>,
+        lower_val_bound: TraceBound<B::Val>,
+    ) -> Stream<C, T>
     where
         B: BatchReader<Time = ()>,
         T: Trace<Key = B::Key, Val = B::Val, R = B::R, Time = <C as WithClock>::Time> + Clone,
@@ -195,11 +221,10 @@ where
 
                 circuit.region("trace", || {
                     let (ExportStream { local, export }, z1feedback) = circuit
-                        .add_feedback_with_export(Z1Trace::new(
-                            false,
-                            circuit.root_scope(),
-                            bounds.clone(),
-                        ));
+                        .add_feedback_with_export(
+                            Z1Trace::new(false, circuit.root_scope(), bounds.clone())
+                                .with_operator(operator),
+                        );
                     let trace = circuit.add_binary_operator_with_preference(
                         <TraceAppend<T, B, C>>::new(circuit.clone()),
                         (&local, OwnershipPreference::STRONGLY_PREFER_OWNED),
@@ -249,6 +274,34 @@ where
         lower_key_bound: TraceBound<B::Key>,
         lower_val_bound: TraceBound<B::Val>,
     ) -> Stream<C, Spine<B>>
+    where
+        B: Batch,
+        Spine<B>: SizeOf,
+    {
+        self.integrate_trace_inner(StatefulOperator::Trace, lower_key_bound, lower_val_bound)
+    }
+
+    /// Like [`Self::integrate_trace`], but creates the trace on behalf of
+    /// `operator`, which determines whether the trace is kept in persistent
+    /// storage.
+    #[track_caller]
+    pub(crate) fn integrate_trace_for(&self, operator: StatefulOperator) -> Stream<C, Spine<B>>
+    where
+        B: Batch,
+        Spine<B>: SizeOf,
+    {
+        self.integrate_trace_inner(operator, TraceBound::new(), TraceBound::new())
+    }
+
+    /// Like [`Self::integrate_trace_for`], but truncates the trace below
+    /// `lower_key_bound` and `lower_val_bound`.
+    #[track_caller]
+    pub(crate) fn integrate_trace_inner(
+        &self,
+        operator: StatefulOperator,
+        lower_key_bound: TraceBound<B::Key>,
+        lower_val_bound: TraceBound<B::Val>,
+    ) -> Stream<C, Spine<B>>
     where
         B: Batch,
         Spine<B>: SizeOf,
@@ -261,11 +314,10 @@ where
 
                 circuit.region("integrate_trace", || {
                     let (ExportStream { local, export }, z1feedback) = circuit
-                        .add_feedback_with_export(Z1Trace::new(
-                            true,
-                            circuit.root_scope(),
-                            bounds.clone(),
-                        ));
+                        .add_feedback_with_export(
+                            Z1Trace::new(true, circuit.root_scope(), bounds.clone())
+                                .with_operator(operator),
+                        );
 
                     let trace = circuit.add_binary_operator_with_preference(
                         UntimedTraceAppend::<Spine<B>>::new(),
@@ -571,6 +623,8 @@ pub struct Z1Trace<T: Trace> {
     bounds: TraceBounds<T::Key, T::Val>,
     effective_key_bound: Option<T::Key>,
     effective_val_bound: Option<T::Val>,
+    // Keep the trace in persistent storage.
+    persistent: bool,
 }
 
 impl<T> Z1Trace<T>
@@ -591,6 +645,23 @@ where
             bounds,
             effective_key_bound: None,
             effective_val_bound: None,
+            persistent: Runtime::persistent_state(StatefulOperator::Trace),
+        }
+    }
+
+    /// Create the trace on behalf of `operator`.  The trace is kept in
+    /// persistent storage if the storage configuration of the runtime
+    /// selects `operator` (see [`Runtime::persistent_state`]).
+    pub fn with_operator(mut self, operator: StatefulOperator) -> Self {
+        self.persistent = Runtime::persistent_state(operator);
+        self
+    }
+
+    fn new_trace(&self) -> T {
+        if self.persistent {
+            T::new_persistent(None)
+        } else {
+            T::new(None)
         }
     }
 }
@@ -608,7 +679,7 @@ where
 
         if scope == 0 && self.trace.is_none() {
             // TODO: use T::with_effort with configurable effort?
-            self.trace = Some(T::new(None));
+            self.trace = Some(self.new_trace());
         }
     }
 
@@ -646,6 +717,23 @@ where
     fn fixedpoint(&self, scope: Scope) -> bool {
         !self.dirty[scope as usize]
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        let trace = self.trace.as_ref().map(checkpoint_batch).transpose()?;
+
+        Ok(Some(encode_data(&(self.time.clone(), trace))?))
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Result<(), Error> {
+        let (time, trace): (T::Time, Option<Vec<u8>>) = decode_data(state)?;
+
+        self.time = time;
+        self.trace = trace
+            .as_deref()
+            .map(|data| restore_trace(self.new_trace(), data))
+            .transpose()?;
+        Ok(())
+    }
 }
 
 impl<T> StrictOperator<T> for Z1Trace<T>
@@ -4,7 +4,7 @@ use crate::{
         operator_traits::{BinaryOperator, Operator},
         ExportId, ExportStream, OwnershipPreference, Scope, WithClock,
     },
-    operator::trace::{DelayedTraceId, TraceAppend, TraceBounds, TraceId, Z1Trace},
+    operator::trace::{DelayedTraceId, TraceAppend, TraceBound, TraceBounds, TraceId, Z1Trace},
     trace::{
         consolidation::consolidate, cursor::Cursor, Batch, BatchReader, Builder, Spine, Trace,
     },
@@ -35,6 +35,19 @@ where
     /// This is a stateful operator that internally maintains the trace of the
     /// collection.
     pub fn upsert<B>(&self) -> Stream<C, B>
+    where
+        K: DBData,
+        V: DBData,
+        B::R: DBData + ZRingValue,
+        B: Batch<Key = K, Val = V, Time = ()>,
+    {
+        self.upsert_with_key_bound(TraceBound::new())
+    }
+
+    /// Like [`Self::upsert`], but allows the internal trace of the collection
+    /// to be truncated below `key_bound`.  The caller must guarantee that the
+    /// input stream won't contain keys below the bound in the future.
+    pub(crate) fn upsert_with_key_bound<B>(&self, key_bound: TraceBound<K>) -> Stream<C, B>
     where
         K: DBData,
         V: DBData,
@@ -63,7 +76,9 @@ where
         //                    z1trace             └───────┘
         // ```
         circuit.region("upsert", || {
-            let bounds = <TraceBounds<K, V>>::unbounded();
+            let bounds = <TraceBounds<K, V>>::new();
+            bounds.add_key_bound(key_bound);
+            bounds.add_val_bound(TraceBound::new());
 
             let (ExportStream { local, export }, z1feedback) = circuit.add_feedback_with_export(
                 Z1Trace::new(false, circuit.root_scope(), bounds.clone()),
@@ -3,12 +3,13 @@
 use crate::{
     algebra::HasZero,
     circuit::{
+        checkpoint::{decode_state, encode_state},
         metadata::{MetaItem, OperatorMeta},
         operator_traits::{Operator, StrictOperator, StrictUnaryOperator, UnaryOperator},
-        Circuit, ExportId, ExportStream, FeedbackConnector, GlobalNodeId, OwnershipPreference,
-        Scope, Stream,
+        Checkpoint, Circuit, ExportId, ExportStream, FeedbackConnector, GlobalNodeId,
+        OwnershipPreference, Scope, Stream,
     },
-    circuit_cache_key, NumEntries,
+    circuit_cache_key, Error, NumEntries,
 };
 use size_of::{Context, SizeOf};
 use std::{borrow::Cow, mem::replace};
@@ -33,7 +34,7 @@ where
 impl<C, D> DelayedFeedback<C, D>
 where
     C: Circuit,
-    D: Eq + SizeOf + NumEntries + Clone + HasZero + 'static,
+    D: Eq + SizeOf + NumEntries + Clone + HasZero + Checkpoint + 'static,
 {
     /// Create a feedback loop with `Z1` operator.  Use [`Self::connect`] to
     /// close the loop.
@@ -82,7 +83,7 @@ pub struct DelayedNestedFeedback<C, D> {
 impl<C, D> DelayedNestedFeedback<C, D>
 where
     C: Circuit,
-    D: Eq + SizeOf + NumEntries + Clone + 'static,
+    D: Eq + SizeOf + NumEntries + Clone + Checkpoint + 'static,
 {
     /// Create a feedback loop with `Z1` operator.  Use [`Self::connect`] to
     /// close the loop.
@@ -116,7 +117,7 @@ where
     /// Applies [`Z1`] operator to `self`.
     pub fn delay(&self) -> Stream<C, D>
     where
-        D: Eq + SizeOf + NumEntries + Clone + HasZero + 'static,
+        D: Eq + SizeOf + NumEntries + Clone + HasZero + Checkpoint + 'static,
     {
         self.circuit()
             .cache_get_or_insert_with(DelayedId::new(self.origin_node_id().clone()), || {
@@ -128,7 +129,7 @@ where
     /// Applies [`Z1Nested`] operator to `self`.
     pub fn delay_nested(&self) -> Stream<C, D>
     where
-        D: Eq + Clone + HasZero + SizeOf + NumEntries + 'static,
+        D: Eq + Clone + HasZero + SizeOf + NumEntries + Checkpoint + 'static,
     {
         self.circuit()
             .cache_get_or_insert_with(NestedDelayedId::new(self.origin_node_id().clone()), || {
@@ -181,7 +182,7 @@ where
 
 impl<T> Operator for Z1<T>
 where
-    T: Eq + SizeOf + NumEntries + Clone + 'static,
+    T: Eq + SizeOf + NumEntries + Clone + Checkpoint + 'static,
 {
     fn name(&self) -> Cow<'static, str> {
         Cow::from("Z^-1")
@@ -211,11 +212,20 @@ where
             true
         }
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(Some(self.values.checkpoint()?))
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Result<(), Error> {
+        self.values = T::restore(state)?;
+        Ok(())
+    }
 }
 
 impl<T> UnaryOperator<T, T> for Z1<T>
 where
-    T: Eq + SizeOf + NumEntries + Clone + 'static,
+    T: Eq + SizeOf + NumEntries + Clone + Checkpoint + 'static,
 {
     fn eval(&mut self, i: &T) -> T {
         replace(&mut self.values, i.clone())
@@ -232,7 +242,7 @@ where
 
 impl<T> StrictOperator<T> for Z1<T>
 where
-    T: Eq + SizeOf + NumEntries + Clone + 'static,
+    T: Eq + SizeOf + NumEntries + Clone + Checkpoint + 'static,
 {
     fn get_output(&mut self) -> T {
         self.empty_output = self.values.num_entries_shallow() == 0;
@@ -246,7 +256,7 @@ where
 
 impl<T> StrictUnaryOperator<T, T> for Z1<T>
 where
-    T: Eq + SizeOf + NumEntries + Clone + 'static,
+    T: Eq + SizeOf + NumEntries + Clone + Checkpoint + 'static,
 {
     fn eval_strict(&mut self, i: &T) {
         self.values = i.clone();
@@ -318,7 +328,7 @@ impl<T> Z1Nested<T> {
 
 impl<T> Operator for Z1Nested<T>
 where
-    T: Eq + SizeOf + NumEntries + Clone + 'static,
+    T: Eq + SizeOf + NumEntries + Clone + Checkpoint + 'static,
 {
     fn name(&self) -> Cow<'static, str> {
         Cow::from("Z^-1 (nested)")
@@ -378,11 +388,33 @@ where
             false
         }
     }
+
+    fn checkpoint(&self) -> Result<Option<Vec<u8>>, Error> {
+        let values = self
+            .values
+            .iter()
+            .map(Checkpoint::checkpoint)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Some(encode_state(&(self.timestamp, values))?))
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Result<(), Error> {
+        let (timestamp, values): (usize, Vec<Vec<u8>>) = decode_state(state)?;
+
+        self.timestamp = timestamp;
+        self.values = values
+            .iter()
+            .map(|value| T::restore(value))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(())
+    }
 }
 
 impl<T> UnaryOperator<T, T> for Z1Nested<T>
 where
-    T: Eq + SizeOf + NumEntries + Clone + 'static,
+    T: Eq + SizeOf + NumEntries + Clone + Checkpoint + 'static,
 {
     fn eval(&mut self, i: &T) -> T {
         debug_assert!(self.timestamp <= self.values.len());
@@ -421,7 +453,7 @@ where
 
 impl<T> StrictOperator<T> for Z1Nested<T>
 where
-    T: Eq + SizeOf + NumEntries + Clone + 'static,
+    T: Eq + SizeOf + NumEntries + Clone + Checkpoint + 'static,
 {
     fn get_output(&mut self) -> T {
         if self.timestamp >= self.values.len() {
@@ -445,7 +477,7 @@ where
 
 impl<T> StrictUnaryOperator<T, T> for Z1Nested<T>
 where
-    T: Eq + SizeOf + NumEntries + Clone + 'static,
+    T: Eq + SizeOf + NumEntries + Clone + Checkpoint + 'static,
 {
     fn eval_strict(&mut self, i: &T) {
         debug_assert!(self.timestamp < self.values.len());
@@ -20,7 +20,7 @@ pub mod spine_fueled;
 
 pub use cursor::{Consumer, Cursor, ValueConsumer};
 #[cfg(feature = "persistence")]
-pub use persistent::PersistentTrace as Spine;
+pub use persistent::HybridSpine as Spine;
 #[cfg(not(feature = "persistence"))]
 pub use spine_fueled::Spine;
 
@@ -33,7 +33,7 @@ use crate::{
     time::{AntichainRef, Timestamp},
     NumEntries,
 };
-#[cfg(feature = "persistence")]
+#[cfg(feature = "with-bincode")]
 use bincode::{Decode, Encode};
 use size_of::SizeOf;
 use std::{fmt::Debug, hash::Hash};
@@ -45,22 +45,26 @@ use std::{fmt::Debug, hash::Hash};
 /// must be generic over any relational data, it is sufficient to impose
 /// `DBData` as a trait bound on types.  Conversely, a trait bound of the form
 /// `B: BatchReader` implies `B::Key: DBData` and `B::Val: DBData`.
-#[cfg(feature = "persistence")]
+///
+/// With the `with-bincode` feature (enabled by default), `DBData` also
+/// requires `Decode` and `Encode`, which allow batches to be written to
+/// persistent storage and to [checkpoints](`crate::circuit::checkpoint`).
+#[cfg(feature = "with-bincode")]
 pub trait DBData:
     Clone + Eq + Ord + Hash + SizeOf + Send + Debug + Decode + Encode + 'static
 {
 }
 
-#[cfg(not(feature = "persistence"))]
+#[cfg(not(feature = "with-bincode"))]
 pub trait DBData: Clone + Eq + Ord + Hash + SizeOf + Send + Debug + 'static {}
 
-#[cfg(feature = "persistence")]
+#[cfg(feature = "with-bincode")]
 impl<T> DBData for T where
     T: Clone + Eq + Ord + Hash + SizeOf + Send + Debug + Decode + Encode + 'static
 {
 }
 
-#[cfg(not(feature = "persistence"))]
+#[cfg(not(feature = "with-bincode"))]
 impl<T> DBData for T where T: Clone + Eq + Ord + Hash + SizeOf + Send + Debug + 'static {}
 
 /// Trait for data types used as weights.
@@ -99,6 +103,15 @@ pub trait Trace: BatchReader {
     /// Allocates a new empty trace.
     fn new(activator: Option<Activator>) -> Self;
 
+    /// Allocates a new empty trace that keeps its contents in persistent
+    /// storage.
+    ///
+    /// Trace types that do not support persistent storage allocate an
+    /// in-memory trace.
+    fn new_persistent(activator: Option<Activator>) -> Self {
+        Self::new(activator)
+    }
+
     /// Push all timestamps in the trace back to `frontier`.
     ///
     /// Modifies all timestamps `t` that are not less than or equal to
@@ -6,10 +6,10 @@
 use std::sync::Arc;
 
 use bincode::decode_from_slice;
-use rocksdb::{BoundColumnFamily, DBRawIterator};
+use rocksdb::{BoundColumnFamily, DBRawIterator, DB};
 
 use super::trace::PersistedValue;
-use super::{ReusableEncodeBuffer, Values, BINCODE_CONFIG, ROCKS_DB_INSTANCE};
+use super::{ReusableEncodeBuffer, Values, BINCODE_CONFIG};
 use crate::algebra::PartialOrder;
 use crate::trace::{Batch, Cursor};
 
@@ -115,10 +115,14 @@ where
 }
 
 impl<'s, B: Batch> PersistentTraceCursor<'s, B> {
-    /// Creates a new [`PersistentTraceCursor`], requires to pass a handle to
-    /// the column family of the trace.
-    pub(super) fn new(cf: &Arc<BoundColumnFamily>, lower_key_bound: &'s Option<B::Key>) -> Self {
-        let mut db_iter = ROCKS_DB_INSTANCE.raw_iterator_cf(cf);
+    /// Creates a new [`PersistentTraceCursor`], requires to pass the database
+    /// and a handle to the column family of the trace.
+    pub(super) fn new(
+        db: &'s DB,
+        cf: &Arc<BoundColumnFamily>,
+        lower_key_bound: &'s Option<B::Key>,
+    ) -> Self {
+        let mut db_iter = db.raw_iterator_cf(cf);
 
         db_iter.seek_to_first();
 
@@ -0,0 +1,396 @@
+//! A trace that is stored either in memory or in RocksDB.
+//!
+//! Whether a trace is stored on disk is decided when the trace is created,
+//! based on the storage configuration of the runtime (see
+//! [`StorageConfig`](`crate::circuit::StorageConfig`)).  Since the type of a
+//! trace is fixed when the circuit is built, [`HybridSpine`] wraps both
+//! implementations and dispatches every call to the one it was created with.
+
+use size_of::SizeOf;
+
+use super::trace::{PersistentConsumer, PersistentTraceValueConsumer};
+use super::{PersistentTrace, PersistentTraceCursor};
+use crate::circuit::{Activator, Runtime, StorageBackend};
+use crate::time::AntichainRef;
+use crate::trace::spine_fueled::{Spine, SpineConsumer, SpineCursor, SpineValueConsumer};
+use crate::trace::{Batch, BatchReader, Consumer, Cursor, Trace, ValueConsumer};
+use crate::NumEntries;
+
+/// Dispatch `$body` to the inner value of `$value`, an instance of the
+/// two-variant enum `$enum`.
+macro_rules! dispatch {
+    ($enum:ident, $value:expr, $inner:ident => $body:expr) => {
+        match $value {
+            $enum::Memory($inner) => $body,
+            $enum::Persistent($inner) => $body,
+        }
+    };
+}
+
+/// A trace that keeps its contents either in memory, in a [`Spine`], or in
+/// persistent storage, in a [`PersistentTrace`].
+///
+/// [`Trace::new`] creates an in-memory trace.  [`Trace::new_persistent`]
+/// creates a persistent trace if the runtime's storage uses
+/// [`StorageBackend::RocksDb`], and otherwise a [`Spine`] that spills to disk.
+#[derive(SizeOf)]
+pub enum HybridSpine<B>
+where
+    B: Batch,
+{
+    Memory(Spine<B>),
+    Persistent(PersistentTrace<B>),
+}
+
+impl<B> HybridSpine<B>
+where
+    B: Batch,
+{
+    /// Returns `true` if the trace is kept in persistent storage.
+    pub fn is_persistent(&self) -> bool {
+        matches!(self, Self::Persistent(_))
+    }
+}
+
+impl<B> Default for HybridSpine<B>
+where
+    B: Batch,
+{
+    fn default() -> Self {
+        <Self as Trace>::new(None)
+    }
+}
+
+impl<B> Clone for HybridSpine<B>
+where
+    B: Batch,
+{
+    fn clone(&self) -> Self {
+        match self {
+            Self::Memory(spine) => Self::Memory(spine.clone()),
+            Self::Persistent(trace) => Self::Persistent(trace.clone()),
+        }
+    }
+}
+
+impl<B> NumEntries for HybridSpine<B>
+where
+    B: Batch,
+{
+    const CONST_NUM_ENTRIES: Option<usize> = None;
+
+    fn num_entries_shallow(&self) -> usize {
+        dispatch!(HybridSpine, self, trace => trace.num_entries_shallow())
+    }
+
+    fn num_entries_deep(&self) -> usize {
+        dispatch!(HybridSpine, self, trace => trace.num_entries_deep())
+    }
+}
+
+impl<B> BatchReader for HybridSpine<B>
+where
+    B: Batch,
+{
+    type Key = B::Key;
+    type Val = B::Val;
+    type Time = B::Time;
+    type R = B::R;
+
+    type Cursor<'s> = HybridSpineCursor<'s, B>;
+    type Consumer = HybridSpineConsumer<B>;
+
+    fn cursor(&self) -> Self::Cursor<'_> {
+        match self {
+            Self::Memory(spine) => HybridSpineCursor::Memory(spine.cursor()),
+            Self::Persistent(trace) => HybridSpineCursor::Persistent(trace.cursor()),
+        }
+    }
+
+    fn consumer(self) -> Self::Consumer {
+        match self {
+            Self::Memory(spine) => HybridSpineConsumer::Memory(spine.consumer()),
+            Self::Persistent(trace) => HybridSpineConsumer::Persistent(trace.consumer()),
+        }
+    }
+
+    fn key_count(&self) -> usize {
+        dispatch!(HybridSpine, self, trace => trace.key_count())
+    }
+
+    fn len(&self) -> usize {
+        dispatch!(HybridSpine, self, trace => trace.len())
+    }
+
+    fn lower(&self) -> AntichainRef<'_, Self::Time> {
+        dispatch!(HybridSpine, self, trace => trace.lower())
+    }
+
+    fn upper(&self) -> AntichainRef<'_, Self::Time> {
+        dispatch!(HybridSpine, self, trace => trace.upper())
+    }
+
+    fn truncate_keys_below(&mut self, lower_bound: &Self::Key) {
+        dispatch!(HybridSpine, self, trace => trace.truncate_keys_below(lower_bound))
+    }
+}
+
+impl<B> Trace for HybridSpine<B>
+where
+    B: Batch,
+{
+    type Batch = B;
+
+    fn new(activator: Option<Activator>) -> Self {
+        Self::Memory(Spine::new(activator))
+    }
+
+    fn new_persistent(activator: Option<Activator>) -> Self {
+        let backend =
+            Runtime::runtime().and_then(|runtime| runtime.storage().map(|storage| storage.backend));
+
+        match backend {
+            Some(StorageBackend::RocksDb) => Self::Persistent(PersistentTrace::new(activator)),
+            _ => Self::Memory(Spine::new_persistent(activator)),
+        }
+    }
+
+    fn recede_to(&mut self, frontier: &Self::Time) {
+        dispatch!(HybridSpine, self, trace => trace.recede_to(frontier))
+    }
+
+    fn exert(&mut self, effort: &mut isize) {
+        dispatch!(HybridSpine, self, trace => trace.exert(effort))
+    }
+
+    fn consolidate(self) -> Option<Self::Batch> {
+        dispatch!(HybridSpine, self, trace => trace.consolidate())
+    }
+
+    fn insert(&mut self, batch: Self::Batch) {
+        dispatch!(HybridSpine, self, trace => trace.insert(batch))
+    }
+
+    fn clear_dirty_flag(&mut self) {
+        dispatch!(HybridSpine, self, trace => trace.clear_dirty_flag())
+    }
+
+    fn dirty(&self) -> bool {
+        dispatch!(HybridSpine, self, trace => trace.dirty())
+    }
+
+    fn truncate_values_below(&mut self, lower_bound: &Self::Val) {
+        dispatch!(HybridSpine, self, trace => trace.truncate_values_below(lower_bound))
+    }
+
+    fn lower_value_bound(&self) -> &Option<Self::Val> {
+        dispatch!(HybridSpine, self, trace => trace.lower_value_bound())
+    }
+}
+
+/// The cursor for [`HybridSpine`].
+pub enum HybridSpineCursor<'s, B: Batch + 's> {
+    Memory(SpineCursor<'s, B>),
+    Persistent(PersistentTraceCursor<'s, B>),
+}
+
+impl<'s, B: Batch> Cursor<B::Key, B::Val, B::Time, B::R> for HybridSpineCursor<'s, B> {
+    fn key_valid(&self) -> bool {
+        dispatch!(HybridSpineCursor, self, cursor => cursor.key_valid())
+    }
+
+    fn val_valid(&self) -> bool {
+        dispatch!(HybridSpineCursor, self, cursor => cursor.val_valid())
+    }
+
+    fn key(&self) -> &B::Key {
+        dispatch!(HybridSpineCursor, self, cursor => cursor.key())
+    }
+
+    fn val(&self) -> &B::Val {
+        dispatch!(HybridSpineCursor, self, cursor => cursor.val())
+    }
+
+    fn map_times<L>(&mut self, logic: L)
+    where
+        L: FnMut(&B::Time, &B::R),
+    {
+        dispatch!(HybridSpineCursor, self, cursor => cursor.map_times(logic))
+    }
+
+    fn fold_times<F, U>(&mut self, init: U, fold: F) -> U
+    where
+        F: FnMut(U, &B::Time, &B::R) -> U,
+    {
+        dispatch!(HybridSpineCursor, self, cursor => cursor.fold_times(init, fold))
+    }
+
+    fn map_times_through<L>(&mut self, upper: &B::Time, logic: L)
+    where
+        L: FnMut(&B::Time, &B::R),
+    {
+        dispatch!(HybridSpineCursor, self, cursor => cursor.map_times_through(upper, logic))
+    }
+
+    fn fold_times_through<F, U>(&mut self, upper: &B::Time, init: U, fold: F) -> U
+    where
+        F: FnMut(U, &B::Time, &B::R) -> U,
+    {
+        dispatch!(HybridSpineCursor, self, cursor => cursor.fold_times_through(upper, init, fold))
+    }
+
+    fn weight(&mut self) -> B::R
+    where
+        B::Time: PartialEq<()>,
+    {
+        dispatch!(HybridSpineCursor, self, cursor => cursor.weight())
+    }
+
+    fn step_key(&mut self) {
+        dispatch!(HybridSpineCursor, self, cursor => cursor.step_key())
+    }
+
+    fn step_key_reverse(&mut self) {
+        dispatch!(HybridSpineCursor, self, cursor => cursor.step_key_reverse())
+    }
+
+    fn seek_key(&mut self, key: &B::Key) {
+        dispatch!(HybridSpineCursor, self, cursor => cursor.seek_key(key))
+    }
+
+    fn seek_key_with<P>(&mut self, predicate: P)
+    where
+        P: Fn(&B::Key) -> bool + Clone,
+    {
+        dispatch!(HybridSpineCursor, self, cursor => cursor.seek_key_with(predicate))
+    }
+
+    fn seek_key_with_reverse<P>(&mut self, predicate: P)
+    where
+        P: Fn(&B::Key) -> bool + Clone,
+    {
+        dispatch!(HybridSpineCursor, self, cursor => cursor.seek_key_with_reverse(predicate))
+    }
+
+    fn seek_key_reverse(&mut self, key: &B::Key) {
+        dispatch!(HybridSpineCursor, self, cursor => cursor.seek_key_reverse(key))
+    }
+
+    fn step_val(&mut self) {
+        dispatch!(HybridSpineCursor, self, cursor => cursor.step_val())
+    }
+
+    fn step_val_reverse(&mut self) {
+        dispatch!(HybridSpineCursor, self, cursor => cursor.step_val_reverse())
+    }
+
+    fn seek_val(&mut self, val: &B::Val) {
+        dispatch!(HybridSpineCursor, self, cursor => cursor.seek_val(val))
+    }
+
+    fn seek_val_reverse(&mut self, val: &B::Val) {
+        dispatch!(HybridSpineCursor, self, cursor => cursor.seek_val_reverse(val))
+    }
+
+    fn seek_val_with<P>(&mut self, predicate: P)
+    where
+        P: Fn(&B::Val) -> bool + Clone,
+    {
+        dispatch!(HybridSpineCursor, self, cursor => cursor.seek_val_with(predicate))
+    }
+
+    fn seek_val_with_reverse<P>(&mut self, predicate: P)
+    where
+        P: Fn(&B::Val) -> bool + Clone,
+    {
+        dispatch!(HybridSpineCursor, self, cursor => cursor.seek_val_with_reverse(predicate))
+    }
+
+    fn rewind_keys(&mut self) {
+        dispatch!(HybridSpineCursor, self, cursor => cursor.rewind_keys())
+    }
+
+    fn fast_forward_keys(&mut self) {
+        dispatch!(HybridSpineCursor, self, cursor => cursor.fast_forward_keys())
+    }
+
+    fn rewind_vals(&mut self) {
+        dispatch!(HybridSpineCursor, self, cursor => cursor.rewind_vals())
+    }
+
+    fn fast_forward_vals(&mut self) {
+        dispatch!(HybridSpineCursor, self, cursor => cursor.fast_forward_vals())
+    }
+}
+
+/// The consumer for [`HybridSpine`].
+pub enum HybridSpineConsumer<B>
+where
+    B: Batch,
+{
+    Memory(SpineConsumer<B>),
+    Persistent(PersistentConsumer<B>),
+}
+
+impl<B> Consumer<B::Key, B::Val, B::R, B::Time> for HybridSpineConsumer<B>
+where
+    B: Batch,
+{
+    type ValueConsumer<'a> = HybridSpineValueConsumer<'a, B>
+    where
+        Self: 'a;
+
+    fn key_valid(&self) -> bool {
+        dispatch!(HybridSpineConsumer, self, consumer => consumer.key_valid())
+    }
+
+    fn peek_key(&self) -> &B::Key {
+        dispatch!(HybridSpineConsumer, self, consumer => consumer.peek_key())
+    }
+
+    fn next_key(&mut self) -> (B::Key, Self::ValueConsumer<'_>) {
+        match self {
+            Self::Memory(consumer) => {
+                let (key, values) = consumer.next_key();
+                (key, HybridSpineValueConsumer::Memory(values))
+            }
+            Self::Persistent(consumer) => {
+                let (key, values) = consumer.next_key();
+                (key, HybridSpineValueConsumer::Persistent(values))
+            }
+        }
+    }
+
+    fn seek_key(&mut self, key: &B::Key)
+    where
+        B::Key: Ord,
+    {
+        dispatch!(HybridSpineConsumer, self, consumer => consumer.seek_key(key))
+    }
+}
+
+/// The value consumer for [`HybridSpine`].
+pub enum HybridSpineValueConsumer<'a, B>
+where
+    B: Batch,
+{
+    Memory(SpineValueConsumer<'a, B>),
+    Persistent(PersistentTraceValueConsumer<'a, B>),
+}
+
+impl<'a, B> ValueConsumer<'a, B::Val, B::R, B::Time> for HybridSpineValueConsumer<'a, B>
+where
+    B: Batch,
+{
+    fn value_valid(&self) -> bool {
+        dispatch!(HybridSpineValueConsumer, self, consumer => consumer.value_valid())
+    }
+
+    fn next_value(&mut self) -> (B::Val, B::R, B::Time) {
+        dispatch!(HybridSpineValueConsumer, self, consumer => consumer.next_value())
+    }
+
+    fn remaining_values(&self) -> usize {
+        dispatch!(HybridSpineValueConsumer, self, consumer => consumer.remaining_values())
+    }
+}
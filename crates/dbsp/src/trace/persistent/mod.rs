@@ -1,7 +1,7 @@
 //! This module implements logic and datastructures to provide a trace that is
 //! using on-disk storage with the help of RocksDB.
 
-use std::cmp::Ordering;
+use std::{cmp::Ordering, collections::HashMap, env, path::PathBuf, sync::Mutex};
 
 use bincode::{
     config::{BigEndian, Fixint},
@@ -14,7 +14,13 @@ use once_cell::sync::Lazy;
 use rocksdb::{Cache, DBCompressionType, Options, DB};
 use uuid::Uuid;
 
+use crate::{
+    circuit::{StorageCompression, StorageConfig},
+    Error, Runtime,
+};
+
 mod cursor;
+mod hybrid;
 mod tests;
 mod trace;
 
@@ -27,32 +33,35 @@ type Values<V, T, R> = Vec<ValueTimeWeights<V, T, R>>;
 
 /// The cursor for the persistent trace.
 pub use cursor::PersistentTraceCursor;
+/// A trace that is either kept in memory or in persistent storage.
+pub use hybrid::{HybridSpine, HybridSpineCursor};
 /// The persistent trace itself, it should be equivalent to the [`Spine`].
 pub use trace::PersistentTrace;
 
-/// DB in-memory cache size [bytes].
-///
-/// # TODO
-/// Set to 1 GiB for now, in the future we should probably make this
-/// configurable or determine it based on the system parameters.
-const DB_DRAM_CACHE_SIZE: usize = 1024 * 1024 * 1024;
-
-/// Path of the RocksDB database file on disk.
+/// Databases opened by [`open_db`], indexed by path.
 ///
-/// # TODO
-/// Eventually should be supplied as a command-line argument or provided in a
-/// config file.
-static DB_PATH: Lazy<String> = Lazy::new(|| format!("/tmp/{}.db", Uuid::new_v4()));
-
-/// Options for the RocksDB database
-static DB_OPTS: Lazy<Options> = Lazy::new(|| {
-    let cache = Cache::new_lru_cache(DB_DRAM_CACHE_SIZE).expect("Can't create cache for DB");
+/// Databases are never closed: the column families of persistent traces
+/// borrow the database for as long as the trace exists, so we leak each
+/// database to obtain a `'static` reference.
+static DATABASES: Lazy<Mutex<HashMap<PathBuf, &'static DB>>> = Lazy::new(Default::default);
+
+/// Storage used by persistent traces created outside of a runtime with
+/// persistent storage, e.g., in tests.
+static DEFAULT_STORAGE: Lazy<StorageConfig> =
+    Lazy::new(|| StorageConfig::new(env::temp_dir().join(format!("{}.db", Uuid::new_v4()))));
+
+/// Options for a RocksDB database configured by `config`.
+fn db_options(config: &StorageConfig) -> Result<Options, rocksdb::Error> {
+    let cache = Cache::new_lru_cache(config.cache_size)?;
     let mut global_opts = Options::default();
     // Create the database file if it's missing (the default behavior)
     global_opts.create_if_missing(true);
-    // Disable compression for now, this will help with benchmarking but
-    // probably not ideal for a production setting
-    global_opts.set_compression_type(DBCompressionType::None);
+    global_opts.set_compression_type(match config.compression {
+        StorageCompression::None => DBCompressionType::None,
+        StorageCompression::Snappy => DBCompressionType::Snappy,
+        StorageCompression::Lz4 => DBCompressionType::Lz4,
+        StorageCompression::Zstd => DBCompressionType::Zstd,
+    });
     // Ensure we use a shared cache for all column families
     global_opts.set_row_cache(&cache);
     // RocksDB doesn't like to close files by default, if we set this it limits
@@ -67,14 +76,42 @@ static DB_OPTS: Lazy<Options> = Lazy::new(|| {
     //global_opts.set_write_buffer_size(1024*1024*4);
     //global_opts.set_target_file_size_base(1024*1024*8);
 
-    global_opts
-});
+    Ok(global_opts)
+}
+
+/// Returns the RocksDB instance at `config.path`, opening (or creating) it
+/// if necessary.
+///
+/// All traces stored under the same path share a database (in different
+/// columns).  The options of a database are determined by the configuration
+/// it was first opened with.
+pub(crate) fn open_db(config: &StorageConfig) -> Result<&'static DB, Error> {
+    let mut databases = DATABASES.lock().unwrap();
+
+    if let Some(db) = databases.get(&config.path) {
+        return Ok(*db);
+    }
+
+    let db = db_options(config)
+        .and_then(|options| DB::open(&options, &config.path))
+        .map_err(|e| {
+            Error::Custom(format!(
+                "failed to open persistent storage at '{}': {e}",
+                config.path.display()
+            ))
+        })?;
+    let db: &'static DB = Box::leak(Box::new(db));
+    databases.insert(config.path.clone(), db);
+
+    Ok(db)
+}
+
+/// Returns the database for persistent traces created by the current worker.
+fn current_db() -> &'static DB {
+    let storage = Runtime::runtime().and_then(|runtime| runtime.storage().cloned());
 
-/// The RocksDB instance that holds all traces (in different columns).
-static ROCKS_DB_INSTANCE: Lazy<DB> = Lazy::new(|| {
-    // Open the database (or create it if it doesn't exist
-    DB::open(&DB_OPTS, DB_PATH.clone()).unwrap()
-});
+    open_db(storage.as_ref().unwrap_or(&*DEFAULT_STORAGE)).unwrap_or_else(|e| panic!("{e}"))
+}
 
 /// Configuration we use for encodings/decodings to/from RocksDB data.
 static BINCODE_CONFIG: bincode::config::Configuration<BigEndian, Fixint> =
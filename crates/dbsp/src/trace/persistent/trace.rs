@@ -7,12 +7,12 @@ use std::sync::Arc;
 
 use bincode::{decode_from_slice, Decode, Encode};
 use rocksdb::compaction_filter::Decision;
-use rocksdb::{BoundColumnFamily, MergeOperands, Options, WriteBatch};
+use rocksdb::{BoundColumnFamily, MergeOperands, Options, WriteBatch, DB};
 use size_of::SizeOf;
 use uuid::Uuid;
 
+use super::{current_db, BINCODE_CONFIG};
 use super::{rocksdb_key_comparator, PersistentTraceCursor, ReusableEncodeBuffer, Values};
-use super::{BINCODE_CONFIG, ROCKS_DB_INSTANCE};
 use crate::algebra::AddAssignByRef;
 use crate::circuit::Activator;
 use crate::time::{Antichain, Timestamp};
@@ -43,6 +43,9 @@ where
     lower_key_bound: Option<B::Key>,
     lower_val_bound: Option<B::Val>,
 
+    /// The database that stores the trace.
+    #[size_of(skip)]
+    db: &'static DB,
     /// Where all the dataz is.
     #[size_of(skip)]
     cf: Arc<BoundColumnFamily<'static>>,
@@ -68,9 +71,7 @@ where
 {
     /// Deletes the RocksDB column family.
     fn drop(&mut self) {
-        ROCKS_DB_INSTANCE
-            .drop_cf(&self.cf_name)
-            .expect("Can't delete CF?");
+        self.db.drop_cf(&self.cf_name).expect("Can't delete CF?");
     }
 }
 
@@ -208,7 +209,7 @@ where
     /// This is an estimate as there is no way to get an exact count from
     /// RocksDB.
     fn key_count(&self) -> usize {
-        ROCKS_DB_INSTANCE
+        self.db
             .property_int_value_cf(&self.cf, rocksdb::properties::ESTIMATE_NUM_KEYS)
             .expect("Can't get key count estimate")
             .map_or_else(|| 0, |c| c as usize)
@@ -230,7 +231,7 @@ where
     }
 
     fn cursor(&self) -> Self::Cursor<'_> {
-        PersistentTraceCursor::new(&self.cf, &self.lower_key_bound)
+        PersistentTraceCursor::new(self.db, &self.cf, &self.lower_key_bound)
     }
 
     fn truncate_keys_below(&mut self, lower_bound: &Self::Key) {
@@ -549,7 +550,9 @@ where
     ///
     /// It works by creating a new column-family with a random name and
     /// configuring it with the right custom functions for comparison, merge,
-    /// and compaction.
+    /// and compaction.  The column family is created in the persistent
+    /// storage of the current [`Runtime`](`crate::Runtime`), or in a
+    /// temporary database if the runtime has no persistent storage.
     ///
     /// # Arguments
     /// - `activator`: This is not used, None should be supplied.
@@ -568,10 +571,10 @@ where
         );
         cf_options.create_if_missing(true);
 
-        ROCKS_DB_INSTANCE
-            .create_cf(cf_name.as_str(), &cf_options)
+        let db = current_db();
+        db.create_cf(cf_name.as_str(), &cf_options)
             .expect("Can't create column family?");
-        let cf = ROCKS_DB_INSTANCE
+        let cf = db
             .cf_handle(cf_name.as_str())
             .expect("Can't find just created column family?");
 
@@ -582,6 +585,7 @@ where
             lower_key_bound: None,
             lower_val_bound: None,
             dirty: false,
+            db,
             cf,
             cf_name,
             _cf_options: cf_options,
@@ -603,7 +607,7 @@ where
             let update: MergeOp<B::Val, B::Time, B::R> = MergeOp::RecedeTo(frontier.clone());
             let encoded_update = tmp_val.encode(&update).expect("Can't encode `vals`");
 
-            ROCKS_DB_INSTANCE
+            self.db
                 .merge_cf(&self.cf, encoded_key, encoded_update)
                 .expect("Can't merge recede update");
             cursor.step_key();
@@ -706,8 +710,6 @@ where
             batch_cursor.step_key();
         }
 
-        ROCKS_DB_INSTANCE
-            .write(sstable)
-            .expect("Could not write batch to db");
+        self.db.write(sstable).expect("Could not write batch to db");
     }
 }
@@ -78,20 +78,46 @@
 //! at low layers: they should still extract fuel from new updates even though
 //! they have completed, at least until they have paid back any "debt" to higher
 //! layers by continuing to provide fuel as updates arrive.
+//!
+//! ## Spilling to disk
+//!
+//! A spine created with [`Spine::with_spill`] writes merged batches that
+//! exceed a configured size to immutable files and only keeps smaller batches
+//! in memory (see [`SpillConfig`]).  Batches at each layer are therefore
+//! [`SpineBatch`]es, stored either in memory or on disk, and cursors read from
+//! both transparently.  Merges of in-memory batches use the batch's own
+//! [`Merger`](`crate::trace::Merger`); merges that involve a spilled batch
+//! stream their inputs into a new file.
+//!
+//! [`Trace`] methods cannot fail, so they panic if the spine fails to read or
+//! write a spilled batch.  The `try_` methods of [`Spine`], such as
+//! [`Spine::try_insert`], return these errors instead.  A merge that fails is
+//! started over the next time the spine performs work, as its inputs are not
+//! modified until the merge completes.
+
+mod spill;
+
+pub use spill::{
+    FileBatch, FileBatchCursor, FileMerger, SpillConfig, SpineBatch, SpineBatchCursor, SpineMerger,
+    DEFAULT_SPILL_THRESHOLD,
+};
+use spill::{Spill, SpillCodec};
 
 use crate::{
     circuit::Activator,
     time::{Antichain, AntichainRef, Timestamp},
     trace::{
         cursor::{Cursor, CursorList},
-        Batch, BatchReader, Consumer, Merger, Trace, ValueConsumer,
+        Batch, BatchReader, Consumer, Trace, ValueConsumer,
     },
     NumEntries,
 };
+use bincode::{Decode, Encode};
 use size_of::SizeOf;
 use std::{
     cmp::max,
     fmt::{self, Debug, Display, Write},
+    io,
     marker::PhantomData,
     mem::replace,
 };
@@ -116,6 +142,8 @@ where
     dirty: bool,
     lower_key_bound: Option<B::Key>,
     lower_val_bound: Option<B::Val>,
+    #[size_of(skip)]
+    spill: Option<Spill<B>>,
 }
 
 impl<B> Display for Spine<B>
@@ -221,15 +249,7 @@ where
     }
 
     fn truncate_keys_below(&mut self, lower_bound: &Self::Key) {
-        self.complete_merges();
-
-        let bound = if let Some(bound) = &self.lower_key_bound {
-            max(bound, lower_bound).clone()
-        } else {
-            lower_bound.clone()
-        };
-        self.lower_key_bound = Some(bound.clone());
-        self.map_batches_mut(|batch| batch.truncate_keys_below(&bound));
+        expect_spill(self.try_truncate_keys_below(lower_bound))
     }
 }
 
@@ -245,23 +265,17 @@ where
         for batch in self.merging.iter() {
             match batch {
                 MergeState::Double(MergeVariant::InProgress(batch1, batch2, _)) => {
-                    s.write_fmt(format_args!(
-                        "[{}+{}],",
-                        batch1.num_entries_deep(),
-                        batch2.num_entries_deep()
-                    ))
-                    .unwrap();
+                    s.write_fmt(format_args!("[{}+{}],", batch1.len(), batch2.len()))
+                        .unwrap();
                 }
                 MergeState::Double(MergeVariant::Complete(Some(batch))) => {
-                    s.write_fmt(format_args!("[{}],", batch.num_entries_deep()))
-                        .unwrap();
+                    s.write_fmt(format_args!("[{}],", batch.len())).unwrap();
                 }
                 MergeState::Double(MergeVariant::Complete(None)) => {
                     s.write_str(".,").unwrap();
                 }
                 MergeState::Single(Some(batch)) => {
-                    s.write_fmt(format_args!("{},", batch.num_entries_deep()))
-                        .unwrap();
+                    s.write_fmt(format_args!("{},", batch.len())).unwrap();
                 }
                 MergeState::Single(None) => {
                     s.write_str("_,").unwrap();
@@ -278,7 +292,7 @@ where
     #[allow(dead_code)]
     fn map_batches<F>(&self, mut map: F)
     where
-        F: FnMut(&B),
+        F: FnMut(&SpineBatch<B>),
     {
         for batch in self.merging.iter().rev() {
             match batch {
@@ -295,7 +309,7 @@ where
 
     fn fold_batches<T, F>(&self, init: T, mut fold: F) -> T
     where
-        F: FnMut(T, &B) -> T,
+        F: FnMut(T, &SpineBatch<B>) -> T,
     {
         self.merging
             .iter()
@@ -314,7 +328,7 @@ where
     // TODO: Use the `Try` trait when stable
     fn try_fold_batches<T, E, F>(&self, init: T, mut fold: F) -> Result<T, E>
     where
-        F: FnMut(T, &B) -> Result<T, E>,
+        F: FnMut(T, &SpineBatch<B>) -> Result<T, E>,
     {
         self.merging
             .iter()
@@ -333,7 +347,7 @@ where
 
 pub struct SpineCursor<'s, B: Batch + 's> {
     #[allow(clippy::type_complexity)]
-    cursor: CursorList<B::Key, B::Val, B::Time, B::R, B::Cursor<'s>>,
+    cursor: CursorList<B::Key, B::Val, B::Time, B::R, SpineBatchCursor<'s, B>>,
 }
 
 impl<'s, B: Batch> SpineCursor<'s, B>
@@ -341,7 +355,7 @@ where
     B::Key: Ord,
     B::Val: Ord,
 {
-    fn new(cursors: Vec<B::Cursor<'s>>) -> Self {
+    fn new(cursors: Vec<SpineBatchCursor<'s, B>>) -> Self {
         Self {
             cursor: CursorList::new(cursors),
         }
@@ -560,12 +574,29 @@ where
         Self::with_effort(1, activator)
     }
 
-    fn recede_to(&mut self, frontier: &B::Time) {
-        // Complete all in-progress merges, as we don't have an easy way to update
-        // timestamps in an ongoing merge.
-        self.complete_merges();
+    /// Allocates a spine that spills large batches to the storage directory
+    /// of the current runtime, if any (see
+    /// [`StorageConfig`](`crate::circuit::StorageConfig`)).
+    ///
+    /// Requires the `with-bincode` feature, which makes batches encodable;
+    /// without it, the spine is kept in memory.
+    #[cfg(feature = "with-bincode")]
+    fn new_persistent(activator: Option<Activator>) -> Self {
+        let spine = Self::new(activator);
+        let storage = crate::circuit::Runtime::runtime().and_then(|runtime| {
+            runtime.storage().map(|storage| {
+                SpillConfig::new(&storage.path).with_threshold(storage.spill_threshold)
+            })
+        });
 
-        self.map_batches_mut(|b| b.recede_to(frontier));
+        match storage {
+            Some(config) => spine.with_spill(config),
+            None => spine,
+        }
+    }
+
+    fn recede_to(&mut self, frontier: &B::Time) {
+        expect_spill(self.try_recede_to(frontier))
     }
 
     /// Apply some amount of effort to trace maintenance.
@@ -574,77 +605,18 @@ where
     /// thought of as analogous to inserting as many empty updates,
     /// where the trace is permitted to perform proportionate work.
     fn exert(&mut self, effort: &mut isize) {
-        // If there is work to be done, ...
-        self.tidy_layers();
-        if !self.reduced() {
-            // If any merges exist, we can directly call `apply_fuel`.
-            if self.merging.iter().any(|b| b.is_double()) {
-                self.apply_fuel(effort);
-            }
-            // Otherwise, we'll need to introduce fake updates to move merges along.
-            else {
-                // Introduce an empty batch with roughly *effort number of virtual updates.
-                let level = (*effort as usize).next_power_of_two().trailing_zeros() as usize;
-                self.introduce_batch(None, level);
-            }
-            // We were not in reduced form, so let's check again in the future.
-            if let Some(activator) = &self.activator {
-                activator.activate();
-            }
-        }
+        expect_spill(self.try_exert(effort))
     }
 
-    fn consolidate(mut self) -> Option<B> {
-        // Merge batches until there is nothing left to merge.
-        let mut fuel = isize::max_value();
-        while !self.reduced() {
-            self.exert(&mut fuel);
-        }
-        // Return the sole remaining batch (if one exists).
-        for merging in self.merging.into_iter() {
-            if let MergeState::Single(Some(batch)) = merging {
-                if !batch.is_empty() {
-                    return Some(batch);
-                }
-            }
-        }
-
-        // Consolidated trace is empty.
-        None
+    fn consolidate(self) -> Option<B> {
+        expect_spill(self.try_consolidate())
     }
 
     // Ideally, this method acts as insertion of `batch`, even if we are not yet
     // able to begin merging the batch. This means it is a good time to perform
     // amortized work proportional to the size of batch.
-    fn insert(&mut self, mut batch: Self::Batch) {
-        assert!(batch.lower() != batch.upper());
-
-        // Ignore empty batches.
-        // Note: we may want to use empty batches to artificially force compaction.
-        if batch.is_empty() {
-            return;
-        }
-
-        if let Some(bound) = &self.lower_key_bound {
-            batch.truncate_keys_below(bound);
-        }
-
-        self.dirty = true;
-        self.lower = self.lower.as_ref().meet(batch.lower());
-        self.upper = self.upper.as_ref().join(batch.upper());
-
-        // Leonid: we do not require batch bounds to grow monotonically.
-        //assert_eq!(batch.lower(), &self.upper);
-
-        let index = batch.len().next_power_of_two();
-        self.introduce_batch(Some(batch), index.trailing_zeros() as usize);
-
-        // If more than one batch remains reschedule ourself.
-        if !self.reduced() {
-            if let Some(activator) = &self.activator {
-                activator.activate();
-            }
-        }
+    fn insert(&mut self, batch: Self::Batch) {
+        expect_spill(self.try_insert(batch))
     }
 
     fn clear_dirty_flag(&mut self) {
@@ -733,7 +705,145 @@ where
             dirty: false,
             lower_key_bound: None,
             lower_val_bound: None,
+            spill: None,
+        }
+    }
+
+    /// Spill merged batches to disk as configured by `config`.
+    ///
+    /// Merged batches with at least `config.threshold` updates are written to
+    /// files in `config.directory`, smaller batches stay in memory.
+    ///
+    /// Spilled batches are encoded with bincode, so the key, value, time, and
+    /// weight types of the batch must implement `Encode` and `Decode`.
+    pub fn with_spill(mut self, config: SpillConfig) -> Self
+    where
+        B::Key: Encode + Decode,
+        B::Val: Encode + Decode,
+        B::Time: Encode + Decode,
+        B::R: Encode + Decode,
+    {
+        self.spill = Some(Spill {
+            config,
+            codec: SpillCodec::new(),
+        });
+        self
+    }
+
+    /// The spill configuration of the spine, if any.
+    pub fn spill_config(&self) -> Option<&SpillConfig> {
+        self.spill.as_ref().map(|spill| &spill.config)
+    }
+
+    /// Like [`Trace::insert`], but returns an error if the spine fails to
+    /// read or write a spilled batch.
+    ///
+    /// On error, `batch` is not inserted, but the spine keeps all updates
+    /// inserted before.
+    pub fn try_insert(&mut self, mut batch: B) -> io::Result<()> {
+        assert!(batch.lower() != batch.upper());
+
+        // Ignore empty batches.
+        // Note: we may want to use empty batches to artificially force compaction.
+        if batch.is_empty() {
+            return Ok(());
+        }
+
+        if let Some(bound) = &self.lower_key_bound {
+            batch.truncate_keys_below(bound);
         }
+
+        let lower = self.lower.as_ref().meet(batch.lower());
+        let upper = self.upper.as_ref().join(batch.upper());
+
+        // Leonid: we do not require batch bounds to grow monotonically.
+        //assert_eq!(batch.lower(), &self.upper);
+
+        let index = batch.len().next_power_of_two();
+        self.try_introduce_batch(Some(batch), index.trailing_zeros() as usize)?;
+
+        self.dirty = true;
+        self.lower = lower;
+        self.upper = upper;
+
+        // If more than one batch remains reschedule ourself.
+        if !self.reduced() {
+            if let Some(activator) = &self.activator {
+                activator.activate();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Like [`Trace::exert`], but returns an error if the spine fails to read
+    /// or write a spilled batch.
+    pub fn try_exert(&mut self, effort: &mut isize) -> io::Result<()> {
+        // If there is work to be done, ...
+        self.tidy_layers();
+        if !self.reduced() {
+            // If any merges exist, we can directly call `apply_fuel`.
+            if self.merging.iter().any(|b| b.is_double()) {
+                self.try_apply_fuel(effort)?;
+            }
+            // Otherwise, we'll need to introduce fake updates to move merges along.
+            else {
+                // Introduce an empty batch with roughly *effort number of virtual updates.
+                let level = (*effort as usize).next_power_of_two().trailing_zeros() as usize;
+                self.try_introduce_batch(None, level)?;
+            }
+            // We were not in reduced form, so let's check again in the future.
+            if let Some(activator) = &self.activator {
+                activator.activate();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Like [`Trace::consolidate`], but returns an error if the spine fails to
+    /// read or write a spilled batch.
+    pub fn try_consolidate(mut self) -> io::Result<Option<B>> {
+        // Merge batches until there is nothing left to merge.
+        let mut fuel = isize::max_value();
+        while !self.reduced() {
+            self.try_exert(&mut fuel)?;
+        }
+        // Return the sole remaining batch (if one exists).
+        for merging in self.merging.into_iter() {
+            if let MergeState::Single(Some(batch)) = merging {
+                if !batch.is_empty() {
+                    return batch.into_batch().map(Some);
+                }
+            }
+        }
+
+        // Consolidated trace is empty.
+        Ok(None)
+    }
+
+    /// Like [`Trace::recede_to`], but returns an error if the spine fails to
+    /// read or write a spilled batch.
+    pub fn try_recede_to(&mut self, frontier: &B::Time) -> io::Result<()> {
+        // Complete all in-progress merges, as we don't have an easy way to update
+        // timestamps in an ongoing merge.
+        self.complete_merges()?;
+
+        self.map_batches_mut(|b| b.recede_to(frontier))
+    }
+
+    /// Like [`BatchReader::truncate_keys_below`], but returns an error if the
+    /// spine fails to read or write a spilled batch.
+    pub fn try_truncate_keys_below(&mut self, lower_bound: &B::Key) -> io::Result<()> {
+        self.complete_merges()?;
+
+        let bound = if let Some(bound) = &self.lower_key_bound {
+            max(bound, lower_bound).clone()
+        } else {
+            lower_bound.clone()
+        };
+        self.lower_key_bound = Some(bound.clone());
+        self.map_batches_mut(|batch| batch.truncate_keys_below(&bound))
     }
 
     /// Introduces a batch at an indicated level.
@@ -742,6 +852,10 @@ where
     /// it can also be used to artificially fuel the computation by supplying
     /// empty batches at non-trivial indices, to move merges along.
     pub fn introduce_batch(&mut self, batch: Option<B>, batch_index: usize) {
+        expect_spill(self.try_introduce_batch(batch, batch_index))
+    }
+
+    fn try_introduce_batch(&mut self, batch: Option<B>, batch_index: usize) -> io::Result<()> {
         // Step 0.  Determine an amount of fuel to use for the computation.
         //
         //          Fuel is used to drive maintenance of the data structure,
@@ -792,7 +906,7 @@ where
         // `fuel` should be sufficient to fully merge all batches up to
         // `batch_index`.  This is required to maintain the invariant that
         // there should be no consecutive in-progress batches in the trace.
-        self.apply_fuel(&mut fuel);
+        self.try_apply_fuel(&mut fuel)?;
 
         // Step 2.  We must ensure the invariant that adjacent layers do not
         //          contain two batches will be satisfied when we insert the
@@ -810,12 +924,12 @@ where
         //          surprised later on. The number of fake updates should
         //          correspond to the deficit for the layer, which perhaps
         //          we should track explicitly.
-        self.roll_up(batch_index);
+        self.roll_up(batch_index)?;
 
         // Step 3. This insertion should be into an empty layer. It is a
         //         logical error otherwise, as we may be violating our
         //         invariant, from which all wonderment derives.
-        self.insert_at(batch, batch_index);
+        self.insert_at(batch.map(SpineBatch::Memory), batch_index);
 
         // Step 4. Tidy the largest layers.
         //
@@ -823,6 +937,8 @@ where
         //         as their ascension is what ensures the merging and
         //         eventual compaction of the largest layers.
         self.tidy_layers();
+
+        Ok(())
     }
 
     /// Ensures that an insertion at layer `index` will succeed.
@@ -832,7 +948,7 @@ where
     /// present at lower levels before the method is called. In doing this,
     /// we should not introduce more virtual records than 2^index, as that
     /// is the amount of excess fuel we have budgeted for completing merges.
-    fn roll_up(&mut self, index: usize) {
+    fn roll_up(&mut self, index: usize) -> io::Result<()> {
         // Ensure entries sufficient for `index`.
         while self.merging.len() <= index {
             self.merging.push(MergeState::Vacant);
@@ -844,7 +960,7 @@ where
             let mut merged = None;
             for i in 0..index {
                 self.insert_at(merged, i);
-                merged = self.complete_at(i);
+                merged = self.complete_at(i)?;
             }
 
             // The merged results should be introduced at level `index`, which should
@@ -854,10 +970,12 @@ where
             // If the insertion results in a merge, we should complete it to ensure
             // the upcoming insertion at `index` does not panic.
             if self.merging[index].is_double() {
-                let merged = self.complete_at(index);
+                let merged = self.complete_at(index)?;
                 self.insert_at(merged, index + 1);
             }
         }
+
+        Ok(())
     }
 
     /// Applies an amount of fuel to merges in progress.
@@ -868,6 +986,10 @@ where
     /// (at the risk of completing merges of large batches later, but tbh
     /// probably not much later).
     pub fn apply_fuel(&mut self, fuel: &mut isize) {
+        expect_spill(self.try_apply_fuel(fuel))
+    }
+
+    fn try_apply_fuel(&mut self, fuel: &mut isize) -> io::Result<()> {
         // For the moment our strategy is to apply fuel independently to each merge
         // in progress, rather than prioritizing small merges. This sounds like a
         // great idea, but we need better accounting in place to ensure that merges
@@ -877,7 +999,7 @@ where
             // Give each level independent fuel, for now.
             let mut fuel = *fuel;
             // Pass along various logging stuffs, in case we need to report success.
-            self.merging[index].work(&self.lower_val_bound, &mut fuel);
+            self.merging[index].work(&self.lower_val_bound, &self.spill, &mut fuel)?;
             // `fuel` could have a deficit at this point, meaning we over-spent when
             // we took a merge step. We could ignore this, or maintain the deficit
             // and account future fuel against it before spending again. It isn't
@@ -889,10 +1011,12 @@ where
             // level, which is "guaranteed" to be complete at this point, by our
             // fueling discipline.
             if self.merging[index].is_complete() {
-                let complete = self.complete_at(index);
+                let complete = self.complete_at(index)?;
                 self.insert_at(complete, index + 1);
             }
         }
+
+        Ok(())
     }
 
     /// Inserts a batch at a specific location.
@@ -900,7 +1024,7 @@ where
     /// This is a non-public internal method that can panic if we try and insert
     /// into a layer which already contains two batches (and is still in the
     /// process of merging).
-    fn insert_at(&mut self, batch: Option<B>, index: usize) {
+    fn insert_at(&mut self, batch: Option<SpineBatch<B>>, index: usize) {
         // Ensure the spine is large enough.
         while self.merging.len() <= index {
             self.merging.push(MergeState::Vacant);
@@ -912,7 +1036,7 @@ where
                 self.merging[index] = MergeState::Single(batch);
             }
             MergeState::Single(old) => {
-                self.merging[index] = MergeState::begin_merge(old, batch);
+                self.merging[index] = MergeState::begin_merge(old, batch, &self.spill);
             }
             MergeState::Double(_) => {
                 panic!("Attempted to insert batch into incomplete merge!")
@@ -921,8 +1045,8 @@ where
     }
 
     /// Completes and extracts what ever is at layer `index`.
-    fn complete_at(&mut self, index: usize) -> Option<B> {
-        self.merging[index].complete(&self.lower_val_bound)
+    fn complete_at(&mut self, index: usize) -> io::Result<Option<SpineBatch<B>>> {
+        self.merging[index].complete(&self.lower_val_bound, &self.spill)
     }
 
     /// Attempts to draw down large layers to size appropriate layers.
@@ -988,19 +1112,23 @@ where
     }
 
     /// Complete all in-progress merges (without starting any new ones).
-    fn complete_merges(&mut self) {
+    fn complete_merges(&mut self) -> io::Result<()> {
         for merge_state in self.merging.iter_mut() {
             if merge_state.is_inprogress() {
                 let mut fuel = isize::max_value();
-                merge_state.work(&self.lower_val_bound, &mut fuel);
+                merge_state.work(&self.lower_val_bound, &self.spill, &mut fuel)?;
             }
         }
         assert!(self.merging.iter().all(|m| !m.is_inprogress()));
+        Ok(())
     }
 
     /// Mutate all batches.  Can only be invoked when there are no in-progress
     /// batches in the trace.
-    fn map_batches_mut<F: FnMut(&mut <Self as Trace>::Batch)>(&mut self, mut f: F) {
+    fn map_batches_mut<F>(&mut self, mut f: F) -> io::Result<()>
+    where
+        F: FnMut(&mut SpineBatch<B>) -> io::Result<()>,
+    {
         for batch in self.merging.iter_mut().rev() {
             match batch {
                 MergeState::Double(MergeVariant::InProgress(_batch1, _batch2, _)) => {
@@ -1009,15 +1137,23 @@ where
                 MergeState::Double(MergeVariant::Complete(Some(batch))) => {
                     // Ref counter can only be >1 while iterating over batch,
                     // which should be impossible as we hold a mutable reference to it.
-                    f(batch)
+                    f(batch)?
                 }
-                MergeState::Single(Some(batch)) => f(batch),
+                MergeState::Single(Some(batch)) => f(batch)?,
                 _ => {}
             }
         }
+
+        Ok(())
     }
 }
 
+/// Unwraps the result of a [`Spine`] operation from a [`Trace`] method, which
+/// cannot return errors reading or writing spilled batches.
+fn expect_spill<T>(result: io::Result<T>) -> T {
+    result.unwrap_or_else(|e| panic!("failed to access spilled batches: {e}"))
+}
+
 /// Describes the state of a layer.
 ///
 /// A layer can be empty, contain a single batch, or contain a pair of batches
@@ -1033,7 +1169,7 @@ where
     ///
     /// The `None` variant is used to represent a structurally empty batch
     /// present to ensure the progress of maintenance work.
-    Single(Option<B>),
+    Single(Option<SpineBatch<B>>),
     /// A layer containing two batches, in the process of merging.
     Double(MergeVariant<B>),
 }
@@ -1075,12 +1211,25 @@ where
     /// which should be done with the `is_complete()` method.
     ///
     /// There is the additional option of input batches.
-    fn complete(&mut self, lower_val_bound: &Option<B::Val>) -> Option<B> {
-        match replace(self, MergeState::Vacant) {
+    ///
+    /// If the merge fails, the layer keeps the batches being merged.
+    fn complete(
+        &mut self,
+        lower_val_bound: &Option<B::Val>,
+        spill: &Option<Spill<B>>,
+    ) -> io::Result<Option<SpineBatch<B>>> {
+        if let MergeState::Double(variant) = self {
+            variant.complete(lower_val_bound, spill)?;
+        }
+
+        Ok(match replace(self, MergeState::Vacant) {
             MergeState::Vacant => None,
             MergeState::Single(batch) => batch,
-            MergeState::Double(variant) => variant.complete(lower_val_bound),
-        }
+            MergeState::Double(MergeVariant::Complete(batch)) => batch,
+            MergeState::Double(MergeVariant::InProgress(..)) => {
+                panic!("Failed to complete a merge!")
+            }
+        })
     }
 
     /// True iff the layer is a complete merge, ready for extraction.
@@ -1098,11 +1247,17 @@ where
     /// If the merge completes, the resulting batch is returned.
     /// If a batch is returned, it is the obligation of the caller
     /// to correctly install the result.
-    fn work(&mut self, lower_val_bound: &Option<B::Val>, fuel: &mut isize) {
+    fn work(
+        &mut self,
+        lower_val_bound: &Option<B::Val>,
+        spill: &Option<Spill<B>>,
+        fuel: &mut isize,
+    ) -> io::Result<()> {
         // We only perform work for merges in progress.
         if let MergeState::Double(layer) = self {
-            layer.work(lower_val_bound, fuel)
+            layer.work(lower_val_bound, spill, fuel)?;
         }
+        Ok(())
     }
 
     /// Extract the merge state, typically temporarily.
@@ -1121,13 +1276,20 @@ where
     /// empty batch whose upper and lower froniers are equal. This
     /// option exists purely for bookkeeping purposes, and no computation
     /// is performed to merge the two batches.
-    fn begin_merge(batch1: Option<B>, batch2: Option<B>) -> MergeState<B> {
+    ///
+    /// If either batch is spilled, the result of the merge is written to a
+    /// file according to `spill`.
+    fn begin_merge(
+        batch1: Option<SpineBatch<B>>,
+        batch2: Option<SpineBatch<B>>,
+        spill: &Option<Spill<B>>,
+    ) -> MergeState<B> {
         let variant = match (batch1, batch2) {
             (Some(batch1), Some(batch2)) => {
                 // Leonid: we do not require batch bounds to grow monotonically.
                 //assert!(batch1.upper() == batch2.lower());
 
-                let begin_merge = <B as Batch>::begin_merge(&batch1, &batch2);
+                let begin_merge = SpineMerger::new(&batch1, &batch2, spill);
                 MergeVariant::InProgress(batch1, batch2, begin_merge)
             }
             (batch @ Some(_), None) | (None, batch @ Some(_)) => MergeVariant::Complete(batch),
@@ -1158,46 +1320,63 @@ where
     B: Batch,
 {
     /// Describes an actual in-progress merge between two non-trivial batches.
-    InProgress(B, B, <B as Batch>::Merger),
+    InProgress(SpineBatch<B>, SpineBatch<B>, SpineMerger<B>),
     /// A merge that requires no further work. May or may not represent a
     /// non-trivial batch.
-    Complete(Option<B>),
+    Complete(Option<SpineBatch<B>>),
 }
 
 impl<B> MergeVariant<B>
 where
     B: Batch,
 {
-    /// Completes and extracts the batch, unless structurally empty.
-    ///
-    /// The result is either `None`, for structurally empty batches,
-    /// or a batch and optionally input batches from which it derived.
-    fn complete(mut self, lower_val_bound: &Option<B::Val>) -> Option<B> {
+    /// Completes the merge.
+    fn complete(
+        &mut self,
+        lower_val_bound: &Option<B::Val>,
+        spill: &Option<Spill<B>>,
+    ) -> io::Result<()> {
         let mut fuel = isize::max_value();
-        self.work(lower_val_bound, &mut fuel);
-        if let MergeVariant::Complete(batch) = self {
-            batch
-        } else {
-            panic!("Failed to complete a merge!");
-        }
+        self.work(lower_val_bound, spill, &mut fuel)
     }
 
     /// Applies some amount of work, potentially completing the merge.
     ///
     /// In case the work completes, the source batches are returned.
     /// This allows the caller to manage the released resources.
-    fn work(&mut self, lower_val_bound: &Option<B::Val>, fuel: &mut isize) {
+    ///
+    /// If the merge fails, it is started over, so that the next call redoes
+    /// the work from the unmodified source batches.
+    fn work(
+        &mut self,
+        lower_val_bound: &Option<B::Val>,
+        spill: &Option<Spill<B>>,
+        fuel: &mut isize,
+    ) -> io::Result<()> {
         let variant = replace(self, MergeVariant::Complete(None));
         if let MergeVariant::InProgress(b1, b2, mut merge) = variant {
-            merge.work(&b1, &b2, lower_val_bound, fuel);
-            if *fuel > 0 {
-                *self = MergeVariant::Complete(Some(merge.done()));
-            } else {
-                *self = MergeVariant::InProgress(b1, b2, merge);
+            let result = match merge.work(&b1, &b2, lower_val_bound, fuel) {
+                Ok(()) if *fuel > 0 => merge.done(spill),
+                Ok(()) => {
+                    *self = MergeVariant::InProgress(b1, b2, merge);
+                    return Ok(());
+                }
+                Err(error) => Err(error),
+            };
+
+            match result {
+                Ok(batch) => *self = MergeVariant::Complete(Some(batch)),
+                Err(error) => {
+                    let merge = SpineMerger::new(&b1, &b2, spill);
+                    *self = MergeVariant::InProgress(b1, b2, merge);
+                    return Err(error);
+                }
             }
         } else {
             *self = variant;
         }
+
+        Ok(())
     }
 }
 
@@ -1221,6 +1400,7 @@ where
 
 #[cfg(test)]
 mod test {
+    use super::{MergeState, MergeVariant, SpillConfig};
     use crate::{
         trace::{
             cursor::CursorPair,
@@ -1232,6 +1412,92 @@ mod test {
     };
     use proptest::{collection::vec, prelude::*};
     use size_of::SizeOf;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn spilled_batches<B>(trace: &Spine<B>) -> usize
+    where
+        B: Batch,
+    {
+        trace
+            .merging
+            .iter()
+            .map(|merge_state| match merge_state {
+                MergeState::Double(MergeVariant::InProgress(batch1, batch2, _)) => {
+                    batch1.is_spilled() as usize + batch2.is_spilled() as usize
+                }
+                MergeState::Double(MergeVariant::Complete(Some(batch)))
+                | MergeState::Single(Some(batch)) => batch.is_spilled() as usize,
+                _ => 0,
+            })
+            .sum()
+    }
+
+    #[test]
+    fn test_spill() {
+        let tempdir = TempDir::new().unwrap();
+        let directory = tempdir.path();
+
+        let mut trace: Spine<OrdKeyBatch<i32, u32, i32>> =
+            Spine::new(None).with_spill(SpillConfig::new(&directory).with_threshold(16));
+        let mut ref_trace: TestBatch<i32, (), u32, i32> = TestBatch::new(None);
+
+        for time in 0..100u32 {
+            let tuples = (0..10)
+                .map(|i| ((time as i32 * 7 + i) % 50, 1 - 2 * (i % 2)))
+                .collect::<Vec<_>>();
+            trace.insert(OrdKeyBatch::from_tuples(time, tuples.clone()));
+            ref_trace.insert(TestBatch::from_keys(time, tuples));
+        }
+
+        assert!(spilled_batches(&trace) > 0);
+        assert!(fs::read_dir(directory).unwrap().count() > 0);
+        assert_batch_eq(&trace, &ref_trace);
+
+        trace.truncate_keys_below(&10);
+        ref_trace.truncate_keys_below(&10);
+        assert_batch_eq(&trace, &ref_trace);
+
+        trace.recede_to(&50);
+        Trace::recede_to(&mut ref_trace, &50);
+        assert_batch_eq(&trace, &ref_trace);
+
+        let batch = trace.consolidate().unwrap();
+        assert_batch_eq(&batch, &ref_trace);
+
+        // Spilled batches are deleted with the trace.
+        assert_eq!(fs::read_dir(directory).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_spill_error() {
+        // The spill directory can't be created under a regular file.
+        let tempdir = TempDir::new().unwrap();
+        let file = tempdir.path().join("file");
+        fs::write(&file, b"").unwrap();
+
+        let mut trace: Spine<OrdKeyBatch<i32, u32, i32>> =
+            Spine::new(None).with_spill(SpillConfig::new(file.join("spill")).with_threshold(16));
+        let mut ref_trace: TestBatch<i32, (), u32, i32> = TestBatch::new(None);
+
+        let mut error = None;
+        for time in 0..100u32 {
+            let tuples = (0..10)
+                .map(|i| ((time as i32 * 7 + i) % 50, 1))
+                .collect::<Vec<_>>();
+            if let Err(e) = trace.try_insert(OrdKeyBatch::from_tuples(time, tuples.clone())) {
+                error = Some(e);
+                break;
+            }
+            ref_trace.insert(TestBatch::from_keys(time, tuples));
+        }
+
+        // The failed merge is started over, so the trace still contains all
+        // updates inserted before the error.
+        assert!(error.is_some());
+        assert_eq!(spilled_batches(&trace), 0);
+        assert_batch_eq(&trace, &ref_trace);
+    }
 
     fn kr_batches(
         max_key: i32,
@@ -1365,6 +1631,34 @@ mod test {
             }
         }
 
+        #[test]
+        fn test_indexed_zset_spill(batches in kvr_batches(100, 5, 2, 500, 20), seed in 0..u64::max_value()) {
+            let tempdir = TempDir::new().unwrap();
+            let spill = SpillConfig::new(tempdir.path()).with_threshold(100);
+            let mut trace: Spine<OrdIndexedZSet<i32, i32, i32>> = Spine::new(None).with_spill(spill);
+            let mut ref_trace: TestBatch<i32, i32, (), i32> = TestBatch::new(None);
+
+            for (tuples, key_bound, val_bound) in batches.into_iter() {
+                let batch = OrdIndexedZSet::from_tuples((), tuples.clone());
+                let ref_batch = TestBatch::from_tuples((), tuples);
+
+                ref_trace.insert(ref_batch);
+                trace.insert(batch);
+
+                assert_trace_eq(&trace, &ref_trace);
+                assert_batch_cursors_eq(trace.cursor(), &ref_trace, seed);
+
+                trace.truncate_keys_below(&key_bound);
+                ref_trace.truncate_keys_below(&key_bound);
+
+                trace.truncate_values_below(&val_bound);
+                ref_trace.truncate_values_below(&val_bound);
+
+                assert_trace_eq(&trace, &ref_trace);
+                assert_batch_cursors_eq(trace.cursor(), &ref_trace, seed);
+            }
+        }
+
         #[test]
         fn test_zset_trace_spine(batches in kr_batches(100, 2, 500, 20), seed in 0..u64::max_value()) {
             let mut trace: Spine<OrdKeyBatch<i32, u32, i32>> = Spine::new(None);
@@ -1418,5 +1712,33 @@ mod test {
                 assert_batch_cursors_eq(trace.cursor(), &ref_trace, seed);
             }
         }
+
+        #[test]
+        fn test_indexed_zset_trace_spill(batches in kvr_batches(100, 5, 2, 300, 20), seed in 0..u64::max_value()) {
+            let tempdir = TempDir::new().unwrap();
+            let spill = SpillConfig::new(tempdir.path()).with_threshold(100);
+            let mut trace: Spine<OrdValBatch<i32, i32, u32, i32>> = Spine::new(None).with_spill(spill);
+            let mut ref_trace: TestBatch<i32, i32, u32, i32> = TestBatch::new(None);
+
+            for (time, (tuples, key_bound, val_bound)) in batches.into_iter().enumerate() {
+                let batch = OrdValBatch::from_tuples(time as u32, tuples.clone());
+                let ref_batch = TestBatch::from_tuples(time as u32, tuples);
+
+                ref_trace.insert(ref_batch);
+                trace.insert(batch);
+
+                assert_trace_eq(&trace, &ref_trace);
+                assert_batch_cursors_eq(trace.cursor(), &ref_trace, seed);
+
+                trace.truncate_keys_below(&key_bound);
+                ref_trace.truncate_keys_below(&key_bound);
+
+                trace.truncate_values_below(&val_bound);
+                ref_trace.truncate_values_below(&val_bound);
+
+                assert_trace_eq(&trace, &ref_trace);
+                assert_batch_cursors_eq(trace.cursor(), &ref_trace, seed);
+            }
+        }
     }
 }
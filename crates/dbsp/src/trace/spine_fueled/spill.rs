@@ -0,0 +1,1586 @@
+//! Spilling large batches of a [`Spine`](`super::Spine`) to disk.
+//!
+//! A spine configured with a [`SpillConfig`] writes every merged batch with
+//! at least [`SpillConfig::threshold`] updates to an immutable file in
+//! [`SpillConfig::directory`] and keeps only smaller, more recent batches in
+//! memory.  This turns the spine into an LSM tree: small batches are merged in
+//! memory using the batch's own [`Merger`], while merges that involve a
+//! spilled batch stream both inputs from their cursors into a new file.
+//!
+//! A spilled batch is a sequence of blocks, one per key, that contain the
+//! bincode-encoded key followed by its values with their `(time, diff)`
+//! pairs.  Only the offsets of the blocks are kept in memory.  Cursors read
+//! blocks on demand, so the page cache of the OS, rather than the heap, holds
+//! the recently accessed parts of the batch.  Note that all values of a key
+//! are loaded at once when the cursor moves to the key.  Spilling therefore
+//! requires the key, value, time, and weight types of the batch to implement
+//! bincode's `Encode` and `Decode` (see [`SpillCodec`]).
+//!
+//! Failures to read or write a spilled batch are returned as I/O errors,
+//! except by [`FileBatchCursor`], which panics because [`Cursor`] methods
+//! cannot fail.
+
+use crate::{
+    algebra::{Lattice, MonoidValue, PartialOrder},
+    time::{Antichain, AntichainRef, Timestamp},
+    trace::{consolidation::consolidate, Batch, BatchReader, Builder, Cursor, Merger},
+};
+use bincode::{config::standard, decode_from_slice, encode_to_vec, Decode, Encode};
+use size_of::SizeOf;
+use std::{
+    cmp::min,
+    collections::HashMap,
+    error::Error as StdError,
+    fmt::{self, Debug, Display},
+    fs::{self, File},
+    io::{self, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write},
+    mem::take,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// Default value of [`SpillConfig::threshold`].
+pub const DEFAULT_SPILL_THRESHOLD: usize = 1 << 20;
+
+/// Sequence number used to generate unique file names within the process.
+static NEXT_FILE_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Values of a single key, with the `(time, diff)` pairs of each value.
+type Values<V, T, R> = Vec<(V, Vec<(T, R)>)>;
+
+/// Configuration of a [`Spine`](`super::Spine`) that spills large batches to
+/// disk.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpillConfig {
+    /// Directory that contains spilled batches.  It is created if it does not
+    /// exist.
+    pub directory: PathBuf,
+
+    /// Minimal number of updates in a merged batch for it to be spilled.
+    pub threshold: usize,
+}
+
+impl SpillConfig {
+    /// Spill batches to `directory` using the default threshold.
+    pub fn new<P>(directory: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self {
+            directory: directory.into(),
+            threshold: DEFAULT_SPILL_THRESHOLD,
+        }
+    }
+
+    /// Set the minimal number of updates in a spilled batch.
+    pub fn with_threshold(mut self, threshold: usize) -> Self {
+        self.threshold = threshold;
+        self
+    }
+}
+
+/// The spill configuration of a [`Spine`](`super::Spine`), with the codec
+/// for its batches.
+pub(super) struct Spill<B>
+where
+    B: Batch,
+{
+    pub(super) config: SpillConfig,
+    pub(super) codec: SpillCodec<B>,
+}
+
+/// Encodes and decodes the keys and values of spilled batches.
+///
+/// [`DBData`](`crate::DBData`) only requires bincode's `Encode` and `Decode`
+/// with the `with-bincode` feature.  The codec captures them for the key,
+/// value, time, and weight types of a batch where they are known, in
+/// [`Spine::with_spill`](`super::Spine::with_spill`), so that the rest of the
+/// spine does not need these bounds.
+pub(super) struct SpillCodec<B>
+where
+    B: Batch,
+{
+    encode_key: fn(&B::Key) -> io::Result<Vec<u8>>,
+    decode_key: fn(&[u8]) -> io::Result<B::Key>,
+    encode_values: fn(&Values<B::Val, B::Time, B::R>) -> io::Result<Vec<u8>>,
+    decode_values: fn(&[u8]) -> io::Result<Values<B::Val, B::Time, B::R>>,
+}
+
+impl<B> SpillCodec<B>
+where
+    B: Batch,
+{
+    pub(super) fn new() -> Self
+    where
+        B::Key: Encode + Decode,
+        B::Val: Encode + Decode,
+        B::Time: Encode + Decode,
+        B::R: Encode + Decode,
+    {
+        Self {
+            encode_key: encode::<B::Key>,
+            decode_key: decode::<B::Key>,
+            encode_values: encode::<Values<B::Val, B::Time, B::R>>,
+            decode_values: decode::<Values<B::Val, B::Time, B::R>>,
+        }
+    }
+}
+
+impl<B> Clone for SpillCodec<B>
+where
+    B: Batch,
+{
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<B> Copy for SpillCodec<B> where B: Batch {}
+
+fn encode<T>(value: &T) -> io::Result<Vec<u8>>
+where
+    T: Encode,
+{
+    encode_to_vec(value, standard()).map_err(invalid_data)
+}
+
+fn decode<T>(bytes: &[u8]) -> io::Result<T>
+where
+    T: Decode,
+{
+    let (value, _) = decode_from_slice(bytes, standard()).map_err(invalid_data)?;
+    Ok(value)
+}
+
+/// A batch in a [`Spine`](`super::Spine`), stored either in memory or in a
+/// file.
+#[derive(SizeOf)]
+pub enum SpineBatch<B>
+where
+    B: Batch,
+{
+    Memory(B),
+    File(FileBatch<B>),
+}
+
+impl<B> SpineBatch<B>
+where
+    B: Batch,
+{
+    /// Wraps `batch`, the result of a merge, spilling it to disk if it is
+    /// large enough according to `spill`.
+    pub(super) fn from_merge(batch: B, spill: &Option<Spill<B>>) -> io::Result<Self> {
+        match spill {
+            Some(spill) if batch.len() >= spill.config.threshold => Ok(Self::File(
+                FileBatch::from_batch(&batch, &spill.config.directory, spill.codec)?,
+            )),
+            _ => Ok(Self::Memory(batch)),
+        }
+    }
+
+    /// Returns `true` if the batch is stored in a file.
+    pub fn is_spilled(&self) -> bool {
+        matches!(self, Self::File(_))
+    }
+
+    /// Returns the batch if it is stored in memory.
+    pub fn as_memory(&self) -> Option<&B> {
+        match self {
+            Self::Memory(batch) => Some(batch),
+            Self::File(_) => None,
+        }
+    }
+
+    /// The number of keys in the batch.
+    pub fn key_count(&self) -> usize {
+        match self {
+            Self::Memory(batch) => batch.key_count(),
+            Self::File(batch) => batch.key_count(),
+        }
+    }
+
+    /// The number of updates in the batch.
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Memory(batch) => batch.len(),
+            Self::File(batch) => batch.len(),
+        }
+    }
+
+    /// True if the batch is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// All times in the batch are greater or equal to an element of `lower`.
+    pub fn lower(&self) -> AntichainRef<'_, B::Time> {
+        match self {
+            Self::Memory(batch) => batch.lower(),
+            Self::File(batch) => batch.lower.as_ref(),
+        }
+    }
+
+    /// All times in the batch are not greater or equal to any element of
+    /// `upper`.
+    pub fn upper(&self) -> AntichainRef<'_, B::Time> {
+        match self {
+            Self::Memory(batch) => batch.upper(),
+            Self::File(batch) => batch.upper.as_ref(),
+        }
+    }
+
+    /// Acquires a cursor to the batch's contents.
+    pub fn cursor(&self) -> SpineBatchCursor<'_, B> {
+        match self {
+            Self::Memory(batch) => SpineBatchCursor::Memory(batch.cursor()),
+            Self::File(batch) => SpineBatchCursor::File(FileBatchCursor::new(batch)),
+        }
+    }
+
+    pub(super) fn truncate_keys_below(&mut self, lower_bound: &B::Key) -> io::Result<()> {
+        match self {
+            Self::Memory(batch) => {
+                batch.truncate_keys_below(lower_bound);
+                Ok(())
+            }
+            Self::File(batch) => batch.truncate_keys_below(lower_bound),
+        }
+    }
+
+    pub(super) fn recede_to(&mut self, frontier: &B::Time) -> io::Result<()> {
+        match self {
+            Self::Memory(batch) => {
+                batch.recede_to(frontier);
+                Ok(())
+            }
+            Self::File(batch) => batch.recede_to(frontier),
+        }
+    }
+
+    /// Converts the batch into an in-memory batch, reading it back from disk
+    /// if necessary.
+    pub(super) fn into_batch(self) -> io::Result<B> {
+        match self {
+            Self::Memory(batch) => Ok(batch),
+            Self::File(batch) => batch.to_batch(),
+        }
+    }
+}
+
+impl<B> Debug for SpineBatch<B>
+where
+    B: Batch + Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Memory(batch) => f.debug_tuple("Memory").field(batch).finish(),
+            Self::File(batch) => f.debug_tuple("File").field(batch).finish(),
+        }
+    }
+}
+
+impl<B> Display for SpineBatch<B>
+where
+    B: Batch + Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Memory(batch) => Display::fmt(batch, f),
+            Self::File(batch) => writeln!(
+                f,
+                "spilled to {}: {} keys, {} updates",
+                batch.path.display(),
+                batch.key_count(),
+                batch.len()
+            ),
+        }
+    }
+}
+
+/// Offsets of the block of a single key in a [`FileBatch`].
+#[derive(Clone, Copy, Debug, SizeOf)]
+struct KeyOffsets {
+    /// Offset of the encoded key.
+    key: u64,
+    /// Offset of the encoded values of the key.
+    vals: u64,
+}
+
+/// A batch stored in an immutable file.
+///
+/// The file is deleted when the batch is dropped.
+#[derive(SizeOf)]
+pub struct FileBatch<B>
+where
+    B: Batch,
+{
+    #[size_of(skip)]
+    path: PathBuf,
+    #[size_of(skip)]
+    codec: SpillCodec<B>,
+    /// Offsets of the blocks of all keys in the file, in key order.
+    offsets: Vec<KeyOffsets>,
+    /// Size of the file.
+    end: u64,
+    /// Index of the first key that has not been truncated by
+    /// [`Self::truncate_keys_below`].
+    first_key: usize,
+    len: usize,
+    lower: Antichain<B::Time>,
+    upper: Antichain<B::Time>,
+}
+
+impl<B> FileBatch<B>
+where
+    B: Batch,
+{
+    /// Writes the contents of `batch` to a new file in `directory`.
+    fn from_batch(batch: &B, directory: &Path, codec: SpillCodec<B>) -> io::Result<Self> {
+        let mut writer = FileBatchWriter::new(directory, codec)?;
+
+        let mut cursor = batch.cursor();
+        while cursor.key_valid() {
+            let vals: Values<B::Val, B::Time, B::R> = read_values(&mut cursor);
+            writer.push(cursor.key(), &vals)?;
+            cursor.step_key();
+        }
+
+        writer.done(batch.lower().to_owned(), batch.upper().to_owned())
+    }
+
+    /// The number of keys in the batch.
+    pub fn key_count(&self) -> usize {
+        self.offsets.len() - self.first_key
+    }
+
+    /// The number of updates in the batch.
+    ///
+    /// Updates of keys removed by [`Self::truncate_keys_below`] are still
+    /// counted, unless all keys have been removed.
+    pub fn len(&self) -> usize {
+        if self.key_count() == 0 {
+            0
+        } else {
+            self.len
+        }
+    }
+
+    /// True if the batch is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Path to the file that stores the batch.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn truncate_keys_below(&mut self, lower_bound: &B::Key) -> io::Result<()> {
+        let mut reader = FileReader::open(&self.path)?;
+        self.first_key = self.advance(&mut reader, self.first_key, |key| key >= lower_bound)?;
+        Ok(())
+    }
+
+    fn recede_to(&mut self, frontier: &B::Time) -> io::Result<()> {
+        // Nothing to do if the batch is entirely before the frontier.
+        if self.upper.as_ref().less_equal(frontier) {
+            return Ok(());
+        }
+
+        let directory = self.path.parent().unwrap_or_else(|| Path::new("."));
+        let mut writer = FileBatchWriter::new(directory, self.codec)?;
+
+        let mut reader = FileReader::open(&self.path)?;
+        for index in self.first_key..self.offsets.len() {
+            let mut vals = self.read_values(&mut reader, index)?;
+            for (_, times) in vals.iter_mut() {
+                for (time, _) in times.iter_mut() {
+                    *time = time.meet(frontier);
+                }
+                consolidate(times);
+            }
+            vals.retain(|(_, times)| !times.is_empty());
+
+            if !vals.is_empty() {
+                writer.push(&self.read_key(&mut reader, index)?, &vals)?;
+            }
+        }
+
+        // Replacing `self` deletes the old file.
+        *self = writer.done(self.lower.clone(), self.upper.clone())?;
+        Ok(())
+    }
+
+    /// Reads the batch back into memory.
+    ///
+    /// Batch builders assign the same timestamp to all updates, so we build
+    /// one batch per timestamp and merge them.
+    fn to_batch(&self) -> io::Result<B> {
+        let mut builders: HashMap<B::Time, B::Builder> = HashMap::new();
+
+        let mut reader = FileReader::open(&self.path)?;
+        for index in self.first_key..self.offsets.len() {
+            let key = self.read_key(&mut reader, index)?;
+            for (val, times) in self.read_values(&mut reader, index)? {
+                for (time, diff) in times {
+                    builders
+                        .entry(time.clone())
+                        .or_insert_with(|| B::Builder::new_builder(time))
+                        .push((B::item_from(key.clone(), val.clone()), diff));
+                }
+            }
+        }
+
+        Ok(builders
+            .into_values()
+            .map(|builder| builder.done())
+            .reduce(|acc, batch| acc.merge(&batch))
+            .unwrap_or_else(|| B::empty(B::Time::minimum())))
+    }
+
+    fn read_key(&self, reader: &mut FileReader, index: usize) -> io::Result<B::Key> {
+        let offsets = &self.offsets[index];
+        (self.codec.decode_key)(reader.read(offsets.key, offsets.vals)?)
+    }
+
+    /// Reads the key with index `index`, if there is one.
+    fn read_key_if_valid(
+        &self,
+        reader: &mut FileReader,
+        index: usize,
+    ) -> io::Result<Option<B::Key>> {
+        if index < self.offsets.len() {
+            self.read_key(reader, index).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn read_values(
+        &self,
+        reader: &mut FileReader,
+        index: usize,
+    ) -> io::Result<Values<B::Val, B::Time, B::R>> {
+        let end = self
+            .offsets
+            .get(index + 1)
+            .map_or(self.end, |offsets| offsets.key);
+        (self.codec.decode_values)(reader.read(self.offsets[index].vals, end)?)
+    }
+
+    /// Returns the index of the first key in `from..` that satisfies
+    /// `predicate`, or the number of keys in the file if there is no such
+    /// key.
+    ///
+    /// Assumes that `predicate` remains true once it turns true.  Uses
+    /// exponential search, so that seeking to a nearby key only reads a few
+    /// keys from disk.
+    fn advance<P>(&self, reader: &mut FileReader, from: usize, predicate: P) -> io::Result<usize>
+    where
+        P: Fn(&B::Key) -> bool,
+    {
+        let mut lower = from;
+        let mut upper = self.offsets.len();
+        let mut step = 1;
+
+        while lower < upper {
+            let probe = min(lower + step, upper) - 1;
+            if predicate(&self.read_key(reader, probe)?) {
+                upper = probe;
+                break;
+            }
+            lower = probe + 1;
+            step *= 2;
+        }
+
+        self.partition_point(reader, lower, upper, predicate)
+    }
+
+    /// Returns the index of the first key in `lower..upper` that satisfies
+    /// `predicate`, or `upper` if there is no such key.
+    ///
+    /// Assumes that `predicate` remains true once it turns true.
+    fn partition_point<P>(
+        &self,
+        reader: &mut FileReader,
+        mut lower: usize,
+        mut upper: usize,
+        predicate: P,
+    ) -> io::Result<usize>
+    where
+        P: Fn(&B::Key) -> bool,
+    {
+        while lower < upper {
+            let middle = lower + (upper - lower) / 2;
+            if predicate(&self.read_key(reader, middle)?) {
+                upper = middle;
+            } else {
+                lower = middle + 1;
+            }
+        }
+
+        Ok(lower)
+    }
+}
+
+impl<B> Debug for FileBatch<B>
+where
+    B: Batch,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FileBatch")
+            .field("path", &self.path)
+            .field("keys", &self.key_count())
+            .field("len", &self.len())
+            .field("lower", &self.lower)
+            .field("upper", &self.upper)
+            .finish()
+    }
+}
+
+impl<B> Drop for FileBatch<B>
+where
+    B: Batch,
+{
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Reads blocks of a [`FileBatch`].
+struct FileReader {
+    file: File,
+    buffer: Vec<u8>,
+}
+
+impl FileReader {
+    fn open(path: &Path) -> io::Result<Self> {
+        let file = File::open(path).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("can't open spilled batch '{}': {e}", path.display()),
+            )
+        })?;
+
+        Ok(Self {
+            file,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// Reads the bytes between offsets `start` and `end`.
+    fn read(&mut self, start: u64, end: u64) -> io::Result<&[u8]> {
+        self.buffer.resize((end - start) as usize, 0);
+        self.file.seek(SeekFrom::Start(start))?;
+        self.file.read_exact(&mut self.buffer)?;
+        Ok(&self.buffer)
+    }
+}
+
+/// Converts a bincode error into an I/O error.
+fn invalid_data<E>(error: E) -> io::Error
+where
+    E: StdError + Send + Sync + 'static,
+{
+    io::Error::new(ErrorKind::InvalidData, error)
+}
+
+/// Writes a [`FileBatch`] one key at a time, in key order.
+///
+/// The file is deleted if the writer is dropped before [`Self::done`] is
+/// called, e.g., when a merge is abandoned.
+struct FileBatchWriter<B>
+where
+    B: Batch,
+{
+    path: PathBuf,
+    codec: SpillCodec<B>,
+    file: BufWriter<File>,
+    offsets: Vec<KeyOffsets>,
+    position: u64,
+    len: usize,
+}
+
+impl<B> FileBatchWriter<B>
+where
+    B: Batch,
+{
+    /// Creates a new file with a unique name in `directory`.
+    fn new(directory: &Path, codec: SpillCodec<B>) -> io::Result<Self> {
+        fs::create_dir_all(directory).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!(
+                    "can't create spill directory '{}': {e}",
+                    directory.display()
+                ),
+            )
+        })?;
+
+        let id = NEXT_FILE_ID.fetch_add(1, Ordering::Relaxed);
+        let path = directory.join(format!("{}-{id}.batch", process::id()));
+        let file = File::create(&path).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("can't create spilled batch '{}': {e}", path.display()),
+            )
+        })?;
+
+        Ok(Self {
+            path,
+            codec,
+            file: BufWriter::new(file),
+            offsets: Vec::new(),
+            position: 0,
+            len: 0,
+        })
+    }
+
+    /// Appends `key` with its values.  Keys must be pushed in increasing
+    /// order.
+    fn push(&mut self, key: &B::Key, vals: &Values<B::Val, B::Time, B::R>) -> io::Result<()> {
+        let key_offset = self.position;
+        self.write(&(self.codec.encode_key)(key)?)?;
+        let vals_offset = self.position;
+        self.write(&(self.codec.encode_values)(vals)?)?;
+
+        self.offsets.push(KeyOffsets {
+            key: key_offset,
+            vals: vals_offset,
+        });
+        self.len += vals.iter().map(|(_, times)| times.len()).sum::<usize>();
+        Ok(())
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.file.write_all(bytes)?;
+        self.position += bytes.len() as u64;
+        Ok(())
+    }
+
+    fn done(
+        mut self,
+        lower: Antichain<B::Time>,
+        upper: Antichain<B::Time>,
+    ) -> io::Result<FileBatch<B>> {
+        self.file.flush()?;
+
+        Ok(FileBatch {
+            path: take(&mut self.path),
+            codec: self.codec,
+            offsets: take(&mut self.offsets),
+            end: self.position,
+            first_key: 0,
+            len: self.len,
+            lower,
+            upper,
+        })
+    }
+}
+
+impl<B> Drop for FileBatchWriter<B>
+where
+    B: Batch,
+{
+    fn drop(&mut self) {
+        if !self.path.as_os_str().is_empty() {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Collects the values of the current key of `cursor`, starting from the
+/// current value.
+fn read_values<K, V, T, R, C>(cursor: &mut C) -> Values<V, T, R>
+where
+    V: Clone,
+    T: Clone,
+    R: Clone,
+    C: Cursor<K, V, T, R>,
+{
+    let mut vals = Vec::new();
+    while cursor.val_valid() {
+        let mut times = Vec::new();
+        cursor.map_times(|time, diff| times.push((time.clone(), diff.clone())));
+        vals.push((cursor.val().clone(), times));
+        cursor.step_val();
+    }
+    vals
+}
+
+/// Merges the values of the same key from two batches, consolidating the
+/// updates of values that occur in both and dropping values below
+/// `lower_val_bound`.
+fn merge_values<V, T, R>(
+    vals1: Values<V, T, R>,
+    vals2: Values<V, T, R>,
+    lower_val_bound: &Option<V>,
+) -> Values<V, T, R>
+where
+    V: Ord,
+    T: Ord,
+    R: MonoidValue,
+{
+    let mut result = Vec::with_capacity(vals1.len() + vals2.len());
+    let mut vals1 = vals1.into_iter().peekable();
+    let mut vals2 = vals2.into_iter().peekable();
+
+    loop {
+        let (val, mut times) = match (vals1.peek(), vals2.peek()) {
+            (Some((val1, _)), Some((val2, _))) if val1 < val2 => vals1.next().unwrap(),
+            (Some((val1, _)), Some((val2, _))) if val1 > val2 => vals2.next().unwrap(),
+            (Some(_), Some(_)) => {
+                let (val, mut times) = vals1.next().unwrap();
+                times.extend(vals2.next().unwrap().1);
+                (val, times)
+            }
+            (Some(_), None) => vals1.next().unwrap(),
+            (None, Some(_)) => vals2.next().unwrap(),
+            (None, None) => break,
+        };
+
+        if matches!(lower_val_bound, Some(bound) if &val < bound) {
+            continue;
+        }
+
+        consolidate(&mut times);
+        if !times.is_empty() {
+            result.push((val, times));
+        }
+    }
+
+    result
+}
+
+/// Progressively merges two batches, at least one of which is spilled, into
+/// a new file.
+///
+/// The merge proceeds one key at a time.  Between calls to [`Self::work`] the
+/// merger only remembers the last key it has written, so that it does not
+/// hold cursors into the batches it merges.  The file is created by the first
+/// call to [`Self::work`], so that creating a merger cannot fail.
+#[derive(SizeOf)]
+pub struct FileMerger<B>
+where
+    B: Batch,
+{
+    #[size_of(skip)]
+    directory: PathBuf,
+    #[size_of(skip)]
+    codec: SpillCodec<B>,
+    #[size_of(skip)]
+    writer: Option<FileBatchWriter<B>>,
+    /// The last key merged so far.
+    last_key: Option<B::Key>,
+    lower: Antichain<B::Time>,
+    upper: Antichain<B::Time>,
+}
+
+impl<B> FileMerger<B>
+where
+    B: Batch,
+{
+    fn new(batch1: &SpineBatch<B>, batch2: &SpineBatch<B>, spill: &Spill<B>) -> Self {
+        Self {
+            directory: spill.config.directory.clone(),
+            codec: spill.codec,
+            writer: None,
+            last_key: None,
+            lower: batch1.lower().meet(batch2.lower()),
+            upper: batch1.upper().join(batch2.upper()),
+        }
+    }
+
+    fn work(
+        &mut self,
+        source1: &SpineBatch<B>,
+        source2: &SpineBatch<B>,
+        lower_val_bound: &Option<B::Val>,
+        fuel: &mut isize,
+    ) -> io::Result<()> {
+        if self.writer.is_none() {
+            self.writer = Some(FileBatchWriter::new(&self.directory, self.codec)?);
+        }
+        let writer = self.writer.as_mut().unwrap();
+
+        let mut source1 = MergeSource::new(source1, self.last_key.as_ref())?;
+        let mut source2 = MergeSource::new(source2, self.last_key.as_ref())?;
+
+        while *fuel > 0 {
+            let key = match (source1.key(), source2.key()) {
+                (Some(key1), Some(key2)) => min(key1, key2).clone(),
+                (Some(key), None) | (None, Some(key)) => key.clone(),
+                (None, None) => break,
+            };
+
+            let vals1 = source1.take_values(&key)?;
+            let vals2 = source2.take_values(&key)?;
+            let updates = vals1
+                .iter()
+                .chain(vals2.iter())
+                .map(|(_, times)| times.len())
+                .sum::<usize>();
+            *fuel -= updates.max(1) as isize;
+
+            let vals = merge_values(vals1, vals2, lower_val_bound);
+            if !vals.is_empty() {
+                writer.push(&key, &vals)?;
+            }
+            self.last_key = Some(key);
+        }
+
+        Ok(())
+    }
+
+    fn done(self) -> io::Result<FileBatch<B>> {
+        let writer = match self.writer {
+            Some(writer) => writer,
+            None => FileBatchWriter::new(&self.directory, self.codec)?,
+        };
+        writer.done(self.lower, self.upper)
+    }
+}
+
+impl<B> Debug for FileMerger<B>
+where
+    B: Batch,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FileMerger")
+            .field("path", &self.writer.as_ref().map(|writer| &writer.path))
+            .field("last_key", &self.last_key)
+            .finish()
+    }
+}
+
+/// One of the inputs of a [`FileMerger`], read one key at a time.
+///
+/// Unlike a [`FileBatchCursor`], it returns errors reading a spilled batch
+/// instead of panicking.
+enum MergeSource<'s, B>
+where
+    B: Batch + 's,
+{
+    Memory(B::Cursor<'s>),
+    File {
+        batch: &'s FileBatch<B>,
+        reader: FileReader,
+        /// Index of the current key.
+        pos: usize,
+        /// The current key, or `None` if all keys have been read.
+        key: Option<B::Key>,
+    },
+}
+
+impl<'s, B> MergeSource<'s, B>
+where
+    B: Batch,
+{
+    /// Reads `batch` starting from the first key greater than `last_key`.
+    fn new(batch: &'s SpineBatch<B>, last_key: Option<&B::Key>) -> io::Result<Self> {
+        match batch {
+            SpineBatch::Memory(batch) => {
+                let mut cursor = batch.cursor();
+                if let Some(last_key) = last_key {
+                    cursor.seek_key(last_key);
+                    if cursor.get_key() == Some(last_key) {
+                        cursor.step_key();
+                    }
+                }
+                Ok(Self::Memory(cursor))
+            }
+            SpineBatch::File(batch) => {
+                let mut reader = FileReader::open(&batch.path)?;
+                let pos = match last_key {
+                    Some(last_key) => {
+                        batch.advance(&mut reader, batch.first_key, |key| key > last_key)?
+                    }
+                    None => batch.first_key,
+                };
+                let key = batch.read_key_if_valid(&mut reader, pos)?;
+
+                Ok(Self::File {
+                    batch,
+                    reader,
+                    pos,
+                    key,
+                })
+            }
+        }
+    }
+
+    fn key(&self) -> Option<&B::Key> {
+        match self {
+            Self::Memory(cursor) => cursor.get_key(),
+            Self::File { key, .. } => key.as_ref(),
+        }
+    }
+
+    /// Returns the values of `key` if the source is at `key` and moves to the
+    /// next key.
+    fn take_values(&mut self, key: &B::Key) -> io::Result<Values<B::Val, B::Time, B::R>> {
+        if self.key() != Some(key) {
+            return Ok(Vec::new());
+        }
+
+        match self {
+            Self::Memory(cursor) => {
+                let vals = read_values(cursor);
+                cursor.step_key();
+                Ok(vals)
+            }
+            Self::File {
+                batch,
+                reader,
+                pos,
+                key: current,
+            } => {
+                let vals = batch.read_values(reader, *pos)?;
+                *pos += 1;
+                *current = batch.read_key_if_valid(reader, *pos)?;
+                Ok(vals)
+            }
+        }
+    }
+}
+
+/// An in-progress merge in a [`Spine`](`super::Spine`).
+///
+/// Batches stored in memory are merged with their own [`Merger`]; merges that
+/// involve a spilled batch use a [`FileMerger`].
+#[derive(SizeOf)]
+pub enum SpineMerger<B>
+where
+    B: Batch,
+{
+    Memory(B::Merger),
+    File(FileMerger<B>),
+}
+
+impl<B> SpineMerger<B>
+where
+    B: Batch,
+{
+    pub(super) fn new(
+        batch1: &SpineBatch<B>,
+        batch2: &SpineBatch<B>,
+        spill: &Option<Spill<B>>,
+    ) -> Self {
+        match (batch1, batch2) {
+            (SpineBatch::Memory(batch1), SpineBatch::Memory(batch2)) => {
+                Self::Memory(batch1.begin_merge(batch2))
+            }
+            _ => Self::File(FileMerger::new(
+                batch1,
+                batch2,
+                spill
+                    .as_ref()
+                    .expect("spilled batches require a spill configuration"),
+            )),
+        }
+    }
+
+    pub(super) fn work(
+        &mut self,
+        source1: &SpineBatch<B>,
+        source2: &SpineBatch<B>,
+        lower_val_bound: &Option<B::Val>,
+        fuel: &mut isize,
+    ) -> io::Result<()> {
+        match (self, source1, source2) {
+            (Self::Memory(merger), SpineBatch::Memory(source1), SpineBatch::Memory(source2)) => {
+                merger.work(source1, source2, lower_val_bound, fuel);
+                Ok(())
+            }
+            (Self::File(merger), source1, source2) => {
+                merger.work(source1, source2, lower_val_bound, fuel)
+            }
+            _ => unreachable!("in-memory merge of a spilled batch"),
+        }
+    }
+
+    pub(super) fn done(self, spill: &Option<Spill<B>>) -> io::Result<SpineBatch<B>> {
+        match self {
+            Self::Memory(merger) => SpineBatch::from_merge(merger.done(), spill),
+            Self::File(merger) => Ok(SpineBatch::File(merger.done()?)),
+        }
+    }
+}
+
+impl<B> Debug for SpineMerger<B>
+where
+    B: Batch,
+    B::Merger: Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Memory(merger) => f.debug_tuple("Memory").field(merger).finish(),
+            Self::File(merger) => f.debug_tuple("File").field(merger).finish(),
+        }
+    }
+}
+
+/// A cursor over a [`FileBatch`].
+///
+/// # Panics
+///
+/// [`Cursor`] methods cannot fail, so the cursor panics if it fails to read
+/// the file.
+pub struct FileBatchCursor<'s, B>
+where
+    B: Batch,
+{
+    batch: &'s FileBatch<B>,
+    reader: FileReader,
+    /// Index of the current key, in `batch.first_key - 1..=batch.offsets.len()`.
+    pos: isize,
+    key: Option<B::Key>,
+    vals: Values<B::Val, B::Time, B::R>,
+    /// Index of the current value in `vals`.
+    val_idx: isize,
+}
+
+impl<'s, B> FileBatchCursor<'s, B>
+where
+    B: Batch,
+{
+    fn new(batch: &'s FileBatch<B>) -> Self {
+        let mut cursor = Self {
+            batch,
+            reader: expect_read(FileReader::open(&batch.path)),
+            pos: 0,
+            key: None,
+            vals: Vec::new(),
+            val_idx: 0,
+        };
+        cursor.move_to(batch.first_key as isize);
+        cursor
+    }
+
+    /// Moves the cursor to the key with index `pos`, loading the key and its
+    /// values if the index is valid.
+    fn move_to(&mut self, pos: isize) {
+        self.val_idx = 0;
+        if pos == self.pos && self.key.is_some() {
+            return;
+        }
+
+        self.pos = pos;
+        if pos >= self.batch.first_key as isize && pos < self.batch.offsets.len() as isize {
+            self.key = Some(expect_read(
+                self.batch.read_key(&mut self.reader, pos as usize),
+            ));
+            self.vals = expect_read(self.batch.read_values(&mut self.reader, pos as usize));
+        } else {
+            self.key = None;
+            self.vals.clear();
+        }
+    }
+
+    /// Moves the cursor forward to the first key that satisfies `predicate`.
+    fn advance_keys<P>(&mut self, predicate: P)
+    where
+        P: Fn(&B::Key) -> bool,
+    {
+        if self.key_valid() {
+            let pos = expect_read(self.batch.advance(
+                &mut self.reader,
+                self.pos as usize,
+                predicate,
+            ));
+            self.move_to(pos as isize);
+        }
+    }
+
+    /// Moves the cursor back to the last key that satisfies `predicate`.
+    fn retreat_keys<P>(&mut self, predicate: P)
+    where
+        P: Fn(&B::Key) -> bool,
+    {
+        if self.key_valid() {
+            let pos = expect_read(self.batch.partition_point(
+                &mut self.reader,
+                self.batch.first_key,
+                self.pos as usize + 1,
+                |key| !predicate(key),
+            ));
+            self.move_to(pos as isize - 1);
+        }
+    }
+
+    fn current_times(&self) -> &[(B::Time, B::R)] {
+        &self.vals[self.val_idx as usize].1
+    }
+}
+
+/// Unwraps the result of reading a spilled batch through a [`FileBatchCursor`].
+fn expect_read<T>(result: io::Result<T>) -> T {
+    result.unwrap_or_else(|e| panic!("failed to read spilled batch: {e}"))
+}
+
+impl<'s, B> Cursor<B::Key, B::Val, B::Time, B::R> for FileBatchCursor<'s, B>
+where
+    B: Batch,
+{
+    fn key_valid(&self) -> bool {
+        self.key.is_some()
+    }
+
+    fn val_valid(&self) -> bool {
+        self.val_idx >= 0 && (self.val_idx as usize) < self.vals.len()
+    }
+
+    fn key(&self) -> &B::Key {
+        self.key.as_ref().unwrap()
+    }
+
+    fn val(&self) -> &B::Val {
+        &self.vals[self.val_idx as usize].0
+    }
+
+    fn fold_times<F, U>(&mut self, init: U, mut fold: F) -> U
+    where
+        F: FnMut(U, &B::Time, &B::R) -> U,
+    {
+        if self.val_valid() {
+            self.current_times()
+                .iter()
+                .fold(init, |init, (time, diff)| fold(init, time, diff))
+        } else {
+            init
+        }
+    }
+
+    fn fold_times_through<F, U>(&mut self, upper: &B::Time, init: U, mut fold: F) -> U
+    where
+        F: FnMut(U, &B::Time, &B::R) -> U,
+    {
+        if self.val_valid() {
+            self.current_times()
+                .iter()
+                .filter(|(time, _)| time.less_equal(upper))
+                .fold(init, |init, (time, diff)| fold(init, time, diff))
+        } else {
+            init
+        }
+    }
+
+    fn weight(&mut self) -> B::R
+    where
+        B::Time: PartialEq<()>,
+    {
+        self.current_times()[0].1.clone()
+    }
+
+    fn step_key(&mut self) {
+        if self.key_valid() {
+            self.move_to(self.pos + 1);
+        }
+    }
+
+    fn step_key_reverse(&mut self) {
+        if self.key_valid() {
+            self.move_to(self.pos - 1);
+        }
+    }
+
+    fn seek_key(&mut self, key: &B::Key) {
+        self.advance_keys(|k| k >= key);
+    }
+
+    fn seek_key_with<P>(&mut self, predicate: P)
+    where
+        P: Fn(&B::Key) -> bool + Clone,
+    {
+        self.advance_keys(predicate);
+    }
+
+    fn seek_key_with_reverse<P>(&mut self, predicate: P)
+    where
+        P: Fn(&B::Key) -> bool + Clone,
+    {
+        self.retreat_keys(predicate);
+    }
+
+    fn seek_key_reverse(&mut self, key: &B::Key) {
+        self.retreat_keys(|k| k <= key);
+    }
+
+    fn step_val(&mut self) {
+        if self.val_valid() {
+            self.val_idx += 1;
+        }
+    }
+
+    fn step_val_reverse(&mut self) {
+        if self.val_valid() {
+            self.val_idx -= 1;
+        }
+    }
+
+    fn seek_val(&mut self, val: &B::Val) {
+        self.seek_val_with(|v| v >= val);
+    }
+
+    fn seek_val_reverse(&mut self, val: &B::Val) {
+        self.seek_val_with_reverse(|v| v <= val);
+    }
+
+    fn seek_val_with<P>(&mut self, predicate: P)
+    where
+        P: Fn(&B::Val) -> bool + Clone,
+    {
+        if self.val_valid() {
+            self.val_idx += self.vals[self.val_idx as usize..]
+                .partition_point(|(val, _)| !predicate(val)) as isize;
+        }
+    }
+
+    fn seek_val_with_reverse<P>(&mut self, predicate: P)
+    where
+        P: Fn(&B::Val) -> bool + Clone,
+    {
+        if self.val_valid() {
+            self.val_idx = self.vals[..=self.val_idx as usize]
+                .partition_point(|(val, _)| predicate(val)) as isize
+                - 1;
+        }
+    }
+
+    fn rewind_keys(&mut self) {
+        self.move_to(self.batch.first_key as isize);
+    }
+
+    fn fast_forward_keys(&mut self) {
+        self.move_to(self.batch.offsets.len() as isize - 1);
+    }
+
+    fn rewind_vals(&mut self) {
+        self.val_idx = 0;
+    }
+
+    fn fast_forward_vals(&mut self) {
+        self.val_idx = self.vals.len() as isize - 1;
+    }
+}
+
+/// Dispatch `$body` to the inner cursor of a [`SpineBatchCursor`].
+macro_rules! dispatch {
+    ($value:expr, $inner:ident => $body:expr) => {
+        match $value {
+            SpineBatchCursor::Memory($inner) => $body,
+            SpineBatchCursor::File($inner) => $body,
+        }
+    };
+}
+
+/// A cursor over a [`SpineBatch`].
+pub enum SpineBatchCursor<'s, B>
+where
+    B: Batch + 's,
+{
+    Memory(B::Cursor<'s>),
+    File(FileBatchCursor<'s, B>),
+}
+
+impl<'s, B> Cursor<B::Key, B::Val, B::Time, B::R> for SpineBatchCursor<'s, B>
+where
+    B: Batch,
+{
+    fn key_valid(&self) -> bool {
+        dispatch!(self, cursor => cursor.key_valid())
+    }
+
+    fn val_valid(&self) -> bool {
+        dispatch!(self, cursor => cursor.val_valid())
+    }
+
+    fn key(&self) -> &B::Key {
+        dispatch!(self, cursor => cursor.key())
+    }
+
+    fn val(&self) -> &B::Val {
+        dispatch!(self, cursor => cursor.val())
+    }
+
+    fn map_times<L>(&mut self, logic: L)
+    where
+        L: FnMut(&B::Time, &B::R),
+    {
+        dispatch!(self, cursor => cursor.map_times(logic))
+    }
+
+    fn fold_times<F, U>(&mut self, init: U, fold: F) -> U
+    where
+        F: FnMut(U, &B::Time, &B::R) -> U,
+    {
+        dispatch!(self, cursor => cursor.fold_times(init, fold))
+    }
+
+    fn map_times_through<L>(&mut self, upper: &B::Time, logic: L)
+    where
+        L: FnMut(&B::Time, &B::R),
+    {
+        dispatch!(self, cursor => cursor.map_times_through(upper, logic))
+    }
+
+    fn fold_times_through<F, U>(&mut self, upper: &B::Time, init: U, fold: F) -> U
+    where
+        F: FnMut(U, &B::Time, &B::R) -> U,
+    {
+        dispatch!(self, cursor => cursor.fold_times_through(upper, init, fold))
+    }
+
+    fn weight(&mut self) -> B::R
+    where
+        B::Time: PartialEq<()>,
+    {
+        dispatch!(self, cursor => cursor.weight())
+    }
+
+    fn step_key(&mut self) {
+        dispatch!(self, cursor => cursor.step_key())
+    }
+
+    fn step_key_reverse(&mut self) {
+        dispatch!(self, cursor => cursor.step_key_reverse())
+    }
+
+    fn seek_key(&mut self, key: &B::Key) {
+        dispatch!(self, cursor => cursor.seek_key(key))
+    }
+
+    fn seek_key_with<P>(&mut self, predicate: P)
+    where
+        P: Fn(&B::Key) -> bool + Clone,
+    {
+        dispatch!(self, cursor => cursor.seek_key_with(predicate))
+    }
+
+    fn seek_key_with_reverse<P>(&mut self, predicate: P)
+    where
+        P: Fn(&B::Key) -> bool + Clone,
+    {
+        dispatch!(self, cursor => cursor.seek_key_with_reverse(predicate))
+    }
+
+    fn seek_key_reverse(&mut self, key: &B::Key) {
+        dispatch!(self, cursor => cursor.seek_key_reverse(key))
+    }
+
+    fn step_val(&mut self) {
+        dispatch!(self, cursor => cursor.step_val())
+    }
+
+    fn step_val_reverse(&mut self) {
+        dispatch!(self, cursor => cursor.step_val_reverse())
+    }
+
+    fn seek_val(&mut self, val: &B::Val) {
+        dispatch!(self, cursor => cursor.seek_val(val))
+    }
+
+    fn seek_val_reverse(&mut self, val: &B::Val) {
+        dispatch!(self, cursor => cursor.seek_val_reverse(val))
+    }
+
+    fn seek_val_with<P>(&mut self, predicate: P)
+    where
+        P: Fn(&B::Val) -> bool + Clone,
+    {
+        dispatch!(self, cursor => cursor.seek_val_with(predicate))
+    }
+
+    fn seek_val_with_reverse<P>(&mut self, predicate: P)
+    where
+        P: Fn(&B::Val) -> bool + Clone,
+    {
+        dispatch!(self, cursor => cursor.seek_val_with_reverse(predicate))
+    }
+
+    fn rewind_keys(&mut self) {
+        dispatch!(self, cursor => cursor.rewind_keys())
+    }
+
+    fn fast_forward_keys(&mut self) {
+        dispatch!(self, cursor => cursor.fast_forward_keys())
+    }
+
+    fn rewind_vals(&mut self) {
+        dispatch!(self, cursor => cursor.rewind_vals())
+    }
+
+    fn fast_forward_vals(&mut self) {
+        dispatch!(self, cursor => cursor.fast_forward_vals())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{FileBatch, FileBatchCursor, SpillCodec};
+    use crate::trace::{
+        ord::OrdValBatch,
+        test_batch::{assert_batch_cursors_eq, assert_batch_eq, batch_to_tuples, TestBatch},
+        Batch, BatchReader, Cursor, Trace,
+    };
+    use tempfile::TempDir;
+
+    type TestFileBatch = FileBatch<OrdValBatch<i32, i32, u32, i32>>;
+    type Tuples = Vec<((i32, i32, u32), i32)>;
+
+    /// Keys `0..100` with `key % 5` values each at times `0..3`.  Keys
+    /// divisible by 5 have no values, so they are not in the batch.
+    fn test_tuples() -> Tuples {
+        (0..100)
+            .flat_map(|k| {
+                (0..k % 5).flat_map(move |v| (0..3).map(move |t| ((k, v, t), 1 + (k + v) % 3)))
+            })
+            .collect()
+    }
+
+    fn test_batches(directory: &TempDir) -> (TestFileBatch, TestBatch<i32, i32, u32, i32>) {
+        let tuples = test_tuples();
+        let batch = (0..3)
+            .map(|time| {
+                let updates = tuples
+                    .iter()
+                    .filter(|((_, _, t), _)| *t == time)
+                    .map(|((k, v, _), r)| ((*k, *v), *r))
+                    .collect();
+                OrdValBatch::from_tuples(time, updates)
+            })
+            .reduce(|acc, batch| acc.merge(&batch))
+            .unwrap();
+
+        let file_batch =
+            FileBatch::from_batch(&batch, directory.path(), SpillCodec::new()).unwrap();
+        (file_batch, TestBatch::from_data(&tuples))
+    }
+
+    /// Reads all updates in `batch` through a cursor, iterating over keys and
+    /// values forward or in reverse.
+    fn cursor_tuples(batch: &TestFileBatch, reverse: bool) -> Tuples {
+        let mut cursor = FileBatchCursor::new(batch);
+        let mut result = Vec::new();
+
+        if reverse {
+            cursor.fast_forward_keys();
+        }
+        while cursor.key_valid() {
+            if reverse {
+                cursor.fast_forward_vals();
+            }
+            while cursor.val_valid() {
+                let (key, val) = (*cursor.key(), *cursor.val());
+                cursor.map_times(|time, diff| result.push(((key, val, *time), *diff)));
+                if reverse {
+                    cursor.step_val_reverse();
+                } else {
+                    cursor.step_val();
+                }
+            }
+            if reverse {
+                cursor.step_key_reverse();
+            } else {
+                cursor.step_key();
+            }
+        }
+
+        result.sort();
+        result
+    }
+
+    fn assert_file_batch_eq(batch: &TestFileBatch, ref_batch: &TestBatch<i32, i32, u32, i32>) {
+        let tuples = batch_to_tuples(ref_batch);
+        assert_eq!(cursor_tuples(batch, false), tuples);
+        assert_eq!(cursor_tuples(batch, true), tuples);
+    }
+
+    #[test]
+    fn test_file_batch_cursor() {
+        let directory = TempDir::new().unwrap();
+        let (batch, ref_batch) = test_batches(&directory);
+
+        assert_eq!(batch.key_count(), 80);
+        assert_eq!(batch.len(), ref_batch.len());
+        assert_file_batch_eq(&batch, &ref_batch);
+        assert_batch_cursors_eq(FileBatchCursor::new(&batch), &ref_batch, 0);
+
+        // Seeking to a missing key moves to the next key in the direction of
+        // the seek.
+        let mut cursor = FileBatchCursor::new(&batch);
+        cursor.seek_key(&5);
+        assert_eq!(cursor.key(), &6);
+        cursor.seek_key_reverse(&5);
+        assert_eq!(cursor.key(), &4);
+        cursor.seek_key(&95);
+        assert_eq!(cursor.key(), &96);
+        cursor.seek_key(&100);
+        assert!(!cursor.key_valid());
+
+        cursor.rewind_keys();
+        assert_eq!(cursor.key(), &1);
+        cursor.seek_key_with(|key| *key >= 33);
+        assert_eq!(cursor.key(), &33);
+        cursor.fast_forward_keys();
+        assert_eq!(cursor.key(), &99);
+        cursor.seek_key_with_reverse(|key| *key <= 67);
+        assert_eq!(cursor.key(), &67);
+        cursor.seek_key_reverse(&0);
+        assert!(!cursor.key_valid());
+    }
+
+    #[test]
+    fn test_file_batch_truncate_keys_below() {
+        let directory = TempDir::new().unwrap();
+        let (mut batch, mut ref_batch) = test_batches(&directory);
+
+        batch.truncate_keys_below(&50).unwrap();
+        ref_batch.truncate_keys_below(&50);
+        assert_eq!(batch.key_count(), 40);
+        assert_file_batch_eq(&batch, &ref_batch);
+
+        // Keys below the bound can't be reached from either direction.
+        let mut cursor = FileBatchCursor::new(&batch);
+        assert_eq!(cursor.key(), &51);
+        cursor.seek_key_reverse(&49);
+        assert!(!cursor.key_valid());
+        cursor.rewind_keys();
+        assert_eq!(cursor.key(), &51);
+        drop(cursor);
+
+        // Truncating below a lower bound is a no-op.
+        batch.truncate_keys_below(&10).unwrap();
+        assert_file_batch_eq(&batch, &ref_batch);
+
+        batch.truncate_keys_below(&100).unwrap();
+        assert_eq!(batch.key_count(), 0);
+        assert!(batch.is_empty());
+        assert!(!FileBatchCursor::new(&batch).key_valid());
+    }
+
+    #[test]
+    fn test_file_batch_recede_to() {
+        let directory = TempDir::new().unwrap();
+        let (mut batch, mut ref_batch) = test_batches(&directory);
+        batch.truncate_keys_below(&20).unwrap();
+        ref_batch.truncate_keys_below(&20);
+
+        let path = batch.path().to_owned();
+        batch.recede_to(&1).unwrap();
+        Trace::recede_to(&mut ref_batch, &1);
+        assert_file_batch_eq(&batch, &ref_batch);
+        assert_batch_cursors_eq(FileBatchCursor::new(&batch), &ref_batch, 0);
+
+        // The batch is rewritten to a new file.
+        assert_ne!(batch.path(), path.as_path());
+        assert!(!path.exists());
+
+        assert_batch_eq(&batch.to_batch().unwrap(), &ref_batch);
+
+        let path = batch.path().to_owned();
+        drop(batch);
+        assert!(!path.exists());
+    }
+}
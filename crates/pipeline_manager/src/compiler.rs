@@ -94,6 +94,41 @@ impl Drop for Compiler {
     }
 }
 
+/// Features of the `dbsp_adapters` crate enabled in each generated pipeline
+/// crate.
+///
+/// Must match the features enabled in the pipeline manager's own
+/// `Cargo.toml`, so that pipelines accept every connector configuration
+/// listed in the manager's API.
+const ADAPTERS_FEATURES: &str =
+    r#"features = ["with-avro", "with-parquet", "with-s3", "with-postgres"]"#;
+
+/// Enable [`ADAPTERS_FEATURES`] in the `dbsp_adapters` dependency declared
+/// in `toml` as an inline table.
+fn enable_adapters_features(toml: &str) -> AnyResult<String> {
+    let mut found = false;
+    let lines: Vec<String> = toml
+        .lines()
+        .map(|line| {
+            let trimmed = line.trim_end();
+            if line.trim_start().starts_with("dbsp_adapters") && trimmed.ends_with('}') {
+                found = true;
+                let body = trimmed[..trimmed.len() - 1].trim_end();
+                format!("{body}, {ADAPTERS_FEATURES} }}")
+            } else {
+                line.to_string()
+            }
+        })
+        .collect();
+
+    if !found {
+        return Err(AnyError::msg(
+            "template does not declare the 'dbsp_adapters' dependency as an inline table",
+        ));
+    }
+    Ok(lines.join("\n"))
+}
+
 /// The `main` function injected in each generated pipeline
 /// crate.
 const MAIN_FUNCTION: &str = r#"
@@ -387,6 +422,12 @@ impl CompilationJob {
                 "[lib]\npath = \"src/lib.rs\"",
                 &format!("\n\n[[bin]]\n{project_name}\npath = \"src/main.rs\""),
             );
+        let project_toml_code = enable_adapters_features(&project_toml_code).map_err(|e| {
+            AnyError::msg(format!(
+                "failed to process template '{}': {e}",
+                config.project_toml_template_path().display()
+            ))
+        })?;
 
         fs::write(&config.project_toml_path(project_id), project_toml_code)
             .await
@@ -495,3 +536,24 @@ impl CompilationJob {
         let _ = self.compiler_process.kill().await;
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::enable_adapters_features;
+
+    #[test]
+    fn test_enable_adapters_features() {
+        let toml = r#"[dependencies]
+dbsp = { git = "https://github.com/vmware/database-stream-processor" }
+dbsp_adapters = { git = "https://github.com/vmware/database-stream-processor" }
+"#;
+        assert_eq!(
+            enable_adapters_features(toml).unwrap(),
+            r#"[dependencies]
+dbsp = { git = "https://github.com/vmware/database-stream-processor" }
+dbsp_adapters = { git = "https://github.com/vmware/database-stream-processor", features = ["with-avro", "with-parquet", "with-s3", "with-postgres"] }"#
+        );
+
+        assert!(enable_adapters_features("[dependencies]\ndbsp = \"0.1\"\n").is_err());
+    }
+}
@@ -144,17 +144,31 @@ observed by the user is outdated, so the request is rejected."
         db::PipelineDescr,
         dbsp_adapters::PipelineConfig,
         dbsp_adapters::InputEndpointConfig,
+        dbsp_adapters::ParseErrorPolicy,
         dbsp_adapters::OutputEndpointConfig,
         dbsp_adapters::TransportConfig,
         dbsp_adapters::FormatConfig,
+        dbsp_adapters::transport::FileAfterRead,
         dbsp_adapters::transport::FileInputConfig,
         dbsp_adapters::transport::FileOutputConfig,
         dbsp_adapters::transport::KafkaInputConfig,
         dbsp_adapters::transport::KafkaOutputConfig,
         dbsp_adapters::transport::KafkaLogLevel,
+        dbsp_adapters::transport::PostgresOutputConfig,
+        dbsp_adapters::transport::S3InputConfig,
+        dbsp_adapters::transport::S3OutputConfig,
         dbsp_adapters::transport::KafkaOutputConfig,
+        dbsp_adapters::format::AvroEncoderConfig,
+        dbsp_adapters::format::AvroParserConfig,
+        dbsp_adapters::format::AvroUpdateFormat,
         dbsp_adapters::format::CsvEncoderConfig,
         dbsp_adapters::format::CsvParserConfig,
+        dbsp_adapters::format::CsvUpdateFormat,
+        dbsp_adapters::format::JsonEncoderConfig,
+        dbsp_adapters::format::JsonParserConfig,
+        dbsp_adapters::format::JsonUpdateFormat,
+        dbsp_adapters::format::ParquetEncoderConfig,
+        dbsp_adapters::format::ParquetParserConfig,
         Direction,
         ProjectId,
         PipelineId,
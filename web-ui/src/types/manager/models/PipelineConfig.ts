@@ -29,6 +29,17 @@ export type PipelineConfig = {
    * Defaults to 0.
    */
   min_batch_size_records?: number
+  /**
+   * Persistent storage for the state of stateful operators.
+   *
+   * When specified, operators selected by
+   * `storage.persistent_operators` (by default all stateful operators)
+   * keep their state in a RocksDB database at `storage.path`, which
+   * allows the state to grow larger than RAM.  Requires the pipeline to be
+   * built with the `persistence` feature of DBSP.  By default, all state is
+   * kept in memory.
+   */
+  storage?: any
   /**
    * Number of DBSP worker threads.
    */